@@ -0,0 +1,317 @@
+use std::path::PathBuf;
+
+use crate::level::GRID_SPACING;
+use balala::{engine::stats::FrameTimeHistory, renderer::renderer::RenderStats};
+use nalgebra::Vector3;
+
+/// Circle around a point at constant height, camera always faces the center.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraPath {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+    /// Seconds per full circle.
+    pub period: f32,
+}
+
+impl CameraPath {
+    /// Path around a grid level of given size.
+    pub fn around_grid(size: u32) -> Self {
+        let extent = size.saturating_sub(1) as f32 * GRID_SPACING;
+        let half = extent * 0.5;
+        Self {
+            center: Vector3::repeat(half),
+            radius: extent + 10.0,
+            period: 20.0,
+        }
+    }
+
+    /// Eye position and yaw in degrees at `time` seconds.
+    pub fn pose(&self, time: f32) -> (Vector3<f32>, f32) {
+        let angle = std::f32::consts::TAU * time / self.period;
+        let eye = self.center + Vector3::new(angle.sin(), 0.0, angle.cos()).scale(self.radius);
+        let to_center = self.center - eye;
+        (eye, to_center.x.atan2(to_center.z).to_degrees())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BenchFormat {
+    Csv,
+    Json,
+}
+
+/// Stress test workload, see `parse_bench_options`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchConfig {
+    /// Cubes along each side of the grid.
+    pub cubes: u32,
+    pub lights: u32,
+    pub seconds: f32,
+    pub format: BenchFormat,
+    /// Report is printed to stdout if not set.
+    pub output: Option<PathBuf>,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            cubes: 20,
+            lights: 0,
+            seconds: 10.0,
+            format: BenchFormat::Csv,
+            output: None,
+        }
+    }
+}
+
+/// Upper estimate of frame rate used to reserve frame time samples.
+const MAX_BENCH_FPS: f32 = 5000.0;
+
+/// Parses `key=value` options of `--bench`: `cubes`, `lights`, `seconds`,
+/// `format` (`csv` or `json`) and `out`. Missing options keep defaults.
+pub fn parse_bench_options<S: AsRef<str>>(options: &[S]) -> Result<BenchConfig, String> {
+    let mut config = BenchConfig::default();
+    for option in options.iter().map(|option| option.as_ref()) {
+        let (key, value) = option
+            .split_once('=')
+            .ok_or_else(|| format!("bench option {:?} must be key=value", option))?;
+        let invalid = || format!("invalid value {:?} for bench option {}", value, key);
+        match key {
+            "cubes" => {
+                config.cubes = value.parse().map_err(|_| invalid())?;
+                if config.cubes == 0 {
+                    return Err(invalid());
+                }
+            }
+            "lights" => config.lights = value.parse().map_err(|_| invalid())?,
+            "seconds" => {
+                config.seconds = value.parse().map_err(|_| invalid())?;
+                if !(config.seconds.is_finite() && config.seconds > 0.0) {
+                    return Err(invalid());
+                }
+            }
+            "format" => {
+                config.format = match value {
+                    "csv" => BenchFormat::Csv,
+                    "json" => BenchFormat::Json,
+                    _ => return Err(invalid()),
+                }
+            }
+            "out" if !value.is_empty() => config.output = Some(PathBuf::from(value)),
+            "out" => return Err(invalid()),
+            _ => return Err(format!("unknown bench option {:?}", key)),
+        }
+    }
+    Ok(config)
+}
+
+/// Frame times and summed renderer counters of a benchmark run.
+#[derive(Debug)]
+pub struct BenchRecorder {
+    frame_times: FrameTimeHistory,
+    totals: RenderStats,
+    elapsed: f32,
+}
+
+impl BenchRecorder {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            frame_times: FrameTimeHistory::new((seconds * MAX_BENCH_FPS) as usize),
+            totals: RenderStats::default(),
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, frame_time: f32, render: &RenderStats) {
+        self.frame_times.push(frame_time);
+        self.elapsed += frame_time;
+        self.totals.draw_calls += render.draw_calls;
+        self.totals.triangles += render.triangles;
+        self.totals.meshes += render.meshes;
+        self.totals.lights += render.lights;
+        self.totals.cameras += render.cameras;
+        self.totals.drawn += render.drawn;
+        self.totals.culled += render.culled;
+    }
+
+    /// Seconds of recorded frames.
+    pub fn get_elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn report(&self) -> BenchReport {
+        let ms = |seconds: f32| seconds * 1000.0;
+        BenchReport {
+            frames: self.frame_times.len(),
+            min_ms: ms(self.frame_times.min()),
+            avg_ms: ms(self.frame_times.average()),
+            p95_ms: ms(self.frame_times.percentile(95.0)),
+            p99_ms: ms(self.frame_times.percentile(99.0)),
+            max_ms: ms(self.frame_times.max()),
+            totals: self.totals,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    pub frames: usize,
+    pub min_ms: f32,
+    pub avg_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+    pub totals: RenderStats,
+}
+
+impl BenchReport {
+    fn fields(&self) -> [(&'static str, String); 13] {
+        [
+            ("frames", self.frames.to_string()),
+            ("min_ms", format!("{:.3}", self.min_ms)),
+            ("avg_ms", format!("{:.3}", self.avg_ms)),
+            ("p95_ms", format!("{:.3}", self.p95_ms)),
+            ("p99_ms", format!("{:.3}", self.p99_ms)),
+            ("max_ms", format!("{:.3}", self.max_ms)),
+            ("draw_calls", self.totals.draw_calls.to_string()),
+            ("triangles", self.totals.triangles.to_string()),
+            ("meshes", self.totals.meshes.to_string()),
+            ("lights", self.totals.lights.to_string()),
+            ("cameras", self.totals.cameras.to_string()),
+            ("drawn", self.totals.drawn.to_string()),
+            ("culled", self.totals.culled.to_string()),
+        ]
+    }
+
+    /// Header line and value line.
+    pub fn to_csv(&self) -> String {
+        let fields = self.fields();
+        let names: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+        let values: Vec<&str> = fields.iter().map(|(_, value)| value.as_str()).collect();
+        format!("{}\n{}\n", names.join(","), values.join(","))
+    }
+
+    /// Single object, all values are numbers.
+    pub fn to_json(&self) -> String {
+        let pairs: Vec<String> = self
+            .fields()
+            .iter()
+            .map(|(name, value)| format!("\"{}\": {}", name, value))
+            .collect();
+        format!("{{{}}}\n", pairs.join(", "))
+    }
+
+    pub fn format(&self, format: BenchFormat) -> String {
+        match format {
+            BenchFormat::Csv => self.to_csv(),
+            BenchFormat::Json => self.to_json(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{Level, LevelDescription};
+    use balala::engine::{system::SystemPhase, Engine};
+
+    #[test]
+    fn bench_camera_path_circles_grid() {
+        let path = CameraPath::around_grid(3);
+        assert_eq!(path.center, Vector3::repeat(2.0));
+        let (start, start_yaw) = path.pose(0.0);
+        assert!((start - Vector3::new(2.0, 2.0, 16.0)).norm() < 1e-5);
+        // Looks back along -Z to the center.
+        assert!((start_yaw.abs() - 180.0).abs() < 1e-3);
+        let (quarter, quarter_yaw) = path.pose(path.period / 4.0);
+        assert!((quarter - Vector3::new(16.0, 2.0, 2.0)).norm() < 1e-4);
+        assert!((quarter_yaw + 90.0).abs() < 1e-3);
+        // Deterministic and periodic.
+        for time in [0.3, 7.1, 13.0] {
+            let (a, _) = path.pose(time);
+            let (b, _) = path.pose(time + path.period);
+            assert!((a - b).norm() < 1e-3);
+            assert!(((a - path.center).norm() - path.radius).abs() < 1e-3);
+            assert_eq!(path.pose(time), path.pose(time));
+        }
+    }
+
+    #[test]
+    fn bench_level_follows_camera_path() {
+        let mut engine = Engine::new_headless();
+        let description = LevelDescription::grid(4, 5);
+        assert_eq!(description.entities.len(), 2 + 64 + 5);
+        let mut level = Level::from_description(description, &mut engine).unwrap();
+        let path = CameraPath::around_grid(4);
+        level.set_camera_path(Some(path));
+        let (scene, camera) = (
+            level.get_scene().clone(),
+            level.get_player().get_camera().clone(),
+        );
+        engine.register_system_in_phase(Box::new(level), SystemPhase::PreUpdate, 0);
+        for _ in 0..30 {
+            engine.advance(1.0 / 60.0, |_, _| {});
+        }
+
+        let camera = engine
+            .borrow_scene(&scene)
+            .unwrap()
+            .borrow_node(&camera)
+            .unwrap();
+        // Camera moved along the circle and stays on it.
+        let eye = camera.get_global_position();
+        let offset = eye - path.center;
+        assert!((offset.y).abs() < 1e-3);
+        assert!((offset.norm() - path.radius).abs() < 1e-3);
+        assert!((eye - path.pose(0.0).0).norm() > 1.0);
+        let to_center = (path.center - eye).normalize();
+        assert!((camera.get_look_vector().normalize() - to_center).norm() < 1e-3);
+    }
+
+    #[test]
+    fn bench_report() {
+        let mut recorder = BenchRecorder::new(1.0);
+        let render = RenderStats {
+            draw_calls: 10,
+            triangles: 120,
+            meshes: 10,
+            lights: 2,
+            cameras: 1,
+            drawn: 8,
+            culled: 2,
+            surfaces_allocated: 0,
+            cull_toggles: 0,
+        };
+        // 1..=100 ms, shuffled.
+        for i in 0..100 {
+            recorder.record(((i * 37) % 100 + 1) as f32 / 1000.0, &render);
+        }
+        assert!((recorder.get_elapsed() - 5.05).abs() < 1e-4);
+        let report = recorder.report();
+        assert_eq!(report.frames, 100);
+        assert!((report.min_ms - 1.0).abs() < 1e-4);
+        assert!((report.avg_ms - 50.5).abs() < 1e-3);
+        assert!((report.p95_ms - 95.0).abs() < 1e-3);
+        assert!((report.p99_ms - 99.0).abs() < 1e-3);
+        assert!((report.max_ms - 100.0).abs() < 1e-3);
+        assert_eq!(report.totals.draw_calls, 1000);
+        assert_eq!(report.totals.triangles, 12000);
+
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "frames,min_ms,avg_ms,p95_ms,p99_ms,max_ms,draw_calls,triangles,meshes,lights,cameras,drawn,culled"
+        );
+        assert_eq!(
+            lines[1],
+            "100,1.000,50.500,95.000,99.000,100.000,1000,12000,1000,200,100,800,200"
+        );
+        assert_eq!(
+            report.to_json().trim(),
+            "{\"frames\": 100, \"min_ms\": 1.000, \"avg_ms\": 50.500, \"p95_ms\": 95.000, \
+             \"p99_ms\": 99.000, \"max_ms\": 100.000, \"draw_calls\": 1000, \"triangles\": 12000, \
+             \"meshes\": 1000, \"lights\": 200, \"cameras\": 100, \"drawn\": 800, \"culled\": 200}"
+        );
+    }
+}
@@ -0,0 +1,619 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use winit::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+use super::Engine;
+use crate::renderer::renderer::PolygonMode;
+
+/// Commands kept in history, oldest are dropped first.
+pub const MAX_HISTORY: usize = 100;
+
+/// Output lines kept for display.
+pub const MAX_OUTPUT: usize = 100;
+
+/// Key that opens and closes the console.
+pub const TOGGLE_KEY: VirtualKeyCode = VirtualKeyCode::Grave;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleError {
+    /// Quote opened at the given character offset was never closed.
+    UnterminatedQuote(usize),
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidArgument {
+        name: &'static str,
+        value: String,
+    },
+    TooManyArguments {
+        max: usize,
+    },
+    /// Command understood its arguments but could not run.
+    Failed(String),
+}
+
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsoleError::UnterminatedQuote(offset) => {
+                write!(f, "unterminated quote at {}", offset)
+            }
+            ConsoleError::UnknownCommand(name) => write!(f, "unknown command: {}", name),
+            ConsoleError::MissingArgument(name) => write!(f, "missing argument <{}>", name),
+            ConsoleError::InvalidArgument { name, value } => {
+                write!(f, "invalid value {:?} for <{}>", value, name)
+            }
+            ConsoleError::TooManyArguments { max } => {
+                write!(f, "too many arguments, expected at most {}", max)
+            }
+            ConsoleError::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConsoleError {}
+
+/// Splits a command line into words. Whitespace separates words unless
+/// inside single or double quotes, backslash escapes the next character
+/// outside single quotes.
+pub fn tokenize(line: &str) -> Result<Vec<String>, ConsoleError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    // Distinguishes an empty quoted word from no word at all.
+    let mut in_token = false;
+    let mut chars = line.char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                in_token = true;
+                let mut closed = false;
+                while let Some((_, inner)) = chars.next() {
+                    if inner == c {
+                        closed = true;
+                        break;
+                    }
+                    if inner == '\\' && c == '"' {
+                        match chars.next() {
+                            Some((_, escaped)) => current.push(escaped),
+                            None => break,
+                        }
+                    } else {
+                        current.push(inner);
+                    }
+                }
+                if !closed {
+                    return Err(ConsoleError::UnterminatedQuote(offset));
+                }
+            }
+            '\\' => {
+                in_token = true;
+                if let Some((_, escaped)) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                in_token = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// Fails if there are more than `max` arguments.
+pub fn expect_at_most(args: &[String], max: usize) -> Result<(), ConsoleError> {
+    if args.len() > max {
+        Err(ConsoleError::TooManyArguments { max })
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses required argument at `index`, `name` is used in error messages.
+pub fn parse_arg<T: FromStr>(
+    args: &[String],
+    index: usize,
+    name: &'static str,
+) -> Result<T, ConsoleError> {
+    let value = args.get(index).ok_or(ConsoleError::MissingArgument(name))?;
+    value.parse().map_err(|_| ConsoleError::InvalidArgument {
+        name,
+        value: value.clone(),
+    })
+}
+
+/// Parses finite number, `inf` and `nan` are rejected.
+pub fn parse_number(
+    args: &[String],
+    index: usize,
+    name: &'static str,
+) -> Result<f32, ConsoleError> {
+    let value: f32 = parse_arg(args, index, name)?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(ConsoleError::InvalidArgument {
+            name,
+            value: args[index].clone(),
+        })
+    }
+}
+
+/// Parses `on|off` switch, `1|0` and `true|false` are accepted too.
+pub fn parse_switch(
+    args: &[String],
+    index: usize,
+    name: &'static str,
+) -> Result<bool, ConsoleError> {
+    let value = args.get(index).ok_or(ConsoleError::MissingArgument(name))?;
+    match value.to_lowercase().as_str() {
+        "on" | "1" | "true" => Ok(true),
+        "off" | "0" | "false" => Ok(false),
+        _ => Err(ConsoleError::InvalidArgument {
+            name,
+            value: value.clone(),
+        }),
+    }
+}
+
+fn switch_name(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Command callback, receives arguments without the command name and returns
+/// text to print.
+pub type Command = Box<dyn FnMut(&mut Engine, &[String]) -> Result<String, ConsoleError>>;
+
+/// Drop-down debug console. While open it takes all keyboard input, the game
+/// must not pass such events to the engine, see `process_event`. Output is
+/// echoed to stdout until there is a text overlay to draw it with.
+pub struct Console {
+    open: bool,
+    line: String,
+    history: Vec<String>,
+    /// Position in history while browsing it with up/down keys.
+    history_cursor: Option<usize>,
+    output: Vec<String>,
+    /// Lines submitted by events, run by `execute_pending`.
+    pending: Vec<String>,
+    commands: BTreeMap<String, Command>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    /// Creates console with built-in engine commands.
+    pub fn new() -> Self {
+        let mut console = Self {
+            open: false,
+            line: String::new(),
+            history: Vec::new(),
+            history_cursor: None,
+            output: Vec::new(),
+            pending: Vec::new(),
+            commands: BTreeMap::new(),
+        };
+        console.register_builtins();
+        console
+    }
+
+    fn register_builtins(&mut self) {
+        self.register("wireframe", |engine, args| {
+            expect_at_most(args, 1)?;
+            let enabled = parse_switch(args, 0, "on|off")?;
+            let renderer = engine
+                .renderer_mut()
+                .ok_or_else(|| ConsoleError::Failed("no renderer".to_string()))?;
+            renderer.set_polygon_mode(if enabled {
+                PolygonMode::Wireframe
+            } else {
+                PolygonMode::Fill
+            });
+            Ok(format!("wireframe {}", switch_name(enabled)))
+        });
+        self.register("vsync", |engine, args| {
+            expect_at_most(args, 1)?;
+            let enabled = parse_switch(args, 0, "on|off")?;
+            let renderer = engine
+                .renderer_mut()
+                .ok_or_else(|| ConsoleError::Failed("no renderer".to_string()))?;
+            renderer.set_vsync(enabled);
+            Ok(format!(
+                "vsync {}",
+                switch_name(renderer.is_vsync_enabled())
+            ))
+        });
+        self.register("timescale", |engine, args| {
+            expect_at_most(args, 1)?;
+            if !args.is_empty() {
+                let scale = parse_number(args, 0, "scale")?;
+                if scale < 0.0 {
+                    return Err(ConsoleError::InvalidArgument {
+                        name: "scale",
+                        value: args[0].clone(),
+                    });
+                }
+                engine.set_time_scale(scale);
+            }
+            Ok(format!("timescale {}", engine.get_time_scale()))
+        });
+        self.register("stats", |engine, args| {
+            expect_at_most(args, 0)?;
+            Ok(engine.stats_string())
+        });
+    }
+
+    /// Adds command or replaces existing one with the same name.
+    pub fn register(
+        &mut self,
+        name: &str,
+        command: impl FnMut(&mut Engine, &[String]) -> Result<String, ConsoleError> + 'static,
+    ) {
+        self.commands.insert(name.to_string(), Box::new(command));
+    }
+
+    /// Names of registered commands in alphabetical order.
+    pub fn get_command_names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(|name| name.as_str())
+    }
+
+    /// Parses and runs a line. Empty line does nothing and returns empty
+    /// string, `help` lists commands.
+    pub fn execute(&mut self, engine: &mut Engine, line: &str) -> Result<String, ConsoleError> {
+        let tokens = tokenize(line)?;
+        let Some((name, args)) = tokens.split_first() else {
+            return Ok(String::new());
+        };
+        if name == "help" && !self.commands.contains_key("help") {
+            expect_at_most(args, 0)?;
+            return Ok(self.get_command_names().collect::<Vec<_>>().join(" "));
+        }
+        match self.commands.get_mut(name) {
+            Some(command) => command(engine, args),
+            None => Err(ConsoleError::UnknownCommand(name.clone())),
+        }
+    }
+
+    /// Runs lines submitted since previous call, echoes them and their
+    /// results to output.
+    pub fn execute_pending(&mut self, engine: &mut Engine) {
+        for line in std::mem::take(&mut self.pending) {
+            self.print(&format!("> {}", line));
+            match self.execute(engine, &line) {
+                Ok(text) => {
+                    for text_line in text.lines() {
+                        self.print(text_line);
+                    }
+                }
+                Err(err) => self.print(&format!("error: {}", err)),
+            }
+        }
+    }
+
+    pub fn print(&mut self, text: &str) {
+        println!("{}", text);
+        if self.output.len() >= MAX_OUTPUT {
+            self.output.remove(0);
+        }
+        self.output.push(text.to_string());
+    }
+
+    pub fn get_output(&self) -> &[String] {
+        &self.output
+    }
+
+    pub fn get_history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Text typed so far.
+    pub fn get_line(&self) -> &str {
+        &self.line
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.history_cursor = None;
+    }
+
+    /// Handles a window event. Returns true if the event was consumed and
+    /// must not reach the game: toggle key presses always are, and all
+    /// keyboard and text events while console is open.
+    pub fn process_event(&mut self, event: &Event<()>) -> bool {
+        let Event::WindowEvent { event, .. } = event else {
+            return false;
+        };
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                if input.virtual_keycode == Some(TOGGLE_KEY) {
+                    if input.state == ElementState::Pressed {
+                        self.set_open(!self.open);
+                    }
+                    return true;
+                }
+                if !self.open {
+                    return false;
+                }
+                if input.state == ElementState::Pressed {
+                    if let Some(key) = input.virtual_keycode {
+                        self.process_key(key);
+                    }
+                }
+                true
+            }
+            WindowEvent::ReceivedCharacter(c) => {
+                if !self.open {
+                    return false;
+                }
+                // Toggle key produces a character too, control characters
+                // are handled as keys.
+                if *c != '`' && *c != '~' && !c.is_control() {
+                    self.line.push(*c);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn process_key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => self.submit(),
+            VirtualKeyCode::Back => {
+                self.line.pop();
+            }
+            VirtualKeyCode::Escape => self.set_open(false),
+            VirtualKeyCode::Up => self.browse_history(true),
+            VirtualKeyCode::Down => self.browse_history(false),
+            _ => (),
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.history_cursor = None;
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.history.last() != Some(&line) {
+            if self.history.len() >= MAX_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        self.pending.push(line);
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        self.history_cursor = match (self.history_cursor, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < self.history.len() => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        self.line = match self.history_cursor {
+            Some(i) => self.history[i].clone(),
+            None => String::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{ElementState, VirtualKeyCode, WindowEvent};
+
+    use super::{parse_arg, parse_number, parse_switch, tokenize, Console, ConsoleError};
+    use crate::engine::{
+        input::tests::{key_event, window_event},
+        Engine,
+    };
+
+    fn args(line: &str) -> Vec<String> {
+        tokenize(line).unwrap()
+    }
+
+    fn type_line(console: &mut Console, text: &str) {
+        for c in text.chars() {
+            console.process_event(&window_event(WindowEvent::ReceivedCharacter(c)));
+        }
+    }
+
+    fn press(console: &mut Console, key: VirtualKeyCode) -> bool {
+        let consumed = console.process_event(&key_event(key, ElementState::Pressed));
+        console.process_event(&key_event(key, ElementState::Released));
+        consumed
+    }
+
+    #[test]
+    fn tokenize_words_and_whitespace() {
+        assert_eq!(
+            args("  spawn_cube 1 -2\t3.5  "),
+            ["spawn_cube", "1", "-2", "3.5"]
+        );
+        assert!(args("").is_empty());
+        assert!(args("   ").is_empty());
+    }
+
+    #[test]
+    fn tokenize_quotes_and_escapes() {
+        assert_eq!(args(r#"remove "Big Cube""#), ["remove", "Big Cube"]);
+        assert_eq!(args("remove 'it''s'"), ["remove", "its"]);
+        assert_eq!(args(r#"say "a \"b\" \\ c""#), ["say", r#"a "b" \ c"#]);
+        // Backslash is literal inside single quotes.
+        assert_eq!(args(r"say 'a\b'"), ["say", r"a\b"]);
+        assert_eq!(args(r"say a\ b"), ["say", "a b"]);
+        // Quotes join with adjacent text, empty quotes are a word.
+        assert_eq!(args(r#"x"y z"w """#), ["xy zw", ""]);
+        assert_eq!(
+            tokenize(r#"remove "Big Cube"#),
+            Err(ConsoleError::UnterminatedQuote(7))
+        );
+        assert_eq!(
+            tokenize("say 'abc"),
+            Err(ConsoleError::UnterminatedQuote(4))
+        );
+    }
+
+    #[test]
+    fn parse_arguments() {
+        let list = args("1.5 -2 abc on OFF 1e3 inf");
+        assert_eq!(parse_number(&list, 0, "x"), Ok(1.5));
+        assert_eq!(parse_number(&list, 1, "x"), Ok(-2.0));
+        assert_eq!(parse_number(&list, 5, "x"), Ok(1000.0));
+        assert_eq!(
+            parse_number(&list, 2, "x"),
+            Err(ConsoleError::InvalidArgument {
+                name: "x",
+                value: "abc".to_string()
+            })
+        );
+        assert!(parse_number(&list, 6, "x").is_err());
+        assert_eq!(
+            parse_number(&list, 7, "y"),
+            Err(ConsoleError::MissingArgument("y"))
+        );
+        assert!(parse_arg::<usize>(&list, 1, "n").is_err());
+        assert_eq!(parse_switch(&list, 3, "s"), Ok(true));
+        assert_eq!(parse_switch(&list, 4, "s"), Ok(false));
+        assert!(parse_switch(&list, 2, "s").is_err());
+    }
+
+    #[test]
+    fn error_messages() {
+        let messages = [
+            (
+                ConsoleError::UnknownCommand("fly".into()),
+                "unknown command: fly",
+            ),
+            (ConsoleError::MissingArgument("x"), "missing argument <x>"),
+            (
+                ConsoleError::InvalidArgument {
+                    name: "scale",
+                    value: "fast".into(),
+                },
+                "invalid value \"fast\" for <scale>",
+            ),
+            (
+                ConsoleError::TooManyArguments { max: 1 },
+                "too many arguments, expected at most 1",
+            ),
+            (
+                ConsoleError::UnterminatedQuote(3),
+                "unterminated quote at 3",
+            ),
+        ];
+        for (err, message) in messages {
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn execute_builtins_and_registered() {
+        let mut engine = Engine::new_headless();
+        let mut console = Console::new();
+        assert_eq!(console.execute(&mut engine, ""), Ok(String::new()));
+        assert_eq!(
+            console.execute(&mut engine, "timescale 0.5"),
+            Ok("timescale 0.5".to_string())
+        );
+        assert_eq!(engine.get_time_scale(), 0.5);
+        assert!(console.execute(&mut engine, "timescale -1").is_err());
+        assert!(console.execute(&mut engine, "timescale 1 2").is_err());
+        assert_eq!(engine.get_time_scale(), 0.5);
+        assert_eq!(
+            console.execute(&mut engine, "wireframe on"),
+            Err(ConsoleError::Failed("no renderer".to_string()))
+        );
+        assert_eq!(
+            console.execute(&mut engine, "wireframe maybe"),
+            Err(ConsoleError::InvalidArgument {
+                name: "on|off",
+                value: "maybe".to_string()
+            })
+        );
+        assert!(console.execute(&mut engine, "stats").is_ok());
+        assert_eq!(
+            console.execute(&mut engine, "teleport"),
+            Err(ConsoleError::UnknownCommand("teleport".to_string()))
+        );
+
+        console.register("echo", |_, args| Ok(args.join("|")));
+        assert_eq!(
+            console.execute(&mut engine, "echo a 'b c'"),
+            Ok("a|b c".to_string())
+        );
+        let help = console.execute(&mut engine, "help").unwrap();
+        assert!(help.contains("echo") && help.contains("timescale"));
+    }
+
+    #[test]
+    fn typing_history_and_capture() {
+        let mut engine = Engine::new_headless();
+        let mut console = Console::new();
+        // Closed console lets keys through, except the toggle key.
+        assert!(!press(&mut console, VirtualKeyCode::W));
+        assert!(press(&mut console, VirtualKeyCode::Grave));
+        assert!(console.is_open());
+        console.process_event(&window_event(WindowEvent::ReceivedCharacter('`')));
+        assert_eq!(console.get_line(), "");
+        assert!(press(&mut console, VirtualKeyCode::W));
+
+        type_line(&mut console, "timescale 2x");
+        press(&mut console, VirtualKeyCode::Back);
+        press(&mut console, VirtualKeyCode::Return);
+        type_line(&mut console, "stats");
+        press(&mut console, VirtualKeyCode::Return);
+        // Empty lines are not recorded.
+        press(&mut console, VirtualKeyCode::Return);
+        console.execute_pending(&mut engine);
+        assert_eq!(engine.get_time_scale(), 2.0);
+        assert_eq!(console.get_history(), ["timescale 2", "stats"]);
+        assert_eq!(console.get_output()[0], "> timescale 2");
+        assert_eq!(console.get_output()[1], "timescale 2");
+
+        press(&mut console, VirtualKeyCode::Up);
+        assert_eq!(console.get_line(), "stats");
+        press(&mut console, VirtualKeyCode::Up);
+        press(&mut console, VirtualKeyCode::Up);
+        assert_eq!(console.get_line(), "timescale 2");
+        press(&mut console, VirtualKeyCode::Down);
+        assert_eq!(console.get_line(), "stats");
+        press(&mut console, VirtualKeyCode::Down);
+        assert_eq!(console.get_line(), "");
+
+        type_line(&mut console, "nope");
+        press(&mut console, VirtualKeyCode::Return);
+        console.execute_pending(&mut engine);
+        assert_eq!(
+            console.get_output().last().unwrap(),
+            "error: unknown command: nope"
+        );
+
+        assert!(press(&mut console, VirtualKeyCode::Escape));
+        assert!(!console.is_open());
+    }
+}
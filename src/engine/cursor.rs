@@ -0,0 +1,154 @@
+use winit::window::CursorGrabMode;
+
+/// Grab modes in order of preference. Locked is ideal for mouse-look but not
+/// supported on Windows, Confined is not supported on macOS.
+pub const GRAB_MODES: [CursorGrabMode; 2] = [CursorGrabMode::Locked, CursorGrabMode::Confined];
+
+/// Tries grab modes in order of preference and returns the first one that
+/// platform accepted, or the error of the last attempt.
+pub fn grab_with_fallback<E>(
+    mut try_grab: impl FnMut(CursorGrabMode) -> Result<(), E>,
+) -> Result<CursorGrabMode, E> {
+    let mut last_error = None;
+    for mode in GRAB_MODES {
+        match try_grab(mode) {
+            Ok(()) => return Ok(mode),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.expect("at least one grab mode"))
+}
+
+/// Requested and applied cursor state. The cursor is released and shown while
+/// window is not focused and restored once focus comes back.
+#[derive(Debug)]
+pub struct CursorState {
+    grab_requested: bool,
+    visible_requested: bool,
+    focused: bool,
+    grab_mode: CursorGrabMode,
+    visible: bool,
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        Self {
+            grab_requested: false,
+            visible_requested: true,
+            focused: true,
+            grab_mode: CursorGrabMode::None,
+            visible: true,
+        }
+    }
+}
+
+impl CursorState {
+    pub fn request_grab(&mut self, grab: bool) {
+        self.grab_requested = grab;
+    }
+
+    pub fn request_visible(&mut self, visible: bool) {
+        self.visible_requested = visible;
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn is_grab_requested(&self) -> bool {
+        self.grab_requested
+    }
+
+    /// Returns (grab, visible) state that must be applied to the window.
+    pub fn desired(&self) -> (bool, bool) {
+        (
+            self.grab_requested && self.focused,
+            self.visible_requested || !self.focused,
+        )
+    }
+
+    pub fn grab_mode(&self) -> CursorGrabMode {
+        self.grab_mode
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.grab_mode != CursorGrabMode::None
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub(crate) fn set_applied_grab(&mut self, mode: CursorGrabMode) {
+        self.grab_mode = mode;
+    }
+
+    pub(crate) fn set_applied_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::window::CursorGrabMode;
+
+    use super::{grab_with_fallback, CursorState};
+
+    #[test]
+    fn fallback_order() {
+        let mut attempts = Vec::new();
+        let result = grab_with_fallback(|mode| {
+            attempts.push(mode);
+            Ok::<(), ()>(())
+        });
+        assert_eq!(result, Ok(CursorGrabMode::Locked));
+        assert_eq!(attempts, vec![CursorGrabMode::Locked]);
+
+        attempts.clear();
+        let result = grab_with_fallback(|mode| {
+            attempts.push(mode);
+            if mode == CursorGrabMode::Locked {
+                Err("unsupported")
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(result, Ok(CursorGrabMode::Confined));
+        assert_eq!(
+            attempts,
+            vec![CursorGrabMode::Locked, CursorGrabMode::Confined]
+        );
+
+        let result = grab_with_fallback(|mode| Err(format!("{:?} refused", mode)));
+        assert_eq!(result, Err("Confined refused".to_string()));
+    }
+
+    #[test]
+    fn focus_state_machine() {
+        let mut cursor = CursorState::default();
+        assert_eq!(cursor.desired(), (false, true));
+
+        cursor.request_grab(true);
+        cursor.request_visible(false);
+        assert_eq!(cursor.desired(), (true, false));
+
+        // Focus loss releases and shows cursor, but keeps the request.
+        cursor.set_focused(false);
+        assert_eq!(cursor.desired(), (false, true));
+        assert!(cursor.is_grab_requested());
+
+        cursor.set_focused(true);
+        assert_eq!(cursor.desired(), (true, false));
+
+        // Release while unfocused must not re-grab on focus gain.
+        cursor.set_focused(false);
+        cursor.request_grab(false);
+        cursor.request_visible(true);
+        cursor.set_focused(true);
+        assert_eq!(cursor.desired(), (false, true));
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashSet;
+
+use nalgebra::Vector2;
+
+use super::input_map::{InputBinding, InputMap};
+use winit::event::{
+    DeviceEvent, ElementState, Event, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// Approximate amount of pixels in one wheel "line" for touchpads that report
+/// pixel deltas.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// Keyboard and mouse state collected from window events. Edge-triggered
+/// queries (`was_key_pressed` etc.) and deltas are accumulated until the engine
+/// consumes them in its next simulation step.
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<VirtualKeyCode>,
+    keys_pressed: HashSet<VirtualKeyCode>,
+    keys_released: HashSet<VirtualKeyCode>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    mouse_position: Vector2<f32>,
+    /// Raw motion from `DeviceEvent::MouseMotion`.
+    mouse_delta: Vector2<f32>,
+    /// Motion derived from `CursorMoved` positions, fallback for platforms
+    /// without raw input.
+    cursor_delta: Vector2<f32>,
+    /// False until the first cursor position after entering the window, so
+    /// the first event does not produce a delta from a stale position.
+    has_cursor_position: bool,
+    raw_motion_available: bool,
+    wheel_delta: f32,
+    input_map: InputMap,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process_event(&mut self, event: &Event<()>) {
+        match event {
+            Event::WindowEvent { event, .. } => self.process_window_event(event),
+            Event::DeviceEvent { event, .. } => self.process_device_event(event),
+            _ => (),
+        }
+    }
+
+    pub fn process_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(key) = input.virtual_keycode {
+                    self.set_key(key, input.state);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => self.set_button(*button, *state),
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = Vector2::new(position.x as f32, position.y as f32);
+                if self.has_cursor_position {
+                    self.cursor_delta += position - self.mouse_position;
+                }
+                self.mouse_position = position;
+                self.has_cursor_position = true;
+            }
+            WindowEvent::CursorEntered { .. } | WindowEvent::CursorLeft { .. } => {
+                self.has_cursor_position = false;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.wheel_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                };
+            }
+            WindowEvent::Focused(false) => {
+                self.release_all();
+                self.has_cursor_position = false;
+            }
+            _ => (),
+        }
+    }
+
+    pub fn process_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.mouse_delta += Vector2::new(delta.0 as f32, delta.1 as f32);
+            self.raw_motion_available = true;
+        }
+    }
+
+    fn set_key(&mut self, key: VirtualKeyCode, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                // Auto-repeat sends more presses for a held key, they're not edges.
+                if self.keys_down.insert(key) {
+                    self.keys_pressed.insert(key);
+                }
+            }
+            ElementState::Released => {
+                if self.keys_down.remove(&key) {
+                    self.keys_released.insert(key);
+                }
+            }
+        }
+    }
+
+    fn set_button(&mut self, button: MouseButton, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if self.buttons_down.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            }
+            ElementState::Released => {
+                if self.buttons_down.remove(&button) {
+                    self.buttons_released.insert(button);
+                }
+            }
+        }
+    }
+
+    /// Releases everything, used when window loses focus and won't receive
+    /// release events anymore, or when keyboard is taken over by UI.
+    pub fn release_all(&mut self) {
+        self.keys_released.extend(self.keys_down.drain());
+        self.buttons_released.extend(self.buttons_down.drain());
+    }
+
+    /// Clears edges and deltas, called by the engine once they were consumed.
+    pub fn clear_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = Vector2::zeros();
+        self.cursor_delta = Vector2::zeros();
+        self.wheel_delta = 0.0;
+    }
+
+    pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn was_key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn was_key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn was_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn was_mouse_button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn input_map(&self) -> &InputMap {
+        &self.input_map
+    }
+
+    pub fn input_map_mut(&mut self) -> &mut InputMap {
+        &mut self.input_map
+    }
+
+    pub fn is_action_active(&self, action: &str) -> bool {
+        self.input_map.is_action_active(self, action)
+    }
+
+    pub fn action_just_pressed(&self, action: &str) -> bool {
+        self.input_map.action_just_pressed(self, action)
+    }
+
+    pub fn action_just_released(&self, action: &str) -> bool {
+        self.input_map.action_just_released(self, action)
+    }
+
+    /// Same as `action_just_pressed`, but also clears press edges of the
+    /// action's bindings. Code that runs every frame uses it so a press is
+    /// handled once even when no simulation step clears the edges.
+    pub fn consume_action_press(&mut self, action: &str) -> bool {
+        let mut pressed = false;
+        for binding in self.input_map.bindings(action) {
+            pressed |= match binding {
+                InputBinding::Key(key) => self.keys_pressed.remove(key),
+                InputBinding::Mouse(button) => self.buttons_pressed.remove(button),
+            };
+        }
+        pressed
+    }
+
+    /// Value of axis from input map in -1..1 range.
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.input_map.axis(self, axis)
+    }
+
+    /// Cursor position in window pixels.
+    pub fn mouse_position(&self) -> Vector2<f32> {
+        self.mouse_position
+    }
+
+    /// Raw mouse motion accumulated since last simulation step.
+    pub fn mouse_delta(&self) -> Vector2<f32> {
+        if self.raw_motion_available {
+            self.mouse_delta
+        } else {
+            self.cursor_delta
+        }
+    }
+
+    /// Returns true once raw mouse motion was received, until then
+    /// `mouse_delta` is derived from cursor positions.
+    pub fn is_raw_motion_available(&self) -> bool {
+        self.raw_motion_available
+    }
+
+    /// Wheel motion in lines accumulated since last simulation step, positive
+    /// values mean scrolling up (away from the user).
+    pub fn wheel_delta(&self) -> f32 {
+        self.wheel_delta
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use nalgebra::Vector2;
+    use winit::{
+        dpi::PhysicalPosition,
+        event::{
+            DeviceEvent, DeviceId, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+            MouseScrollDelta, VirtualKeyCode, WindowEvent,
+        },
+        window::WindowId,
+    };
+
+    use super::InputState;
+
+    pub fn window_event(event: WindowEvent<'static>) -> Event<'static, ()> {
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        }
+    }
+
+    #[allow(deprecated)]
+    pub fn key_event(key: VirtualKeyCode, state: ElementState) -> Event<'static, ()> {
+        window_event(WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState::empty(),
+            },
+            is_synthetic: false,
+        })
+    }
+
+    #[allow(deprecated)]
+    pub fn button_event(button: MouseButton, state: ElementState) -> Event<'static, ()> {
+        window_event(WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button,
+            modifiers: ModifiersState::empty(),
+        })
+    }
+
+    pub fn motion_event(dx: f64, dy: f64) -> Event<'static, ()> {
+        Event::DeviceEvent {
+            device_id: unsafe { DeviceId::dummy() },
+            event: DeviceEvent::MouseMotion { delta: (dx, dy) },
+        }
+    }
+
+    #[allow(deprecated)]
+    pub fn wheel_event(delta: MouseScrollDelta) -> Event<'static, ()> {
+        window_event(WindowEvent::MouseWheel {
+            device_id: unsafe { DeviceId::dummy() },
+            delta,
+            phase: winit::event::TouchPhase::Moved,
+            modifiers: ModifiersState::empty(),
+        })
+    }
+
+    #[test]
+    fn key_edges_are_latched_until_cleared() {
+        let mut input = InputState::new();
+        input.process_event(&key_event(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(input.is_key_down(VirtualKeyCode::W));
+        assert!(input.was_key_pressed(VirtualKeyCode::W));
+
+        // Press and release within one frame keeps both edges.
+        input.process_event(&key_event(VirtualKeyCode::W, ElementState::Released));
+        assert!(!input.is_key_down(VirtualKeyCode::W));
+        assert!(input.was_key_pressed(VirtualKeyCode::W));
+        assert!(input.was_key_released(VirtualKeyCode::W));
+
+        input.clear_frame();
+        assert!(!input.was_key_pressed(VirtualKeyCode::W));
+        assert!(!input.was_key_released(VirtualKeyCode::W));
+    }
+
+    #[test]
+    fn consumed_press_is_seen_once() {
+        let mut input = InputState::new();
+        input.input_map_mut().bind("jump", VirtualKeyCode::Space);
+        input.input_map_mut().bind("jump", MouseButton::Right);
+        input.process_event(&key_event(VirtualKeyCode::Space, ElementState::Pressed));
+        input.process_event(&button_event(MouseButton::Right, ElementState::Pressed));
+
+        assert!(input.consume_action_press("jump"));
+        assert!(!input.consume_action_press("jump"));
+        assert!(!input.action_just_pressed("jump"));
+        // Held state stays, only the edge is gone.
+        assert!(input.is_action_active("jump"));
+        assert!(!input.consume_action_press("missing"));
+    }
+
+    #[test]
+    fn key_repeat_is_not_an_edge() {
+        let mut input = InputState::new();
+        input.process_event(&key_event(VirtualKeyCode::Space, ElementState::Pressed));
+        input.clear_frame();
+        input.process_event(&key_event(VirtualKeyCode::Space, ElementState::Pressed));
+        assert!(input.is_key_down(VirtualKeyCode::Space));
+        assert!(!input.was_key_pressed(VirtualKeyCode::Space));
+    }
+
+    #[test]
+    fn mouse_state() {
+        let mut input = InputState::new();
+        input.process_event(&button_event(MouseButton::Left, ElementState::Pressed));
+        input.process_event(&motion_event(3.0, -1.0));
+        input.process_event(&motion_event(2.0, 4.0));
+        input.process_event(&wheel_event(MouseScrollDelta::LineDelta(0.0, 1.0)));
+        input.process_event(&wheel_event(MouseScrollDelta::PixelDelta(
+            PhysicalPosition::new(0.0, 40.0),
+        )));
+        input.process_event(&window_event(WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(100.0, 50.0),
+            #[allow(deprecated)]
+            modifiers: ModifiersState::empty(),
+        }));
+
+        assert!(input.is_mouse_button_down(MouseButton::Left));
+        assert!(input.was_mouse_button_pressed(MouseButton::Left));
+        assert_eq!(input.mouse_delta().x, 5.0);
+        assert_eq!(input.mouse_delta().y, 3.0);
+        assert_eq!(input.wheel_delta(), 3.0);
+        assert_eq!(input.mouse_position().x, 100.0);
+
+        input.clear_frame();
+        assert_eq!(input.mouse_delta().norm(), 0.0);
+        assert_eq!(input.wheel_delta(), 0.0);
+        assert!(input.is_mouse_button_down(MouseButton::Left));
+        assert!(!input.was_mouse_button_pressed(MouseButton::Left));
+        assert_eq!(input.mouse_position().y, 50.0);
+    }
+
+    fn cursor_moved(x: f64, y: f64) -> Event<'static, ()> {
+        window_event(WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(x, y),
+            #[allow(deprecated)]
+            modifiers: ModifiersState::empty(),
+        })
+    }
+
+    #[test]
+    fn cursor_motion_fallback() {
+        let mut input = InputState::new();
+        // First position must not produce a jump from (0, 0).
+        input.process_event(&cursor_moved(400.0, 300.0));
+        assert_eq!(input.mouse_delta(), Vector2::zeros());
+
+        // Several events between updates are accumulated.
+        input.process_event(&cursor_moved(410.0, 300.0));
+        input.process_event(&cursor_moved(415.0, 290.0));
+        assert_eq!(input.mouse_delta(), Vector2::new(15.0, -10.0));
+        input.clear_frame();
+        assert_eq!(input.mouse_delta(), Vector2::zeros());
+
+        // Re-entering the window starts from the new position.
+        input.process_event(&window_event(WindowEvent::CursorLeft {
+            device_id: unsafe { DeviceId::dummy() },
+        }));
+        input.process_event(&cursor_moved(0.0, 0.0));
+        input.process_event(&cursor_moved(2.0, 1.0));
+        assert_eq!(input.mouse_delta(), Vector2::new(2.0, 1.0));
+
+        // Raw motion takes over as soon as it is available.
+        assert!(!input.is_raw_motion_available());
+        input.clear_frame();
+        input.process_event(&motion_event(1.0, 1.0));
+        input.process_event(&motion_event(0.5, -3.0));
+        input.process_event(&cursor_moved(100.0, 100.0));
+        assert!(input.is_raw_motion_available());
+        assert_eq!(input.mouse_delta(), Vector2::new(1.5, -2.0));
+        input.clear_frame();
+        assert_eq!(input.mouse_delta(), Vector2::zeros());
+    }
+
+    #[test]
+    fn focus_loss_releases_keys() {
+        let mut input = InputState::new();
+        input.process_event(&key_event(VirtualKeyCode::A, ElementState::Pressed));
+        input.clear_frame();
+        input.process_event(&window_event(WindowEvent::Focused(false)));
+        assert!(!input.is_key_down(VirtualKeyCode::A));
+        assert!(input.was_key_released(VirtualKeyCode::A));
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use super::input::InputState;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+impl From<VirtualKeyCode> for InputBinding {
+    fn from(key: VirtualKeyCode) -> Self {
+        InputBinding::Key(key)
+    }
+}
+
+impl From<MouseButton> for InputBinding {
+    fn from(button: MouseButton) -> Self {
+        InputBinding::Mouse(button)
+    }
+}
+
+impl InputBinding {
+    fn is_down(&self, input: &InputState) -> bool {
+        match self {
+            InputBinding::Key(key) => input.is_key_down(*key),
+            InputBinding::Mouse(button) => input.is_mouse_button_down(*button),
+        }
+    }
+
+    fn was_pressed(&self, input: &InputState) -> bool {
+        match self {
+            InputBinding::Key(key) => input.was_key_pressed(*key),
+            InputBinding::Mouse(button) => input.was_mouse_button_pressed(*button),
+        }
+    }
+
+    fn was_released(&self, input: &InputState) -> bool {
+        match self {
+            InputBinding::Key(key) => input.was_key_released(*key),
+            InputBinding::Mouse(button) => input.was_mouse_button_released(*button),
+        }
+    }
+}
+
+/// Axis made of two actions, e.g. "move_x" of "move_left" and "move_right".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AxisBinding {
+    pub negative: String,
+    pub positive: String,
+}
+
+/// Maps named actions onto one or more keys or mouse buttons.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    #[serde(default)]
+    actions: BTreeMap<String, Vec<InputBinding>>,
+    #[serde(default)]
+    axes: BTreeMap<String, AxisBinding>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds binding to action, action is created if it does not exist.
+    pub fn bind(&mut self, action: &str, binding: impl Into<InputBinding>) {
+        let binding = binding.into();
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Replaces every binding of action with given one.
+    pub fn rebind(&mut self, action: &str, binding: impl Into<InputBinding>) {
+        self.actions
+            .insert(action.to_string(), vec![binding.into()]);
+    }
+
+    /// Replaces bindings of action keeping their order, first one is primary.
+    pub fn set_bindings(&mut self, action: &str, bindings: Vec<InputBinding>) {
+        self.actions.insert(action.to_string(), bindings);
+    }
+
+    /// Returns first action that uses given binding.
+    pub fn find_action(&self, binding: impl Into<InputBinding>) -> Option<&str> {
+        let binding = binding.into();
+        self.actions
+            .iter()
+            .find(|(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| action.as_str())
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: impl Into<InputBinding>) {
+        let binding = binding.into();
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, negative: &str, positive: &str) {
+        self.axes.insert(
+            axis.to_string(),
+            AxisBinding {
+                negative: negative.to_string(),
+                positive: positive.to_string(),
+            },
+        );
+    }
+
+    pub fn bindings(&self, action: &str) -> &[InputBinding] {
+        self.actions.get(action).map_or(&[], |b| b.as_slice())
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = (&str, &[InputBinding])> {
+        self.actions.iter().map(|(k, v)| (k.as_str(), v.as_slice()))
+    }
+
+    pub fn is_action_active(&self, input: &InputState, action: &str) -> bool {
+        self.bindings(action).iter().any(|b| b.is_down(input))
+    }
+
+    pub fn action_just_pressed(&self, input: &InputState, action: &str) -> bool {
+        self.bindings(action).iter().any(|b| b.was_pressed(input))
+    }
+
+    pub fn action_just_released(&self, input: &InputState, action: &str) -> bool {
+        // Action is released only when its last held binding goes up.
+        !self.is_action_active(input, action)
+            && self.bindings(action).iter().any(|b| b.was_released(input))
+    }
+
+    /// Returns -1, 0 or 1 depending on which half of the axis is held, 0 if both.
+    pub fn axis(&self, input: &InputState, axis: &str) -> f32 {
+        match self.axes.get(axis) {
+            Some(binding) => {
+                let mut value = 0.0;
+                if self.is_action_active(input, &binding.negative) {
+                    value -= 1.0;
+                }
+                if self.is_action_active(input, &binding.positive) {
+                    value += 1.0;
+                }
+                value
+            }
+            None => 0.0,
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::{ElementState, MouseButton, VirtualKeyCode};
+
+    use super::{InputBinding, InputMap};
+    use crate::engine::input::{
+        tests::{button_event, key_event},
+        InputState,
+    };
+
+    fn press(input: &mut InputState, key: VirtualKeyCode) {
+        input.process_event(&key_event(key, ElementState::Pressed));
+    }
+
+    fn release(input: &mut InputState, key: VirtualKeyCode) {
+        input.process_event(&key_event(key, ElementState::Released));
+    }
+
+    fn movement_map() -> InputMap {
+        let mut map = InputMap::new();
+        map.bind("move_left", VirtualKeyCode::A);
+        map.bind("move_left", VirtualKeyCode::Left);
+        map.bind("move_right", VirtualKeyCode::D);
+        map.bind("move_right", VirtualKeyCode::Right);
+        map.bind("fire", MouseButton::Left);
+        map.bind_axis("move_x", "move_left", "move_right");
+        map
+    }
+
+    #[test]
+    fn multiple_keys_per_action() {
+        let map = movement_map();
+        let mut input = InputState::new();
+        press(&mut input, VirtualKeyCode::Left);
+        assert!(map.is_action_active(&input, "move_left"));
+        assert!(map.action_just_pressed(&input, "move_left"));
+        press(&mut input, VirtualKeyCode::A);
+        release(&mut input, VirtualKeyCode::Left);
+        assert!(map.is_action_active(&input, "move_left"));
+        assert!(!map.action_just_released(&input, "move_left"));
+        release(&mut input, VirtualKeyCode::A);
+        assert!(map.action_just_released(&input, "move_left"));
+
+        input.process_event(&button_event(MouseButton::Left, ElementState::Pressed));
+        assert!(map.action_just_pressed(&input, "fire"));
+        assert!(!map.is_action_active(&input, "unknown"));
+    }
+
+    #[test]
+    fn rebind_mid_session() {
+        let mut map = movement_map();
+        let mut input = InputState::new();
+        press(&mut input, VirtualKeyCode::A);
+        assert!(map.is_action_active(&input, "move_left"));
+
+        map.rebind("move_left", VirtualKeyCode::J);
+        assert!(!map.is_action_active(&input, "move_left"));
+        press(&mut input, VirtualKeyCode::J);
+        assert!(map.is_action_active(&input, "move_left"));
+        assert_eq!(
+            map.bindings("move_left"),
+            &[InputBinding::Key(VirtualKeyCode::J)]
+        );
+    }
+
+    #[test]
+    fn find_action_by_binding() {
+        let mut map = movement_map();
+        assert_eq!(map.find_action(VirtualKeyCode::Left), Some("move_left"));
+        assert_eq!(map.find_action(MouseButton::Left), Some("fire"));
+        assert_eq!(map.find_action(VirtualKeyCode::Q), None);
+
+        map.set_bindings(
+            "move_left",
+            vec![VirtualKeyCode::Q.into(), VirtualKeyCode::A.into()],
+        );
+        assert_eq!(map.find_action(VirtualKeyCode::Left), None);
+        assert_eq!(
+            map.bindings("move_left")[0],
+            InputBinding::Key(VirtualKeyCode::Q)
+        );
+    }
+
+    #[test]
+    fn axis_combination() {
+        let map = movement_map();
+        let mut input = InputState::new();
+        assert_eq!(map.axis(&input, "move_x"), 0.0);
+        press(&mut input, VirtualKeyCode::D);
+        assert_eq!(map.axis(&input, "move_x"), 1.0);
+        press(&mut input, VirtualKeyCode::Left);
+        assert_eq!(map.axis(&input, "move_x"), 0.0);
+        release(&mut input, VirtualKeyCode::D);
+        assert_eq!(map.axis(&input, "move_x"), -1.0);
+        assert_eq!(map.axis(&input, "move_y"), 0.0);
+    }
+
+    #[test]
+    fn input_state_queries() {
+        let mut input = InputState::new();
+        *input.input_map_mut() = movement_map();
+        press(&mut input, VirtualKeyCode::Right);
+        assert!(input.is_action_active("move_right"));
+        assert!(input.action_just_pressed("move_right"));
+        assert_eq!(input.axis("move_x"), 1.0);
+        release(&mut input, VirtualKeyCode::Right);
+        assert!(input.action_just_released("move_right"));
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let map = movement_map();
+        let text = map.to_toml().unwrap();
+        assert_eq!(InputMap::from_toml(&text).unwrap(), map);
+
+        let map = InputMap::from_toml(
+            r#"
+            [actions]
+            jump = [{ Key = "Space" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            map.bindings("jump"),
+            &[InputBinding::Key(VirtualKeyCode::Space)]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::time::{Duration, Instant};
+
+/// Source of time for the frame limiter, abstracted so waiting logic can be
+/// tested without real sleeps.
+pub trait Clock {
+    /// Time elapsed since some fixed point.
+    fn now(&mut self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+    /// Called on every iteration of the busy wait.
+    fn spin(&mut self);
+}
+
+#[derive(Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&mut self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    fn spin(&mut self) {
+        std::hint::spin_loop();
+    }
+}
+
+/// Time left for the frame that started at `frame_start`.
+pub fn remaining_budget(frame_start: Duration, budget: Duration, now: Duration) -> Duration {
+    (frame_start + budget).saturating_sub(now)
+}
+
+/// Waits until `deadline`: sleeps coarsely while more than `spin_threshold` is
+/// left (OS sleep granularity is poor), then spins for the rest.
+pub fn wait_until(clock: &mut impl Clock, deadline: Duration, spin_threshold: Duration) {
+    loop {
+        let now = clock.now();
+        if now >= deadline {
+            break;
+        }
+        let left = deadline - now;
+        if left > spin_threshold {
+            clock.sleep(left - spin_threshold);
+        } else {
+            clock.spin();
+        }
+    }
+}
+
+/// Caps frame rate by waiting out the rest of frame budget at the end of
+/// each frame. Only meant for non-vsync operation.
+#[derive(Debug)]
+pub struct FrameLimiter<C: Clock = SystemClock> {
+    clock: C,
+    target_fps: Option<u32>,
+    frame_start: Option<Duration>,
+    spin_threshold: Duration,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new(SystemClock::default())
+    }
+}
+
+impl<C: Clock> FrameLimiter<C> {
+    /// Part of the budget that is busy-waited instead of slept.
+    pub const DEFAULT_SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            target_fps: None,
+            frame_start: None,
+            spin_threshold: Self::DEFAULT_SPIN_THRESHOLD,
+        }
+    }
+
+    pub fn set_target_fps(&mut self, fps: Option<u32>) {
+        self.target_fps = fps.filter(|fps| *fps > 0);
+        self.frame_start = None;
+    }
+
+    pub fn target_fps(&self) -> Option<u32> {
+        self.target_fps
+    }
+
+    pub fn budget(&self) -> Option<Duration> {
+        self.target_fps
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64))
+    }
+
+    /// Forgets current frame start, next `end_frame` starts measuring anew.
+    pub fn reset(&mut self) {
+        self.frame_start = None;
+    }
+
+    /// Waits out the rest of current frame budget. Frames are scheduled back
+    /// to back so waiting jitter does not accumulate; if a frame ran late the
+    /// schedule restarts from now instead of rushing to catch up.
+    pub fn end_frame(&mut self) {
+        let Some(budget) = self.budget() else {
+            return;
+        };
+        let now = self.clock.now();
+        let frame_start = match self.frame_start {
+            Some(frame_start) => frame_start,
+            None => {
+                self.frame_start = Some(now);
+                return;
+            }
+        };
+        let deadline = frame_start + budget;
+        if remaining_budget(frame_start, budget, now).is_zero() {
+            self.frame_start = Some(now);
+            return;
+        }
+        wait_until(&mut self.clock, deadline, self.spin_threshold);
+        self.frame_start = Some(deadline);
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{remaining_budget, wait_until, Clock, FrameLimiter};
+
+    /// Fake clock, sleeps overshoot by `oversleep` and each spin takes `spin_step`.
+    #[derive(Default)]
+    struct MockClock {
+        now: Duration,
+        oversleep: Duration,
+        spin_step: Duration,
+        sleeps: Vec<Duration>,
+        spins: u32,
+    }
+
+    impl Clock for MockClock {
+        fn now(&mut self) -> Duration {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.sleeps.push(duration);
+            self.now += duration + self.oversleep;
+        }
+
+        fn spin(&mut self) {
+            self.spins += 1;
+            self.now += self.spin_step;
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn budget() {
+        let mut limiter = FrameLimiter::new(MockClock::default());
+        assert_eq!(limiter.budget(), None);
+        limiter.set_target_fps(Some(100));
+        assert_eq!(limiter.budget(), Some(ms(10)));
+        limiter.set_target_fps(Some(0));
+        assert_eq!(limiter.budget(), None);
+
+        assert_eq!(remaining_budget(ms(0), ms(10), ms(4)), ms(6));
+        assert_eq!(remaining_budget(ms(0), ms(10), ms(15)), Duration::ZERO);
+    }
+
+    #[test]
+    fn hybrid_wait() {
+        let mut clock = MockClock {
+            oversleep: Duration::from_micros(500),
+            spin_step: Duration::from_micros(100),
+            ..Default::default()
+        };
+        wait_until(&mut clock, ms(10), ms(2));
+        assert_eq!(clock.sleeps, vec![ms(8)]);
+        assert!(clock.spins > 0);
+        assert!(clock.now >= ms(10));
+        assert!(clock.now < ms(10) + Duration::from_micros(200));
+
+        // Nothing to wait for.
+        let mut clock = MockClock {
+            now: ms(20),
+            ..Default::default()
+        };
+        wait_until(&mut clock, ms(10), ms(2));
+        assert!(clock.sleeps.is_empty());
+        assert_eq!(clock.spins, 0);
+    }
+
+    #[test]
+    fn frames_are_paced() {
+        let clock = MockClock {
+            spin_step: Duration::from_micros(100),
+            ..Default::default()
+        };
+        let mut limiter = FrameLimiter::new(clock);
+        limiter.set_target_fps(Some(100));
+
+        limiter.end_frame();
+        for frame in 1..=3 {
+            limiter.clock.now += ms(3);
+            limiter.end_frame();
+            assert_eq!(limiter.clock().now, ms(10 * frame));
+        }
+
+        // Late frame does not wait and does not cause a catch-up burst.
+        limiter.clock.now += ms(25);
+        let late = limiter.clock().now;
+        limiter.end_frame();
+        assert_eq!(limiter.clock().now, late);
+        limiter.clock.now += ms(1);
+        limiter.end_frame();
+        assert_eq!(limiter.clock().now, late + ms(10));
+    }
+}
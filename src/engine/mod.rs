@@ -1,35 +1,140 @@
-use std::{cell::RefCell, path::Path, rc::Rc};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
 
-use winit::event_loop::EventLoop;
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::CursorGrabMode,
+};
 
+use self::{
+    cursor::{grab_with_fallback, CursorState},
+    input::InputState,
+    limiter::FrameLimiter,
+    state::RunState,
+    stats::{EngineStats, StatsAccumulator},
+    system::{EngineSystem, SystemContext, SystemId, SystemPhase, SystemRegistry},
+    timestep::FixedTimestep,
+    window::WindowState,
+};
 use crate::{
-    renderer::renderer::Renderer,
-    resource::{texture::Texture, Resource, ResourceKind},
+    renderer::renderer::{Renderer, WindowSettings},
+    resource::{
+        model::{Model, ModelHandle},
+        texture::Texture,
+        Resource, ResourceError, ResourceKind,
+    },
     scene::Scene,
     utils::pool::{Handle, Pool},
 };
 
+pub mod console;
+pub mod cursor;
+pub mod input;
+pub mod input_map;
+pub mod limiter;
+pub mod state;
+pub mod stats;
+pub mod system;
+pub mod timestep;
+pub mod window;
+
 pub struct Engine {
-    pub renderer: Renderer,
+    /// `None` for headless engine.
+    renderer: Option<Renderer>,
+    pub input: InputState,
     scenes: Pool<Scene>,
     resources: Vec<Rc<RefCell<Resource>>>,
-    running: bool,
+    state: RunState,
+    timestep: FixedTimestep,
+    interpolation: bool,
+    cursor: CursorState,
+    limiter: FrameLimiter,
+    stats: StatsAccumulator,
+    last_frame_time: Instant,
+    time_scale: f32,
+    unscaled_delta_time: f32,
+    systems: SystemRegistry,
+    window: WindowState,
+    /// Relative resource paths are loaded from this directory.
+    asset_root: PathBuf,
 }
 
+/// Upper bound of frame time, so long stalls (debugger pause, window drag) do not
+/// make simulation jump too far in a single update.
+pub const MAX_DELTA_TIME: f32 = 0.1;
+
+/// Aspect ratio used for cameras of headless engine.
+pub const HEADLESS_ASPECT_RATIO: f32 = 4.0 / 3.0;
+
 impl Engine {
     pub fn new(el: &EventLoop<()>) -> Self {
+        Self::with_renderer(Some(Renderer::new(el)))
+    }
+
+    pub fn with_window_settings(el: &EventLoop<()>, settings: &WindowSettings) -> Self {
+        Self::with_renderer(Some(Renderer::with_settings(el, settings)))
+    }
+
+    /// Creates engine without window and GL context. Scenes, updates and
+    /// resource loading work as usual (textures are decoded but never
+    /// uploaded), `render` does nothing.
+    pub fn new_headless() -> Self {
+        Self::with_renderer(None)
+    }
+
+    fn with_renderer(renderer: Option<Renderer>) -> Self {
+        let mut window = WindowState::new(HEADLESS_ASPECT_RATIO);
+        if let Some(renderer) = &renderer {
+            let size = renderer.context.inner_size();
+            window.on_resize(size.width, size.height);
+        }
         Engine {
-            renderer: Renderer::new(el),
+            window,
+            renderer,
+            input: InputState::new(),
             scenes: Pool::new(),
             resources: Vec::new(),
-            running: true,
+            state: RunState::new(Instant::now()),
+            timestep: FixedTimestep::default(),
+            interpolation: true,
+            cursor: CursorState::default(),
+            limiter: FrameLimiter::default(),
+            stats: StatsAccumulator::default(),
+            last_frame_time: Instant::now(),
+            time_scale: 1.0,
+            unscaled_delta_time: 0.0,
+            systems: SystemRegistry::default(),
+            asset_root: PathBuf::from("."),
         }
     }
 
+    pub fn is_headless(&self) -> bool {
+        self.renderer.is_none()
+    }
+
+    pub fn renderer(&self) -> Option<&Renderer> {
+        self.renderer.as_ref()
+    }
+
+    pub fn renderer_mut(&mut self) -> Option<&mut Renderer> {
+        self.renderer.as_mut()
+    }
+
     pub fn add_scene(&mut self, scene: Scene) -> Handle<Scene> {
         self.scenes.spawn(scene)
     }
 
+    /// Removes scene from the engine and hands it back, None for stale
+    /// handles.
+    pub fn take_scene(&mut self, handle: &Handle<Scene>) -> Option<Scene> {
+        self.scenes.take(handle.clone())
+    }
+
     pub fn borrow_scene(&self, handle: &Handle<Scene>) -> Option<&Scene> {
         if let Some(scene) = self.scenes.borrow(handle) {
             return Some(scene);
@@ -44,11 +149,57 @@ impl Engine {
         None
     }
 
+    /// Enables or disables both update and render of the scene.
+    pub fn set_scene_enabled(&mut self, handle: &Handle<Scene>, enabled: bool) {
+        if let Some(scene) = self.scenes.borrow_mut(handle) {
+            scene.set_update_enabled(enabled);
+            scene.set_render_enabled(enabled);
+        }
+    }
+
+    pub fn set_scene_update_enabled(&mut self, handle: &Handle<Scene>, enabled: bool) {
+        if let Some(scene) = self.scenes.borrow_mut(handle) {
+            scene.set_update_enabled(enabled);
+        }
+    }
+
+    pub fn set_scene_render_enabled(&mut self, handle: &Handle<Scene>, enabled: bool) {
+        if let Some(scene) = self.scenes.borrow_mut(handle) {
+            scene.set_render_enabled(enabled);
+        }
+    }
+
+    /// Enables specified scene and disables all others.
+    pub fn set_active_scene(&mut self, handle: &Handle<Scene>) {
+        for (scene_handle, scene) in self.scenes.pair_iter_mut() {
+            let enabled = scene_handle == *handle;
+            scene.set_update_enabled(enabled);
+            scene.set_render_enabled(enabled);
+        }
+    }
+
+    /// Sets directory relative resource paths are resolved against, current
+    /// directory by default. Resources keep the paths they were requested
+    /// with, so already loaded ones are still found by them.
+    pub fn set_asset_root(&mut self, root: &Path) {
+        self.asset_root = root.to_path_buf();
+    }
+
+    pub fn get_asset_root(&self) -> &Path {
+        &self.asset_root
+    }
+
+    /// Path a resource requested with `path` is loaded from, absolute paths
+    /// are kept as is.
+    pub fn resolve_asset_path(&self, path: &Path) -> PathBuf {
+        self.asset_root.join(path)
+    }
+
     pub fn request_texture(&mut self, path: &Path) -> Option<Rc<RefCell<Resource>>> {
         for existing in self.resources.iter() {
             let resource = existing.borrow_mut();
             if resource.path == path {
-                if let ResourceKind::Texture(tex) = resource.borrow_kind() {
+                if let ResourceKind::Texture(_tex) = resource.borrow_kind() {
                     return Some(existing.clone());
                 } else {
                     println!("{:?} 资源不合法!", path);
@@ -57,7 +208,7 @@ impl Engine {
             }
         }
 
-        if let Ok(texture) = Texture::load(path) {
+        if let Ok(texture) = Texture::load(&self.resolve_asset_path(path)) {
             let resource = Rc::new(RefCell::new(Resource::new(
                 path,
                 ResourceKind::Texture(texture),
@@ -69,32 +220,857 @@ impl Engine {
         None
     }
 
-    pub fn update(&mut self) {
-        let client_size = self.renderer.context.inner_size();
-        let aspect_ratio = client_size.width as f32 / client_size.height as f32;
-        for i in 0..self.scenes.capacity() {
-            if let Some(scene) = self.scenes.at_mut(i) {
-                scene.update(aspect_ratio);
+    /// Loads model or returns already loaded one, textures of the model are
+    /// requested along with it. Errors are logged and returned to the caller.
+    pub fn request_model(&mut self, path: &Path) -> Result<ModelHandle, ResourceError> {
+        for existing in self.resources.iter() {
+            let resource = existing.borrow();
+            if resource.path == path {
+                if let ResourceKind::Model(_) = resource.borrow_kind() {
+                    return Ok(ModelHandle(existing.clone()));
+                }
+                println!("{:?} 资源不合法!", path);
+                return Err(ResourceError::KindMismatch(path.to_path_buf()));
+            }
+        }
+
+        match Model::load(&self.resolve_asset_path(path)) {
+            Ok(mut model) => {
+                // Texture paths are relative to the model file.
+                let directory = path.parent().unwrap_or(Path::new(""));
+                for surface in model.surfaces_mut() {
+                    if let Some(texture_path) = surface.texture_path.as_ref() {
+                        let texture_path = directory.join(texture_path);
+                        surface.texture = self.request_texture(&texture_path);
+                        if surface.texture.is_none() {
+                            println!("Failed to load texture {:?}", texture_path);
+                        }
+                    }
+                }
+                let handle = ModelHandle::new(path, model);
+                self.resources.push(handle.resource().clone());
+                Ok(handle)
+            }
+            Err(err) => {
+                println!("Failed to load model {:?}: {}", path, err);
+                Err(err)
             }
         }
     }
 
-    pub fn render(&mut self) {
-        self.renderer.upload_resources(&mut self.resources);
-        let mut alive_scenes: Vec<&Scene> = Vec::new();
-        for i in 0..self.scenes.capacity() {
-            if let Some(scene) = self.scenes.at(i) {
-                alive_scenes.push(scene);
+    /// Advances simulation by the real time passed since previous call. Time is
+    /// consumed in fixed steps, for each step `fixed_update` is called with the
+    /// step length right before scenes are updated.
+    pub fn update(&mut self, fixed_update: impl FnMut(&mut Engine, f32)) {
+        let frame_time = self.state.frame_time(Instant::now());
+        self.advance(frame_time, fixed_update);
+    }
+
+    /// Same as `update`, but with explicit frame time instead of real time,
+    /// useful for tests and tools that drive simulation manually.
+    pub fn advance(&mut self, frame_time: f32, mut fixed_update: impl FnMut(&mut Engine, f32)) {
+        let update_start = Instant::now();
+        let frame_time = frame_time.min(MAX_DELTA_TIME);
+        self.unscaled_delta_time = frame_time;
+        if self.state.is_paused() || self.time_scale == 0.0 {
+            // Scenes and game code are frozen, last state keeps rendering.
+            // Input that arrived while frozen is dropped.
+            self.input.clear_frame();
+            self.stats.record_update(0.0, 0, 0);
+            return;
+        }
+
+        // Time scale changes amount of fixed steps, not their length, so
+        // simulation stays deterministic at any speed.
+        let (mut scenes_updated, mut nodes_updated) = (0, 0);
+        let steps = self.timestep.advance(frame_time * self.time_scale);
+        for _ in 0..steps {
+            let dt = self.timestep.step();
+            fixed_update(self, dt);
+            self.run_systems(SystemPhase::PreUpdate, dt);
+            let (scenes, nodes) = self.update_scenes(dt);
+            scenes_updated += scenes;
+            nodes_updated += nodes;
+            self.run_systems(SystemPhase::Update, dt);
+            self.run_systems(SystemPhase::PostUpdate, dt);
+            // Input edges and deltas are seen by exactly one step, if no step
+            // happens this frame they stay latched for the next one.
+            self.input.clear_frame();
+        }
+        self.stats.record_update(
+            update_start.elapsed().as_secs_f32(),
+            scenes_updated,
+            nodes_updated,
+        );
+    }
+
+    /// Feeds window and device events into engine input state.
+    pub fn process_event(&mut self, event: &Event<()>) {
+        self.input.process_event(event);
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } => {
+                self.cursor.set_focused(*focused);
+                self.apply_cursor_state();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => self.on_resize(size.width, size.height),
+            _ => (),
+        }
+    }
+
+    fn on_resize(&mut self, width: u32, height: u32) {
+        self.window.on_resize(width, height);
+        if let Some(renderer) = &mut self.renderer {
+            renderer.resize(width, height);
+        }
+    }
+
+    /// Returns true while window has zero-area client size (minimized),
+    /// rendering is skipped in this state.
+    pub fn is_render_suspended(&self) -> bool {
+        self.window.is_suspended()
+    }
+
+    /// Grabs cursor inside the window (for mouse-look). Grab is released
+    /// automatically while window is not focused.
+    pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
+        self.cursor.request_grab(grabbed);
+        self.apply_cursor_state();
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor.request_visible(visible);
+        self.apply_cursor_state();
+    }
+
+    /// Returns true if cursor is currently grabbed by the window.
+    pub fn is_cursor_grabbed(&self) -> bool {
+        self.cursor.is_grabbed()
+    }
+
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor.is_visible()
+    }
+
+    pub fn cursor_state(&self) -> &CursorState {
+        &self.cursor
+    }
+
+    fn apply_cursor_state(&mut self) {
+        let Some(renderer) = &self.renderer else {
+            return;
+        };
+        let (grab, visible) = self.cursor.desired();
+        let window = &renderer.context;
+
+        if grab != self.cursor.is_grabbed() {
+            if grab {
+                match grab_with_fallback(|mode| window.set_cursor_grab(mode)) {
+                    Ok(mode) => self.cursor.set_applied_grab(mode),
+                    Err(err) => println!("Failed to grab cursor: {}", err),
+                }
+            } else {
+                if let Err(err) = window.set_cursor_grab(CursorGrabMode::None) {
+                    println!("Failed to release cursor: {}", err);
+                }
+                self.cursor.set_applied_grab(CursorGrabMode::None);
+            }
+        }
+
+        if visible != self.cursor.is_visible() {
+            window.set_cursor_visible(visible);
+            self.cursor.set_applied_visible(visible);
+        }
+    }
+
+    /// Registers system in `Update` phase, systems with lower order run first.
+    pub fn register_system(&mut self, system: Box<dyn EngineSystem>, order: i32) -> SystemId {
+        self.systems.register(system, SystemPhase::Update, order)
+    }
+
+    pub fn register_system_in_phase(
+        &mut self,
+        system: Box<dyn EngineSystem>,
+        phase: SystemPhase,
+        order: i32,
+    ) -> SystemId {
+        self.systems.register(system, phase, order)
+    }
+
+    pub fn remove_system(&mut self, id: SystemId) -> Option<Box<dyn EngineSystem>> {
+        self.systems.remove(id)
+    }
+
+    fn run_systems(&mut self, phase: SystemPhase, dt: f32) {
+        let mut ctx = SystemContext {
+            scenes: &mut self.scenes,
+            resources: &mut self.resources,
+            input: &self.input,
+            cursor_grabbed: self.cursor.is_grabbed(),
+            client_size: self.window.client_size(),
+            dt,
+        };
+        self.systems.run(phase, &mut ctx);
+    }
+
+    fn scenes_to_render(scenes: &Pool<Scene>) -> Vec<&Scene> {
+        scenes
+            .iter()
+            .filter(|scene| scene.is_render_enabled())
+            .collect()
+    }
+
+    /// Returns number of updated scenes and nodes.
+    fn update_scenes(&mut self, dt: f32) -> (usize, usize) {
+        let aspect_ratio = self.window.aspect_ratio();
+        let (mut scenes, mut nodes) = (0, 0);
+        for scene in self.scenes.iter_mut() {
+            if scene.is_update_enabled() {
+                nodes += scene.update(aspect_ratio, dt);
+                scenes += 1;
             }
         }
-        self.renderer.render(alive_scenes.as_slice());
+        (scenes, nodes)
+    }
+
+    /// Returns length of fixed simulation step in seconds.
+    pub fn get_delta_time(&self) -> f32 {
+        self.timestep.step()
+    }
+
+    /// Real time of the last frame, not affected by time scale or pause.
+    pub fn get_unscaled_delta_time(&self) -> f32 {
+        self.unscaled_delta_time
+    }
+
+    /// Sets simulation speed multiplier, 0 freezes simulation while rendering
+    /// continues. Input and renderer stay on real time.
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = if scale.is_finite() {
+            scale.max(0.0)
+        } else {
+            1.0
+        };
+    }
+
+    pub fn get_time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_simulation_rate(&mut self, rate_hz: f32) {
+        self.timestep.set_rate(rate_hz);
+    }
+
+    /// Limits amount of simulation steps performed in a single frame.
+    pub fn set_max_simulation_steps(&mut self, max_steps: u32) {
+        self.timestep.set_max_steps(max_steps);
+    }
+
+    /// Enables blending of node transforms between two last simulation steps.
+    pub fn set_interpolation(&mut self, interpolation: bool) {
+        self.interpolation = interpolation;
+    }
+
+    /// Blend factor between previous and current simulation state used for
+    /// rendering, 1.0 if interpolation is disabled.
+    pub fn get_interpolation_alpha(&self) -> f32 {
+        if self.interpolation {
+            self.timestep.alpha()
+        } else {
+            1.0
+        }
+    }
+
+    pub fn render(&mut self) {
+        let alpha = self.get_interpolation_alpha();
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        if self.window.is_suspended() {
+            // Nothing to draw into while minimized, do not swap either.
+            self.last_frame_time = Instant::now();
+            return;
+        }
+        let render_start = Instant::now();
+        self.stats
+            .push_frame_time((render_start - self.last_frame_time).as_secs_f32());
+        self.last_frame_time = render_start;
+
+        renderer.upload_resources(&self.resources);
+        let scenes = Self::scenes_to_render(&self.scenes);
+        renderer.render(scenes.as_slice(), alpha);
+        renderer.present();
+        self.stats
+            .record_render(render_start.elapsed().as_secs_f32(), renderer.get_stats());
+        // Event loop keeps polling, so the limiter blocks here. With vsync on
+        // swap already waits and the limiter must not add its own delay.
+        if renderer.is_vsync_enabled() {
+            self.limiter.reset();
+        } else {
+            self.limiter.end_frame();
+        }
+    }
+
+    /// Returns frame timings and counters of recent frames.
+    pub fn stats(&self) -> &EngineStats {
+        self.stats.stats()
+    }
+
+    /// One line summary of `stats`, for logging or on-screen display.
+    pub fn stats_string(&self) -> String {
+        self.stats.stats().to_string()
+    }
+
+    /// Caps frame rate while vsync is disabled, `None` removes the cap.
+    pub fn set_target_fps(&mut self, fps: Option<u32>) {
+        self.limiter.set_target_fps(fps);
+    }
+
+    pub fn get_target_fps(&self) -> Option<u32> {
+        self.limiter.target_fps()
     }
 
     pub fn is_running(&self) -> bool {
-        self.running
+        !self.state.should_exit()
     }
 
+    /// Requests exit, main loop must stop once `should_exit` returns true.
     pub fn stop(&mut self) {
-        self.running = false;
+        self.state.request_exit();
+    }
+
+    /// Releases engine in deterministic order: scenes and systems first, then
+    /// resources, then GPU objects while GL context is current, and finally
+    /// the context itself. Requests exit, safe to call more than once.
+    pub fn shutdown(&mut self) {
+        self.stop();
+        self.systems = SystemRegistry::default();
+        self.scenes = Pool::new();
+        self.resources.clear();
+        if let Some(renderer) = self.renderer.take() {
+            renderer.shutdown();
+        }
+    }
+
+    pub fn should_exit(&self) -> bool {
+        self.state.should_exit()
+    }
+
+    /// Control flow for the event loop, `Exit` once stop was requested.
+    pub fn control_flow(&self) -> ControlFlow {
+        self.state.control_flow()
+    }
+
+    /// Halts scene and game updates, rendering continues with the last state.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.state.set_paused(paused, Instant::now());
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.is_paused()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::Path, rc::Rc};
+
+    use nalgebra::{Matrix4, Vector2, Vector3};
+
+    use super::{
+        system::{EngineSystem, SystemContext},
+        Engine,
+    };
+    use crate::{
+        math::color::Color,
+        renderer::surface::{Surface, SurfaceSharedData},
+        resource::{
+            fbx::tests::material_fixture, model::ModelImportError, ResourceError, ResourceKind,
+        },
+        scene::{
+            node::{Camera, Mesh, Node, NodeKind},
+            Scene,
+        },
+        utils::pool::Handle,
+    };
+
+    const STEP: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn headless_raw_surface() {
+        let mut engine = Engine::new_headless();
+        let data = SurfaceSharedData::from_raw(
+            vec![Vector3::zeros(), Vector3::x(), Vector3::y(), Vector3::z()],
+            vec![Vector2::zeros(); 4],
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            vec![0, 1, 2, 0, 2, 3],
+        )
+        .unwrap();
+        let mut mesh = Mesh::default();
+        mesh.add_surface(Surface::new(&Rc::new(RefCell::new(data))));
+        let mut scene = Scene::new();
+        let node = scene.add_node(Node::new(NodeKind::Mesh(mesh)));
+        scene.add_node(Node::new(NodeKind::Camera(Camera::default())));
+        let scene = engine.add_scene(scene);
+        engine.advance(STEP, |_, _| {});
+        engine.render();
+
+        let scene = engine.borrow_scene(&scene).unwrap();
+        let NodeKind::Mesh(mesh) = scene.borrow_node(&node).unwrap().borrow_kind() else {
+            unreachable!();
+        };
+        let bounds = mesh.bounding_box().unwrap();
+        assert_eq!(bounds.max, Vector3::repeat(1.0));
+    }
+
+    #[test]
+    fn headless_index_types() {
+        let mut engine = Engine::new_headless();
+        let mut scene = Scene::new();
+        // Strip of quads, the long one has more vertices than 16 bits index.
+        let strip = |quads: u32| {
+            let positions = (0..(quads + 1) * 2)
+                .map(|i| Vector3::new((i / 2) as f32, (i % 2) as f32, 0.0))
+                .collect::<Vec<_>>();
+            let indices = (0..quads)
+                .flat_map(|i| [i * 2, i * 2 + 2, i * 2 + 1, i * 2 + 1, i * 2 + 2, i * 2 + 3])
+                .collect();
+            let count = positions.len();
+            let data = SurfaceSharedData::from_raw(
+                positions,
+                vec![Vector2::zeros(); count],
+                vec![Vector3::z(); count],
+                Vec::new(),
+                Vec::new(),
+                indices,
+            )
+            .unwrap();
+            Rc::new(RefCell::new(data))
+        };
+        let (short, long) = (strip(4), strip(40000));
+        for data in [&short, &long] {
+            let mut mesh = Mesh::default();
+            mesh.add_surface(Surface::new(data));
+            scene.add_node(Node::new(NodeKind::Mesh(mesh)));
+        }
+        scene.add_node(Node::new(NodeKind::Camera(Camera::default())));
+        engine.add_scene(scene);
+        engine.advance(STEP, |_, _| {});
+        engine.render();
+
+        assert_eq!(short.borrow().get_indices().gl_type(), glow::UNSIGNED_SHORT);
+        assert_eq!(long.borrow().get_indices().gl_type(), glow::UNSIGNED_INT);
+        assert_eq!(long.borrow().get_triangle_count(), 80000);
+    }
+
+    #[test]
+    fn headless_scene_graph() {
+        let mut engine = Engine::new_headless();
+        assert!(engine.is_headless());
+
+        let mut scene = Scene::new();
+        let mut parent = Node::new(NodeKind::Base);
+        parent.set_local_position(Vector3::new(1.0, 0.0, 0.0));
+        let parent = scene.add_node(parent);
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        let mut child = Node::new(NodeKind::Mesh(mesh));
+        child.set_local_position(Vector3::new(0.0, 2.0, 0.0));
+        let child = scene.add_node(child);
+        scene.link_nodes(&child, &parent);
+        let camera = scene.add_node(Node::new(NodeKind::Camera(Camera::default())));
+        let scene = engine.add_scene(scene);
+
+        let mut steps = 0;
+        engine.advance(STEP * 2.5, |engine, _| {
+            steps += 1;
+            engine
+                .borrow_scene_mut(&scene)
+                .unwrap()
+                .borrow_node_mut(&parent)
+                .unwrap()
+                .offset(Vector3::new(1.0, 0.0, 0.0));
+        });
+        assert_eq!(steps, 2);
+        assert_eq!(engine.stats().scenes_updated, 2);
+
+        let scene = engine.borrow_scene(&scene).unwrap();
+        let child = scene.borrow_node(&child).unwrap();
+        assert_eq!(child.get_global_position(), Vector3::new(3.0, 2.0, 0.0));
+        let camera = scene.borrow_node(&camera).unwrap();
+        if let NodeKind::Camera(camera) = camera.borrow_kind() {
+            assert_ne!(camera.get_view_projection_matrix(), Matrix4::identity());
+        }
+
+        // Nothing to render to, must not touch GL.
+        engine.render();
+    }
+
+    #[test]
+    fn headless_texture_stays_pending() {
+        let mut engine = Engine::new_headless();
+        let path = Path::new("./src/assets/textures/box.png");
+        let texture = engine.request_texture(path).unwrap();
+        assert!(std::rc::Rc::ptr_eq(
+            &texture,
+            &engine.request_texture(path).unwrap()
+        ));
+
+        engine.render();
+        let resource = texture.borrow();
+        if let ResourceKind::Texture(texture) = resource.borrow_kind() {
+            assert!(texture.need_upload);
+            assert!(texture.gpu_tex.is_none());
+            assert!(texture.width > 0 && texture.height > 0);
+        } else {
+            panic!("expected texture resource");
+        }
+    }
+
+    #[test]
+    fn headless_pause_freezes_updates() {
+        let mut engine = Engine::new_headless();
+        engine.set_paused(true);
+        let mut steps = 0;
+        engine.advance(1.0, |_, _| steps += 1);
+        assert_eq!(steps, 0);
+
+        engine.set_paused(false);
+        engine.advance(STEP, |_, _| steps += 1);
+        assert_eq!(steps, 1);
+    }
+
+    #[test]
+    fn take_scene() {
+        let mut engine = Engine::new_headless();
+        let mut scene = Scene::new();
+        let node = scene.add_node(Node::new(NodeKind::Base));
+        let handle = engine.add_scene(scene);
+
+        let mut scene = engine.take_scene(&handle).unwrap();
+        assert!(engine.borrow_scene(&handle).is_none());
+        assert!(engine.take_scene(&handle).is_none());
+        engine.advance(STEP, |_, _| {});
+        assert_eq!(engine.stats().scenes_updated, 0);
+
+        // Taken scene keeps its nodes and can be added back.
+        scene
+            .borrow_node_mut(&node)
+            .unwrap()
+            .offset(Vector3::new(1.0, 0.0, 0.0));
+        let handle = engine.add_scene(scene);
+        engine.advance(STEP, |_, _| {});
+        let scene = engine.borrow_scene(&handle).unwrap();
+        assert_eq!(
+            scene.borrow_node(&node).unwrap().get_global_position().x,
+            1.0
+        );
+    }
+
+    #[test]
+    fn disabled_scenes_are_skipped() {
+        let mut engine = Engine::new_headless();
+        let make_scene = |engine: &mut Engine| {
+            let mut scene = Scene::new();
+            let node = scene.add_node(Node::new(NodeKind::Base));
+            (engine.add_scene(scene), node)
+        };
+        let (first, first_node) = make_scene(&mut engine);
+        let (second, second_node) = make_scene(&mut engine);
+
+        let step = |engine: &mut Engine| {
+            engine.advance(STEP, |engine, _| {
+                for (scene, node) in [(&first, &first_node), (&second, &second_node)] {
+                    engine
+                        .borrow_scene_mut(scene)
+                        .unwrap()
+                        .borrow_node_mut(node)
+                        .unwrap()
+                        .offset(Vector3::new(1.0, 0.0, 0.0));
+                }
+            })
+        };
+        let position = |engine: &Engine, scene, node| {
+            engine
+                .borrow_scene(scene)
+                .unwrap()
+                .borrow_node(node)
+                .unwrap()
+                .get_global_position()
+                .x
+        };
+
+        engine.set_active_scene(&first);
+        step(&mut engine);
+        step(&mut engine);
+        assert_eq!(engine.stats().scenes_updated, 1);
+        assert_eq!(position(&engine, &first, &first_node), 2.0);
+        assert_eq!(position(&engine, &second, &second_node), 0.0);
+        assert_eq!(Engine::scenes_to_render(&engine.scenes).len(), 1);
+
+        // Re-enabled scene continues from its own state.
+        engine.set_scene_enabled(&second, true);
+        step(&mut engine);
+        assert_eq!(position(&engine, &first, &first_node), 3.0);
+        assert_eq!(position(&engine, &second, &second_node), 3.0);
+
+        // Update and render can be toggled separately.
+        engine.set_scene_update_enabled(&first, false);
+        step(&mut engine);
+        assert_eq!(position(&engine, &first, &first_node), 3.0);
+        assert_eq!(Engine::scenes_to_render(&engine.scenes).len(), 2);
+        engine.set_scene_render_enabled(&second, false);
+        assert_eq!(Engine::scenes_to_render(&engine.scenes).len(), 1);
+    }
+
+    #[test]
+    fn instantiate_model_twice() {
+        let mut engine = Engine::new_headless();
+        let path = Path::new("./src/assets/models/cube.fbx");
+        let model = engine.request_model(path).unwrap();
+        let cached = engine.request_model(path).unwrap();
+        assert!(std::rc::Rc::ptr_eq(model.resource(), cached.resource()));
+        drop(cached);
+
+        let model_nodes = match model.resource().borrow().borrow_kind() {
+            ResourceKind::Model(model) => model.get_node_count(),
+            _ => unreachable!(),
+        };
+        assert!(model_nodes > 1);
+
+        let mut scene = Scene::new();
+        let first = scene.instantiate_model(&model);
+        let second = scene.instantiate_model(&model);
+        assert_ne!(first, second);
+        // Scene root plus two instances.
+        assert_eq!(scene.update(1.0, 0.0), 1 + 2 * model_nodes);
+
+        let first_mesh = &scene.borrow_node(&first).unwrap().children()[0];
+        let second_mesh = &scene.borrow_node(&second).unwrap().children()[0];
+        let surface_data = |handle| match scene.borrow_node(handle).unwrap().borrow_kind() {
+            NodeKind::Mesh(mesh) => mesh.surfaces[0].data.clone(),
+            _ => panic!("expected mesh"),
+        };
+        let data = surface_data(first_mesh);
+        assert!(std::rc::Rc::ptr_eq(&data, &surface_data(second_mesh)));
+        // Model, two instances and the local clone.
+        assert_eq!(std::rc::Rc::strong_count(&data), 4);
+    }
+
+    #[test]
+    fn asset_root() {
+        let mut engine = Engine::new_headless();
+        assert_eq!(
+            engine.resolve_asset_path(Path::new("textures/box.png")),
+            Path::new("./textures/box.png")
+        );
+        assert!(engine
+            .request_texture(Path::new("textures/box.png"))
+            .is_none());
+
+        engine.set_asset_root(Path::new("./src/assets"));
+        let texture = engine
+            .request_texture(Path::new("textures/box.png"))
+            .unwrap();
+        assert_eq!(texture.borrow().path, Path::new("textures/box.png"));
+        assert!(engine.request_model(Path::new("models/cube.fbx")).is_ok());
+        // Absolute paths ignore the root.
+        let absolute = std::env::current_dir()
+            .unwrap()
+            .join("src/assets/textures/floor.png");
+        assert_eq!(engine.resolve_asset_path(&absolute), absolute);
+        assert!(engine.request_texture(&absolute).is_some());
+    }
+
+    #[test]
+    fn model_textures() {
+        let mut engine = Engine::new_headless();
+        let path = material_fixture("balala_engine_materials");
+        let model = engine.request_model(&path).unwrap();
+        let mut scene = Scene::new();
+        scene.instantiate_model(&model);
+        let surface = |name: &str| {
+            let handle = scene.find_nodes(|node| node.get_name() == name)[0].clone();
+            match scene.borrow_node(&handle).unwrap().borrow_kind() {
+                NodeKind::Mesh(mesh) => (
+                    mesh.surfaces[0].get_texture().cloned(),
+                    mesh.surfaces[0].get_material().get_diffuse(),
+                ),
+                _ => panic!("expected mesh"),
+            }
+        };
+
+        let (boxed, _) = surface("Boxed");
+        let boxed = boxed.unwrap();
+        assert_eq!(boxed.borrow().path, path.parent().unwrap().join("box.png"));
+        assert!(Rc::ptr_eq(&boxed, &surface("Second").0.unwrap()));
+        assert!(surface("Floored").0.is_some());
+        let (texture, tint) = surface("Painted");
+        assert!(texture.is_none());
+        assert_eq!(tint, Color::opaque(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn request_model_errors() {
+        let mut engine = Engine::new_headless();
+        assert!(matches!(
+            engine.request_model(Path::new("./src/assets/models/missing.fbx")),
+            Err(ResourceError::Io(_))
+        ));
+        assert!(matches!(
+            engine.request_model(Path::new("./src/assets/models/ascii.fbx")),
+            Err(ResourceError::Model(ModelImportError::AsciiFbx))
+        ));
+        assert!(matches!(
+            engine.request_model(Path::new("./src/assets/textures/box.png")),
+            Err(ResourceError::UnsupportedFormat(_))
+        ));
+        engine
+            .request_texture(Path::new("./src/assets/textures/box.png"))
+            .unwrap();
+        assert!(matches!(
+            engine.request_model(Path::new("./src/assets/textures/box.png")),
+            Err(ResourceError::KindMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn time_scale() {
+        let mut engine = Engine::new_headless();
+        let mut scene = Scene::new();
+        scene.set_time_scale(0.5);
+        let slow = engine.add_scene(scene);
+        let normal = engine.add_scene(Scene::new());
+
+        let mut steps = 0;
+        engine.set_time_scale(0.5);
+        for _ in 0..10 {
+            engine.advance(STEP, |_, _| steps += 1);
+        }
+        assert_eq!(steps, 5);
+        assert_eq!(engine.get_unscaled_delta_time(), STEP);
+        let elapsed =
+            |engine: &Engine, scene| engine.borrow_scene(scene).unwrap().get_elapsed_time();
+        assert!((elapsed(&engine, &normal) - 5.0 * STEP).abs() < 1e-6);
+        assert!((elapsed(&engine, &slow) - 2.5 * STEP).abs() < 1e-6);
+
+        engine.set_time_scale(0.0);
+        for _ in 0..10 {
+            engine.advance(STEP, |_, _| steps += 1);
+        }
+        assert_eq!(steps, 5);
+        assert!((elapsed(&engine, &normal) - 5.0 * STEP).abs() < 1e-6);
+
+        engine.set_time_scale(-1.0);
+        assert_eq!(engine.get_time_scale(), 0.0);
+        engine.set_time_scale(2.0);
+        engine.advance(STEP, |_, _| steps += 1);
+        assert_eq!(steps, 7);
+    }
+
+    #[test]
+    fn systems_run_after_scene_update() {
+        struct Follower {
+            scene: Handle<Scene>,
+            node: Handle<Node>,
+            seen: Rc<RefCell<Vec<f32>>>,
+        }
+
+        impl EngineSystem for Follower {
+            fn update(&mut self, ctx: &mut SystemContext) {
+                let scene = ctx.scenes.borrow_mut(&self.scene).unwrap();
+                let node = scene.borrow_node_mut(&self.node).unwrap();
+                // Global transform is already updated for this step.
+                self.seen.borrow_mut().push(node.get_global_position().x);
+                node.offset(Vector3::new(ctx.dt, 0.0, 0.0));
+            }
+        }
+
+        let mut engine = Engine::new_headless();
+        let mut scene = Scene::new();
+        let node = scene.add_node(Node::new(NodeKind::Base));
+        let scene = engine.add_scene(scene);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let id = engine.register_system(
+            Box::new(Follower {
+                scene,
+                node,
+                seen: seen.clone(),
+            }),
+            0,
+        );
+
+        engine.advance(STEP * 3.5, |_, _| {});
+        assert_eq!(seen.borrow().len(), 3);
+        assert!((seen.borrow()[2] - 2.0 * STEP).abs() < 1e-6);
+
+        assert!(engine.remove_system(id).is_some());
+        engine.advance(STEP, |_, _| {});
+        assert_eq!(seen.borrow().len(), 3);
+    }
+
+    #[test]
+    fn minimized_window_keeps_projection_finite() {
+        use winit::{dpi::PhysicalSize, event::WindowEvent};
+
+        use crate::engine::input::tests::window_event;
+
+        let mut engine = Engine::new_headless();
+        let mut scene = Scene::new();
+        let camera = scene.add_node(Node::new(NodeKind::Camera(Camera::default())));
+        let scene = engine.add_scene(scene);
+
+        engine.process_event(&window_event(WindowEvent::Resized(PhysicalSize::new(0, 0))));
+        assert!(engine.is_render_suspended());
+        engine.advance(STEP, |_, _| {});
+        engine.render();
+        let projection = |engine: &Engine| match engine
+            .borrow_scene(&scene)
+            .unwrap()
+            .borrow_node(&camera)
+            .unwrap()
+            .borrow_kind()
+        {
+            NodeKind::Camera(camera) => camera.get_view_projection_matrix(),
+            _ => unreachable!(),
+        };
+        assert!(projection(&engine).iter().all(|v| v.is_finite()));
+
+        engine.process_event(&window_event(WindowEvent::Resized(PhysicalSize::new(
+            640, 480,
+        ))));
+        assert!(!engine.is_render_suspended());
+        engine.advance(STEP, |_, _| {});
+        assert!(projection(&engine).iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn shutdown_twice() {
+        let mut engine = Engine::new_headless();
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        let mut scene = Scene::new();
+        scene.add_node(Node::new(NodeKind::Mesh(mesh)));
+        let scene = engine.add_scene(scene);
+        let texture = engine
+            .request_texture(Path::new("./src/assets/textures/box.png"))
+            .unwrap();
+
+        engine.shutdown();
+        assert!(engine.should_exit());
+        assert!(engine.borrow_scene(&scene).is_none());
+        // Only the local reference keeps the texture alive.
+        assert_eq!(Rc::strong_count(&texture), 1);
+
+        engine.shutdown();
+        engine.advance(STEP, |_, _| {});
+        engine.render();
     }
 }
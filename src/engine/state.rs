@@ -0,0 +1,113 @@
+use std::time::Instant;
+
+use winit::event_loop::ControlFlow;
+
+use super::MAX_DELTA_TIME;
+
+/// Run state of the engine: exit request, pause flag and frame time accounting.
+#[derive(Debug)]
+pub struct RunState {
+    exit_requested: bool,
+    paused: bool,
+    last_update_time: Instant,
+}
+
+impl RunState {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            exit_requested: false,
+            paused: false,
+            last_update_time: now,
+        }
+    }
+
+    pub fn request_exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    pub fn should_exit(&self) -> bool {
+        self.exit_requested
+    }
+
+    /// Control flow the main loop must switch to.
+    pub fn control_flow(&self) -> ControlFlow {
+        if self.exit_requested {
+            ControlFlow::Exit
+        } else {
+            ControlFlow::Poll
+        }
+    }
+
+    /// Pauses or resumes. Clock restarts at `now` on resume, so time spent
+    /// paused never reaches the simulation.
+    pub fn set_paused(&mut self, paused: bool, now: Instant) {
+        if self.paused && !paused {
+            self.last_update_time = now;
+        }
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns time passed since previous call clamped to `MAX_DELTA_TIME`,
+    /// or zero while paused.
+    pub fn frame_time(&mut self, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.last_update_time);
+        self.last_update_time = now;
+        if self.paused {
+            0.0
+        } else {
+            elapsed.as_secs_f32().min(MAX_DELTA_TIME)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use winit::event_loop::ControlFlow;
+
+    use super::RunState;
+    use crate::engine::MAX_DELTA_TIME;
+
+    #[test]
+    fn pause_does_not_accumulate_time() {
+        let start = Instant::now();
+        let mut state = RunState::new(start);
+        let mut now = start + Duration::from_millis(16);
+        assert!((state.frame_time(now) - 0.016).abs() < 1e-6);
+
+        state.set_paused(true, now);
+        for _ in 0..10 {
+            now += Duration::from_secs(1);
+            assert_eq!(state.frame_time(now), 0.0);
+        }
+
+        // Resume without any ticks in between, first frame is short again.
+        now += Duration::from_secs(30);
+        state.set_paused(false, now);
+        now += Duration::from_millis(10);
+        assert!((state.frame_time(now) - 0.010).abs() < 1e-6);
+
+        // Long stall while running is still clamped.
+        now += Duration::from_secs(5);
+        assert_eq!(state.frame_time(now), MAX_DELTA_TIME);
+    }
+
+    #[test]
+    fn exit_flag_propagates_to_control_flow() {
+        let mut state = RunState::new(Instant::now());
+        assert!(!state.should_exit());
+        assert_eq!(state.control_flow(), ControlFlow::Poll);
+
+        state.set_paused(true, Instant::now());
+        assert_eq!(state.control_flow(), ControlFlow::Poll);
+
+        state.request_exit();
+        assert!(state.should_exit());
+        assert_eq!(state.control_flow(), ControlFlow::Exit);
+    }
+}
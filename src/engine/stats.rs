@@ -0,0 +1,241 @@
+use std::fmt;
+
+use crate::renderer::renderer::RenderStats;
+
+/// Ring buffer of recent frame times (in seconds).
+#[derive(Debug, Clone)]
+pub struct FrameTimeHistory {
+    samples: Vec<f32>,
+    capacity: usize,
+    next: usize,
+}
+
+impl FrameTimeHistory {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            samples: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+        }
+    }
+
+    pub fn push(&mut self, frame_time: f32) {
+        if self.samples.len() < self.capacity {
+            self.samples.push(frame_time);
+        } else {
+            self.samples[self.next] = frame_time;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn last(&self) -> Option<f32> {
+        if self.samples.is_empty() {
+            None
+        } else {
+            let index = (self.next + self.capacity - 1) % self.capacity;
+            Some(self.samples[index])
+        }
+    }
+
+    pub fn min(&self) -> f32 {
+        self.samples.iter().copied().reduce(f32::min).unwrap_or(0.0)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.samples.iter().copied().reduce(f32::max).unwrap_or(0.0)
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.samples.is_empty() {
+            0.0
+        } else {
+            self.samples.iter().sum::<f32>() / self.samples.len() as f32
+        }
+    }
+
+    /// Nearest-rank percentile, `percent` is in 0..100 range.
+    pub fn percentile(&self, percent: f32) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort_by(f32::total_cmp);
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * sorted.len() as f32).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.next = 0;
+    }
+}
+
+/// Snapshot of engine performance counters. Times are in seconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EngineStats {
+    /// Frames per second, exponentially smoothed.
+    pub fps: f32,
+    pub last_frame_time: f32,
+    pub avg_frame_time: f32,
+    pub p99_frame_time: f32,
+    /// Time spent in last `Engine::update`.
+    pub update_time: f32,
+    /// Time spent in last `Engine::render`, without frame limiter wait.
+    pub render_time: f32,
+    /// Scenes and nodes updated during last `Engine::update`, summed over its
+    /// fixed steps.
+    pub scenes_updated: usize,
+    pub nodes_updated: usize,
+    pub render: RenderStats,
+}
+
+impl fmt::Display for EngineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FPS {:.0} | frame {:.2}ms avg {:.2}ms p99 {:.2}ms | update {:.2}ms render {:.2}ms | \
+             scenes {} nodes {} | draw calls {} triangles {} | meshes drawn {} culled {}",
+            self.fps,
+            self.last_frame_time * 1000.0,
+            self.avg_frame_time * 1000.0,
+            self.p99_frame_time * 1000.0,
+            self.update_time * 1000.0,
+            self.render_time * 1000.0,
+            self.scenes_updated,
+            self.nodes_updated,
+            self.render.draw_calls,
+            self.render.triangles,
+            self.render.drawn,
+            self.render.culled,
+        )
+    }
+}
+
+/// Collects timings from the engine and keeps `EngineStats` up to date.
+#[derive(Debug, Clone)]
+pub struct StatsAccumulator {
+    history: FrameTimeHistory,
+    smoothed_frame_time: Option<f32>,
+    stats: EngineStats,
+}
+
+impl Default for StatsAccumulator {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_HISTORY_LEN)
+    }
+}
+
+impl StatsAccumulator {
+    /// Number of frames kept for average and percentile.
+    pub const DEFAULT_HISTORY_LEN: usize = 240;
+    /// Weight of the newest frame in smoothed FPS.
+    pub const FPS_SMOOTHING: f32 = 0.1;
+
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            history: FrameTimeHistory::new(history_len),
+            smoothed_frame_time: None,
+            stats: EngineStats::default(),
+        }
+    }
+
+    pub fn push_frame_time(&mut self, frame_time: f32) {
+        self.history.push(frame_time);
+        let smoothed = match self.smoothed_frame_time {
+            Some(smoothed) => smoothed + (frame_time - smoothed) * Self::FPS_SMOOTHING,
+            None => frame_time,
+        };
+        self.smoothed_frame_time = Some(smoothed);
+
+        self.stats.fps = if smoothed > 0.0 { 1.0 / smoothed } else { 0.0 };
+        self.stats.last_frame_time = frame_time;
+        self.stats.avg_frame_time = self.history.average();
+        self.stats.p99_frame_time = self.history.percentile(99.0);
+    }
+
+    pub fn record_update(&mut self, time: f32, scenes: usize, nodes: usize) {
+        self.stats.update_time = time;
+        self.stats.scenes_updated = scenes;
+        self.stats.nodes_updated = nodes;
+    }
+
+    pub fn record_render(&mut self, time: f32, render: RenderStats) {
+        self.stats.render_time = time;
+        self.stats.render = render;
+    }
+
+    pub fn history(&self) -> &FrameTimeHistory {
+        &self.history
+    }
+
+    pub fn stats(&self) -> &EngineStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameTimeHistory, StatsAccumulator};
+
+    #[test]
+    fn average_and_percentile() {
+        let mut history = FrameTimeHistory::new(100);
+        assert_eq!(history.average(), 0.0);
+        assert_eq!(history.percentile(99.0), 0.0);
+        assert_eq!(history.last(), None);
+        assert_eq!(history.min(), 0.0);
+
+        // Shuffled 1..=100 ms.
+        for i in 0..100 {
+            history.push(((i * 37) % 100 + 1) as f32 / 1000.0);
+        }
+        assert!((history.average() - 0.0505).abs() < 1e-6);
+        assert_eq!(history.percentile(99.0), 0.099);
+        assert_eq!(history.min(), 0.001);
+        assert_eq!(history.max(), 0.1);
+        assert_eq!(history.percentile(50.0), 0.050);
+        assert_eq!(history.percentile(100.0), 0.100);
+        assert_eq!(history.percentile(0.0), 0.001);
+    }
+
+    #[test]
+    fn ring_buffer_keeps_recent_frames() {
+        let mut history = FrameTimeHistory::new(4);
+        for frame_time in [10.0, 10.0, 10.0, 10.0, 1.0, 2.0, 3.0] {
+            history.push(frame_time);
+        }
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.last(), Some(3.0));
+        assert_eq!(history.average(), 4.0);
+        assert_eq!(history.percentile(99.0), 10.0);
+    }
+
+    #[test]
+    fn accumulator() {
+        let mut accumulator = StatsAccumulator::new(8);
+        accumulator.push_frame_time(0.02);
+        assert_eq!(accumulator.stats().fps, 50.0);
+        for _ in 0..200 {
+            accumulator.push_frame_time(0.01);
+        }
+        let stats = accumulator.stats();
+        assert!((stats.fps - 100.0).abs() < 0.1);
+        assert_eq!(stats.last_frame_time, 0.01);
+        assert!((stats.avg_frame_time - 0.01).abs() < 1e-6);
+        assert_eq!(stats.p99_frame_time, 0.01);
+
+        accumulator.record_update(0.002, 1, 12);
+        let stats = accumulator.stats();
+        assert_eq!((stats.scenes_updated, stats.nodes_updated), (1, 12));
+        assert!(stats.to_string().starts_with("FPS 100 | frame 10.00ms"));
+    }
+}
@@ -0,0 +1,176 @@
+use std::{cell::RefCell, rc::Rc};
+
+use nalgebra::Vector2;
+
+use crate::{engine::input::InputState, resource::Resource, scene::Scene, utils::pool::Pool};
+
+/// Point of the fixed step at which a system runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SystemPhase {
+    /// Before scene updates, right after the game's fixed update callback.
+    PreUpdate,
+    /// After scene updates, global transforms are up to date.
+    Update,
+    /// After all `Update` systems.
+    PostUpdate,
+}
+
+/// Engine state available to systems.
+pub struct SystemContext<'a> {
+    pub scenes: &'a mut Pool<Scene>,
+    pub resources: &'a mut Vec<Rc<RefCell<Resource>>>,
+    pub input: &'a InputState,
+    /// True while the cursor is grabbed for mouse-look.
+    pub cursor_grabbed: bool,
+    /// Window client area in pixels, zero for headless engines.
+    pub client_size: Vector2<f32>,
+    /// Length of fixed step in seconds.
+    pub dt: f32,
+}
+
+pub trait EngineSystem {
+    fn update(&mut self, ctx: &mut SystemContext);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SystemId(u64);
+
+struct SystemEntry {
+    id: SystemId,
+    phase: SystemPhase,
+    order: i32,
+    system: Box<dyn EngineSystem>,
+}
+
+/// Registered systems sorted by phase and order. Systems with equal order run
+/// in registration order.
+#[derive(Default)]
+pub struct SystemRegistry {
+    entries: Vec<SystemEntry>,
+    next_id: u64,
+}
+
+impl SystemRegistry {
+    pub fn register(
+        &mut self,
+        system: Box<dyn EngineSystem>,
+        phase: SystemPhase,
+        order: i32,
+    ) -> SystemId {
+        let id = SystemId(self.next_id);
+        self.next_id += 1;
+        let position = self
+            .entries
+            .partition_point(|entry| (entry.phase, entry.order) <= (phase, order));
+        self.entries.insert(
+            position,
+            SystemEntry {
+                id,
+                phase,
+                order,
+                system,
+            },
+        );
+        id
+    }
+
+    /// Removes system and gives it back to the caller.
+    pub fn remove(&mut self, id: SystemId) -> Option<Box<dyn EngineSystem>> {
+        let position = self.entries.iter().position(|entry| entry.id == id)?;
+        Some(self.entries.remove(position).system)
+    }
+
+    pub fn contains(&self, id: SystemId) -> bool {
+        self.entries.iter().any(|entry| entry.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn run(&mut self, phase: SystemPhase, ctx: &mut SystemContext) {
+        for entry in self.entries.iter_mut() {
+            if entry.phase == phase {
+                entry.system.update(ctx);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use nalgebra::Vector2;
+
+    use super::{EngineSystem, SystemContext, SystemPhase, SystemRegistry};
+    use crate::{engine::input::InputState, utils::pool::Pool};
+
+    struct Recorder {
+        name: &'static str,
+        log: Rc<RefCell<Vec<(&'static str, f32)>>>,
+    }
+
+    impl EngineSystem for Recorder {
+        fn update(&mut self, ctx: &mut SystemContext) {
+            self.log.borrow_mut().push((self.name, ctx.dt));
+        }
+    }
+
+    #[test]
+    fn ordering_and_removal() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let recorder = |name| {
+            Box::new(Recorder {
+                name,
+                log: log.clone(),
+            })
+        };
+
+        let mut registry = SystemRegistry::default();
+        registry.register(recorder("late"), SystemPhase::Update, 10);
+        registry.register(recorder("post"), SystemPhase::PostUpdate, -100);
+        let early = registry.register(recorder("early"), SystemPhase::Update, -5);
+        registry.register(recorder("late2"), SystemPhase::Update, 10);
+        registry.register(recorder("pre"), SystemPhase::PreUpdate, 100);
+        assert_eq!(registry.len(), 5);
+
+        let (mut scenes, mut resources, input) = (Pool::new(), Vec::new(), InputState::new());
+        let mut ctx = SystemContext {
+            scenes: &mut scenes,
+            resources: &mut resources,
+            input: &input,
+            cursor_grabbed: false,
+            client_size: Vector2::zeros(),
+            dt: 0.25,
+        };
+        for phase in [
+            SystemPhase::PreUpdate,
+            SystemPhase::Update,
+            SystemPhase::PostUpdate,
+        ] {
+            registry.run(phase, &mut ctx);
+        }
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                ("pre", 0.25),
+                ("early", 0.25),
+                ("late", 0.25),
+                ("late2", 0.25),
+                ("post", 0.25)
+            ]
+        );
+
+        assert!(registry.remove(early).is_some());
+        assert!(!registry.contains(early));
+        assert!(registry.remove(early).is_none());
+        log.borrow_mut().clear();
+        registry.run(SystemPhase::Update, &mut ctx);
+        assert_eq!(*log.borrow(), vec![("late", 0.25), ("late2", 0.25)]);
+    }
+}
@@ -0,0 +1,110 @@
+/// Fixed time step accumulator. Real frame time is accumulated and consumed in
+/// equal steps, the remainder is exposed as interpolation factor for rendering.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: f32,
+    accumulator: f32,
+    max_steps: u32,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
+
+impl FixedTimestep {
+    /// Default limit of catch-up steps per frame.
+    pub const DEFAULT_MAX_STEPS: u32 = 8;
+
+    pub fn new(rate_hz: f32) -> Self {
+        Self {
+            step: 1.0 / rate_hz.max(1.0),
+            accumulator: 0.0,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+        }
+    }
+
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    pub fn set_rate(&mut self, rate_hz: f32) {
+        self.step = 1.0 / rate_hz.max(1.0);
+        self.accumulator = self.accumulator.min(self.step);
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: u32) {
+        self.max_steps = max_steps.max(1);
+    }
+
+    /// Accumulates frame time and returns how many fixed steps must be simulated.
+    /// If the simulation falls behind more than `max_steps`, the excess time is
+    /// dropped instead of being carried over (spiral-of-death guard).
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time.max(0.0);
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            if steps == self.max_steps {
+                self.accumulator %= self.step;
+                break;
+            }
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Blend factor between previous and current simulation state, in 0..1 range.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+
+    /// Drops accumulated time, next frame starts from a clean state.
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedTimestep;
+
+    #[test]
+    fn steps_match_elapsed_time() {
+        let mut timestep = FixedTimestep::new(60.0);
+        let frame_times = [0.016, 0.001, 0.033, 0.05, 0.0, 0.017, 0.008, 0.1, 0.002];
+        let mut total_time = 0.0;
+        let mut total_steps = 0;
+        for _ in 0..50 {
+            for frame_time in frame_times {
+                total_time += frame_time;
+                total_steps += timestep.advance(frame_time);
+                let alpha = timestep.alpha();
+                assert!((0.0..1.0).contains(&alpha), "alpha {}", alpha);
+            }
+        }
+        let expected = (total_time / timestep.step()).floor() as u32;
+        assert!(total_steps.abs_diff(expected) <= 1);
+        let simulated = total_steps as f32 * timestep.step() + timestep.alpha() * timestep.step();
+        assert!((simulated - total_time).abs() < 1e-2);
+    }
+
+    #[test]
+    fn catch_up_is_capped() {
+        let mut timestep = FixedTimestep::new(100.0);
+        timestep.set_max_steps(4);
+        assert_eq!(timestep.advance(1.0), 4);
+        assert!(timestep.alpha() < 1.0);
+        // Dropped time is not carried into the next frame.
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+
+    #[test]
+    fn no_steps_for_short_frames() {
+        let mut timestep = FixedTimestep::new(50.0);
+        assert_eq!(timestep.advance(0.01), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-5);
+        assert_eq!(timestep.advance(0.011), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use nalgebra::Vector2;
+
+/// Returns aspect ratio of the client area, `None` for zero-area (minimized)
+/// windows.
+pub fn aspect_ratio(width: u32, height: u32) -> Option<f32> {
+    if width == 0 || height == 0 {
+        None
+    } else {
+        Some(width as f32 / height as f32)
+    }
+}
+
+/// Tracks client area size. While the window is minimized rendering is
+/// suspended and the last valid aspect ratio is kept for cameras.
+#[derive(Debug, Clone)]
+pub struct WindowState {
+    aspect_ratio: f32,
+    /// Client area in pixels, zero until the first resize.
+    client_size: Vector2<f32>,
+    suspended: bool,
+}
+
+impl WindowState {
+    pub fn new(default_aspect_ratio: f32) -> Self {
+        Self {
+            aspect_ratio: default_aspect_ratio,
+            client_size: Vector2::zeros(),
+            suspended: false,
+        }
+    }
+
+    /// Handles new client size. Returns true if rendering has just resumed
+    /// after being suspended.
+    pub fn on_resize(&mut self, width: u32, height: u32) -> bool {
+        self.client_size = Vector2::new(width as f32, height as f32);
+        match aspect_ratio(width, height) {
+            Some(aspect_ratio) => {
+                self.aspect_ratio = aspect_ratio;
+                let resumed = self.suspended;
+                self.suspended = false;
+                resumed
+            }
+            None => {
+                self.suspended = true;
+                false
+            }
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn client_size(&self) -> Vector2<f32> {
+        self.client_size
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+
+    use super::{aspect_ratio, WindowState};
+
+    #[test]
+    fn aspect_guard() {
+        assert_eq!(aspect_ratio(800, 600), Some(800.0 / 600.0));
+        assert_eq!(aspect_ratio(0, 600), None);
+        assert_eq!(aspect_ratio(800, 0), None);
+        assert_eq!(aspect_ratio(0, 0), None);
+    }
+
+    #[test]
+    fn suspend_and_resume() {
+        let mut state = WindowState::new(1.0);
+        assert!(!state.on_resize(800, 400));
+        assert_eq!(state.aspect_ratio(), 2.0);
+        assert!(!state.is_suspended());
+
+        // Minimize keeps last valid aspect.
+        assert!(!state.on_resize(0, 0));
+        assert!(state.is_suspended());
+        assert_eq!(state.aspect_ratio(), 2.0);
+        assert!(!state.on_resize(0, 0));
+        assert!(state.aspect_ratio().is_finite());
+
+        assert_eq!(state.client_size(), Vector2::zeros());
+
+        assert!(state.on_resize(800, 600));
+        assert!(!state.is_suspended());
+        assert_eq!(state.client_size(), Vector2::new(800.0, 600.0));
+        assert_eq!(state.aspect_ratio(), 800.0 / 600.0);
+        assert!(!state.on_resize(1024, 768));
+    }
+}
@@ -0,0 +1,1116 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bench::CameraPath,
+    player::{Player, PlayerSettings, SpawnPolicy, FLOOR_HEIGHT, PLAYER_HALF_EXTENTS},
+    settings::{Settings, SettingsHandle},
+    ACTION_REMOVE, ACTION_RESPAWN, ACTION_SELECT,
+};
+use balala::{
+    engine::{
+        system::{EngineSystem, SystemContext},
+        Engine,
+    },
+    math::{color::Color, ray::Ray},
+    resource::ResourceError,
+    scene::{
+        node::{Camera, Light, Mesh, Node, NodeKind},
+        Scene,
+    },
+    utils::pool::Handle,
+};
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// Angular speed of the cubes in radians per second.
+const CUBE_ROTATION_SPEED: f32 = 6.0;
+
+/// Entities with this tag block player movement.
+pub const TAG_COLLIDER: &str = "collider";
+/// Entities with this tag spin around the Y axis.
+pub const TAG_ROTATING: &str = "rotating";
+/// Entities with this tag and no kind are places where the player appears.
+pub const TAG_SPAWN: &str = "spawn";
+
+const DEFAULT_KILL_Y: f32 = -50.0;
+
+/// Asset directory of the demo when run from the repository root, paths below
+/// are relative to it.
+pub const DEFAULT_ASSETS_DIR: &str = "./src/assets";
+
+/// Level file shipped with the demo.
+pub const DEMO_LEVEL_PATH: &str = "levels/demo.toml";
+
+pub const CUBE_TEXTURE_PATH: &str = "textures/box.png";
+pub const FLOOR_TEXTURE_PATH: &str = "textures/floor.png";
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    Resource(ResourceError),
+    /// Description is well-formed but inconsistent.
+    Invalid(String),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "I/O error: {}", err),
+            LevelError::Parse(err) => write!(f, "parse error: {}", err),
+            LevelError::Serialize(err) => write!(f, "serialize error: {}", err),
+            LevelError::Resource(err) => write!(f, "resource error: {}", err),
+            LevelError::Invalid(msg) => write!(f, "invalid level: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<std::io::Error> for LevelError {
+    fn from(err: std::io::Error) -> Self {
+        LevelError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for LevelError {
+    fn from(err: toml::de::Error) -> Self {
+        LevelError::Parse(err)
+    }
+}
+
+impl From<toml::ser::Error> for LevelError {
+    fn from(err: toml::ser::Error) -> Self {
+        LevelError::Serialize(err)
+    }
+}
+
+impl From<ResourceError> for LevelError {
+    fn from(err: ResourceError) -> Self {
+        LevelError::Resource(err)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Primitive {
+    Cube,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightType {
+    #[default]
+    Point,
+    /// Shines along the entity rotation, position is ignored.
+    Directional,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightDescription {
+    pub kind: LightType,
+    pub radius: f32,
+    pub color: [f32; 3],
+}
+
+impl Default for LightDescription {
+    fn default() -> Self {
+        let light = Light::default();
+        Self {
+            kind: LightType::Point,
+            radius: light.get_radius(),
+            color: light.get_color().rgb().into(),
+        }
+    }
+}
+
+impl LightDescription {
+    pub fn to_light(&self) -> Light {
+        let [r, g, b] = self.color;
+        let color = Color::opaque(r, g, b);
+        match self.kind {
+            LightType::Point => Light::new(self.radius, color),
+            LightType::Directional => Light::directional(color),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self {
+            fov: Camera::default().get_fov(),
+        }
+    }
+}
+
+/// Scene node of a level file. At most one of `primitive`, `model`, `light`
+/// and `camera` can be set, node without any of them is an empty pivot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityDescription {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primitive: Option<Primitive>,
+    /// Model file, instantiated as a subtree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub position: [f32; 3],
+    /// Euler angles in degrees around X, Y and Z.
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+    /// Texture of a primitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub texture: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light: Option<LightDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraDescription>,
+}
+
+impl Default for EntityDescription {
+    fn default() -> Self {
+        Self {
+            name: String::from("Node"),
+            primitive: None,
+            model: None,
+            tags: Vec::new(),
+            position: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: [1.0; 3],
+            texture: None,
+            light: None,
+            camera: None,
+        }
+    }
+}
+
+impl EntityDescription {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnDescription {
+    /// Position of the player's feet, used when the level has no entities
+    /// tagged "spawn".
+    pub position: [f32; 3],
+    /// Heading in degrees.
+    pub yaw: f32,
+    pub spawn_policy: SpawnPolicy,
+    /// Player falling below this height respawns.
+    pub kill_y: f32,
+}
+
+impl Default for SpawnDescription {
+    fn default() -> Self {
+        Self {
+            position: [0.0, FLOOR_HEIGHT, 20.0],
+            yaw: 0.0,
+            spawn_policy: SpawnPolicy::First,
+            kill_y: DEFAULT_KILL_Y,
+        }
+    }
+}
+
+impl SpawnDescription {
+    pub fn transform(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&Vector3::from(self.position))
+            * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw.to_radians())
+                .to_homogeneous()
+    }
+}
+
+/// Content of a level file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelDescription {
+    pub player: SpawnDescription,
+    #[serde(rename = "entity")]
+    pub entities: Vec<EntityDescription>,
+}
+
+const LEVEL_FIELDS: [&str; 2] = ["player", "entity"];
+const SPAWN_FIELDS: [&str; 4] = ["position", "yaw", "spawn_policy", "kill_y"];
+const ENTITY_FIELDS: [&str; 10] = [
+    "name",
+    "primitive",
+    "model",
+    "tags",
+    "position",
+    "rotation",
+    "scale",
+    "texture",
+    "light",
+    "camera",
+];
+const LIGHT_FIELDS: [&str; 3] = ["kind", "radius", "color"];
+const CAMERA_FIELDS: [&str; 1] = ["fov"];
+
+/// Appends paths of keys of `table` missing in `known` to `unknown`.
+fn collect_unknown_fields(
+    table: &toml::Table,
+    path: &str,
+    known: &[&str],
+    unknown: &mut Vec<String>,
+) {
+    for key in table.keys() {
+        if !known.contains(&key.as_str()) {
+            if path.is_empty() {
+                unknown.push(key.clone());
+            } else {
+                unknown.push(format!("{}.{}", path, key));
+            }
+        }
+    }
+}
+
+/// Distance between centers of neighbour cubes in grid levels.
+pub const GRID_SPACING: f32 = 2.0;
+
+/// Point `index` of `count` points spread over a cube `[0, extent]^3`
+/// along a golden angle spiral, so any count gives an even deterministic
+/// layout.
+fn scatter_point(index: u32, count: u32, extent: f32) -> Vector3<f32> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    let t = (index as f32 + 0.5) / count.max(1) as f32;
+    let angle = index as f32 * golden_angle;
+    let half = extent * 0.5;
+    let radius = half * t.sqrt();
+    Vector3::new(
+        half + radius * angle.cos(),
+        extent * t,
+        half + radius * angle.sin(),
+    )
+}
+
+impl LevelDescription {
+    /// The 3x3x3 grid of spinning cubes on a floor.
+    pub fn demo() -> Self {
+        Self::grid(3, 0)
+    }
+
+    /// `size`x`size`x`size` grid of spinning cubes on a floor lit by the sun
+    /// and `lights` point lights spread around it.
+    pub fn grid(size: u32, lights: u32) -> Self {
+        let mut entities = vec![
+            EntityDescription {
+                name: String::from("Floor"),
+                primitive: Some(Primitive::Cube),
+                tags: vec![TAG_COLLIDER.to_string()],
+                scale: [100.0, 0.1, 100.0],
+                texture: Some(PathBuf::from(FLOOR_TEXTURE_PATH)),
+                ..Default::default()
+            },
+            EntityDescription {
+                name: String::from("Sun"),
+                rotation: [50.0, 30.0, 0.0],
+                light: Some(LightDescription {
+                    kind: LightType::Directional,
+                    color: [0.8, 0.8, 0.7],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ];
+        for i in 0..size {
+            for j in 0..size {
+                for k in 0..size {
+                    entities.push(EntityDescription {
+                        name: String::from("Cube"),
+                        primitive: Some(Primitive::Cube),
+                        tags: vec![TAG_COLLIDER.to_string(), TAG_ROTATING.to_string()],
+                        position: [
+                            i as f32 * GRID_SPACING,
+                            j as f32 * GRID_SPACING,
+                            k as f32 * GRID_SPACING,
+                        ],
+                        texture: Some(PathBuf::from(CUBE_TEXTURE_PATH)),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        let extent = size.saturating_sub(1) as f32 * GRID_SPACING;
+        for i in 0..lights {
+            entities.push(EntityDescription {
+                name: format!("Light{}", i),
+                position: scatter_point(i, lights, extent).into(),
+                light: Some(LightDescription {
+                    radius: GRID_SPACING * 4.0,
+                    color: [1.0, 1.0, 1.0],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+        }
+        Self {
+            player: SpawnDescription::default(),
+            entities,
+        }
+    }
+
+    /// Parses level, returns it with paths of unknown fields which are
+    /// ignored.
+    pub fn from_toml(source: &str) -> Result<(Self, Vec<String>), LevelError> {
+        let table: toml::Table = source.parse()?;
+        let mut unknown = Vec::new();
+        collect_unknown_fields(&table, "", &LEVEL_FIELDS, &mut unknown);
+        if let Some(player) = table.get("player").and_then(|v| v.as_table()) {
+            collect_unknown_fields(player, "player", &SPAWN_FIELDS, &mut unknown);
+        }
+        let entities = table.get("entity").and_then(|v| v.as_array());
+        for (i, entity) in entities.into_iter().flatten().enumerate() {
+            if let Some(entity) = entity.as_table() {
+                let path = format!("entity[{}]", i);
+                collect_unknown_fields(entity, &path, &ENTITY_FIELDS, &mut unknown);
+                for (key, known) in [("light", &LIGHT_FIELDS[..]), ("camera", &CAMERA_FIELDS[..])] {
+                    if let Some(table) = entity.get(key).and_then(|v| v.as_table()) {
+                        let path = format!("{}.{}", path, key);
+                        collect_unknown_fields(table, &path, known, &mut unknown);
+                    }
+                }
+            }
+        }
+        let description = table.try_into()?;
+        Ok((description, unknown))
+    }
+
+    pub fn to_toml(&self) -> Result<String, LevelError> {
+        Ok(toml::to_string(self)?)
+    }
+}
+
+pub struct Level {
+    scene: Handle<Scene>,
+    player: Player,
+
+    cubes: Vec<Handle<Node>>,
+    angle: f32,
+    description: LevelDescription,
+    /// Root node of every entity, in description order.
+    entities: Vec<Handle<Node>>,
+    selected: Option<Handle<Node>>,
+    /// Scripted camera replacing player control, with time along it.
+    camera_path: Option<(CameraPath, f32)>,
+    /// Settings applied to the player, with their last applied revision.
+    settings: Option<(SettingsHandle, u32)>,
+    /// Transforms of spawn points, never empty.
+    spawns: Vec<Matrix4<f32>>,
+    /// State of the generator used by random spawn policy.
+    spawn_random: u32,
+}
+
+impl Level {
+    /// Builds the demo level without reading any files.
+    pub fn new(engine: &mut Engine) -> Level {
+        Level::from_description(LevelDescription::demo(), engine)
+            .expect("demo level has no external models")
+    }
+
+    /// Loads level file, unknown fields are reported and ignored, missing
+    /// textures are skipped.
+    pub fn from_file(path: &Path, engine: &mut Engine) -> Result<Level, LevelError> {
+        let source = std::fs::read_to_string(path)?;
+        let (description, unknown) = LevelDescription::from_toml(&source)?;
+        for field in unknown.iter() {
+            println!("{:?}: unknown field {}", path, field);
+        }
+        Level::from_description(description, engine)
+    }
+
+    pub fn from_description(
+        description: LevelDescription,
+        engine: &mut Engine,
+    ) -> Result<Level, LevelError> {
+        let mut scene = Scene::new();
+        let mut cubes = Vec::new();
+        let mut entities = Vec::new();
+        let mut spawns = Vec::new();
+
+        for entity in description.entities.iter() {
+            let kinds = [
+                entity.primitive.is_some(),
+                entity.model.is_some(),
+                entity.light.is_some(),
+                entity.camera.is_some(),
+            ];
+            if kinds.iter().filter(|set| **set).count() > 1 {
+                return Err(LevelError::Invalid(format!(
+                    "entity {:?} has more than one of primitive, model, light and camera",
+                    entity.name
+                )));
+            }
+
+            let handle = if let Some(path) = entity.model.as_ref() {
+                let model = engine.request_model(path)?;
+                scene.instantiate_model(&model)
+            } else {
+                let kind = if let Some(Primitive::Cube) = entity.primitive {
+                    let mut mesh = Mesh::default();
+                    mesh.make_cube();
+                    if let Some(texture) = entity
+                        .texture
+                        .as_ref()
+                        .and_then(|path| engine.request_texture(path))
+                    {
+                        mesh.apply_texture(texture);
+                    }
+                    NodeKind::Mesh(mesh)
+                } else if let Some(light) = entity.light.as_ref() {
+                    NodeKind::Light(light.to_light())
+                } else if let Some(camera) = entity.camera.as_ref() {
+                    let mut result = Camera::default();
+                    result.set_fov(camera.fov);
+                    NodeKind::Camera(result)
+                } else {
+                    NodeKind::Base
+                };
+                scene.add_node(Node::new(kind))
+            };
+
+            if let Some(node) = scene.borrow_node_mut(&handle) {
+                node.set_name(&entity.name);
+                node.set_local_position(Vector3::from(entity.position));
+                let [x, y, z] = entity.rotation;
+                node.set_local_rotation(UnitQuaternion::from_euler_angles(
+                    x.to_radians(),
+                    y.to_radians(),
+                    z.to_radians(),
+                ));
+                node.set_local_scale(Vector3::from(entity.scale));
+                node.set_collider(entity.has_tag(TAG_COLLIDER));
+            }
+            if entity.has_tag(TAG_ROTATING) {
+                cubes.push(handle.clone());
+            }
+            if entity.has_tag(TAG_SPAWN) {
+                match scene.borrow_node_mut(&handle) {
+                    Some(node) if matches!(node.borrow_kind(), NodeKind::Base) => {
+                        // Entities are roots, local transform is the global one.
+                        node.calculate_local_transform();
+                        spawns.push(node.local_transform);
+                    }
+                    _ => println!(
+                        "entity {:?}: only empty entities can be spawns",
+                        entity.name
+                    ),
+                }
+            }
+            entities.push(handle);
+        }
+        if spawns.is_empty() {
+            spawns.push(description.player.transform());
+        }
+
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        player.spawn_at(&mut scene, &spawns[0]);
+
+        Ok(Level {
+            player,
+            cubes,
+            angle: 0.0,
+            scene: engine.add_scene(scene),
+            description,
+            entities,
+            selected: None,
+            camera_path: None,
+            settings: None,
+            spawns,
+            spawn_random: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(1, |time| time.subsec_nanos() | 1),
+        })
+    }
+
+    /// Scene the level is built in.
+    pub fn get_scene(&self) -> &Handle<Scene> {
+        &self.scene
+    }
+
+    /// Player walking the level.
+    pub fn get_player(&self) -> &Player {
+        &self.player
+    }
+
+    /// Root nodes of level entities, in the order of its description.
+    pub fn get_entities(&self) -> &[Handle<Node>] {
+        &self.entities
+    }
+
+    /// Moves camera along the path instead of player input, `None` gives
+    /// control back to the player.
+    pub fn set_camera_path(&mut self, path: Option<CameraPath>) {
+        self.camera_path = path.map(|path| (path, 0.0));
+    }
+
+    /// Applies player settings and bindings now and again whenever they are
+    /// edited.
+    pub fn set_settings(&mut self, handle: SettingsHandle) {
+        self.apply_settings(&handle.borrow());
+        self.settings = Some((handle.clone(), handle.get_revision()));
+    }
+
+    fn apply_settings(&mut self, settings: &Settings) {
+        self.player.set_settings(settings.player.clone());
+        settings.apply_bindings(self.player.controls_mut());
+    }
+
+    pub fn get_spawns(&self) -> &[Matrix4<f32>] {
+        &self.spawns
+    }
+
+    /// Moves the player to a spawn point picked by the level's policy,
+    /// returns its index.
+    pub fn respawn(&mut self, scene: &mut Scene) -> Option<usize> {
+        // Xorshift, good enough to vary spawn points.
+        self.spawn_random ^= self.spawn_random << 13;
+        self.spawn_random ^= self.spawn_random >> 17;
+        self.spawn_random ^= self.spawn_random << 5;
+        let position = self.player.get_feet_position(scene)?;
+        let index = self.description.player.spawn_policy.select(
+            &self.spawns,
+            position,
+            self.spawn_random,
+        )?;
+        self.player.spawn_at(scene, &self.spawns[index]);
+        Some(index)
+    }
+
+    /// True if the player fell below the kill height of the level.
+    pub fn is_out_of_bounds(&self, scene: &Scene) -> bool {
+        self.player
+            .get_feet_position(scene)
+            .is_some_and(|feet| feet.y < self.description.player.kill_y)
+    }
+
+    /// Cube picked with the select action, if any.
+    pub fn get_selected(&self) -> Option<&Handle<Node>> {
+        self.selected.as_ref()
+    }
+
+    /// Highlights the nearest cube hit by the ray, a miss or a hit on
+    /// anything else clears the selection.
+    pub fn select(&mut self, scene: &mut Scene, ray: &Ray) -> Option<Handle<Node>> {
+        let hit = scene
+            .pick(ray)
+            .map(|(handle, _)| handle)
+            .filter(|handle| self.cubes.contains(handle));
+        if let Some(previous) = self.selected.take() {
+            set_tint(scene, &previous, Color::WHITE);
+        }
+        if let Some(handle) = hit.as_ref() {
+            set_tint(scene, handle, SELECTION_TINT);
+        }
+        self.selected = hit.clone();
+        hit
+    }
+
+    /// Removes the nearest cube hit by the ray from the scene.
+    pub fn remove(&mut self, scene: &mut Scene, ray: &Ray) -> Option<Handle<Node>> {
+        let handle = scene
+            .pick(ray)
+            .map(|(handle, _)| handle)
+            .filter(|handle| self.cubes.contains(handle))?;
+        if self.selected.as_ref() == Some(&handle) {
+            self.selected = None;
+        }
+        self.cubes.retain(|cube| *cube != handle);
+        scene.unlink_node(&handle);
+        scene.remove_node(handle.clone());
+        Some(handle)
+    }
+
+    /// Writes description the level was built from.
+    pub fn save(&self, path: &Path) -> Result<(), LevelError> {
+        std::fs::write(path, self.description.to_toml()?)?;
+        Ok(())
+    }
+}
+
+/// Level logic runs as an engine system before scene updates, so moved nodes
+/// get their global transforms in the same step.
+impl EngineSystem for Level {
+    fn update(&mut self, ctx: &mut SystemContext) {
+        let dt = ctx.dt;
+        self.angle += CUBE_ROTATION_SPEED * dt;
+
+        if let Some((handle, revision)) = self.settings.take() {
+            if handle.get_revision() != revision {
+                self.apply_settings(&handle.borrow());
+            }
+            self.settings = Some((handle.clone(), handle.get_revision()));
+        }
+
+        self.player.process_input(ctx.input);
+
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.angle);
+        if let Some(scene) = ctx.scenes.borrow_mut(&self.scene) {
+            self.cubes.retain(|cube| scene.is_valid_handle(cube));
+            for node_handle in self.cubes.iter() {
+                if let Some(node) = scene.borrow_node_mut(node_handle) {
+                    node.set_local_rotation(rotation);
+                }
+            }
+
+            let ray = if ctx.cursor_grabbed {
+                self.player.get_view_ray(scene)
+            } else {
+                self.player
+                    .get_cursor_ray(scene, ctx.input.mouse_position(), ctx.client_size)
+            };
+            if let Some(ray) = ray {
+                if ctx.input.action_just_pressed(ACTION_SELECT) {
+                    self.select(scene, &ray);
+                }
+                if ctx.input.action_just_pressed(ACTION_REMOVE) {
+                    self.remove(scene, &ray);
+                }
+            }
+
+            if let Some((path, time)) = self.camera_path.as_mut() {
+                *time += dt;
+                let (eye, yaw) = path.pose(*time);
+                let eye_offset = PLAYER_HALF_EXTENTS.y + self.player.get_eye_height();
+                self.player
+                    .place(scene, eye - Vector3::new(0.0, eye_offset, 0.0), yaw);
+            } else {
+                self.player.update(scene, dt);
+                if ctx.input.action_just_pressed(ACTION_RESPAWN) || self.is_out_of_bounds(scene) {
+                    self.respawn(scene);
+                }
+            }
+        }
+    }
+}
+
+const SELECTION_TINT: Color = Color::opaque(1.0, 0.6, 0.2);
+
+fn set_tint(scene: &mut Scene, handle: &Handle<Node>, tint: Color) {
+    if let Some(node) = scene.borrow_node_mut(handle) {
+        if let NodeKind::Mesh(mesh) = node.borrow_kind_mut() {
+            mesh.set_tint(tint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::PlayerMode;
+    use balala::engine::system::SystemPhase;
+    use nalgebra::Vector2;
+    use std::rc::Rc;
+
+    #[test]
+    fn level_runs_headless() {
+        let mut engine = Engine::new_headless();
+        let level = Level::new(&mut engine);
+        let (scene, cube) = (level.scene.clone(), level.cubes[0].clone());
+        engine.register_system_in_phase(Box::new(level), SystemPhase::PreUpdate, 0);
+
+        for _ in 0..10 {
+            engine.advance(1.0 / 60.0, |_, _| {});
+            engine.render();
+        }
+        let cube = engine
+            .borrow_scene(&scene)
+            .unwrap()
+            .borrow_node(&cube)
+            .unwrap();
+        assert!(cube.get_look_vector().x.abs() > 0.0);
+        // Root, floor, sun, cubes and the player's camera and pivot.
+        assert_eq!(engine.stats().nodes_updated, 1 + 2 + 27 + 2);
+    }
+
+    #[test]
+    fn level_cubes_share_geometry() {
+        let mut engine = Engine::new_headless();
+        let level = Level::new(&mut engine);
+        let scene = engine.borrow_scene(&level.scene).unwrap();
+        let data = |handle: &Handle<Node>| match scene.borrow_node(handle).unwrap().borrow_kind() {
+            NodeKind::Mesh(mesh) => mesh.get_surfaces()[0].get_data().clone(),
+            _ => unreachable!(),
+        };
+        let first = data(&level.cubes[0]);
+        assert!(level
+            .cubes
+            .iter()
+            .all(|cube| Rc::ptr_eq(&first, &data(cube))));
+        // Cubes, the floor, the cache and `first`.
+        assert_eq!(Rc::strong_count(&first), level.cubes.len() + 3);
+    }
+
+    #[test]
+    fn pick_select_and_remove_cube() {
+        let mut engine = Engine::new_headless();
+        let mut level = Level::new(&mut engine);
+        engine.advance(1.0 / 60.0, |_, _| {});
+
+        // Row of cubes at x = 0, y = 2, the nearest one along -Z is at z = 4.
+        let expected = level.cubes[5].clone();
+        let scene = engine.borrow_scene_mut(&level.scene).unwrap();
+        let ray = Ray::new(Vector3::new(0.0, 2.0, 10.0), -Vector3::z()).unwrap();
+        let tint = |scene: &Scene, handle: &Handle<Node>| match scene.borrow_node(handle) {
+            Some(node) => match node.borrow_kind() {
+                NodeKind::Mesh(mesh) => mesh.get_tint(),
+                _ => unreachable!(),
+            },
+            None => unreachable!(),
+        };
+
+        assert!(level.select(scene, &ray) == Some(expected.clone()));
+        assert!(level.get_selected() == Some(&expected));
+        assert_eq!(tint(scene, &expected), SELECTION_TINT);
+
+        // Sky clears selection and highlight.
+        let sky = Ray::new(Vector3::new(0.0, 2.0, 10.0), Vector3::y()).unwrap();
+        assert!(level.select(scene, &sky).is_none());
+        assert!(level.get_selected().is_none());
+        assert_eq!(tint(scene, &expected), Color::WHITE);
+
+        level.select(scene, &ray);
+        assert!(level.remove(scene, &ray) == Some(expected.clone()));
+        assert!(scene.borrow_node(&expected).is_none());
+        assert!(!level.cubes.contains(&expected));
+        assert!(level.get_selected().is_none());
+        // Next cube along the ray is picked now.
+        assert!(level.select(scene, &ray) == Some(level.cubes[4].clone()));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn free_cursor_picks_under_cursor() {
+        use winit::{
+            dpi::{PhysicalPosition, PhysicalSize},
+            event::{DeviceId, ElementState, Event, MouseButton, WindowEvent},
+            window::WindowId,
+        };
+        let window_event = |event| Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        };
+
+        let mut engine = Engine::new_headless();
+        engine
+            .input
+            .input_map_mut()
+            .bind(ACTION_SELECT, MouseButton::Left);
+        engine.process_event(&window_event(WindowEvent::Resized(PhysicalSize::new(
+            800, 600,
+        ))));
+        let mut level = Level::new(&mut engine);
+        let (scene_handle, cubes) = (level.scene.clone(), level.cubes.clone());
+        let camera_handle = level.player.get_camera().clone();
+        // Facing the grid of cubes.
+        let scene = engine.borrow_scene_mut(&scene_handle).unwrap();
+        level
+            .player
+            .place(scene, Vector3::new(2.0, FLOOR_HEIGHT, 14.0), 180.0);
+        engine.register_system_in_phase(Box::new(level), SystemPhase::PreUpdate, 0);
+        engine.advance(1.0 / 60.0, |_, _| {});
+
+        // Nearest cube away from the screen center, the grabbed cursor would
+        // pick something else.
+        let scene = engine.borrow_scene(&scene_handle).unwrap();
+        let node = scene.borrow_node(&camera_handle).unwrap();
+        let NodeKind::Camera(camera) = node.borrow_kind() else {
+            unreachable!();
+        };
+        let client_size = Vector2::new(800.0, 600.0);
+        let viewport = camera.get_viewport_pixels(client_size);
+        let eye = node.get_global_position();
+        let (target, screen) = cubes
+            .iter()
+            .filter_map(|cube| {
+                let position = scene.borrow_node(cube)?.get_global_position();
+                let screen = camera.world_to_screen(position, viewport)?;
+                let inside = screen.x > 0.0
+                    && screen.y > 0.0
+                    && screen.x < client_size.x
+                    && screen.y < client_size.y;
+                (inside && (screen - client_size * 0.5).norm() > 100.0)
+                    .then(|| (cube.clone(), screen, (position - eye).norm()))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(cube, screen, _)| (cube, screen))
+            .unwrap();
+        let center_hit = scene
+            .pick(&camera.make_ray(client_size * 0.5, viewport))
+            .map(|(handle, _)| handle);
+        assert!(center_hit.as_ref() != Some(&target));
+
+        let cursor = PhysicalPosition::new(screen.x as f64, (client_size.y - screen.y) as f64);
+        engine.process_event(&window_event(WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: cursor,
+            modifiers: Default::default(),
+        }));
+        engine.process_event(&window_event(WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            modifiers: Default::default(),
+        }));
+        engine.advance(1.0 / 60.0, |_, _| {});
+
+        let scene = engine.borrow_scene(&scene_handle).unwrap();
+        let tint = |handle: &Handle<Node>| match scene.borrow_node(handle).unwrap().borrow_kind() {
+            NodeKind::Mesh(mesh) => mesh.get_tint(),
+            _ => unreachable!(),
+        };
+        assert_eq!(tint(&target), SELECTION_TINT);
+        assert_eq!(
+            cubes
+                .iter()
+                .filter(|cube| tint(cube) == SELECTION_TINT)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn demo_level_file_matches_builtin() {
+        let source =
+            std::fs::read_to_string(Path::new(DEFAULT_ASSETS_DIR).join(DEMO_LEVEL_PATH)).unwrap();
+        let (description, unknown) = LevelDescription::from_toml(&source).unwrap();
+        assert!(unknown.is_empty());
+        assert_eq!(description, LevelDescription::demo());
+    }
+
+    const FIXTURE_LEVEL: &str = r#"
+    [player]
+    position = [1.0, 0.05, 2.0]
+    yaw = 90.0
+
+    [[entity]]
+    name = "Ground"
+    primitive = "cube"
+    tags = ["collider"]
+    scale = [10.0, 0.1, 10.0]
+    texture = "./src/assets/textures/floor.png"
+
+    [[entity]]
+    name = "Crate"
+    primitive = "cube"
+    tags = ["rotating"]
+    position = [3.0, 1.0, -2.0]
+    rotation = [0.0, 90.0, 0.0]
+    texture = "./does/not/exist.png"
+    colour = "red"
+
+    [[entity]]
+    name = "Lamp"
+    position = [0.0, 5.0, 0.0]
+    light = { radius = 20.0, color = [1.0, 0.5, 0.0], flicker = true }
+
+    [[entity]]
+    name = "Marker"
+"#;
+
+    #[test]
+    fn load_fixture_level() {
+        let (_, unknown) = LevelDescription::from_toml(FIXTURE_LEVEL).unwrap();
+        assert_eq!(unknown, vec!["entity[1].colour", "entity[2].light.flicker"]);
+
+        let path = std::env::temp_dir().join("balala_fixture_level.toml");
+        std::fs::write(&path, FIXTURE_LEVEL).unwrap();
+        let mut engine = Engine::new_headless();
+        let level = Level::from_file(&path, &mut engine).unwrap();
+        let (scene_handle, cubes, entities, player_pivot) = (
+            level.scene.clone(),
+            level.cubes.clone(),
+            level.get_entities().to_vec(),
+            level.player.get_pivot().clone(),
+        );
+        engine.register_system_in_phase(Box::new(level), SystemPhase::PreUpdate, 0);
+        engine.advance(1.0 / 60.0, |_, _| {});
+        // Root, four entities, player's pivot and camera.
+        assert_eq!(engine.stats().nodes_updated, 1 + 4 + 2);
+
+        let scene = engine.borrow_scene(&scene_handle).unwrap();
+        let find = |name: &str| {
+            entities
+                .iter()
+                .find(|handle| scene.borrow_node(handle).unwrap().name == name)
+                .unwrap()
+                .clone()
+        };
+        let ground = scene.borrow_node(&find("Ground")).unwrap();
+        assert!(ground.is_collider());
+        match ground.borrow_kind() {
+            NodeKind::Mesh(mesh) => assert!(mesh.get_surfaces()[0].get_texture().is_some()),
+            _ => panic!("ground must be a mesh"),
+        }
+
+        let crate_handle = find("Crate");
+        assert_eq!(cubes, vec![crate_handle.clone()]);
+        let crate_node = scene.borrow_node(&crate_handle).unwrap();
+        assert!(!crate_node.is_collider());
+        assert_eq!(
+            crate_node.get_local_position(),
+            Vector3::new(3.0, 1.0, -2.0)
+        );
+        match crate_node.borrow_kind() {
+            // Missing texture is skipped.
+            NodeKind::Mesh(mesh) => assert!(mesh.get_surfaces()[0].get_texture().is_none()),
+            _ => panic!("crate must be a mesh"),
+        }
+
+        match scene.borrow_node(&find("Lamp")).unwrap().borrow_kind() {
+            NodeKind::Light(light) => {
+                assert_eq!(light.get_radius(), 20.0);
+                assert_eq!(light.get_color(), Color::opaque(1.0, 0.5, 0.0));
+            }
+            _ => panic!("lamp must be a light"),
+        }
+        assert!(matches!(
+            scene.borrow_node(&find("Marker")).unwrap().borrow_kind(),
+            NodeKind::Base
+        ));
+
+        let pivot = scene.borrow_node(&player_pivot).unwrap();
+        let spawn = pivot.get_global_position();
+        assert!((spawn - Vector3::new(1.0, 0.05 + PLAYER_HALF_EXTENTS.y, 2.0)).norm() < 0.1);
+    }
+
+    #[test]
+    fn invalid_level() {
+        let mut engine = Engine::new_headless();
+        let (description, _) = LevelDescription::from_toml(
+            r#"
+        [[entity]]
+        primitive = "cube"
+        camera = { fov = 60.0 }
+        "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Level::from_description(description, &mut engine),
+            Err(LevelError::Invalid(_))
+        ));
+        assert!(matches!(
+            LevelDescription::from_toml("[[entity]]\nprimitive = \"sphere\""),
+            Err(LevelError::Parse(_))
+        ));
+        assert!(matches!(
+            Level::from_file(Path::new("./does/not/exist.toml"), &mut engine),
+            Err(LevelError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn save_level_round_trip() {
+        let mut engine = Engine::new_headless();
+        let level = Level::new(&mut engine);
+        let path = std::env::temp_dir().join("balala_saved_level.toml");
+        level.save(&path).unwrap();
+        let loaded = Level::from_file(&path, &mut engine).unwrap();
+        assert_eq!(loaded.description, level.description);
+        assert_eq!(loaded.cubes.len(), 27);
+    }
+
+    #[test]
+    fn explicit_level_errors_are_reported() {
+        let mut engine = Engine::new_headless();
+        engine.set_asset_root(Path::new(DEFAULT_ASSETS_DIR));
+        assert!(matches!(
+            Level::from_file(Path::new("missing.toml"), &mut engine),
+            Err(LevelError::Io(_))
+        ));
+        // Demo level textures resolve against the asset root.
+        Level::from_file(
+            &engine.resolve_asset_path(Path::new(DEMO_LEVEL_PATH)),
+            &mut engine,
+        )
+        .unwrap();
+        assert!(engine
+            .request_texture(Path::new(CUBE_TEXTURE_PATH))
+            .is_some());
+    }
+
+    fn spawn_entity(name: &str, position: [f32; 3], yaw: f32) -> EntityDescription {
+        EntityDescription {
+            name: name.to_string(),
+            tags: vec![TAG_SPAWN.to_string()],
+            position,
+            rotation: [0.0, yaw, 0.0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn level_spawn_points() {
+        let mut engine = Engine::new_headless();
+        let mut description = LevelDescription::demo();
+        // Default spawn is used when the level has none.
+        let level = Level::from_description(description.clone(), &mut engine).unwrap();
+        assert_eq!(level.get_spawns(), [description.player.transform()]);
+
+        description.player.spawn_policy = SpawnPolicy::Nearest;
+        description.player.kill_y = -20.0;
+        description.entities.extend([
+            spawn_entity("SpawnA", [0.0, FLOOR_HEIGHT, 20.0], 180.0),
+            spawn_entity("SpawnB", [30.0, FLOOR_HEIGHT, 0.0], -90.0),
+            EntityDescription {
+                primitive: Some(Primitive::Cube),
+                ..spawn_entity("NotASpawn", [0.0, 0.0, 0.0], 0.0)
+            },
+        ]);
+        let source = description.to_toml().unwrap();
+        let (description, unknown) = LevelDescription::from_toml(&source).unwrap();
+        assert!(unknown.is_empty());
+        let mut level = Level::from_description(description, &mut engine).unwrap();
+        assert_eq!(level.get_spawns().len(), 2);
+        let scene_handle = level.scene.clone();
+        let scene = engine.borrow_scene_mut(&scene_handle).unwrap();
+        let feet = level.player.get_feet_position(scene).unwrap();
+        assert!((feet - Vector3::new(0.0, FLOOR_HEIGHT, 20.0)).norm() < 1e-5);
+        assert!((level.player.get_yaw().abs() - 180.0).abs() < 1e-3);
+
+        // Kill height is checked against the feet, nearest spawn is picked.
+        level
+            .player
+            .place(scene, Vector3::new(25.0, -19.0, 0.0), 0.0);
+        assert!(!level.is_out_of_bounds(scene));
+        level
+            .player
+            .place(scene, Vector3::new(25.0, -21.0, 0.0), 0.0);
+        assert!(level.is_out_of_bounds(scene));
+        assert_eq!(level.respawn(scene), Some(1));
+        assert!(!level.is_out_of_bounds(scene));
+        assert!((level.player.get_yaw() + 90.0).abs() < 1e-3);
+
+        // Falling below the kill height while the level runs respawns.
+        level.player.set_mode(PlayerMode::Fly);
+        level
+            .player
+            .place(scene, Vector3::new(-5.0, -40.0, 15.0), 0.0);
+        let player_pivot = level.player.get_pivot().clone();
+        engine.register_system_in_phase(Box::new(level), SystemPhase::PreUpdate, 0);
+        engine.advance(1.0 / 60.0, |_, _| {});
+        let scene = engine.borrow_scene(&scene_handle).unwrap();
+        let pivot = scene
+            .borrow_node(&player_pivot)
+            .unwrap()
+            .get_local_position();
+        assert!(
+            (pivot - Vector3::new(0.0, FLOOR_HEIGHT + PLAYER_HALF_EXTENTS.y, 20.0)).norm() < 1e-3
+        );
+    }
+}
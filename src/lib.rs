@@ -0,0 +1,6 @@
+pub mod engine;
+pub mod math;
+pub mod renderer;
+pub mod resource;
+pub mod scene;
+pub mod utils;
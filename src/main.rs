@@ -1,331 +1,1148 @@
-use std::{path::Path, time::Instant};
-
-use engine::Engine;
-use glutin::surface::GlSurface;
-use nalgebra::{Matrix4, UnitQuaternion, UnitVector3, Vector2, Vector3};
-use scene::{
-    node::{Camera, Mesh, Node, NodeKind},
-    Scene,
+use std::{
+    fmt,
+    path::{Path, PathBuf},
 };
-use utils::pool::Handle;
+
+use balala::{
+    engine::{
+        console::{expect_at_most, parse_number, Console, ConsoleError},
+        input::InputState,
+        input_map::InputMap,
+        system::SystemPhase,
+        Engine,
+    },
+    renderer::renderer::{PolygonMode, WindowSettings},
+    scene::{
+        node::{Mesh, Node, NodeKind},
+        Scene,
+    },
+    utils::pool::Handle,
+};
+use bench::{parse_bench_options, BenchConfig, BenchRecorder, CameraPath};
+use level::{
+    Level, LevelDescription, LevelError, CUBE_TEXTURE_PATH, DEFAULT_ASSETS_DIR, DEMO_LEVEL_PATH,
+};
+use nalgebra::Vector3;
+use settings::{Settings, SettingsHandle};
 use winit::{
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window,
+    event::{Event, MouseButton, VirtualKeyCode, WindowEvent},
+    event_loop::EventLoop,
 };
 
-mod engine;
-mod math;
-mod renderer;
-mod resource;
-mod scene;
-mod utils;
-
-pub struct Controller {
-    move_forward: bool,
-    move_backward: bool,
-    move_left: bool,
-    move_right: bool,
+pub mod bench;
+pub mod level;
+pub mod player;
+pub mod settings;
+
+pub const ACTION_PAUSE: &str = "pause";
+pub const ACTION_QUIT: &str = "quit";
+pub const ACTION_SELECT: &str = "select";
+pub const ACTION_REMOVE: &str = "remove";
+pub const ACTION_TOGGLE_STATS: &str = "toggle_stats";
+pub const ACTION_CYCLE_POLYGON_MODE: &str = "cycle_polygon_mode";
+pub const ACTION_TOGGLE_BOUNDS: &str = "toggle_bounds";
+pub const ACTION_FREEZE_CULLING: &str = "freeze_culling";
+pub const ACTION_TOGGLE_FULLSCREEN: &str = "toggle_fullscreen";
+pub const ACTION_SCREENSHOT: &str = "screenshot";
+pub const ACTION_RESPAWN: &str = "respawn";
+
+/// Default keys of debug actions.
+pub const DEBUG_BINDINGS: [(&str, VirtualKeyCode); 6] = [
+    (ACTION_TOGGLE_STATS, VirtualKeyCode::F1),
+    (ACTION_CYCLE_POLYGON_MODE, VirtualKeyCode::F2),
+    (ACTION_TOGGLE_BOUNDS, VirtualKeyCode::F3),
+    (ACTION_FREEZE_CULLING, VirtualKeyCode::F4),
+    (ACTION_TOGGLE_FULLSCREEN, VirtualKeyCode::F11),
+    (ACTION_SCREENSHOT, VirtualKeyCode::F12),
+];
+
+/// Game level bindings, handled outside of the simulation so they work while
+/// it is paused.
+pub fn game_input_map() -> InputMap {
+    let mut map = InputMap::new();
+    map.bind(ACTION_PAUSE, VirtualKeyCode::Escape);
+    map.bind(ACTION_QUIT, VirtualKeyCode::F10);
+    map.bind(ACTION_SELECT, MouseButton::Left);
+    map.bind(ACTION_REMOVE, MouseButton::Right);
+    map.bind(ACTION_RESPAWN, VirtualKeyCode::F6);
+    for (action, key) in DEBUG_BINDINGS {
+        map.bind(action, key);
+    }
+    map
 }
 
-pub struct Player {
-    camera: Handle<Node>,
-    pivot: Handle<Node>,
-    controller: Controller,
-    yaw: f32,
-    pitch: f32,
-    last_mouse_pos: Vector2<f32>,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugCommand {
+    ShowStats(bool),
+    SetPolygonMode(PolygonMode),
+    DrawBounds(bool),
+    FreezeCulling(bool),
+    Fullscreen(bool),
+    Screenshot,
 }
 
-impl Player {
-    pub fn new(scene: &mut Scene) -> Player {
-        let mut camera = Node::new(NodeKind::Camera(Camera::default()));
-        camera.set_local_position(Vector3::new(0.0, 2.0, 0.0));
-
-        let mut pivot = Node::new(NodeKind::Base);
-        pivot.set_local_position(Vector3::new(0.0, 0.0, 20.0));
-
-        let camera_handle = scene.add_node(camera);
-        let pivot_handle = scene.add_node(pivot);
-        scene.link_nodes(&camera_handle, &pivot_handle);
-
-        Player {
-            camera: camera_handle,
-            pivot: pivot_handle,
-            controller: Controller {
-                move_backward: false,
-                move_forward: false,
-                move_left: false,
-                move_right: false,
-            },
-            yaw: 0.0,
-            pitch: 0.0,
-            last_mouse_pos: Vector2::zeros(),
+impl fmt::Display for DebugCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        match self {
+            DebugCommand::ShowStats(shown) => write!(f, "stats {}", on_off(*shown)),
+            DebugCommand::SetPolygonMode(mode) => write!(f, "polygon mode {:?}", mode),
+            DebugCommand::DrawBounds(enabled) => write!(f, "bounds {}", on_off(*enabled)),
+            DebugCommand::FreezeCulling(frozen) => {
+                write!(f, "culling freeze {}", on_off(*frozen))
+            }
+            DebugCommand::Fullscreen(enabled) => write!(f, "fullscreen {}", on_off(*enabled)),
+            DebugCommand::Screenshot => write!(f, "screenshot"),
         }
     }
+}
 
-    pub fn update(&mut self, scene: &mut Scene) {
-        if let Some(pivot_node) = scene.borrow_node_mut(&self.pivot) {
-            let mut velocity = Vector3::<f32>::zeros();
-            let look = pivot_node.get_look_vector();
-            let side = pivot_node.get_side_vector();
+/// State of debug toggles, each debug action flips or cycles one of them.
+#[derive(Debug)]
+pub struct DebugToggles {
+    show_stats: bool,
+    polygon_mode: PolygonMode,
+    draw_bounds: bool,
+    freeze_culling: bool,
+    fullscreen: bool,
+}
 
-            if self.controller.move_forward {
-                velocity += look;
-            }
-            if self.controller.move_backward {
-                velocity -= look;
-            }
-            if self.controller.move_left {
-                velocity += side;
-            }
-            if self.controller.move_right {
-                velocity -= side;
-            }
+impl Default for DebugToggles {
+    fn default() -> Self {
+        Self {
+            show_stats: true,
+            polygon_mode: PolygonMode::Fill,
+            draw_bounds: false,
+            freeze_culling: false,
+            fullscreen: false,
+        }
+    }
+}
 
-            if let Some(normal) = velocity.try_normalize(0.) {
-                pivot_node.offset(normal);
-            }
-            pivot_node.set_local_rotation(UnitQuaternion::from_axis_angle(
-                &Vector3::y_axis(),
-                self.yaw.to_radians(),
-            ));
-
-            if let Some(camera_node) = scene.borrow_node_mut(&self.camera) {
-                camera_node.set_local_rotation(UnitQuaternion::from_axis_angle(
-                    &Vector3::x_axis(),
-                    self.pitch.to_radians(),
-                ));
-            }
+impl DebugToggles {
+    /// Returns commands for debug actions pressed this frame, in the order of
+    /// `DEBUG_BINDINGS`. Presses are consumed, so frames without a simulation
+    /// step don't handle them again.
+    pub fn process(&mut self, input: &mut InputState) -> Vec<DebugCommand> {
+        let mut commands = Vec::new();
+        if input.consume_action_press(ACTION_TOGGLE_STATS) {
+            self.show_stats = !self.show_stats;
+            commands.push(DebugCommand::ShowStats(self.show_stats));
+        }
+        if input.consume_action_press(ACTION_CYCLE_POLYGON_MODE) {
+            self.polygon_mode = self.polygon_mode.next();
+            commands.push(DebugCommand::SetPolygonMode(self.polygon_mode));
+        }
+        if input.consume_action_press(ACTION_TOGGLE_BOUNDS) {
+            self.draw_bounds = !self.draw_bounds;
+            commands.push(DebugCommand::DrawBounds(self.draw_bounds));
         }
+        if input.consume_action_press(ACTION_FREEZE_CULLING) {
+            self.freeze_culling = !self.freeze_culling;
+            commands.push(DebugCommand::FreezeCulling(self.freeze_culling));
+        }
+        if input.consume_action_press(ACTION_TOGGLE_FULLSCREEN) {
+            self.fullscreen = !self.fullscreen;
+            commands.push(DebugCommand::Fullscreen(self.fullscreen));
+        }
+        if input.consume_action_press(ACTION_SCREENSHOT) {
+            commands.push(DebugCommand::Screenshot);
+        }
+        commands
     }
 
-    pub fn process_event<'a>(&mut self, event: &winit::event::Event<()>) -> bool {
-        match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CursorMoved { position, .. } => {
-                    let mouse_velocity = Vector2::new(
-                        position.x as f32 - self.last_mouse_pos.x,
-                        position.y as f32 - self.last_mouse_pos.y,
-                    );
-                    let sens: f32 = 0.3;
+    pub fn is_stats_shown(&self) -> bool {
+        self.show_stats
+    }
+}
 
-                    self.pitch += mouse_velocity.y * sens;
-                    self.yaw -= mouse_velocity.x * sens;
+/// File name for a screenshot taken at `time`.
+fn screenshot_path(time: std::time::SystemTime) -> PathBuf {
+    let seconds = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    PathBuf::from(format!("screenshot_{}.png", seconds))
+}
 
-                    if self.pitch > 90.0 {
-                        self.pitch = 90.0;
-                    } else if self.pitch < -90.0 {
-                        self.pitch = -90.0;
-                    }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PauseCommand {
+    Pause,
+    Resume,
+    Quit,
+}
 
-                    self.last_mouse_pos = Vector2::new(position.x as f32, position.y as f32);
-                }
-                WindowEvent::KeyboardInput { input, .. } => match input.state {
-                    ElementState::Pressed => {
-                        if let Some(key) = input.virtual_keycode {
-                            match key {
-                                VirtualKeyCode::W => self.controller.move_forward = true,
-                                VirtualKeyCode::S => self.controller.move_backward = true,
-                                VirtualKeyCode::A => self.controller.move_left = true,
-                                VirtualKeyCode::D => self.controller.move_right = true,
-                                _ => (),
-                            }
-                        }
-                    }
-                    ElementState::Released => {
-                        if let Some(key) = input.virtual_keycode {
-                            match key {
-                                VirtualKeyCode::W => self.controller.move_forward = false,
-                                VirtualKeyCode::S => self.controller.move_backward = false,
-                                VirtualKeyCode::A => self.controller.move_left = false,
-                                VirtualKeyCode::D => self.controller.move_right = false,
-                                _ => (),
-                            }
-                        }
-                    }
-                },
-                _ => (),
-            },
-            _ => (),
+/// Pause state machine: pause action releases the cursor and pauses, pause
+/// action or a click into the window resumes.
+#[derive(Debug, Default)]
+pub struct PauseToggle {
+    paused: bool,
+}
+
+impl PauseToggle {
+    pub fn process(&mut self, input: &InputState) -> Option<PauseCommand> {
+        if input.action_just_pressed(ACTION_QUIT) {
+            return Some(PauseCommand::Quit);
+        }
+        let toggle = input.action_just_pressed(ACTION_PAUSE);
+        if self.paused {
+            if toggle || input.was_mouse_button_pressed(MouseButton::Left) {
+                self.paused = false;
+                return Some(PauseCommand::Resume);
+            }
+        } else if toggle {
+            self.paused = true;
+            return Some(PauseCommand::Pause);
         }
-        false
+        None
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 }
 
-pub struct Level {
-    scene: Handle<Scene>,
-    player: Player,
+/// Console commands working on the level scene.
+fn register_scene_commands(console: &mut Console, scene: Handle<Scene>) {
+    let list_scene = scene.clone();
+    console.register("list_nodes", move |engine, args| {
+        expect_at_most(args, 1)?;
+        let filter = args.first().map(|filter| filter.to_lowercase());
+        let scene = engine
+            .borrow_scene(&list_scene)
+            .ok_or_else(|| ConsoleError::Failed("no level scene".to_string()))?;
+        let lines: Vec<String> = scene
+            .find_nodes(|node| {
+                filter
+                    .as_ref()
+                    .is_none_or(|f| node.get_name().to_lowercase().contains(f))
+            })
+            .iter()
+            .filter_map(|handle| scene.borrow_node(handle))
+            .map(|node| {
+                let p = node.get_global_position();
+                format!("{} ({:.2}, {:.2}, {:.2})", node.get_name(), p.x, p.y, p.z)
+            })
+            .collect();
+        Ok(format!("{} nodes\n{}", lines.len(), lines.join("\n")))
+    });
 
-    cubes: Vec<Handle<Node>>,
-    angle: f32,
+    let remove_scene = scene.clone();
+    console.register("remove", move |engine, args| {
+        expect_at_most(args, 1)?;
+        let name = args
+            .first()
+            .ok_or(ConsoleError::MissingArgument("node name"))?;
+        let scene = engine
+            .borrow_scene_mut(&remove_scene)
+            .ok_or_else(|| ConsoleError::Failed("no level scene".to_string()))?;
+        let handle = scene
+            .find_nodes(|node| node.get_name() == name)
+            .into_iter()
+            .next()
+            .ok_or_else(|| ConsoleError::Failed(format!("no node named {:?}", name)))?;
+        scene.unlink_node(&handle);
+        scene.remove_node(handle);
+        Ok(format!("removed {}", name))
+    });
+
+    console.register("spawn_cube", move |engine, args| {
+        expect_at_most(args, 3)?;
+        let position = Vector3::new(
+            parse_number(args, 0, "x")?,
+            parse_number(args, 1, "y")?,
+            parse_number(args, 2, "z")?,
+        );
+        let texture = engine.request_texture(Path::new(CUBE_TEXTURE_PATH));
+        let scene = engine
+            .borrow_scene_mut(&scene)
+            .ok_or_else(|| ConsoleError::Failed("no level scene".to_string()))?;
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        if let Some(texture) = texture {
+            mesh.apply_texture(texture);
+        }
+        let mut node = Node::new(NodeKind::Mesh(mesh));
+        node.set_name("Cube");
+        node.set_local_position(position);
+        node.set_collider(true);
+        scene.add_node(node);
+        Ok(format!(
+            "spawned cube at ({}, {}, {})",
+            position.x, position.y, position.z
+        ))
+    });
 }
 
-impl Level {
-    pub fn new(engine: &mut Engine) -> Level {
-        let mut cubes: Vec<Handle<Node>> = Vec::new();
+pub const USAGE: &str = "\
+Usage: balala [options]
 
-        let mut scene = Scene::new();
+Options:
+  --assets <dir>       directory asset paths are relative to [default: ./src/assets]
+  --level <file>       level file to load instead of the demo level
+  --settings <file>    settings file to use instead of the one in config directory
+  --width <pixels>     window width
+  --height <pixels>    window height
+  --fullscreen         start in borderless fullscreen
+  --no-vsync           disable vsync
+  --msaa <samples>     samples per pixel: 1, 2, 4, 8 or 16
+                       window options override settings file for this run
+  --bench [key=value]  run stress test and exit, keys: cubes, lights, seconds,
+                       format (csv or json), out
+  --help               print this help
+";
 
-        {
-            let mut floor_mesh = Mesh::default();
-            floor_mesh.make_cube();
-            if let Some(texture) =
-                engine.request_texture(Path::new("./src/assets/textures/floor.png"))
-            {
-                floor_mesh.apply_texture(texture);
-            }
-            let mut floor_node = Node::new(NodeKind::Mesh(floor_mesh));
-            floor_node.set_name("Floor");
-            floor_node.set_local_scale(Vector3::new(100.0, 0.1, 100.0));
-            scene.add_node(floor_node);
-        }
-
-        for i in 0..3 {
-            for j in 0..3 {
-                for k in 0..3 {
-                    let mut cube_mesh = Mesh::default();
-                    cube_mesh.make_cube();
-                    if let Some(texture) =
-                        engine.request_texture(Path::new("./src/assets/textures/box.png"))
-                    {
-                        cube_mesh.apply_texture(texture);
-                    }
-                    let mut cube_node = Node::new(NodeKind::Mesh(cube_mesh));
-                    cube_node.set_name("Cube");
+/// Window options given on command line, they take precedence over the
+/// settings file but are not saved to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WindowOverrides {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: Option<bool>,
+    pub vsync: Option<bool>,
+    pub msaa: Option<u8>,
+}
 
-                    let pos = Vector3::new(i as f32 * 2.0, j as f32 * 2.0, k as f32 * 2.0);
-                    cube_node.set_local_position(pos);
-                    cubes.push(scene.add_node(cube_node));
-                }
-            }
-        }
+impl WindowOverrides {
+    pub fn apply(&self, window: &mut WindowSettings) {
+        window.width = self.width.unwrap_or(window.width);
+        window.height = self.height.unwrap_or(window.height);
+        window.fullscreen = self.fullscreen.unwrap_or(window.fullscreen);
+        window.vsync = self.vsync.unwrap_or(window.vsync);
+        window.msaa = self.msaa.or(window.msaa);
+    }
+}
 
-        let player = Player::new(&mut scene);
+/// Options of the demo binary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchConfig {
+    pub assets: PathBuf,
+    pub level: Option<PathBuf>,
+    /// Settings file, `None` uses the platform config location.
+    pub settings: Option<PathBuf>,
+    pub window: WindowOverrides,
+    pub bench: Option<BenchConfig>,
+    pub help: bool,
+}
 
-        Level {
-            player,
-            cubes,
-            angle: 0.0,
-            scene: engine.add_scene(scene),
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        Self {
+            assets: PathBuf::from(DEFAULT_ASSETS_DIR),
+            level: None,
+            settings: None,
+            window: WindowOverrides::default(),
+            bench: None,
+            help: false,
         }
     }
+}
 
-    pub fn update(&mut self, engine: &mut Engine) {
-        self.angle += 0.1;
-
-        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.angle);
-        if let Some(scene) = engine.borrow_scene_mut(&self.scene) {
-            for node_handle in self.cubes.iter() {
-                if let Some(node) = scene.borrow_node_mut(node_handle) {
-                    node.set_local_rotation(rotation);
+/// Parses command line arguments without the program name.
+pub fn parse_args(args: &[String]) -> Result<LaunchConfig, String> {
+    let mut config = LaunchConfig::default();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next_if(|value| !value.starts_with("--"))
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "--assets" => config.assets = PathBuf::from(value(arg)?),
+            "--level" => config.level = Some(PathBuf::from(value(arg)?)),
+            "--settings" => config.settings = Some(PathBuf::from(value(arg)?)),
+            "--width" | "--height" => {
+                let text = value(arg)?;
+                let size = text.parse().ok().filter(|size| *size > 0).ok_or_else(|| {
+                    format!("invalid {} {:?}, expected positive integer", arg, text)
+                })?;
+                if arg == "--width" {
+                    config.window.width = Some(size);
+                } else {
+                    config.window.height = Some(size);
                 }
             }
-
-            self.player.update(scene);
+            "--fullscreen" => config.window.fullscreen = Some(true),
+            "--no-vsync" => config.window.vsync = Some(false),
+            "--msaa" => {
+                let text = value(arg)?;
+                let samples = text
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|samples| samples.is_power_of_two() && *samples <= 16)
+                    .ok_or_else(|| {
+                        format!("invalid --msaa {:?}, expected 1, 2, 4, 8 or 16", text)
+                    })?;
+                config.window.msaa = Some(samples);
+            }
+            "--bench" => {
+                let mut options = Vec::new();
+                while let Some(option) = args.next_if(|option| !option.starts_with("--")) {
+                    options.push(option);
+                }
+                config.bench = Some(parse_bench_options(&options)?);
+            }
+            "--help" | "-h" => config.help = true,
+            _ => return Err(format!("unknown argument {:?}", arg)),
         }
     }
+    if config.bench.is_some() && config.level.is_some() {
+        return Err("--level can not be used with --bench, which builds its own level".to_string());
+    }
+    Ok(config)
+}
+
+/// Benchmark in progress.
+struct BenchRun {
+    config: BenchConfig,
+    recorder: BenchRecorder,
 }
 
 pub struct Game {
     engine: Engine,
-    level: Level,
+    pause: PauseToggle,
+    console: Console,
+    debug: DebugToggles,
+    bench: Option<BenchRun>,
+    settings: SettingsHandle,
+    /// Where settings are saved at exit, `None` keeps them in memory only.
+    settings_path: Option<PathBuf>,
+    /// Revisions of settings last written to disk and applied to the window.
+    saved_revision: u32,
+    video_revision: u32,
 }
 
 impl Game {
-    pub fn new(el: &EventLoop<()>) -> Game {
-        let mut engine = Engine::new(el);
-        let level = Level::new(&mut engine);
-        Game { engine, level }
+    /// Fails only if the level given in config can not be loaded, the demo
+    /// level falls back to the built-in one.
+    pub fn new(el: &EventLoop<()>, config: &LaunchConfig) -> Result<Game, LevelError> {
+        let settings_path = config.settings.clone().or_else(Settings::default_path);
+        let settings = settings_path
+            .as_deref()
+            .map(Settings::load)
+            .unwrap_or_default();
+        let mut window = settings.video;
+        config.window.apply(&mut window);
+        let mut engine = Engine::with_window_settings(el, &window);
+        engine.set_asset_root(&config.assets);
+        let settings = SettingsHandle::new(settings);
+        if let Some(bench) = config.bench.as_ref() {
+            // Benchmark runs must not change user settings.
+            return Ok(Game::bench(engine, bench.clone(), settings));
+        }
+        let level = match config.level.as_ref() {
+            Some(path) => Level::from_file(path, &mut engine)?,
+            None => {
+                let path = engine.resolve_asset_path(Path::new(DEMO_LEVEL_PATH));
+                Level::from_file(&path, &mut engine).unwrap_or_else(|err| {
+                    println!("failed to load {:?}: {}, using built-in level", path, err);
+                    Level::new(&mut engine)
+                })
+            }
+        };
+        let mut game = Game::with_level(engine, level, settings);
+        game.settings_path = settings_path;
+        Ok(game)
     }
 
-    pub fn update(&mut self) {
-        self.level.update(&mut self.engine);
+    /// Runs grid level of the benchmark with scripted camera and no vsync,
+    /// exits once it is done.
+    fn bench(mut engine: Engine, config: BenchConfig, settings: SettingsHandle) -> Game {
+        let description = LevelDescription::grid(config.cubes, config.lights);
+        let mut level = Level::from_description(description, &mut engine)
+            .expect("grid level has no external models");
+        level.set_camera_path(Some(CameraPath::around_grid(config.cubes)));
+        if let Some(renderer) = engine.renderer_mut() {
+            renderer.set_vsync(false);
+        }
+        engine.set_target_fps(None);
+        let mut game = Game::with_level(engine, level, settings);
+        game.bench = Some(BenchRun {
+            recorder: BenchRecorder::new(config.seconds),
+            config,
+        });
+        game
     }
 
-    pub fn run(mut self, el: EventLoop<()>) {
-        let mut last_frame_inst = Instant::now();
+    fn with_level(mut engine: Engine, mut level: Level, settings: SettingsHandle) -> Game {
+        *engine.input.input_map_mut() = game_input_map();
+        settings
+            .borrow()
+            .apply_bindings(engine.input.input_map_mut());
+        level.set_settings(settings.clone());
+        let mut console = Console::new();
+        register_scene_commands(&mut console, level.get_scene().clone());
+        engine.register_system_in_phase(Box::new(level), SystemPhase::PreUpdate, 0);
+        engine.set_cursor_grabbed(true);
+        engine.set_cursor_visible(false);
+        Game {
+            engine,
+            pause: PauseToggle::default(),
+            console,
+            debug: DebugToggles::default(),
+            bench: None,
+            saved_revision: settings.get_revision(),
+            video_revision: settings.get_revision(),
+            settings,
+            settings_path: None,
+        }
+    }
 
-        let (mut frame_count, mut accum_time) = (0, 0.0);
-        el.run(move |event, _target, control_flow| {
-            control_flow.set_poll();
+    /// Settings of the running game, edits apply on the next frame.
+    pub fn get_settings(&self) -> &SettingsHandle {
+        &self.settings
+    }
+
+    /// Applies edited settings that live outside of the level: window options
+    /// and game bindings.
+    fn sync_settings(&mut self) {
+        let revision = self.settings.get_revision();
+        if revision == self.video_revision {
+            return;
+        }
+        self.video_revision = revision;
+        let settings = self.settings.borrow();
+        settings.apply_bindings(self.engine.input.input_map_mut());
+        if let Some(renderer) = self.engine.renderer_mut() {
+            renderer.set_vsync(settings.video.vsync);
+            if renderer.is_fullscreen() != settings.video.fullscreen {
+                renderer.set_fullscreen(settings.video.fullscreen);
+            }
+        }
+    }
+
+    /// Writes settings if they were edited since the last save.
+    fn save_settings(&mut self) {
+        let revision = self.settings.get_revision();
+        let Some(path) = self.settings_path.as_ref() else {
+            return;
+        };
+        if revision == self.saved_revision {
+            return;
+        }
+        match self.settings.borrow().save(path) {
+            Ok(()) => self.saved_revision = revision,
+            Err(err) => println!("Failed to save settings {:?}: {}", path, err),
+        }
+    }
+
+    /// Console gets events first, keyboard does not reach the player while
+    /// it is open.
+    fn process_event(&mut self, event: &Event<()>) {
+        let was_open = self.console.is_open();
+        if !self.console.process_event(event) {
+            self.engine.process_event(event);
+        }
+        if self.console.is_open() && !was_open {
+            // Keys held when console opened would keep the player moving.
+            self.engine.input.release_all();
+        }
+    }
+
+    /// Applies pause and quit actions of current frame, must run before
+    /// engine update which consumes the input.
+    fn handle_pause(&mut self) {
+        match self.pause.process(&self.engine.input) {
+            Some(PauseCommand::Pause) => {
+                self.engine.set_paused(true);
+                self.engine.set_cursor_grabbed(false);
+                self.engine.set_cursor_visible(true);
+            }
+            Some(PauseCommand::Resume) => {
+                // Motion of the free cursor must not turn the player.
+                self.engine.input.clear_frame();
+                self.engine.set_paused(false);
+                self.engine.set_cursor_grabbed(true);
+                self.engine.set_cursor_visible(false);
+            }
+            Some(PauseCommand::Quit) => self.engine.shutdown(),
+            None => (),
+        }
+    }
+
+    /// Applies debug actions of current frame to the renderer, headless
+    /// engine only logs them.
+    fn handle_debug_keys(&mut self) {
+        for command in self.debug.process(&mut self.engine.input) {
+            println!("{}", command);
+            let Some(renderer) = self.engine.renderer_mut() else {
+                continue;
+            };
+            match command {
+                DebugCommand::ShowStats(_) => (),
+                DebugCommand::SetPolygonMode(mode) => renderer.set_polygon_mode(mode),
+                DebugCommand::DrawBounds(enabled) => renderer.set_draw_bounds(enabled),
+                DebugCommand::FreezeCulling(frozen) => renderer.set_culling_frozen(frozen),
+                DebugCommand::Fullscreen(enabled) => {
+                    renderer.set_fullscreen(enabled);
+                    self.settings
+                        .edit(|settings| settings.video.fullscreen = enabled);
+                    self.video_revision = self.settings.get_revision();
+                }
+                DebugCommand::Screenshot => {
+                    renderer.request_screenshot(&screenshot_path(std::time::SystemTime::now()))
+                }
+            }
+        }
+    }
 
-            self.level.player.process_event(&event);
+    /// Records last rendered frame, once enough time was recorded writes the
+    /// report and stops the engine.
+    fn record_bench_frame(&mut self, frame: usize) {
+        let Some(bench) = self.bench.as_mut() else {
+            return;
+        };
+        // First frame time includes startup.
+        if frame > 1 {
+            let stats = self.engine.stats();
+            bench.recorder.record(stats.last_frame_time, &stats.render);
+        }
+        if bench.recorder.get_elapsed() < bench.config.seconds {
+            return;
+        }
+        let report = bench.recorder.report().format(bench.config.format);
+        match bench.config.output.as_ref() {
+            Some(path) => match std::fs::write(path, &report) {
+                Ok(()) => println!("Benchmark report written to {:?}", path),
+                Err(err) => println!("Failed to write benchmark report {:?}: {}", path, err),
+            },
+            None => print!("{}", report),
+        }
+        self.engine.shutdown();
+    }
+
+    pub fn run(mut self, el: EventLoop<()>) {
+        let mut rendered_frames = 0;
+        let mut frame_count = 0;
+        el.run(move |event, _target, control_flow| {
+            self.process_event(&event);
             match event {
                 Event::MainEventsCleared => {
-                    self.update();
-                    self.engine.update();
-                    accum_time += last_frame_inst.elapsed().as_secs_f32();
-                    last_frame_inst = Instant::now();
+                    self.console.execute_pending(&mut self.engine);
+                    self.sync_settings();
+                    self.handle_debug_keys();
+                    self.handle_pause();
+                    self.engine.update(|_, _| {});
                     frame_count += 1;
                     if frame_count == 100 {
-                        println!(
-                            "Avg frame time {}ms",
-                            accum_time * 1000.0 / frame_count as f32
-                        );
-                        accum_time = 0.0;
+                        if self.debug.is_stats_shown() {
+                            println!("{}", self.engine.stats_string());
+                        }
                         frame_count = 0;
                     }
                 }
                 Event::RedrawRequested(_) => {}
                 Event::RedrawEventsCleared => {
                     self.engine.render();
-                    self.engine
-                        .renderer
-                        .gl_surface
-                        .swap_buffers(&self.engine.renderer.gl_context)
-                        .unwrap();
-                    self.engine.renderer.context.request_redraw();
+                    rendered_frames += 1;
+                    self.record_bench_frame(rendered_frames);
                 }
                 Event::WindowEvent {
-                    window_id: _,
-                    event,
-                } => match event {
-                    WindowEvent::CloseRequested => {
-                        self.engine.stop();
-                        *control_flow = ControlFlow::Exit;
-                    }
-                    WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => self.engine.stop(),
-                    _ => (),
-                },
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => self.engine.shutdown(),
+                Event::LoopDestroyed => self.save_settings(),
                 _ => (),
             }
+            *control_flow = self.engine.control_flow();
         });
     }
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = match parse_args(&args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if config.help {
+        print!("{}", USAGE);
+        return;
+    }
     let el = EventLoop::new();
-    Game::new(&el).run(el);
+    match Game::new(&el, &config) {
+        Ok(game) => game.run(el),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
-#[test]
-fn fbx() {
-    use fbxcel_dom::any::*;
-    use std::fs::File;
-    use std::io::BufReader;
-
-    let file = File::open("./src/assets/models/cube.fbx").expect("Failed to open file");
-    let reader = BufReader::new(file);
-
-    match AnyDocument::from_seekable_reader(reader).expect("Failed to load document") {
-        AnyDocument::V7400(ver, doc) => {
-            println!("Loaded FBX DOM successfully: FBX version = {:?}", ver);
-            for scene in doc.scenes() {
-                println!("Scene object: object_id={:?}", scene.object_id());
-                let root_id = scene
-                    .root_object_id()
-                    .expect("Failed to get root object ID");
-                println!("\tRoot object ID: {:?}", root_id);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bench::BenchFormat,
+        player::{default_input_map, tests::key_event, ACTION_JUMP},
+    };
+    use balala::engine::input_map::InputBinding;
+
+    fn headless_game() -> Game {
+        let mut engine = Engine::new_headless();
+        let level = Level::new(&mut engine);
+        Game::with_level(engine, level, SettingsHandle::default())
+    }
+
+    #[test]
+    fn fbx() {
+        use fbxcel_dom::any::*;
+        use std::fs::File;
+        use std::io::BufReader;
+
+        let file = File::open("./src/assets/models/cube.fbx").expect("Failed to open file");
+        let reader = BufReader::new(file);
+
+        let mut engine = Engine::new_headless();
+        let mut scenes = Vec::new();
+        match AnyDocument::from_seekable_reader(reader).expect("Failed to load document") {
+            AnyDocument::V7400(ver, doc) => {
+                println!("Loaded FBX DOM successfully: FBX version = {:?}", ver);
+                for scene in doc.scenes() {
+                    println!("Scene object: object_id={:?}", scene.object_id());
+                    let root_id = scene
+                        .root_object_id()
+                        .expect("Failed to get root object ID");
+                    println!("\tRoot object ID: {:?}", root_id);
+
+                    let mut mesh = Mesh::default();
+                    mesh.make_cube();
+                    let mut scene = Scene::new();
+                    let node = scene.add_node(Node::new(NodeKind::Mesh(mesh)));
+                    scenes.push((engine.add_scene(scene), node));
+                }
+            }
+            _ => panic!("FBX version unsupported by this example"),
+        }
+        assert!(!scenes.is_empty());
+
+        engine.advance(1.0 / 60.0, |_, _| {});
+        engine.render();
+        for (scene, node) in scenes.iter() {
+            let scene = engine.borrow_scene(scene).expect("scene must be alive");
+            assert!(scene.borrow_node(node).is_some());
+        }
+    }
+
+    #[test]
+    fn escape_pauses_and_releases_cursor() {
+        use winit::event::{DeviceId, ElementState};
+        use winit::window::WindowId;
+
+        let mut game = headless_game();
+        let frame = |game: &mut Game, event: Option<Event<'static, ()>>| {
+            if let Some(event) = event {
+                game.engine.process_event(&event);
             }
+            game.handle_pause();
+            game.engine.advance(1.0 / 60.0, |_, _| {});
+        };
+        #[allow(deprecated)]
+        let click = |state| Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::MouseInput {
+                device_id: unsafe { DeviceId::dummy() },
+                state,
+                button: MouseButton::Left,
+                modifiers: Default::default(),
+            },
+        };
+
+        frame(&mut game, Some(key_event(VirtualKeyCode::Escape, true)));
+        assert!(game.engine.is_paused() && game.pause.is_paused());
+        assert!(!game.engine.cursor_state().is_grab_requested());
+        // Holding the key does not toggle again.
+        frame(&mut game, None);
+        assert!(game.engine.is_paused());
+
+        frame(&mut game, Some(key_event(VirtualKeyCode::Escape, false)));
+        frame(&mut game, Some(key_event(VirtualKeyCode::Escape, true)));
+        assert!(!game.engine.is_paused());
+        assert!(game.engine.cursor_state().is_grab_requested());
+        frame(&mut game, Some(key_event(VirtualKeyCode::Escape, false)));
+
+        // Clicking does nothing while playing, resumes while paused.
+        frame(&mut game, Some(click(ElementState::Pressed)));
+        frame(&mut game, Some(click(ElementState::Released)));
+        assert!(!game.engine.is_paused());
+        frame(&mut game, Some(key_event(VirtualKeyCode::Escape, true)));
+        frame(&mut game, Some(key_event(VirtualKeyCode::Escape, false)));
+        assert!(game.engine.is_paused());
+        frame(&mut game, Some(click(ElementState::Pressed)));
+        assert!(!game.engine.is_paused());
+        assert!(game.engine.cursor_state().is_grab_requested());
+
+        assert!(!game.engine.should_exit());
+        frame(&mut game, Some(key_event(VirtualKeyCode::F10, true)));
+        assert!(game.engine.should_exit());
+    }
+
+    #[test]
+    fn console_scene_commands() {
+        let mut game = headless_game();
+        game.engine.advance(1.0 / 60.0, |_, _| {});
+        let run = |game: &mut Game, line: &str| game.console.execute(&mut game.engine, line);
+
+        let cubes = run(&mut game, "list_nodes cube").unwrap();
+        assert!(cubes.starts_with("27 nodes"));
+        assert_eq!(
+            run(&mut game, "spawn_cube 1 2"),
+            Err(ConsoleError::MissingArgument("z"))
+        );
+        run(&mut game, "spawn_cube 10 0.5 -3").unwrap();
+        game.engine.advance(1.0 / 60.0, |_, _| {});
+        let cubes = run(&mut game, "list_nodes CUBE").unwrap();
+        assert!(cubes.starts_with("28 nodes"));
+        assert!(cubes.contains("Cube (10.00, 0.50, -3.00)"));
+
+        assert_eq!(
+            run(&mut game, "remove Floor"),
+            Ok("removed Floor".to_string())
+        );
+        assert!(run(&mut game, "list_nodes floor")
+            .unwrap()
+            .starts_with("0 nodes"));
+        assert_eq!(
+            run(&mut game, "remove Floor"),
+            Err(ConsoleError::Failed("no node named \"Floor\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn console_captures_keyboard() {
+        let mut game = headless_game();
+        game.process_event(&key_event(VirtualKeyCode::W, true));
+        assert!(game.engine.input.is_key_down(VirtualKeyCode::W));
+
+        game.process_event(&key_event(VirtualKeyCode::Grave, true));
+        assert!(game.console.is_open());
+        // Held key is released and new ones do not reach the player.
+        assert!(!game.engine.input.is_key_down(VirtualKeyCode::W));
+        game.process_event(&key_event(VirtualKeyCode::D, true));
+        assert!(!game.engine.input.is_key_down(VirtualKeyCode::D));
+        // Escape closes the console instead of pausing.
+        game.process_event(&key_event(VirtualKeyCode::Escape, true));
+        game.handle_pause();
+        assert!(!game.console.is_open() && !game.engine.is_paused());
+    }
+
+    #[test]
+    fn debug_keys_map_to_actions() {
+        let map = game_input_map();
+        for (action, key) in DEBUG_BINDINGS {
+            assert_eq!(map.find_action(key), Some(action));
+        }
+        // Player bindings do not overlap debug keys.
+        let controls = default_input_map();
+        for (_, key) in DEBUG_BINDINGS {
+            assert_eq!(controls.find_action(key), None);
+        }
+    }
+
+    #[test]
+    fn debug_toggles() {
+        let mut input = InputState::new();
+        *input.input_map_mut() = game_input_map();
+        let mut toggles = DebugToggles::default();
+        let tap = |input: &mut InputState, toggles: &mut DebugToggles, key| {
+            input.process_event(&key_event(key, true));
+            input.process_event(&key_event(key, false));
+            let commands = toggles.process(input);
+            input.clear_frame();
+            commands
+        };
+
+        let modes: Vec<_> = (0..4)
+            .flat_map(|_| tap(&mut input, &mut toggles, VirtualKeyCode::F2))
+            .collect();
+        assert_eq!(
+            modes,
+            [
+                DebugCommand::SetPolygonMode(PolygonMode::Wireframe),
+                DebugCommand::SetPolygonMode(PolygonMode::Points),
+                DebugCommand::SetPolygonMode(PolygonMode::Fill),
+                DebugCommand::SetPolygonMode(PolygonMode::Wireframe),
+            ]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F1),
+            [DebugCommand::ShowStats(false)]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F1),
+            [DebugCommand::ShowStats(true)]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F3),
+            [DebugCommand::DrawBounds(true)]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F4),
+            [DebugCommand::FreezeCulling(true)]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F4),
+            [DebugCommand::FreezeCulling(false)]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F11),
+            [DebugCommand::Fullscreen(true)]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F12),
+            [DebugCommand::Screenshot]
+        );
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::F12),
+            [DebugCommand::Screenshot]
+        );
+        assert!(tap(&mut input, &mut toggles, VirtualKeyCode::F9).is_empty());
+        assert_eq!(
+            DebugCommand::FreezeCulling(true).to_string(),
+            "culling freeze on"
+        );
+
+        // Rebinding moves the toggle to another key.
+        input.input_map_mut().set_bindings(
+            ACTION_TOGGLE_BOUNDS,
+            vec![InputBinding::Key(VirtualKeyCode::B)],
+        );
+        assert!(tap(&mut input, &mut toggles, VirtualKeyCode::F3).is_empty());
+        assert_eq!(
+            tap(&mut input, &mut toggles, VirtualKeyCode::B),
+            [DebugCommand::DrawBounds(false)]
+        );
+    }
+
+    #[test]
+    fn debug_keys_fire_once_per_press() {
+        let mut game = headless_game();
+        game.process_event(&key_event(VirtualKeyCode::F1, true));
+        assert_eq!(
+            game.debug.process(&mut game.engine.input),
+            [DebugCommand::ShowStats(false)]
+        );
+        // Frame shorter than a fixed step runs no step to clear the press.
+        game.engine.advance(1.0 / 240.0, |_, _| {});
+        assert!(game.debug.process(&mut game.engine.input).is_empty());
+        assert!(!game.debug.is_stats_shown());
+    }
+
+    #[test]
+    fn screenshot_file_name() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1234);
+        assert_eq!(screenshot_path(time), PathBuf::from("screenshot_1234.png"));
+    }
+
+    #[test]
+    fn bench_arguments() {
+        let args =
+            |line: &str| -> Vec<String> { line.split_whitespace().map(String::from).collect() };
+        assert_eq!(parse_args(&args("")).unwrap().bench, None);
+        assert_eq!(
+            parse_args(&args("--bench")).unwrap().bench,
+            Some(BenchConfig::default())
+        );
+        let config = parse_args(&args(
+            "--bench cubes=5 lights=8 seconds=2.5 format=json out=bench.json --no-vsync",
+        ))
+        .unwrap();
+        assert_eq!(config.window.vsync, Some(false));
+        assert_eq!(
+            config.bench,
+            Some(BenchConfig {
+                cubes: 5,
+                lights: 8,
+                seconds: 2.5,
+                format: BenchFormat::Json,
+                output: Some(PathBuf::from("bench.json")),
+            })
+        );
+
+        for (line, error) in [
+            ("cubes=0", "invalid value \"0\" for bench option cubes"),
+            ("cubes=-3", "invalid value \"-3\" for bench option cubes"),
+            ("seconds=0", "invalid value \"0\" for bench option seconds"),
+            (
+                "seconds=inf",
+                "invalid value \"inf\" for bench option seconds",
+            ),
+            (
+                "format=xml",
+                "invalid value \"xml\" for bench option format",
+            ),
+            ("out=", "invalid value \"\" for bench option out"),
+            ("speed=2", "unknown bench option \"speed\""),
+            ("cubes", "bench option \"cubes\" must be key=value"),
+        ] {
+            assert_eq!(parse_bench_options(&args(line)), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn launch_arguments() {
+        let parse = |line: &str| {
+            let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+            parse_args(&args)
+        };
+        assert_eq!(parse(""), Ok(LaunchConfig::default()));
+        assert!(parse("--help").unwrap().help);
+        assert!(parse("-h").unwrap().help);
+
+        let config = parse(
+            "--assets /opt/balala/assets --level my.toml --settings my_settings.toml \
+             --width 1280 --height 720 --fullscreen --no-vsync --msaa 4",
+        )
+        .unwrap();
+        assert_eq!(config.assets, PathBuf::from("/opt/balala/assets"));
+        assert_eq!(config.level, Some(PathBuf::from("my.toml")));
+        assert_eq!(config.settings, Some(PathBuf::from("my_settings.toml")));
+        let mut window = WindowSettings::default();
+        config.window.apply(&mut window);
+        assert_eq!(
+            window,
+            WindowSettings {
+                width: 1280,
+                height: 720,
+                fullscreen: true,
+                vsync: false,
+                msaa: Some(4),
+            }
+        );
+        // Later value wins.
+        assert_eq!(
+            parse("--width 10 --width 20").unwrap().window.width,
+            Some(20)
+        );
+        // Options not given keep values of the settings file.
+        let mut window = WindowSettings {
+            width: 1920,
+            fullscreen: true,
+            msaa: Some(8),
+            ..Default::default()
+        };
+        parse("--height 900").unwrap().window.apply(&mut window);
+        assert_eq!((window.width, window.height), (1920, 900));
+        assert!(window.fullscreen);
+        assert_eq!(window.msaa, Some(8));
+
+        for (line, error) in [
+            ("--width", "--width requires a value"),
+            ("--level --fullscreen", "--level requires a value"),
+            (
+                "--width 0",
+                "invalid --width \"0\", expected positive integer",
+            ),
+            (
+                "--height big",
+                "invalid --height \"big\", expected positive integer",
+            ),
+            (
+                "--msaa 3",
+                "invalid --msaa \"3\", expected 1, 2, 4, 8 or 16",
+            ),
+            (
+                "--msaa 32",
+                "invalid --msaa \"32\", expected 1, 2, 4, 8 or 16",
+            ),
+            ("--vsync", "unknown argument \"--vsync\""),
+            ("level.toml", "unknown argument \"level.toml\""),
+            (
+                "--bench --level my.toml",
+                "--level can not be used with --bench, which builds its own level",
+            ),
+            (
+                "--bench cubes=x",
+                "invalid value \"x\" for bench option cubes",
+            ),
+        ] {
+            assert_eq!(parse(line), Err(error.to_string()), "{}", line);
+        }
+    }
+
+    #[test]
+    fn settings_bindings_feed_input_maps() {
+        let defaults = Settings::default();
+        // Every default action of both maps has an entry.
+        for map in [default_input_map(), game_input_map()] {
+            for (action, bindings) in map.actions() {
+                assert_eq!(defaults.bindings[action], bindings);
+            }
+        }
+
+        let (settings, _) = Settings::from_toml(
+            r#"
+[bindings]
+jump = [{ Key = "J" }]
+pause = [{ Key = "P" }]
+select = [{ Mouse = "Middle" }]
+"#,
+        );
+        let mut controls = default_input_map();
+        settings.apply_bindings(&mut controls);
+        let mut game = game_input_map();
+        settings.apply_bindings(&mut game);
+        assert_eq!(controls.find_action(VirtualKeyCode::J), Some(ACTION_JUMP));
+        assert_eq!(controls.find_action(VirtualKeyCode::Space), None);
+        assert_eq!(game.find_action(VirtualKeyCode::P), Some(ACTION_PAUSE));
+        assert_eq!(game.find_action(MouseButton::Middle), Some(ACTION_SELECT));
+        // Game actions do not leak into player controls and back.
+        assert_eq!(controls.find_action(VirtualKeyCode::P), None);
+        assert_eq!(game.find_action(VirtualKeyCode::J), None);
+
+        let mut input = InputState::new();
+        *input.input_map_mut() = game;
+        input.process_event(&key_event(VirtualKeyCode::P, true));
+        assert!(input.action_just_pressed(ACTION_PAUSE));
+    }
+
+    #[test]
+    fn settings_edits_apply_live() {
+        let mut game = headless_game();
+        let handle = game.get_settings().clone();
+        handle.edit(|settings| {
+            settings.player.invert_y = true;
+            settings.bindings.insert(
+                ACTION_JUMP.to_string(),
+                vec![InputBinding::Key(VirtualKeyCode::J)],
+            );
+            settings.bindings.insert(
+                ACTION_PAUSE.to_string(),
+                vec![InputBinding::Key(VirtualKeyCode::P)],
+            );
+        });
+        game.sync_settings();
+        game.engine.advance(1.0 / 60.0, |_, _| {});
+        assert_eq!(
+            game.engine.input.input_map().find_action(VirtualKeyCode::P),
+            Some(ACTION_PAUSE)
+        );
+
+        let mut engine = Engine::new_headless();
+        let mut level = Level::new(&mut engine);
+        level.set_settings(handle.clone());
+        assert!(level.get_player().get_settings().invert_y);
+        handle.edit(|settings| settings.player.invert_y = false);
+        assert!(level.get_player().get_settings().invert_y);
+        let (scene, camera) = (
+            level.get_scene().clone(),
+            level.get_player().get_camera().clone(),
+        );
+        engine.register_system_in_phase(Box::new(level), SystemPhase::PreUpdate, 0);
+        let eye_height = |engine: &Engine| {
+            let scene = engine.borrow_scene(&scene).unwrap();
+            scene.borrow_node(&camera).unwrap().get_global_position().y
+        };
+        engine.advance(1.0 / 60.0, |_, _| {});
+        let standing = eye_height(&engine);
+        // Level is owned by the engine now, the rebound key must make the player
+        // jump.
+        engine.process_event(&key_event(VirtualKeyCode::J, true));
+        for _ in 0..5 {
+            engine.advance(1.0 / 60.0, |_, _| {});
         }
-        _ => panic!("FBX version unsupported by this example"),
+        assert!(eye_height(&engine) > standing + 0.1);
     }
 }
@@ -0,0 +1,178 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AxisAlignedBoundingBox {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl AxisAlignedBoundingBox {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Box of no points is invalid, see `is_valid`.
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        let mut min = Vector3::repeat(f32::MAX);
+        let mut max = Vector3::repeat(-f32::MAX);
+        for point in points.iter() {
+            min = min.inf(point);
+            max = max.sup(point);
+        }
+        Self { min, max }
+    }
+
+    /// False for boxes with min above max on any axis, such as the one of no
+    /// points. Boxes of a single point are valid.
+    pub fn is_valid(&self) -> bool {
+        self.min.x <= self.max.x && self.min.y <= self.max.y && self.min.z <= self.max.z
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max).scale(0.5)
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min).scale(0.5)
+    }
+
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+            && point.z >= self.min.z
+            && point.z <= self.max.z
+    }
+
+    /// Boxes touching by a face are not considered intersecting.
+    pub fn intersects(&self, other: &AxisAlignedBoundingBox) -> bool {
+        self.min.x < other.max.x
+            && self.max.x > other.min.x
+            && self.min.y < other.max.y
+            && self.max.y > other.min.y
+            && self.min.z < other.max.z
+            && self.max.z > other.min.z
+    }
+
+    pub fn translate(&self, offset: Vector3<f32>) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    pub fn merge(&self, other: &AxisAlignedBoundingBox) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Distance along the ray to the first intersection with the box, zero if
+    /// origin is inside. `direction` does not need to be normalized, result
+    /// is in units of its length.
+    pub fn ray_intersection(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::MAX;
+        for i in 0..3 {
+            if direction[i].abs() <= f32::EPSILON {
+                if origin[i] < self.min[i] || origin[i] > self.max[i] {
+                    return None;
+                }
+                continue;
+            }
+            let inv = 1.0 / direction[i];
+            let mut t0 = (self.min[i] - origin[i]) * inv;
+            let mut t1 = (self.max[i] - origin[i]) * inv;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_min > t_max {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+
+    /// Corner `i` takes max along X, Y and Z if bit 0, 1 and 2 of `i` is set.
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        let mut corners = [Vector3::zeros(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = Vector3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+        }
+        corners
+    }
+
+    /// Box enclosing all eight transformed corners of this box.
+    pub fn transform(&self, transform: &Matrix4<f32>) -> Self {
+        let corners = self
+            .corners()
+            .map(|corner| transform.transform_point(&Point3::from(corner)).coords);
+        Self::from_points(&corners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+
+    use super::AxisAlignedBoundingBox;
+
+    #[test]
+    fn transform_encloses_rotated_box() {
+        let aabb = AxisAlignedBoundingBox::new(Vector3::repeat(-0.5), Vector3::repeat(0.5));
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 45f32.to_radians());
+        let transform = Matrix4::new_translation(&Vector3::new(1.0, 2.0, 3.0))
+            * rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 2.0, 1.0));
+        let world = aabb.transform(&transform);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((world.min - Vector3::new(1.0 - half, 1.0, 3.0 - half)).norm() < 1e-5);
+        assert!((world.max - Vector3::new(1.0 + half, 3.0, 3.0 + half)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn ray_intersection() {
+        let aabb = AxisAlignedBoundingBox::new(Vector3::new(2.0, -1.0, -1.0), Vector3::repeat(3.0));
+        let hit = aabb.ray_intersection(Vector3::zeros(), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(hit, Some(2.0));
+        assert_eq!(
+            aabb.ray_intersection(Vector3::zeros(), Vector3::new(-1.0, 0.0, 0.0)),
+            None
+        );
+        assert_eq!(
+            aabb.ray_intersection(Vector3::new(0.0, 5.0, 0.0), Vector3::new(1.0, 0.0, 0.0)),
+            None
+        );
+        // Diagonal ray and origin inside.
+        let hit = aabb.ray_intersection(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(hit, Some(2.0));
+        assert_eq!(
+            aabb.ray_intersection(Vector3::new(2.5, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn validity() {
+        assert!(!AxisAlignedBoundingBox::from_points(&[]).is_valid());
+        assert!(AxisAlignedBoundingBox::from_points(&[Vector3::x()]).is_valid());
+        assert!(!AxisAlignedBoundingBox::new(Vector3::x(), Vector3::zeros()).is_valid());
+    }
+
+    #[test]
+    fn touching_boxes_do_not_intersect() {
+        let a = AxisAlignedBoundingBox::new(Vector3::zeros(), Vector3::repeat(1.0));
+        let b = a.translate(Vector3::new(1.0, 0.0, 0.0));
+        assert!(!a.intersects(&b));
+        assert!(a.intersects(&a.translate(Vector3::repeat(0.5))));
+        assert_eq!(a.merge(&b).max, Vector3::new(2.0, 1.0, 1.0));
+    }
+}
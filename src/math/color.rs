@@ -0,0 +1,116 @@
+use std::ops::Mul;
+
+use nalgebra::Vector3;
+
+use super::Lerp;
+
+/// Linear RGBA color with components in 0..1 range.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::opaque(1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::opaque(0.0, 0.0, 0.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn opaque(r: f32, g: f32, b: f32) -> Self {
+        Self { r, g, b, a: 1.0 }
+    }
+
+    /// Opaque color from 0..255 components, without gamma conversion.
+    pub fn from_rgb8(r: u8, g: u8, b: u8) -> Self {
+        Self::from_rgba8(r, g, b, 255)
+    }
+
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let unit = |v: u8| v as f32 / 255.0;
+        Self::new(unit(r), unit(g), unit(b), unit(a))
+    }
+
+    /// Components rounded to 0..255, out of range ones are clamped.
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        self.to_array()
+            .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn rgb(&self) -> Vector3<f32> {
+        Vector3::new(self.r, self.g, self.b)
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Color {
+            r: self.r.lerp(&other.r, t),
+            g: self.g.lerp(&other.g, t),
+            b: self.b.lerp(&other.b, t),
+            a: self.a.lerp(&other.a, t),
+        }
+    }
+}
+
+/// Component-wise product, used to combine tints.
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        Color::new(
+            self.r * other.r,
+            self.g * other.g,
+            self.b * other.b,
+            self.a * other.a,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::Color;
+    use crate::math::Lerp;
+
+    #[test]
+    fn conversions() {
+        let orange = Color::from_rgb8(255, 128, 0);
+        assert_eq!(orange.to_array(), [1.0, 128.0 / 255.0, 0.0, 1.0]);
+        assert_eq!(orange.to_rgba8(), [255, 128, 0, 255]);
+        assert_eq!(orange.rgb(), Vector3::new(1.0, 128.0 / 255.0, 0.0));
+        assert_eq!(Color::from_rgba8(0, 0, 0, 51).a, 0.2);
+        // Over-bright components are clamped.
+        assert_eq!(
+            Color::new(2.0, -1.0, 0.5, 1.0).to_rgba8(),
+            [255, 0, 128, 255]
+        );
+        for value in 0..=255 {
+            assert_eq!(
+                Color::from_rgba8(value, value, value, value).to_rgba8(),
+                [value; 4]
+            );
+        }
+    }
+
+    #[test]
+    fn lerp() {
+        let mid = Color::BLACK.lerp(&Color::new(1.0, 0.5, 0.0, 0.0), 0.5);
+        assert_eq!(mid, Color::new(0.5, 0.25, 0.0, 0.5));
+    }
+}
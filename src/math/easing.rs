@@ -0,0 +1,427 @@
+//! Standard easing curves (see easings.net) and a simple tweening utility.
+//! Every curve maps 0 to 0 and 1 to 1.
+
+use std::f32::consts::PI;
+
+use crate::utils::pool::{Handle, Pool};
+
+use super::Lerp;
+
+pub type Easing = fn(f32) -> f32;
+
+const BACK_C1: f32 = 1.70158;
+const BACK_C2: f32 = BACK_C1 * 1.525;
+const BACK_C3: f32 = BACK_C1 + 1.0;
+const ELASTIC_C4: f32 = (2.0 * PI) / 3.0;
+const ELASTIC_C5: f32 = (2.0 * PI) / 4.5;
+
+pub fn linear(t: f32) -> f32 {
+    t
+}
+
+pub fn quad_in(t: f32) -> f32 {
+    t * t
+}
+
+pub fn quad_out(t: f32) -> f32 {
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+
+pub fn quad_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        2.0 * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+    }
+}
+
+pub fn cubic_in(t: f32) -> f32 {
+    t * t * t
+}
+
+pub fn cubic_out(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+pub fn cubic_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+pub fn quart_in(t: f32) -> f32 {
+    t * t * t * t
+}
+
+pub fn quart_out(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(4)
+}
+
+pub fn quart_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        8.0 * t * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(4) / 2.0
+    }
+}
+
+pub fn sine_in(t: f32) -> f32 {
+    1.0 - (t * PI / 2.0).cos()
+}
+
+pub fn sine_out(t: f32) -> f32 {
+    (t * PI / 2.0).sin()
+}
+
+pub fn sine_in_out(t: f32) -> f32 {
+    -((PI * t).cos() - 1.0) / 2.0
+}
+
+pub fn expo_in(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else {
+        2.0f32.powf(10.0 * t - 10.0)
+    }
+}
+
+pub fn expo_out(t: f32) -> f32 {
+    if t >= 1.0 {
+        1.0
+    } else {
+        1.0 - 2.0f32.powf(-10.0 * t)
+    }
+}
+
+pub fn expo_in_out(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else if t >= 1.0 {
+        1.0
+    } else if t < 0.5 {
+        2.0f32.powf(20.0 * t - 10.0) / 2.0
+    } else {
+        (2.0 - 2.0f32.powf(-20.0 * t + 10.0)) / 2.0
+    }
+}
+
+pub fn back_in(t: f32) -> f32 {
+    BACK_C3 * t * t * t - BACK_C1 * t * t
+}
+
+pub fn back_out(t: f32) -> f32 {
+    1.0 + BACK_C3 * (t - 1.0).powi(3) + BACK_C1 * (t - 1.0).powi(2)
+}
+
+pub fn back_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        ((2.0 * t).powi(2) * ((BACK_C2 + 1.0) * 2.0 * t - BACK_C2)) / 2.0
+    } else {
+        ((2.0 * t - 2.0).powi(2) * ((BACK_C2 + 1.0) * (t * 2.0 - 2.0) + BACK_C2) + 2.0) / 2.0
+    }
+}
+
+pub fn elastic_in(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else if t >= 1.0 {
+        1.0
+    } else {
+        -(2.0f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * ELASTIC_C4).sin()
+    }
+}
+
+pub fn elastic_out(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else if t >= 1.0 {
+        1.0
+    } else {
+        2.0f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * ELASTIC_C4).sin() + 1.0
+    }
+}
+
+pub fn elastic_in_out(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else if t >= 1.0 {
+        1.0
+    } else if t < 0.5 {
+        -(2.0f32.powf(20.0 * t - 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin()) / 2.0
+    } else {
+        (2.0f32.powf(-20.0 * t + 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin()) / 2.0 + 1.0
+    }
+}
+
+pub fn bounce_out(t: f32) -> f32 {
+    const N1: f32 = 7.5625;
+    const D1: f32 = 2.75;
+
+    if t < 1.0 / D1 {
+        N1 * t * t
+    } else if t < 2.0 / D1 {
+        let t = t - 1.5 / D1;
+        N1 * t * t + 0.75
+    } else if t < 2.5 / D1 {
+        let t = t - 2.25 / D1;
+        N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D1;
+        N1 * t * t + 0.984375
+    }
+}
+
+pub fn bounce_in(t: f32) -> f32 {
+    1.0 - bounce_out(1.0 - t)
+}
+
+pub fn bounce_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+    } else {
+        (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+    }
+}
+
+/// Interpolates value from `start` to `end` over `duration` seconds using given easing.
+#[derive(Debug, Clone)]
+pub struct Tween<T: Lerp + Copy> {
+    start: T,
+    end: T,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+}
+
+impl<T: Lerp + Copy> Tween<T> {
+    pub fn new(start: T, end: T, duration: f32, easing: Easing) -> Self {
+        Self {
+            start,
+            end,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            easing,
+        }
+    }
+
+    /// Advances tween by `dt` seconds and returns current value. Elapsed time never
+    /// exceeds duration, so the final value is exactly `end`.
+    pub fn update(&mut self, dt: f32) -> T {
+        self.elapsed = (self.elapsed + dt.max(0.0)).min(self.duration);
+        self.value()
+    }
+
+    pub fn value(&self) -> T {
+        if self.is_finished() {
+            self.end
+        } else {
+            self.start.lerp(&self.end, (self.easing)(self.progress()))
+        }
+    }
+
+    /// Normalized progress in 0..1 range.
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+/// Collection of tweens updated together once per frame.
+pub struct Tweener<T: Lerp + Copy> {
+    tweens: Pool<Tween<T>>,
+    handles: Vec<Handle<Tween<T>>>,
+}
+
+impl<T: Lerp + Copy> Default for Tweener<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Lerp + Copy> Tweener<T> {
+    pub fn new() -> Self {
+        Self {
+            tweens: Pool::new(),
+            handles: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, tween: Tween<T>) -> Handle<Tween<T>> {
+        let handle = self.tweens.spawn(tween);
+        self.handles.push(handle.clone());
+        handle
+    }
+
+    pub fn get(&self, handle: &Handle<Tween<T>>) -> Option<&Tween<T>> {
+        self.tweens.borrow(handle)
+    }
+
+    /// Returns current value of tween, None if tween was removed.
+    pub fn value(&self, handle: &Handle<Tween<T>>) -> Option<T> {
+        self.tweens.borrow(handle).map(|tween| tween.value())
+    }
+
+    pub fn remove(&mut self, handle: &Handle<Tween<T>>) {
+        if let Some(i) = self.handles.iter().position(|h| h == handle) {
+            self.tweens.free(self.handles.remove(i));
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for handle in self.handles.iter() {
+            if let Some(tween) = self.tweens.borrow_mut(handle) {
+                tween.update(dt);
+            }
+        }
+    }
+
+    /// Frees every finished tween, their handles become invalid.
+    pub fn remove_finished(&mut self) {
+        let tweens = &mut self.tweens;
+        self.handles.retain(|handle| {
+            let finished = tweens.borrow(handle).is_none_or(|t| t.is_finished());
+            if finished {
+                tweens.free(handle.clone());
+            }
+            !finished
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use crate::math::color::Color;
+
+    use super::*;
+
+    const ALL: [(&str, Easing); 22] = [
+        ("linear", linear),
+        ("quad_in", quad_in),
+        ("quad_out", quad_out),
+        ("quad_in_out", quad_in_out),
+        ("cubic_in", cubic_in),
+        ("cubic_out", cubic_out),
+        ("cubic_in_out", cubic_in_out),
+        ("quart_in", quart_in),
+        ("quart_out", quart_out),
+        ("quart_in_out", quart_in_out),
+        ("sine_in", sine_in),
+        ("sine_out", sine_out),
+        ("sine_in_out", sine_in_out),
+        ("expo_in", expo_in),
+        ("expo_out", expo_out),
+        ("expo_in_out", expo_in_out),
+        ("back_in", back_in),
+        ("back_out", back_out),
+        ("back_in_out", back_in_out),
+        ("elastic_in", elastic_in),
+        ("elastic_out", elastic_out),
+        ("elastic_in_out", elastic_in_out),
+    ];
+
+    #[test]
+    fn easing_end_points() {
+        let bounce: [(&str, Easing); 3] = [
+            ("bounce_in", bounce_in),
+            ("bounce_out", bounce_out),
+            ("bounce_in_out", bounce_in_out),
+        ];
+        for (name, f) in ALL.iter().chain(bounce.iter()) {
+            assert!(f(0.0).abs() < 1e-5, "{} at 0 is {}", name, f(0.0));
+            assert!((f(1.0) - 1.0).abs() < 1e-5, "{} at 1 is {}", name, f(1.0));
+        }
+    }
+
+    #[test]
+    fn easing_monotonic() {
+        // Back, elastic and bounce overshoot or oscillate by design.
+        for (name, f) in ALL.iter().take(16) {
+            let mut prev = f(0.0);
+            for i in 1..=100 {
+                let v = f(i as f32 / 100.0);
+                assert!(v >= prev - 1e-6, "{} is not monotonic at {}", name, i);
+                prev = v;
+            }
+        }
+    }
+
+    #[test]
+    fn tween_completes_exactly_at_duration() {
+        let mut tween = Tween::new(2.0f32, 10.0, 1.0, quad_in_out);
+        for _ in 0..9 {
+            tween.update(0.1);
+            assert!(!tween.is_finished() || tween.elapsed() >= 1.0);
+        }
+        let v = tween.update(0.1);
+        assert!(tween.is_finished());
+        assert_eq!(v, 10.0);
+
+        // Overshooting dt is clamped.
+        let mut tween = Tween::new(Vector3::zeros(), Vector3::new(1.0, 2.0, 3.0), 0.5, linear);
+        assert_eq!(tween.update(100.0), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(tween.elapsed(), 0.5);
+        assert_eq!(tween.progress(), 1.0);
+
+        // Negative dt does not rewind.
+        let mut tween = Tween::new(0.0f32, 1.0, 1.0, linear);
+        tween.update(0.25);
+        assert_eq!(tween.update(-1.0), 0.25);
+    }
+
+    #[test]
+    fn tween_color() {
+        let mut tween = Tween::new(Color::BLACK, Color::WHITE, 2.0, linear);
+        let half = tween.update(1.0);
+        assert!((half.r - 0.5).abs() < 1e-6);
+        assert_eq!(half.a, 1.0);
+    }
+
+    #[test]
+    fn tweener() {
+        let mut tweener = Tweener::new();
+        let short = tweener.push(Tween::new(0.0f32, 1.0, 0.5, linear));
+        let long = tweener.push(Tween::new(0.0f32, 1.0, 1.0, linear));
+        tweener.update(0.5);
+        assert_eq!(tweener.value(&short), Some(1.0));
+        assert_eq!(tweener.value(&long), Some(0.5));
+
+        tweener.remove_finished();
+        assert_eq!(tweener.len(), 1);
+        assert!(tweener.value(&short).is_none());
+        assert!(tweener.get(&long).is_some());
+
+        tweener.remove(&long);
+        assert!(tweener.is_empty());
+    }
+}
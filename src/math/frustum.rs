@@ -0,0 +1,147 @@
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+use super::{aabb::AxisAlignedBoundingBox, sphere::BoundingSphere};
+
+/// Volume seen by a camera, bounded by six planes facing inwards.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes. XYZ is unit normal and
+    /// W is distance, points with `normal.dot(point) + w >= 0` are inside.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts planes from rows of view-projection matrix (Gribb/Hartmann).
+    /// Planes of a singular matrix are left unnormalized.
+    pub fn from_view_projection(matrix: &Matrix4<f32>) -> Self {
+        let row = |i: usize| -> Vector4<f32> { matrix.row(i).transpose() };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| {
+            let length = plane.xyz().norm();
+            if length > f32::EPSILON {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Self { planes }
+    }
+
+    pub fn planes(&self) -> &[Vector4<f32>; 6] {
+        &self.planes
+    }
+
+    pub fn is_point_inside(&self, point: Vector3<f32>) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(&point) + plane.w >= 0.0)
+    }
+
+    /// Conservative test, boxes near frustum corners may pass while being
+    /// outside, but a visible box never fails.
+    pub fn is_aabb_visible(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner farthest along the normal.
+            let mut corner = aabb.min;
+            for i in 0..3 {
+                if plane[i] >= 0.0 {
+                    corner[i] = aabb.max[i];
+                }
+            }
+            plane.xyz().dot(&corner) + plane.w >= 0.0
+        })
+    }
+
+    /// Conservative as `is_aabb_visible`, spheres with infinite radius are
+    /// always visible.
+    pub fn is_sphere_visible(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(&sphere.center) + plane.w >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+    use super::Frustum;
+    use crate::math::{aabb::AxisAlignedBoundingBox, sphere::BoundingSphere};
+
+    /// 90 degree frustum looking down -Z from the origin, near 1 and far 10.
+    fn symmetric() -> Frustum {
+        let projection = Matrix4::new_perspective(1.0, 90f32.to_radians(), 1.0, 10.0);
+        Frustum::from_view_projection(&projection)
+    }
+
+    fn cube(center: Vector3<f32>, half: f32) -> AxisAlignedBoundingBox {
+        AxisAlignedBoundingBox::new(center.add_scalar(-half), center.add_scalar(half))
+    }
+
+    #[test]
+    fn symmetric_planes() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let expected = [
+            Vector4::new(half, 0.0, -half, 0.0),
+            Vector4::new(-half, 0.0, -half, 0.0),
+            Vector4::new(0.0, half, -half, 0.0),
+            Vector4::new(0.0, -half, -half, 0.0),
+            Vector4::new(0.0, 0.0, -1.0, -1.0),
+            Vector4::new(0.0, 0.0, 1.0, 10.0),
+        ];
+        for (plane, expected) in symmetric().planes().iter().zip(expected.iter()) {
+            assert!(
+                (plane - expected).norm() < 1e-5,
+                "{:?} != {:?}",
+                plane,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn box_visibility() {
+        let frustum = symmetric();
+        assert!(frustum.is_point_inside(Vector3::new(0.0, 0.0, -5.0)));
+        assert!(!frustum.is_point_inside(Vector3::new(6.0, 0.0, -5.0)));
+
+        // Inside.
+        assert!(frustum.is_aabb_visible(&cube(Vector3::new(0.0, 0.0, -5.0), 1.0)));
+        // Behind, beyond far plane, too far to the side and above.
+        assert!(!frustum.is_aabb_visible(&cube(Vector3::new(0.0, 0.0, 3.0), 1.0)));
+        assert!(!frustum.is_aabb_visible(&cube(Vector3::new(0.0, 0.0, -12.0), 1.0)));
+        assert!(!frustum.is_aabb_visible(&cube(Vector3::new(8.0, 0.0, -5.0), 1.0)));
+        assert!(!frustum.is_aabb_visible(&cube(Vector3::new(0.0, 8.0, -5.0), 1.0)));
+        // Straddling left, near and far planes.
+        assert!(frustum.is_aabb_visible(&cube(Vector3::new(-5.5, 0.0, -5.0), 1.0)));
+        assert!(frustum.is_aabb_visible(&cube(Vector3::new(0.0, 0.0, -0.5), 1.0)));
+        assert!(frustum.is_aabb_visible(&cube(Vector3::new(0.0, 0.0, -10.5), 1.0)));
+        // Enclosing the whole frustum.
+        assert!(frustum.is_aabb_visible(&cube(Vector3::zeros(), 100.0)));
+    }
+
+    #[test]
+    fn sphere_visibility() {
+        let frustum = symmetric();
+        let sphere = |z: f32, radius: f32| BoundingSphere::new(Vector3::new(0.0, 0.0, z), radius);
+        assert!(frustum.is_sphere_visible(&sphere(-5.0, 1.0)));
+        // Behind the camera, reaching over the near plane and unbounded.
+        assert!(!frustum.is_sphere_visible(&sphere(5.0, 3.0)));
+        assert!(frustum.is_sphere_visible(&sphere(5.0, 7.0)));
+        assert!(frustum.is_sphere_visible(&sphere(5.0, f32::INFINITY)));
+    }
+
+    #[test]
+    fn moved_camera() {
+        // Same frustum viewed from a camera at X = 20 looking down -Z.
+        let view = Matrix4::look_at_rh(
+            &Point3::new(20.0, 0.0, 0.0),
+            &Point3::new(20.0, 0.0, -1.0),
+            &Vector3::y(),
+        );
+        let projection = Matrix4::new_perspective(1.0, 90f32.to_radians(), 1.0, 10.0);
+        let frustum = Frustum::from_view_projection(&(projection * view));
+        assert!(frustum.is_aabb_visible(&cube(Vector3::new(20.0, 0.0, -5.0), 1.0)));
+        assert!(!frustum.is_aabb_visible(&cube(Vector3::new(0.0, 0.0, -5.0), 1.0)));
+    }
+}
@@ -1 +1,199 @@
+use nalgebra::{Matrix3, Matrix4, Rotation3, UnitQuaternion, Vector3};
+
+pub mod aabb;
+pub mod color;
+pub mod easing;
+pub mod frustum;
+pub mod noise;
+pub mod packer;
+pub mod ray;
 pub mod rect;
+pub mod sphere;
+pub mod triangulator;
+
+/// Linear interpolation between two values, `t` is in 0..1 range.
+pub trait Lerp {
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vector3<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self).scale(t)
+    }
+}
+
+/// Splits affine matrix into translation, rotation and scale. Mirrored matrices
+/// (negative determinant) are represented by negative scale on the X axis.
+/// Returns None if matrix is singular.
+pub fn decompose(m: &Matrix4<f32>) -> Option<(Vector3<f32>, UnitQuaternion<f32>, Vector3<f32>)> {
+    let translation = Vector3::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+
+    let mut basis: Matrix3<f32> = m.fixed_view::<3, 3>(0, 0).into_owned();
+    let mut scale = Vector3::new(
+        basis.column(0).norm(),
+        basis.column(1).norm(),
+        basis.column(2).norm(),
+    );
+    if scale.iter().any(|s| *s <= f32::EPSILON) {
+        return None;
+    }
+
+    if basis.determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+
+    for i in 0..3 {
+        let mut column = basis.column_mut(i);
+        column /= scale[i];
+    }
+
+    // Iterative fit starting from identity stalls at half turns, start it
+    // from the direct conversion instead, which is exact for orthonormal
+    // bases.
+    let guess = UnitQuaternion::new_normalize(
+        *UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(basis))
+            .quaternion(),
+    );
+    let rotation = Rotation3::from_matrix_eps(&basis, f32::EPSILON, 0, guess.to_rotation_matrix());
+
+    Some((
+        translation,
+        UnitQuaternion::from_rotation_matrix(&rotation),
+        scale,
+    ))
+}
+
+/// Order of elemental rotations of Euler angles, `Xyz` rotates about X first
+/// and Z last.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum RotationOrder {
+    #[default]
+    Xyz,
+    Xzy,
+    Yzx,
+    Yxz,
+    Zxy,
+    Zyx,
+}
+
+/// Converts Euler angles in degrees into rotation, angles are about fixed
+/// (extrinsic) axes applied in given order.
+pub fn quat_from_euler(degrees: Vector3<f32>, order: RotationOrder) -> UnitQuaternion<f32> {
+    let axis = |i: usize| {
+        let axis = [Vector3::x_axis(), Vector3::y_axis(), Vector3::z_axis()][i];
+        UnitQuaternion::from_axis_angle(&axis, degrees[i].to_radians())
+    };
+    let [first, second, third] = match order {
+        RotationOrder::Xyz => [0, 1, 2],
+        RotationOrder::Xzy => [0, 2, 1],
+        RotationOrder::Yzx => [1, 2, 0],
+        RotationOrder::Yxz => [1, 0, 2],
+        RotationOrder::Zxy => [2, 0, 1],
+        RotationOrder::Zyx => [2, 1, 0],
+    };
+    axis(third) * axis(second) * axis(first)
+}
+
+/// Builds matrix from translation, rotation and scale, inverse of [`decompose`].
+pub fn compose(
+    translation: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
+    scale: Vector3<f32>,
+) -> Matrix4<f32> {
+    Matrix4::new_translation(&translation)
+        * rotation.to_homogeneous()
+        * Matrix4::new_nonuniform_scaling(&scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+
+    use super::{compose, decompose, quat_from_euler, RotationOrder};
+
+    #[test]
+    fn decompose_compose_round_trip() {
+        let translations = [
+            Vector3::zeros(),
+            Vector3::new(1.0, -2.0, 3.0),
+            Vector3::new(-100.0, 0.5, 42.0),
+        ];
+        let rotations = [
+            UnitQuaternion::identity(),
+            UnitQuaternion::from_euler_angles(0.3, -1.2, 2.5),
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_2),
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::PI),
+        ];
+        let scales = [
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(2.0, 0.5, 3.0),
+            Vector3::new(-1.0, 1.0, 1.0),
+            Vector3::new(1.0, -2.0, 0.5),
+            Vector3::new(-1.0, -1.0, -1.0),
+            Vector3::new(100.0, 0.1, 100.0),
+        ];
+
+        for t in translations.iter() {
+            for r in rotations.iter() {
+                for s in scales.iter() {
+                    let m = compose(*t, *r, *s);
+                    let (dt, dr, ds) = decompose(&m).unwrap();
+                    assert!((dt - t).norm() < 1e-4);
+                    let restored = compose(dt, dr, ds);
+                    assert!(
+                        (restored - m).abs().max() < 1e-4,
+                        "{:?} != {:?}",
+                        restored,
+                        m
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decompose_positive_scale_is_exact() {
+        let r = UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3);
+        let s = Vector3::new(2.0, 3.0, 4.0);
+        let (_, dr, ds) = decompose(&compose(Vector3::zeros(), r, s)).unwrap();
+        assert!(dr.angle_to(&r) < 1e-4);
+        assert!((ds - s).norm() < 1e-4);
+    }
+
+    #[test]
+    fn euler_rotation_order() {
+        let degrees = Vector3::new(30.0, -45.0, 60.0);
+        let radians = degrees.map(f32::to_radians);
+        let xyz = quat_from_euler(degrees, RotationOrder::Xyz);
+        let expected = UnitQuaternion::from_euler_angles(radians.x, radians.y, radians.z);
+        assert!(xyz.angle_to(&expected) < 1e-5);
+
+        let [x, y, z] = [Vector3::x_axis(), Vector3::y_axis(), Vector3::z_axis()]
+            .map(|axis| UnitQuaternion::from_axis_angle(&axis, radians.dot(&axis)));
+        let zyx = quat_from_euler(degrees, RotationOrder::Zyx);
+        assert!(zyx.angle_to(&(x * y * z)) < 1e-5);
+        let yxz = quat_from_euler(degrees, RotationOrder::Yxz);
+        assert!(yxz.angle_to(&(z * x * y)) < 1e-5);
+        assert!(xyz.angle_to(&zyx) > 0.1);
+
+        // Single axis rotation does not depend on order.
+        let single = Vector3::new(0.0, 90.0, 0.0);
+        assert!(
+            quat_from_euler(single, RotationOrder::Zxy)
+                .angle_to(&quat_from_euler(single, RotationOrder::Xyz))
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn decompose_singular() {
+        let m = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 0.0, 1.0));
+        assert!(decompose(&m).is_none());
+    }
+}
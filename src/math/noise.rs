@@ -0,0 +1,402 @@
+//! Deterministic coherent noise: value noise, gradient (Perlin) noise and fractal
+//! Brownian motion built on top of them. Every function returns values in [-1, 1].
+
+use nalgebra::{Vector2, Vector3};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NoiseKind {
+    Value,
+    Perlin,
+}
+
+/// Seeded noise generator. Same seed always produces the same field.
+#[derive(Clone)]
+pub struct Noise {
+    perm: [u8; 512],
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn grad_2d(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+fn grad_3d(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    // 12 cube edge directions of improved Perlin noise, padded to 16.
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 14 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 13 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        let mut table = [0u8; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+
+        // Fisher-Yates shuffle driven by a LCG.
+        let mut state = seed ^ 0x9E37_79B9;
+        for i in (1..256).rev() {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let j = (state >> 8) as usize % (i + 1);
+            table.swap(i, j);
+        }
+
+        let mut perm = [0u8; 512];
+        for i in 0..512 {
+            perm[i] = table[i & 255];
+        }
+
+        Self { perm }
+    }
+
+    fn hash_2d(&self, x: i32, y: i32) -> u8 {
+        let xi = (x & 255) as usize;
+        let yi = (y & 255) as usize;
+        self.perm[self.perm[xi] as usize + yi]
+    }
+
+    fn hash_3d(&self, x: i32, y: i32, z: i32) -> u8 {
+        let zi = (z & 255) as usize;
+        self.perm[self.hash_2d(x, y) as usize + zi]
+    }
+
+    fn lattice_value(hash: u8) -> f32 {
+        hash as f32 / 127.5 - 1.0
+    }
+
+    pub fn value_2d(&self, point: Vector2<f32>) -> f32 {
+        let (x0, y0) = (point.x.floor(), point.y.floor());
+        let (ix, iy) = (x0 as i32, y0 as i32);
+        let u = fade(point.x - x0);
+        let v = fade(point.y - y0);
+
+        let a = Self::lattice_value(self.hash_2d(ix, iy));
+        let b = Self::lattice_value(self.hash_2d(ix + 1, iy));
+        let c = Self::lattice_value(self.hash_2d(ix, iy + 1));
+        let d = Self::lattice_value(self.hash_2d(ix + 1, iy + 1));
+
+        lerp(lerp(a, b, u), lerp(c, d, u), v)
+    }
+
+    pub fn value_3d(&self, point: Vector3<f32>) -> f32 {
+        let (x0, y0, z0) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+        let u = fade(point.x - x0);
+        let v = fade(point.y - y0);
+        let w = fade(point.z - z0);
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            Self::lattice_value(self.hash_3d(ix + dx, iy + dy, iz + dz))
+        };
+
+        let front = lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        );
+        let back = lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        );
+        lerp(front, back, w)
+    }
+
+    pub fn perlin_2d(&self, point: Vector2<f32>) -> f32 {
+        let (x0, y0) = (point.x.floor(), point.y.floor());
+        let (ix, iy) = (x0 as i32, y0 as i32);
+        let (fx, fy) = (point.x - x0, point.y - y0);
+        let u = fade(fx);
+        let v = fade(fy);
+
+        let a = grad_2d(self.hash_2d(ix, iy), fx, fy);
+        let b = grad_2d(self.hash_2d(ix + 1, iy), fx - 1.0, fy);
+        let c = grad_2d(self.hash_2d(ix, iy + 1), fx, fy - 1.0);
+        let d = grad_2d(self.hash_2d(ix + 1, iy + 1), fx - 1.0, fy - 1.0);
+
+        lerp(lerp(a, b, u), lerp(c, d, u), v).clamp(-1.0, 1.0)
+    }
+
+    pub fn perlin_3d(&self, point: Vector3<f32>) -> f32 {
+        let (x0, y0, z0) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+        let (fx, fy, fz) = (point.x - x0, point.y - y0, point.z - z0);
+        let u = fade(fx);
+        let v = fade(fy);
+        let w = fade(fz);
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            grad_3d(
+                self.hash_3d(ix + dx, iy + dy, iz + dz),
+                fx - dx as f32,
+                fy - dy as f32,
+                fz - dz as f32,
+            )
+        };
+
+        let front = lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        );
+        let back = lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        );
+        lerp(front, back, w).clamp(-1.0, 1.0)
+    }
+
+    pub fn sample_2d(&self, kind: NoiseKind, point: Vector2<f32>) -> f32 {
+        match kind {
+            NoiseKind::Value => self.value_2d(point),
+            NoiseKind::Perlin => self.perlin_2d(point),
+        }
+    }
+
+    pub fn sample_3d(&self, kind: NoiseKind, point: Vector3<f32>) -> f32 {
+        match kind {
+            NoiseKind::Value => self.value_3d(point),
+            NoiseKind::Perlin => self.perlin_3d(point),
+        }
+    }
+
+    /// Fractal sum of `octaves` layers of 3D Perlin noise. Each octave multiplies
+    /// frequency by `lacunarity` and amplitude by `gain`. Result is normalized by
+    /// the total amplitude so it stays in [-1, 1].
+    pub fn fbm(&self, point: Vector3<f32>, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        self.fbm_3d(NoiseKind::Perlin, point, octaves, lacunarity, gain)
+    }
+
+    pub fn fbm_2d(
+        &self,
+        kind: NoiseKind,
+        point: Vector2<f32>,
+        octaves: u32,
+        lacunarity: f32,
+        gain: f32,
+    ) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * self.sample_2d(kind, point * frequency);
+            total_amplitude += amplitude;
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+        if total_amplitude > 0.0 {
+            (sum / total_amplitude).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn fbm_3d(
+        &self,
+        kind: NoiseKind,
+        point: Vector3<f32>,
+        octaves: u32,
+        lacunarity: f32,
+        gain: f32,
+    ) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * self.sample_3d(kind, point * frequency);
+            total_amplitude += amplitude;
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+        if total_amplitude > 0.0 {
+            (sum / total_amplitude).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct NoiseParams {
+    pub seed: u32,
+    pub kind: NoiseKind,
+    /// Number of noise cells across the whole texture.
+    pub frequency: f32,
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            kind: NoiseKind::Perlin,
+            frequency: 4.0,
+            octaves: 4,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+/// Fills RGBA8 pixel buffer (same layout as `Texture::pixels`) with grayscale
+/// fractal noise, alpha is always 255.
+pub fn fill_texture(width: u32, height: u32, params: &NoiseParams) -> Vec<u8> {
+    let noise = Noise::new(params.seed);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let point = Vector2::new(
+                x as f32 / width.max(1) as f32 * params.frequency,
+                y as f32 / height.max(1) as f32 * params.frequency,
+            );
+            let n = noise.fbm_2d(
+                params.kind,
+                point,
+                params.octaves,
+                params.lacunarity,
+                params.gain,
+            );
+            let v = ((n * 0.5 + 0.5) * 255.0).round() as u8;
+            pixels.extend_from_slice(&[v, v, v, 255]);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Vector2, Vector3};
+
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    #[test]
+    fn pinned_values() {
+        let noise = Noise::new(1234);
+        let pinned = [
+            (noise.value_2d(Vector2::new(0.5, 0.5)), 0.5470588),
+            (noise.value_3d(Vector3::new(1.3, 2.7, 0.2)), 0.07591239),
+            (noise.perlin_2d(Vector2::new(3.25, 1.75)), 0.030326843),
+            (noise.perlin_3d(Vector3::new(0.1, 4.6, 2.3)), -0.04595328),
+            (
+                noise.fbm(Vector3::new(0.7, 0.2, 5.1), 4, 2.0, 0.5),
+                0.23232597,
+            ),
+        ];
+        for (i, (actual, expected)) in pinned.iter().enumerate() {
+            assert!(
+                (actual - expected).abs() < EPSILON,
+                "sample {} changed: {}",
+                i,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn deterministic_per_seed() {
+        let p = Vector3::new(12.3, -4.5, 6.7);
+        assert_eq!(Noise::new(7).perlin_3d(p), Noise::new(7).perlin_3d(p));
+        assert_ne!(Noise::new(7).value_3d(p), Noise::new(8).value_3d(p));
+    }
+
+    #[test]
+    fn gradient_noise_is_zero_on_lattice() {
+        let noise = Noise::new(99);
+        for i in -5..5 {
+            assert_eq!(noise.perlin_2d(Vector2::new(i as f32, 3.0)), 0.0);
+            assert_eq!(noise.perlin_3d(Vector3::new(i as f32, 1.0, -2.0)), 0.0);
+        }
+    }
+
+    #[test]
+    fn output_within_bounds() {
+        let noise = Noise::new(42);
+        for i in 0..2000 {
+            let t = i as f32 * 0.173;
+            let p2 = Vector2::new(t, t * 0.61 - 50.0);
+            let p3 = Vector3::new(t * 0.37, -t, t * 1.3);
+            for kind in [NoiseKind::Value, NoiseKind::Perlin] {
+                let samples = [
+                    noise.sample_2d(kind, p2),
+                    noise.sample_3d(kind, p3),
+                    noise.fbm_2d(kind, p2, 5, 2.0, 0.5),
+                    noise.fbm_3d(kind, p3, 5, 2.0, 0.5),
+                ];
+                for v in samples {
+                    assert!((-1.0..=1.0).contains(&v));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn continuous_across_lattice_lines() {
+        let noise = Noise::new(5);
+        let step = 1e-3;
+        for kind in [NoiseKind::Value, NoiseKind::Perlin] {
+            for cell in -3..3 {
+                for offset in [0.1, 0.45, 0.9] {
+                    // Cross vertical lattice line x = cell.
+                    let x = cell as f32;
+                    let y = cell as f32 * 0.7 + offset;
+                    let a = noise.sample_2d(kind, Vector2::new(x - step, y));
+                    let b = noise.sample_2d(kind, Vector2::new(x + step, y));
+                    assert!((a - b).abs() < 0.01, "2D jump at x={}", x);
+
+                    let a = noise.sample_3d(kind, Vector3::new(y, x - step, offset));
+                    let b = noise.sample_3d(kind, Vector3::new(y, x + step, offset));
+                    assert!((a - b).abs() < 0.01, "3D jump at y={}", x);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn texture_fill() {
+        let params = NoiseParams {
+            seed: 3,
+            ..Default::default()
+        };
+        let pixels = fill_texture(16, 8, &params);
+        assert_eq!(pixels.len(), 16 * 8 * 4);
+        assert!(pixels
+            .chunks(4)
+            .all(|p| p[0] == p[1] && p[1] == p[2] && p[3] == 255));
+        assert_eq!(pixels, fill_texture(16, 8, &params));
+    }
+}
@@ -0,0 +1,308 @@
+use super::rect::Rect;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PackError {
+    /// Item is larger than the whole bin, it will never fit.
+    TooLarge,
+    /// Item could fit into empty bin, but no skyline segment has enough room.
+    NoSpace,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PackedRect {
+    pub rect: Rect<i32>,
+    /// True if item was rotated by 90 degrees, so `rect.width` is item's height.
+    pub rotated: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct SkylineSegment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+/// Bottom-left skyline rectangle packer, used for texture atlases and glyph caches.
+#[derive(Debug)]
+pub struct SkylinePacker {
+    width: i32,
+    height: i32,
+    padding: i32,
+    allow_rotate: bool,
+    skyline: Vec<SkylineSegment>,
+    used_area: i64,
+    count: usize,
+}
+
+impl SkylinePacker {
+    pub fn new(width: i32, height: i32) -> Self {
+        let mut packer = Self {
+            width,
+            height,
+            padding: 0,
+            allow_rotate: false,
+            skyline: Vec::new(),
+            used_area: 0,
+            count: 0,
+        };
+        packer.clear();
+        packer
+    }
+
+    /// Sets empty space left between packed items. Must be set before packing.
+    pub fn with_padding(mut self, padding: i32) -> Self {
+        self.padding = padding.max(0);
+        self.clear();
+        self
+    }
+
+    /// Allows packer to rotate items by 90 degrees if that gives better placement.
+    pub fn with_rotation(mut self, allow_rotate: bool) -> Self {
+        self.allow_rotate = allow_rotate;
+        self
+    }
+
+    pub fn clear(&mut self) {
+        self.skyline.clear();
+        // Padding is appended to the right and bottom of every item, extending the
+        // bin by the same amount lets items touch its far edges.
+        self.skyline.push(SkylineSegment {
+            x: 0,
+            y: 0,
+            width: self.width + self.padding,
+        });
+        self.used_area = 0;
+        self.count = 0;
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Ratio of packed items area to bin area in 0..1 range.
+    pub fn occupancy(&self) -> f32 {
+        let area = self.width as i64 * self.height as i64;
+        if area > 0 {
+            self.used_area as f32 / area as f32
+        } else {
+            0.0
+        }
+    }
+
+    pub fn pack(&mut self, w: i32, h: i32) -> Option<Rect<i32>> {
+        self.try_pack(w, h).ok().map(|packed| packed.rect)
+    }
+
+    pub fn try_pack(&mut self, w: i32, h: i32) -> Result<PackedRect, PackError> {
+        let fits_upright = w <= self.width && h <= self.height;
+        let fits_rotated = self.allow_rotate && h <= self.width && w <= self.height;
+        if w <= 0 || h <= 0 || !(fits_upright || fits_rotated) {
+            return Err(PackError::TooLarge);
+        }
+
+        let mut best = self.find_position(w, h).map(|(i, y)| (i, y, false));
+        if self.allow_rotate && w != h {
+            if let Some((i, y)) = self.find_position(h, w) {
+                let better = match best {
+                    Some((_, best_y, _)) => y + w < best_y + h,
+                    None => true,
+                };
+                if better {
+                    best = Some((i, y, true));
+                }
+            }
+        }
+
+        let (index, y, rotated) = best.ok_or(PackError::NoSpace)?;
+        let (w, h) = if rotated { (h, w) } else { (w, h) };
+        let x = self.skyline[index].x;
+        self.add_level(index, x, y, w + self.padding, h + self.padding);
+
+        self.used_area += w as i64 * h as i64;
+        self.count += 1;
+
+        Ok(PackedRect {
+            rect: Rect::new(x, y, w, h),
+            rotated,
+        })
+    }
+
+    /// Returns index of segment and y coordinate for the lowest placement.
+    fn find_position(&self, w: i32, h: i32) -> Option<(usize, i32)> {
+        let (w, h) = (w + self.padding, h + self.padding);
+        let mut best: Option<(usize, i32, i32)> = None;
+        for i in 0..self.skyline.len() {
+            if let Some(y) = self.fit(i, w, h) {
+                let segment_width = self.skyline[i].width;
+                let better = match best {
+                    Some((_, best_y, best_width)) => {
+                        y < best_y || (y == best_y && segment_width < best_width)
+                    }
+                    None => true,
+                };
+                if better {
+                    best = Some((i, y, segment_width));
+                }
+            }
+        }
+        best.map(|(i, y, _)| (i, y))
+    }
+
+    fn fit(&self, index: usize, w: i32, h: i32) -> Option<i32> {
+        let x = self.skyline[index].x;
+        if x + w > self.width + self.padding {
+            return None;
+        }
+        let mut width_left = w;
+        let mut y = 0;
+        let mut i = index;
+        while width_left > 0 {
+            let segment = self.skyline.get(i)?;
+            y = y.max(segment.y);
+            if y + h > self.height + self.padding {
+                return None;
+            }
+            width_left -= segment.width;
+            i += 1;
+        }
+        Some(y)
+    }
+
+    fn add_level(&mut self, index: usize, x: i32, y: i32, w: i32, h: i32) {
+        self.skyline.insert(
+            index,
+            SkylineSegment {
+                x,
+                y: y + h,
+                width: w,
+            },
+        );
+
+        // Shrink or remove segments now covered by the new one.
+        let i = index + 1;
+        while i < self.skyline.len() {
+            let prev_end = self.skyline[i - 1].x + self.skyline[i - 1].width;
+            let segment = &mut self.skyline[i];
+            if segment.x < prev_end {
+                let shrink = prev_end - segment.x;
+                segment.x += shrink;
+                segment.width -= shrink;
+                if segment.width <= 0 {
+                    self.skyline.remove(i);
+                    continue;
+                }
+            }
+            break;
+        }
+
+        // Merge neighbours with equal height.
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::rect::Rect;
+
+    use super::{PackError, SkylinePacker};
+
+    fn overlaps(a: &Rect<i32>, b: &Rect<i32>) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    fn pseudo_random_sizes(seed: u32, count: usize, min: i32, max: i32) -> Vec<(i32, i32)> {
+        let mut state = seed;
+        let mut next = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            min + ((state >> 8) % (max - min + 1) as u32) as i32
+        };
+        (0..count).map(|_| (next(), next())).collect()
+    }
+
+    fn check_layout(packer: &SkylinePacker, rects: &[Rect<i32>], padding: i32) {
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x >= 0 && a.y >= 0);
+            assert!(a.x + a.width <= packer.width() && a.y + a.height <= packer.height());
+            for b in rects.iter().skip(i + 1) {
+                let padded = Rect::new(a.x, a.y, a.width + padding, a.height + padding);
+                assert!(!overlaps(&padded, b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn random_rects_do_not_overlap() {
+        for (seed, padding) in [(1, 0), (2, 1), (3, 4)] {
+            let mut packer = SkylinePacker::new(512, 512).with_padding(padding);
+            let mut rects = Vec::new();
+            for (w, h) in pseudo_random_sizes(seed, 300, 4, 64) {
+                if let Some(rect) = packer.pack(w, h) {
+                    assert_eq!((rect.width, rect.height), (w, h));
+                    rects.push(rect);
+                }
+            }
+            assert!(rects.len() > 50);
+            assert_eq!(rects.len(), packer.count());
+            check_layout(&packer, &rects, padding);
+        }
+    }
+
+    #[test]
+    fn fill_rate() {
+        // Equal squares tile the bin perfectly.
+        let mut packer = SkylinePacker::new(128, 128);
+        for _ in 0..64 {
+            assert!(packer.pack(16, 16).is_some());
+        }
+        assert_eq!(packer.occupancy(), 1.0);
+        assert_eq!(packer.try_pack(1, 1), Err(PackError::NoSpace));
+
+        // Mixed sizes, sorted by height, as an atlas builder would do.
+        let mut sizes = pseudo_random_sizes(7, 200, 8, 40);
+        sizes.sort_by_key(|s| std::cmp::Reverse(s.1));
+        let mut packer = SkylinePacker::new(256, 256);
+        for (w, h) in sizes {
+            packer.pack(w, h);
+        }
+        assert!(packer.occupancy() > 0.75, "{}", packer.occupancy());
+    }
+
+    #[test]
+    fn rejections() {
+        let mut packer = SkylinePacker::new(64, 32);
+        assert_eq!(packer.try_pack(65, 1), Err(PackError::TooLarge));
+        assert_eq!(packer.try_pack(1, 33), Err(PackError::TooLarge));
+        assert_eq!(packer.try_pack(0, 10), Err(PackError::TooLarge));
+        assert!(packer.pack(64, 20).is_some());
+        assert_eq!(packer.try_pack(10, 20), Err(PackError::NoSpace));
+        assert!(packer.pack(64, 12).is_some());
+        assert_eq!(packer.occupancy(), 1.0);
+    }
+
+    #[test]
+    fn rotation() {
+        let mut packer = SkylinePacker::new(64, 16).with_rotation(true);
+        let packed = packer.try_pack(8, 40).unwrap();
+        assert!(packed.rotated);
+        assert_eq!((packed.rect.width, packed.rect.height), (40, 8));
+
+        let mut packer = SkylinePacker::new(64, 16);
+        assert_eq!(packer.try_pack(8, 40), Err(PackError::TooLarge));
+    }
+}
@@ -0,0 +1,48 @@
+use nalgebra::Vector3;
+
+use super::aabb::AxisAlignedBoundingBox;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    /// Normalized direction.
+    pub dir: Vector3<f32>,
+}
+
+impl Ray {
+    /// Returns None if direction has zero length.
+    pub fn new(origin: Vector3<f32>, dir: Vector3<f32>) -> Option<Self> {
+        dir.try_normalize(f32::EPSILON)
+            .map(|dir| Self { origin, dir })
+    }
+
+    pub fn point_at(&self, t: f32) -> Vector3<f32> {
+        self.origin + self.dir.scale(t)
+    }
+
+    /// Distance to the first intersection with the box.
+    pub fn aabb_intersection(&self, aabb: &AxisAlignedBoundingBox) -> Option<f32> {
+        aabb.ray_intersection(self.origin, self.dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::Ray;
+    use crate::math::aabb::AxisAlignedBoundingBox;
+
+    #[test]
+    fn ray_basics() {
+        assert!(Ray::new(Vector3::zeros(), Vector3::zeros()).is_none());
+        let ray = Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, -3.0)).unwrap();
+        assert_eq!(ray.dir, Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(ray.point_at(2.0), Vector3::new(0.0, 1.0, -2.0));
+        let aabb = AxisAlignedBoundingBox::new(
+            Vector3::new(-1.0, 0.0, -6.0),
+            Vector3::new(1.0, 2.0, -4.0),
+        );
+        assert_eq!(ray.aabb_intersection(&aabb), Some(4.0));
+    }
+}
@@ -0,0 +1,207 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use super::aabb::AxisAlignedBoundingBox;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vector3<f32>, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Builds an approximate minimal sphere using Ritter's algorithm. The result
+    /// is usually 5-20% larger than the optimal sphere but always encloses every point.
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        let first = match points.first() {
+            Some(first) => *first,
+            None => return Self::new(Vector3::zeros(), 0.0),
+        };
+
+        let farthest_from = |origin: Vector3<f32>| -> Vector3<f32> {
+            let mut result = origin;
+            let mut max_dist = 0.0;
+            for point in points.iter() {
+                let dist = (point - origin).norm_squared();
+                if dist > max_dist {
+                    max_dist = dist;
+                    result = *point;
+                }
+            }
+            result
+        };
+
+        let a = farthest_from(first);
+        let b = farthest_from(a);
+
+        let mut center = (a + b).scale(0.5);
+        let mut radius = (b - a).norm() * 0.5;
+
+        // Grow sphere to include points that are still outside.
+        for point in points.iter() {
+            let to_point = point - center;
+            let dist = to_point.norm();
+            if dist > radius {
+                let new_radius = (radius + dist) * 0.5;
+                center += to_point.scale((new_radius - radius) / dist);
+                radius = new_radius;
+            }
+        }
+
+        Self { center, radius }
+    }
+
+    pub fn from_aabb(aabb: &AxisAlignedBoundingBox) -> Self {
+        Self {
+            center: aabb.center(),
+            radius: aabb.half_extents().norm(),
+        }
+    }
+
+    /// Returns sphere that encloses both spheres.
+    pub fn merge(&self, other: &BoundingSphere) -> Self {
+        let d = other.center - self.center;
+        let dist = d.norm();
+
+        if dist + other.radius <= self.radius {
+            return *self;
+        }
+        if dist + self.radius <= other.radius {
+            return *other;
+        }
+
+        let radius = (dist + self.radius + other.radius) * 0.5;
+        let center = self.center + d.scale((radius - self.radius) / dist);
+        Self { center, radius }
+    }
+
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        (point - self.center).norm_squared() <= self.radius * self.radius
+    }
+
+    pub fn intersects_sphere(&self, other: &BoundingSphere) -> bool {
+        let r = self.radius + other.radius;
+        (other.center - self.center).norm_squared() <= r * r
+    }
+
+    pub fn intersects_aabb(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        let closest = self.center.sup(&aabb.min).inf(&aabb.max);
+        (closest - self.center).norm_squared() <= self.radius * self.radius
+    }
+
+    /// Transforms sphere by given matrix. Radius is scaled by the largest axis
+    /// scale, so the result is conservative for non-uniform scaling.
+    pub fn transform(&self, m: &Matrix4<f32>) -> Self {
+        let center = m.transform_point(&Point3::from(self.center)).coords;
+        let max_scale = (0..3)
+            .map(|i| m.fixed_view::<3, 1>(0, i).norm())
+            .fold(0.0, f32::max);
+        Self {
+            center,
+            radius: self.radius * max_scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, Vector3};
+
+    use crate::math::{aabb::AxisAlignedBoundingBox, sphere::BoundingSphere};
+
+    const EPSILON: f32 = 1e-4;
+
+    /// Simple LCG so the point sets are "random" yet reproducible.
+    fn pseudo_random_points(seed: u32, count: usize, spread: f32) -> Vec<Vector3<f32>> {
+        let mut state = seed;
+        let mut next = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0
+        };
+        (0..count)
+            .map(|_| {
+                Vector3::new(
+                    next() * spread,
+                    next() * spread * 0.5,
+                    next() * spread * 2.0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn from_points_encloses_all_points() {
+        for (seed, count) in [(1, 3), (42, 50), (1337, 500), (7, 1)] {
+            let points = pseudo_random_points(seed, count, 10.0);
+            let sphere = BoundingSphere::from_points(&points);
+            for point in points.iter() {
+                assert!((point - sphere.center).norm() <= sphere.radius + EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn from_points_empty() {
+        let sphere = BoundingSphere::from_points(&[]);
+        assert_eq!(sphere.radius, 0.0);
+    }
+
+    #[test]
+    fn from_aabb() {
+        let aabb = AxisAlignedBoundingBox::new(
+            Vector3::new(-1.0, -2.0, -3.0),
+            Vector3::new(1.0, 2.0, 3.0),
+        );
+        let sphere = BoundingSphere::from_aabb(&aabb);
+        assert_eq!(sphere.center, Vector3::zeros());
+        assert!((sphere.radius - 14.0f32.sqrt()).abs() < EPSILON);
+        assert!(sphere.contains_point(aabb.min));
+        assert!(sphere.contains_point(aabb.max));
+        assert!(sphere.intersects_aabb(&aabb));
+    }
+
+    #[test]
+    fn merge_encloses_both() {
+        let a = BoundingSphere::new(Vector3::new(-2.0, 0.0, 0.0), 1.0);
+        let b = BoundingSphere::new(Vector3::new(3.0, 0.0, 0.0), 2.0);
+        let merged = a.merge(&b);
+        assert!((merged.radius - 4.0).abs() < EPSILON);
+        assert!((merged.center - Vector3::new(1.0, 0.0, 0.0)).norm() < EPSILON);
+
+        let inner = BoundingSphere::new(Vector3::new(3.5, 0.0, 0.0), 0.5);
+        assert_eq!(b.merge(&inner), b);
+        assert_eq!(inner.merge(&b), b);
+    }
+
+    #[test]
+    fn intersections() {
+        let a = BoundingSphere::new(Vector3::zeros(), 1.0);
+        let b = BoundingSphere::new(Vector3::new(1.5, 0.0, 0.0), 1.0);
+        let c = BoundingSphere::new(Vector3::new(3.0, 0.0, 0.0), 0.5);
+        assert!(a.intersects_sphere(&b));
+        assert!(!a.intersects_sphere(&c));
+
+        let aabb =
+            AxisAlignedBoundingBox::new(Vector3::new(1.5, -1.0, -1.0), Vector3::new(2.5, 1.0, 1.0));
+        assert!(!a.intersects_aabb(&aabb));
+        assert!(b.intersects_aabb(&aabb));
+    }
+
+    #[test]
+    fn transform_is_conservative_under_non_uniform_scale() {
+        let points = pseudo_random_points(99, 100, 1.0);
+        let sphere = BoundingSphere::from_points(&points);
+        let m = Matrix4::new_translation(&Vector3::new(5.0, -1.0, 2.0))
+            * Matrix4::new_rotation(Vector3::new(0.3, 0.7, -0.2))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(3.0, 0.5, 1.0));
+        let transformed = sphere.transform(&m);
+        assert!((transformed.radius - sphere.radius * 3.0).abs() < EPSILON);
+        for point in points.iter() {
+            let p = m.transform_point(&(*point).into()).coords;
+            assert!((p - transformed.center).norm() <= transformed.radius + EPSILON);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use nalgebra::{Vector2, Vector3};
+
+/// Splits simple polygon into triangles, returns their corners as indices of
+/// `points`. The polygon is projected onto its best-fit plane, so it may be
+/// slightly non-planar. Triangles keep winding of the polygon, degenerate
+/// polygons with zero area give none.
+pub fn triangulate(points: &[Vector3<f32>]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    // Newell's normal, its length is twice the area of the polygon.
+    let mut normal = Vector3::<f32>::zeros();
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+    let extent = points
+        .iter()
+        .map(|point| (point - points[0]).norm_squared())
+        .fold(0.0, f32::max);
+    if normal.norm() <= f32::EPSILON * extent {
+        return Vec::new();
+    }
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Basis of the plane with u x v = normal, counter-clockwise polygons
+    // stay counter-clockwise in projection.
+    let normal = normal.normalize();
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    let u = normal.cross(&axis).normalize();
+    let v = normal.cross(&u);
+    let projected: Vec<_> = points
+        .iter()
+        .map(|point| Vector2::new(point.dot(&u), point.dot(&v)))
+        .collect();
+    triangulate_2d(&projected)
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Signed area, positive for counter-clockwise polygons.
+pub fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        area += cross(*a, points[(i + 1) % points.len()]);
+    }
+    area * 0.5
+}
+
+/// Same as `triangulate` for planar polygons. Convex polygons are split into
+/// a fan, concave ones are ear clipped.
+pub fn triangulate_2d(points: &[Vector2<f32>]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    if count < 3 {
+        return Vec::new();
+    }
+    let area = signed_area(points);
+    let extent = points
+        .iter()
+        .map(|point| (point - points[0]).norm_squared())
+        .fold(0.0, f32::max);
+    if area.abs() <= f32::EPSILON * extent {
+        return Vec::new();
+    }
+    // Turns are positive where the polygon bends to its inner side.
+    let sign = area.signum();
+    let turn =
+        |a: usize, b: usize, c: usize| cross(points[b] - points[a], points[c] - points[b]) * sign;
+    if (0..count).all(|i| turn(i, (i + 1) % count, (i + 2) % count) >= 0.0) {
+        return (1..count - 1).map(|i| [0, i, i + 1]).collect();
+    }
+
+    let contains = |[a, b, c]: [usize; 3], p: usize| {
+        let p = points[p];
+        if p == points[a] || p == points[b] || p == points[c] {
+            return false;
+        }
+        cross(points[b] - points[a], p - points[a]) * sign >= 0.0
+            && cross(points[c] - points[b], p - points[b]) * sign >= 0.0
+            && cross(points[a] - points[c], p - points[c]) * sign >= 0.0
+    };
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = Vec::with_capacity(count - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let triangle = |i: usize| {
+            [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ]
+        };
+        let is_ear = |i: usize| {
+            let corners = triangle(i);
+            turn(corners[0], corners[1], corners[2]) > 0.0
+                && !remaining.iter().any(|&p| contains(corners, p))
+        };
+        // Self-intersecting polygons may have no ears left, clipping any
+        // vertex still covers them.
+        let ear = (0..n).find(|&i| is_ear(i)).unwrap_or(0);
+        triangles.push(triangle(ear));
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use nalgebra::{Vector2, Vector3};
+
+    use super::{signed_area, triangulate, triangulate_2d};
+
+    fn polygon(points: &[[f32; 2]]) -> Vec<Vector2<f32>> {
+        points.iter().map(|&p| Vector2::from(p)).collect()
+    }
+
+    fn star(points: usize) -> Vec<Vector2<f32>> {
+        (0..points * 2)
+            .map(|i| {
+                let angle = i as f32 * PI / points as f32;
+                let radius = if i % 2 == 0 { 2.0 } else { 0.5 };
+                Vector2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect()
+    }
+
+    /// Checks triangle count, area and that every triangle turns the same
+    /// way as the polygon.
+    fn check(points: &[Vector2<f32>]) {
+        let triangles = triangulate_2d(points);
+        assert_eq!(triangles.len(), points.len() - 2);
+        let area = signed_area(points);
+        let mut total = 0.0;
+        for triangle in triangles.iter() {
+            let triangle_area = signed_area(&triangle.map(|i| points[i]));
+            assert!(triangle_area * area > 0.0, "{:?} is flipped", triangle);
+            total += triangle_area;
+        }
+        assert!((total - area).abs() < 1e-4, "{} != {}", total, area);
+    }
+
+    #[test]
+    fn convex_fan() {
+        let square = polygon(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        assert_eq!(triangulate_2d(&square), [[0, 1, 2], [0, 2, 3]]);
+        check(&square);
+    }
+
+    #[test]
+    fn concave_polygons() {
+        let l_shape = polygon(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ]);
+        check(&l_shape);
+        // Fan from the first vertex would cover the notch of this one.
+        let notched = polygon(&[
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+        ]);
+        check(&notched);
+        check(&star(5));
+
+        let mut clockwise = star(7);
+        clockwise.reverse();
+        check(&clockwise);
+    }
+
+    #[test]
+    fn degenerate_polygons() {
+        let line = polygon(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]);
+        assert!(triangulate_2d(&line).is_empty());
+        let repeated = polygon(&[[1.0, 1.0], [1.0, 1.0], [1.0, 1.0]]);
+        assert!(triangulate_2d(&repeated).is_empty());
+        assert!(triangulate_2d(&line[..2]).is_empty());
+    }
+
+    #[test]
+    fn tilted_polygon() {
+        // L-shape in a plane through the origin, tilted about the X axis.
+        let (sin, cos) = 0.3f32.sin_cos();
+        let points: Vec<_> = [
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ]
+        .iter()
+        .map(|&[x, y]| Vector3::new(x, y * cos, y * sin))
+        .collect();
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        let normal = Vector3::new(0.0, -sin, cos);
+        let area: f32 = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let doubled = (points[b] - points[a]).cross(&(points[c] - points[a]));
+                assert!(doubled.dot(&normal) > 0.0);
+                doubled.norm() * 0.5
+            })
+            .sum();
+        assert!((area - 3.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,1566 @@
+use crate::level::SpawnDescription;
+use balala::{
+    engine::{
+        input::InputState,
+        input_map::{InputBinding, InputMap},
+    },
+    math::{aabb::AxisAlignedBoundingBox, easing, ray::Ray, Lerp},
+    scene::{
+        collision::{cast_ray, move_and_collide, overlaps_any},
+        node::{Camera, Node, NodeKind},
+        Scene,
+    },
+    utils::pool::Handle,
+};
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+pub const ACTION_MOVE_FORWARD: &str = "move_forward";
+pub const ACTION_MOVE_BACKWARD: &str = "move_backward";
+pub const ACTION_MOVE_LEFT: &str = "move_left";
+pub const ACTION_MOVE_RIGHT: &str = "move_right";
+pub const ACTION_JUMP: &str = "jump";
+pub const ACTION_SPRINT: &str = "sprint";
+pub const ACTION_CROUCH: &str = "crouch";
+pub const ACTION_TOGGLE_FLY: &str = "toggle_fly";
+pub const ACTION_FLY_UP: &str = "fly_up";
+pub const ACTION_FLY_DOWN: &str = "fly_down";
+pub const ACTION_TOGGLE_VIEW: &str = "toggle_view";
+pub const ACTION_LOOK_LEFT: &str = "look_left";
+pub const ACTION_LOOK_RIGHT: &str = "look_right";
+pub const ACTION_LOOK_UP: &str = "look_up";
+pub const ACTION_LOOK_DOWN: &str = "look_down";
+pub const AXIS_MOVE_X: &str = "move_x";
+pub const AXIS_MOVE_Z: &str = "move_z";
+pub const AXIS_LOOK_X: &str = "look_x";
+pub const AXIS_LOOK_Y: &str = "look_y";
+pub const AXIS_FLY_Y: &str = "fly_y";
+
+/// Rebindable actions of the player controller.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlayerAction {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sprint,
+    Crouch,
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    ToggleFly,
+    FlyUp,
+    FlyDown,
+    ToggleView,
+}
+
+impl PlayerAction {
+    pub const ALL: [PlayerAction; 15] = [
+        PlayerAction::MoveForward,
+        PlayerAction::MoveBackward,
+        PlayerAction::MoveLeft,
+        PlayerAction::MoveRight,
+        PlayerAction::Jump,
+        PlayerAction::Sprint,
+        PlayerAction::Crouch,
+        PlayerAction::LookLeft,
+        PlayerAction::LookRight,
+        PlayerAction::LookUp,
+        PlayerAction::LookDown,
+        PlayerAction::ToggleFly,
+        PlayerAction::FlyUp,
+        PlayerAction::FlyDown,
+        PlayerAction::ToggleView,
+    ];
+
+    /// Name of the action in the input map.
+    pub fn name(self) -> &'static str {
+        match self {
+            PlayerAction::MoveForward => ACTION_MOVE_FORWARD,
+            PlayerAction::MoveBackward => ACTION_MOVE_BACKWARD,
+            PlayerAction::MoveLeft => ACTION_MOVE_LEFT,
+            PlayerAction::MoveRight => ACTION_MOVE_RIGHT,
+            PlayerAction::Jump => ACTION_JUMP,
+            PlayerAction::Sprint => ACTION_SPRINT,
+            PlayerAction::Crouch => ACTION_CROUCH,
+            PlayerAction::LookLeft => ACTION_LOOK_LEFT,
+            PlayerAction::LookRight => ACTION_LOOK_RIGHT,
+            PlayerAction::LookUp => ACTION_LOOK_UP,
+            PlayerAction::LookDown => ACTION_LOOK_DOWN,
+            PlayerAction::ToggleFly => ACTION_TOGGLE_FLY,
+            PlayerAction::FlyUp => ACTION_FLY_UP,
+            PlayerAction::FlyDown => ACTION_FLY_DOWN,
+            PlayerAction::ToggleView => ACTION_TOGGLE_VIEW,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PlayerAction> {
+        PlayerAction::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+}
+
+/// Default key bindings of the demo.
+pub fn default_input_map() -> InputMap {
+    let mut map = InputMap::new();
+    map.bind(ACTION_MOVE_FORWARD, VirtualKeyCode::W);
+    map.bind(ACTION_MOVE_BACKWARD, VirtualKeyCode::S);
+    map.bind(ACTION_MOVE_LEFT, VirtualKeyCode::A);
+    map.bind(ACTION_MOVE_RIGHT, VirtualKeyCode::D);
+    map.bind_axis(AXIS_MOVE_X, ACTION_MOVE_RIGHT, ACTION_MOVE_LEFT);
+    map.bind_axis(AXIS_MOVE_Z, ACTION_MOVE_BACKWARD, ACTION_MOVE_FORWARD);
+    map.bind(ACTION_JUMP, VirtualKeyCode::Space);
+    map.bind(ACTION_SPRINT, VirtualKeyCode::LShift);
+    map.bind(ACTION_CROUCH, VirtualKeyCode::LControl);
+    map.bind(ACTION_LOOK_LEFT, VirtualKeyCode::Left);
+    map.bind(ACTION_LOOK_RIGHT, VirtualKeyCode::Right);
+    map.bind(ACTION_LOOK_UP, VirtualKeyCode::Up);
+    map.bind(ACTION_LOOK_DOWN, VirtualKeyCode::Down);
+    map.bind_axis(AXIS_LOOK_X, ACTION_LOOK_LEFT, ACTION_LOOK_RIGHT);
+    map.bind_axis(AXIS_LOOK_Y, ACTION_LOOK_DOWN, ACTION_LOOK_UP);
+    map.bind(ACTION_TOGGLE_FLY, VirtualKeyCode::V);
+    map.bind(ACTION_FLY_UP, VirtualKeyCode::E);
+    map.bind(ACTION_FLY_DOWN, VirtualKeyCode::Q);
+    map.bind_axis(AXIS_FLY_Y, ACTION_FLY_DOWN, ACTION_FLY_UP);
+    map.bind(ACTION_TOGGLE_VIEW, VirtualKeyCode::F5);
+    map
+}
+
+/// Clamps value between two limits given in any order.
+fn clamp_range(value: f32, a: f32, b: f32) -> f32 {
+    value.clamp(a.min(b), a.max(b))
+}
+
+/// Look and movement settings of the player, angles are in degrees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSettings {
+    /// Degrees per pixel of mouse motion.
+    pub mouse_sensitivity: f32,
+    /// Degrees per second at full deflection of look axes (stick or arrow keys).
+    pub controller_sensitivity: f32,
+    pub invert_y: bool,
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+    /// Speed factor while sprinting.
+    pub sprint_multiplier: f32,
+    /// Speed factor while crouching, overrides sprint.
+    pub crouch_multiplier: f32,
+    /// Distance of the third person camera behind the eye.
+    pub third_person_distance: f32,
+    /// Height of the third person camera above the eye.
+    pub third_person_height: f32,
+    /// Field of view limits of the first person wheel zoom, in degrees.
+    pub min_fov: f32,
+    pub max_fov: f32,
+    /// Field of view change per wheel line.
+    pub fov_step: f32,
+    /// Camera distance limits of the third person wheel zoom.
+    pub min_third_person_distance: f32,
+    pub max_third_person_distance: f32,
+    /// Camera distance change per wheel line.
+    pub distance_step: f32,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            mouse_sensitivity: 0.3,
+            controller_sensitivity: 120.0,
+            invert_y: false,
+            min_pitch: -90.0,
+            max_pitch: 90.0,
+            sprint_multiplier: 1.8,
+            crouch_multiplier: 0.5,
+            third_person_distance: 4.0,
+            third_person_height: 0.5,
+            min_fov: 20.0,
+            max_fov: 70.0,
+            fov_step: 5.0,
+            min_third_person_distance: 1.5,
+            max_third_person_distance: 10.0,
+            distance_step: 0.5,
+        }
+    }
+}
+
+impl PlayerSettings {
+    /// Converts mouse motion in pixels into (yaw, pitch) change.
+    pub fn mouse_look(&self, delta: Vector2<f32>) -> Vector2<f32> {
+        self.look(delta * self.mouse_sensitivity)
+    }
+
+    /// Converts look axes in -1..1 range into (yaw, pitch) change over `dt`.
+    /// Axes are positive to the right and up.
+    pub fn controller_look(&self, axes: Vector2<f32>, dt: f32) -> Vector2<f32> {
+        self.look(Vector2::new(axes.x, -axes.y) * self.controller_sensitivity * dt)
+    }
+
+    /// Screen space delta (x right, y down) to (yaw, pitch) change.
+    fn look(&self, delta: Vector2<f32>) -> Vector2<f32> {
+        let pitch = if self.invert_y { -delta.y } else { delta.y };
+        Vector2::new(-delta.x, pitch)
+    }
+
+    /// New field of view target after `wheel` lines, scrolling up zooms in.
+    pub fn zoom_fov(&self, fov: f32, wheel: f32) -> f32 {
+        clamp_range(fov - wheel * self.fov_step, self.min_fov, self.max_fov)
+    }
+
+    /// New third person distance target after `wheel` lines, scrolling up
+    /// moves the camera closer.
+    pub fn zoom_distance(&self, distance: f32, wheel: f32) -> f32 {
+        clamp_range(
+            distance - wheel * self.distance_step,
+            self.min_third_person_distance,
+            self.max_third_person_distance,
+        )
+    }
+
+    pub fn clamp_pitch(&self, pitch: f32) -> f32 {
+        clamp_range(pitch, self.min_pitch, self.max_pitch)
+    }
+}
+
+/// Height of the floor top, the floor is a unit cube scaled to 0.1 in Y.
+pub const FLOOR_HEIGHT: f32 = 0.05;
+/// Half size of the player's collision box, centered on the pivot.
+pub const PLAYER_HALF_EXTENTS: Vector3<f32> = Vector3::new(0.4, 0.9, 0.4);
+/// Camera height above the pivot.
+const EYE_HEIGHT: f32 = 1.1;
+/// Camera height above the pivot while crouching.
+const CROUCH_EYE_HEIGHT: f32 = 0.3;
+/// Full height of the collision box while crouching, feet stay in place.
+const CROUCH_HEIGHT: f32 = 1.1;
+/// Time of the camera transition between standing and crouching.
+const CROUCH_TRANSITION_TIME: f32 = 0.2;
+/// Obstacles this low are stepped over instead of blocking.
+const STEP_HEIGHT: f32 = 0.3;
+/// Gravity acceleration in units per second squared.
+const GRAVITY: f32 = 25.0;
+/// Initial vertical speed of a jump.
+const JUMP_SPEED: f32 = 8.0;
+/// Time of the camera transition between first and third person.
+const VIEW_TRANSITION_TIME: f32 = 0.25;
+/// Time constant of the wheel zoom animation, zoom settles in about 0.15 s.
+const ZOOM_SMOOTHING: f32 = 0.05;
+/// Third person camera keeps this distance from walls in front of it.
+const CAMERA_WALL_MARGIN: f32 = 0.2;
+/// Base speed of the fly mode.
+const FLY_SPEED: f32 = 20.0;
+/// Fly speed multiplier change per wheel line.
+const FLY_SPEED_STEP: f32 = 1.2;
+const MIN_FLY_SPEED_MULTIPLIER: f32 = 0.1;
+const MAX_FLY_SPEED_MULTIPLIER: f32 = 10.0;
+/// Jump is still allowed this long after walking off an edge.
+const COYOTE_TIME: f32 = 0.1;
+/// Jump pressed this long before landing is performed on landing.
+const JUMP_BUFFER_TIME: f32 = 0.1;
+
+/// Vertical kinematics of the player: gravity, jumping and ground contact.
+#[derive(Debug, Clone, Default)]
+pub struct VerticalMotion {
+    velocity: f32,
+    grounded: bool,
+    coyote_timer: f32,
+    jump_buffer_timer: f32,
+}
+
+impl VerticalMotion {
+    pub fn request_jump(&mut self) {
+        self.jump_buffer_timer = JUMP_BUFFER_TIME;
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Applies pending jump and gravity, returns vertical displacement over
+    /// `dt`. Result of moving the player must be passed to `resolve`.
+    pub fn integrate(&mut self, dt: f32) -> f32 {
+        if self.jump_buffer_timer > 0.0 && (self.grounded || self.coyote_timer > 0.0) {
+            self.velocity = JUMP_SPEED;
+            self.grounded = false;
+            self.coyote_timer = 0.0;
+            self.jump_buffer_timer = 0.0;
+        }
+        self.jump_buffer_timer = (self.jump_buffer_timer - dt).max(0.0);
+
+        self.velocity -= GRAVITY * dt;
+        self.velocity * dt
+    }
+
+    /// Updates ground contact after collision, `blocked` is true if vertical
+    /// movement hit something and `grounded` if it was a surface below.
+    pub fn resolve(&mut self, blocked: bool, grounded: bool, dt: f32) {
+        if blocked {
+            self.velocity = 0.0;
+        }
+        if grounded {
+            self.grounded = true;
+            self.coyote_timer = COYOTE_TIME;
+        } else if self.grounded {
+            // Walked off an edge, coyote timer starts now.
+            self.grounded = false;
+        } else {
+            self.coyote_timer = (self.coyote_timer - dt).max(0.0);
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlayerMode {
+    /// Regular movement with gravity and collision.
+    Walk,
+    /// Debug noclip camera, moves along the look direction including pitch.
+    Fly,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CameraView {
+    FirstPerson,
+    /// Camera orbits the player at a distance.
+    ThirdPerson,
+}
+
+/// Offset of the third person camera from the eye in world space, `rotation`
+/// is the look rotation so pitch orbits around the eye.
+pub fn orbit_offset(rotation: &UnitQuaternion<f32>, distance: f32, height: f32) -> Vector3<f32> {
+    rotation * Vector3::new(0.0, 0.0, -distance) + Vector3::new(0.0, height, 0.0)
+}
+
+/// Moves `value` toward `target` by at most `step`.
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
+/// Shortens camera `offset` from `origin` so it stays in front of colliders.
+pub fn clip_camera_offset(
+    origin: Vector3<f32>,
+    offset: Vector3<f32>,
+    colliders: &[AxisAlignedBoundingBox],
+) -> Vector3<f32> {
+    let distance = offset.norm();
+    match offset.try_normalize(f32::EPSILON) {
+        Some(direction) => match cast_ray(origin, direction, distance, colliders) {
+            Some(hit) => direction.scale((hit - CAMERA_WALL_MARGIN).max(0.0)),
+            None => offset,
+        },
+        None => offset,
+    }
+}
+
+/// Yaw and pitch in degrees that make the player look along Z axis of
+/// `transform`.
+pub fn spawn_orientation(transform: &Matrix4<f32>) -> (f32, f32) {
+    let look = transform.transform_vector(&Vector3::z());
+    let horizontal = look.x.hypot(look.z);
+    if horizontal <= f32::EPSILON {
+        // Straight up or down, heading is undefined.
+        return (0.0, if look.y > 0.0 { -90.0 } else { 90.0 });
+    }
+    (
+        look.x.atan2(look.z).to_degrees(),
+        (-look.y).atan2(horizontal).to_degrees(),
+    )
+}
+
+/// How a spawn point is picked when the player respawns.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnPolicy {
+    /// Always the first spawn point of the level.
+    #[default]
+    First,
+    /// Spawn point closest to where the player was.
+    Nearest,
+    Random,
+}
+
+impl SpawnPolicy {
+    /// Index of the spawn to use, `random` is any value from a random source.
+    pub fn select(
+        self,
+        spawns: &[Matrix4<f32>],
+        position: Vector3<f32>,
+        random: u32,
+    ) -> Option<usize> {
+        if spawns.is_empty() {
+            return None;
+        }
+        match self {
+            SpawnPolicy::First => Some(0),
+            SpawnPolicy::Nearest => {
+                let distance = |transform: &Matrix4<f32>| {
+                    (transform.transform_point(&Point3::origin()).coords - position).norm_squared()
+                };
+                (0..spawns.len())
+                    .min_by(|a, b| distance(&spawns[*a]).total_cmp(&distance(&spawns[*b])))
+            }
+            SpawnPolicy::Random => Some(random as usize % spawns.len()),
+        }
+    }
+}
+
+pub struct Player {
+    camera: Handle<Node>,
+    pivot: Handle<Node>,
+    /// Movement input, x is strafe (positive to the left) and y is forward.
+    move_input: Vector2<f32>,
+    /// Mouse motion of the current step, applied in `update`.
+    look_delta: Vector2<f32>,
+    /// Look axes (arrow keys), x is right and y is up.
+    look_axes: Vector2<f32>,
+    yaw: f32,
+    pitch: f32,
+    mode: PlayerMode,
+    view: CameraView,
+    /// Camera transition progress, 0 is first person and 1 is third person.
+    view_amount: f32,
+    fov: f32,
+    fov_target: f32,
+    /// Third person camera distance before wall clipping.
+    distance: f32,
+    distance_target: f32,
+    /// Vertical fly input, positive is up.
+    fly_input: f32,
+    fly_speed_multiplier: f32,
+    move_speed: f32,
+    sprint_input: bool,
+    crouch_input: bool,
+    crouched: bool,
+    /// Camera transition progress, 0 is standing and 1 is crouching.
+    crouch_amount: f32,
+    vertical: VerticalMotion,
+    /// Key bindings of the controller.
+    controls: InputMap,
+    /// Scratch buffer for collider queries.
+    colliders: Vec<AxisAlignedBoundingBox>,
+    settings: PlayerSettings,
+}
+
+impl Player {
+    pub fn new(scene: &mut Scene, settings: PlayerSettings) -> Player {
+        let camera = Camera::default();
+        let fov = camera.get_fov();
+        let mut camera = Node::new(NodeKind::Camera(camera));
+        camera.set_local_position(Vector3::new(0.0, EYE_HEIGHT, 0.0));
+
+        let camera_handle = scene.add_node(camera);
+        let pivot_handle = scene.add_node(Node::new(NodeKind::Base));
+        scene.link_nodes(&camera_handle, &pivot_handle);
+
+        let spawn = SpawnDescription::default();
+        let mut player = Player {
+            camera: camera_handle,
+            pivot: pivot_handle,
+            move_input: Vector2::zeros(),
+            look_delta: Vector2::zeros(),
+            look_axes: Vector2::zeros(),
+            yaw: 0.0,
+            pitch: 0.0,
+            mode: PlayerMode::Walk,
+            view: CameraView::FirstPerson,
+            view_amount: 0.0,
+            fov,
+            fov_target: fov,
+            distance: settings.third_person_distance,
+            distance_target: settings.third_person_distance,
+            fly_input: 0.0,
+            fly_speed_multiplier: 1.0,
+            move_speed: 10.0,
+            sprint_input: false,
+            crouch_input: false,
+            crouched: false,
+            crouch_amount: 0.0,
+            vertical: VerticalMotion::default(),
+            controls: default_input_map(),
+            colliders: Vec::new(),
+            settings,
+        };
+        player.place(scene, Vector3::from(spawn.position), spawn.yaw);
+        player
+    }
+
+    /// Current movement speed with sprint and crouch factors applied.
+    pub fn get_speed(&self) -> f32 {
+        if self.crouched {
+            self.move_speed * self.settings.crouch_multiplier
+        } else if self.sprint_input {
+            self.move_speed * self.settings.sprint_multiplier
+        } else {
+            self.move_speed
+        }
+    }
+
+    /// Camera height above the pivot for current crouch transition.
+    pub fn get_eye_height(&self) -> f32 {
+        EYE_HEIGHT.lerp(&CROUCH_EYE_HEIGHT, easing::quad_in_out(self.crouch_amount))
+    }
+
+    /// Collision box at `position` of the pivot for given stance.
+    fn body_at(position: Vector3<f32>, crouched: bool) -> AxisAlignedBoundingBox {
+        let min = position - PLAYER_HALF_EXTENTS;
+        let mut max = position + PLAYER_HALF_EXTENTS;
+        if crouched {
+            max.y = min.y + CROUCH_HEIGHT;
+        }
+        AxisAlignedBoundingBox::new(min, max)
+    }
+
+    pub fn update(&mut self, scene: &mut Scene, dt: f32) {
+        let look = self.settings.mouse_look(self.look_delta)
+            + self.settings.controller_look(self.look_axes, dt);
+        self.yaw += look.x;
+        self.pitch = self.settings.clamp_pitch(self.pitch + look.y);
+        self.look_delta = Vector2::zeros();
+
+        let target = match self.view {
+            CameraView::FirstPerson => 0.0,
+            CameraView::ThirdPerson => 1.0,
+        };
+        self.view_amount = approach(self.view_amount, target, dt / VIEW_TRANSITION_TIME);
+        let blend = 1.0 - (-dt / ZOOM_SMOOTHING).exp();
+        self.fov = self.fov.lerp(&self.fov_target, blend);
+        self.distance = self.distance.lerp(&self.distance_target, blend);
+
+        if self.mode == PlayerMode::Fly {
+            self.update_fly(scene, dt);
+            return;
+        }
+
+        let pivot_node = match scene.borrow_node(&self.pivot) {
+            Some(pivot_node) => pivot_node,
+            None => return,
+        };
+        let look = pivot_node.get_look_vector();
+        let side = pivot_node.get_side_vector();
+        // Pivot is a child of the scene root, so its local position is global.
+        let position = pivot_node.get_local_position();
+
+        // Stand up only when there is room above.
+        let standing = Self::body_at(position, false);
+        if self.crouch_input {
+            self.crouched = true;
+        } else if self.crouched {
+            self.colliders.clear();
+            scene.query_colliders(&standing, &mut self.colliders);
+            self.crouched = overlaps_any(&standing, &self.colliders);
+        }
+        let target = if self.crouched { 1.0 } else { 0.0 };
+        self.crouch_amount = approach(self.crouch_amount, target, dt / CROUCH_TRANSITION_TIME);
+
+        let direction = look.scale(self.move_input.y) + side.scale(self.move_input.x);
+        let mut motion = direction
+            .try_normalize(0.)
+            .map_or(Vector3::zeros(), |normal| {
+                normal.scale(self.get_speed() * dt)
+            });
+        motion.y = self.vertical.integrate(dt);
+
+        let body = Self::body_at(position, self.crouched);
+        let region = body
+            .merge(&body.translate(motion))
+            .merge(&body.translate(Vector3::new(0.0, STEP_HEIGHT, 0.0)));
+        self.colliders.clear();
+        scene.query_colliders(&region, &mut self.colliders);
+        let result = move_and_collide(body, motion, &self.colliders, STEP_HEIGHT);
+        self.vertical.resolve(result.blocked.y, result.grounded, dt);
+
+        self.apply_transform(scene, position + result.offset);
+    }
+
+    /// Moves freely along the camera look direction, ignoring gravity and
+    /// collision.
+    fn update_fly(&mut self, scene: &mut Scene, dt: f32) {
+        let position = match scene.borrow_node(&self.pivot) {
+            Some(pivot_node) => pivot_node.get_local_position(),
+            None => return,
+        };
+        let rotation = self.get_look_rotation();
+        let look = rotation * Vector3::z();
+        let side = rotation * Vector3::x();
+        let direction = look.scale(self.move_input.y)
+            + side.scale(self.move_input.x)
+            + Vector3::y().scale(self.fly_input);
+        let speed = FLY_SPEED * self.fly_speed_multiplier;
+        let motion = direction
+            .try_normalize(0.)
+            .map_or(Vector3::zeros(), |normal| normal.scale(speed * dt));
+        self.apply_transform(scene, position + motion);
+    }
+
+    /// Camera orientation of the player, yaw around Y then pitch around X.
+    pub fn get_look_rotation(&self) -> UnitQuaternion<f32> {
+        UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw.to_radians())
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.pitch.to_radians())
+    }
+
+    /// Yaw of the player in degrees.
+    pub fn get_yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    /// Node that carries the player's position and collision.
+    pub fn get_pivot(&self) -> &Handle<Node> {
+        &self.pivot
+    }
+
+    /// Camera node attached to the pivot.
+    pub fn get_camera(&self) -> &Handle<Node> {
+        &self.camera
+    }
+
+    /// Camera offset from the eye in world space for current view transition.
+    pub fn get_camera_offset(&self) -> Vector3<f32> {
+        let amount = easing::quad_in_out(self.view_amount);
+        if amount <= 0.0 {
+            return Vector3::zeros();
+        }
+        orbit_offset(
+            &self.get_look_rotation(),
+            self.distance * amount,
+            self.settings.third_person_height * amount,
+        )
+    }
+
+    fn apply_transform(&mut self, scene: &mut Scene, position: Vector3<f32>) {
+        let eye = Vector3::new(0.0, self.get_eye_height(), 0.0);
+        let mut offset = self.get_camera_offset();
+        if offset != Vector3::zeros() {
+            let origin = position + eye;
+            let region = AxisAlignedBoundingBox::from_points(&[origin, origin + offset]);
+            self.colliders.clear();
+            scene.query_colliders(&region, &mut self.colliders);
+            offset = clip_camera_offset(origin, offset, &self.colliders);
+        }
+
+        let yaw = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw.to_radians());
+        if let Some(pivot_node) = scene.borrow_node_mut(&self.pivot) {
+            pivot_node.set_local_position(position);
+            pivot_node.set_local_rotation(yaw);
+        }
+        if let Some(camera_node) = scene.borrow_node_mut(&self.camera) {
+            // Camera is a child of the pivot, which already has the yaw.
+            camera_node.set_local_position(eye + yaw.inverse() * offset);
+            camera_node.set_local_rotation(UnitQuaternion::from_axis_angle(
+                &Vector3::x_axis(),
+                self.pitch.to_radians(),
+            ));
+            if let NodeKind::Camera(camera) = camera_node.borrow_kind_mut() {
+                camera.set_fov(self.fov);
+            }
+        }
+    }
+
+    /// Ray from the camera through the center of the screen, uses transforms
+    /// of the last scene update.
+    pub fn get_view_ray(&self, scene: &Scene) -> Option<Ray> {
+        let transform = scene
+            .borrow_node(&self.camera)?
+            .get_interpolated_global_transform(1.0);
+        let origin = transform.transform_point(&Point3::origin()).coords;
+        Ray::new(origin, transform.transform_vector(&Vector3::z()))
+    }
+
+    /// Ray from the camera through the cursor, `cursor` is in window pixels
+    /// from the top left corner like winit reports it. Uses camera matrices
+    /// of the last scene update.
+    pub fn get_cursor_ray(
+        &self,
+        scene: &Scene,
+        cursor: Vector2<f32>,
+        client_size: Vector2<f32>,
+    ) -> Option<Ray> {
+        let NodeKind::Camera(camera) = scene.borrow_node(&self.camera)?.borrow_kind() else {
+            return None;
+        };
+        let viewport = camera.get_viewport_pixels(client_size);
+        let point = Vector2::new(cursor.x, client_size.y - cursor.y);
+        Some(camera.make_ray(point, viewport))
+    }
+
+    /// Moves player so its feet are at `feet` and it faces `yaw` degrees.
+    pub fn place(&mut self, scene: &mut Scene, feet: Vector3<f32>, yaw: f32) {
+        self.yaw = yaw;
+        self.pitch = 0.0;
+        self.vertical = VerticalMotion::default();
+        self.apply_transform(scene, feet + Vector3::new(0.0, PLAYER_HALF_EXTENTS.y, 0.0));
+    }
+
+    /// Moves player to the origin of a spawn transform and looks along its Z
+    /// axis, roll and scale are ignored.
+    pub fn spawn_at(&mut self, scene: &mut Scene, transform: &Matrix4<f32>) {
+        let feet = transform.transform_point(&Point3::origin()).coords;
+        let (yaw, pitch) = spawn_orientation(transform);
+        self.place(scene, feet, yaw);
+        self.pitch = self.settings.clamp_pitch(pitch);
+        self.apply_transform(scene, feet + Vector3::new(0.0, PLAYER_HALF_EXTENTS.y, 0.0));
+        for handle in [&self.pivot, &self.camera] {
+            if let Some(node) = scene.borrow_node_mut(handle) {
+                node.reset_interpolation();
+            }
+        }
+    }
+
+    /// Position of the player's feet.
+    pub fn get_feet_position(&self, scene: &Scene) -> Option<Vector3<f32>> {
+        let pivot = scene.borrow_node(&self.pivot)?;
+        Some(pivot.get_local_position() - Vector3::new(0.0, PLAYER_HALF_EXTENTS.y, 0.0))
+    }
+
+    pub fn get_mode(&self) -> PlayerMode {
+        self.mode
+    }
+
+    /// Switching back to walk mode starts from rest, gravity and collision
+    /// take over on next update.
+    pub fn set_mode(&mut self, mode: PlayerMode) {
+        if self.mode == mode {
+            return;
+        }
+        self.mode = mode;
+        self.vertical = VerticalMotion::default();
+        self.crouched = false;
+        self.crouch_amount = 0.0;
+    }
+
+    /// Reads movement keys and mouse motion accumulated since previous step.
+    /// Mouse motion comes from raw device events, or from cursor positions
+    /// when raw input is unavailable.
+    pub fn process_input(&mut self, input: &InputState) {
+        let controls = &self.controls;
+        self.move_input = Vector2::new(
+            controls.axis(input, AXIS_MOVE_X),
+            controls.axis(input, AXIS_MOVE_Z),
+        );
+        self.look_delta += input.mouse_delta();
+        self.look_axes = Vector2::new(
+            controls.axis(input, AXIS_LOOK_X),
+            controls.axis(input, AXIS_LOOK_Y),
+        );
+        self.sprint_input = controls.is_action_active(input, ACTION_SPRINT);
+        self.crouch_input = controls.is_action_active(input, ACTION_CROUCH);
+        let toggle_fly = controls.action_just_pressed(input, ACTION_TOGGLE_FLY);
+        let jump = controls.action_just_pressed(input, ACTION_JUMP);
+
+        match self.mode {
+            PlayerMode::Walk => {
+                // Edge-triggered, so key repeat does not produce extra jumps.
+                if jump {
+                    self.vertical.request_jump();
+                }
+                let wheel = input.wheel_delta();
+                if wheel != 0.0 {
+                    match self.view {
+                        CameraView::FirstPerson => {
+                            self.fov_target = self.settings.zoom_fov(self.fov_target, wheel)
+                        }
+                        CameraView::ThirdPerson => {
+                            self.distance_target =
+                                self.settings.zoom_distance(self.distance_target, wheel)
+                        }
+                    }
+                }
+            }
+            PlayerMode::Fly => {
+                let mut fly = controls.axis(input, AXIS_FLY_Y);
+                if controls.is_action_active(input, ACTION_JUMP) {
+                    fly += 1.0;
+                }
+                if self.crouch_input {
+                    fly -= 1.0;
+                }
+                self.fly_input = fly.clamp(-1.0, 1.0);
+                self.crouch_input = false;
+                self.fly_speed_multiplier = (self.fly_speed_multiplier
+                    * FLY_SPEED_STEP.powf(input.wheel_delta()))
+                .clamp(MIN_FLY_SPEED_MULTIPLIER, MAX_FLY_SPEED_MULTIPLIER);
+            }
+        }
+
+        if controls.action_just_pressed(input, ACTION_TOGGLE_VIEW) {
+            self.view = match self.view {
+                CameraView::FirstPerson => CameraView::ThirdPerson,
+                CameraView::ThirdPerson => CameraView::FirstPerson,
+            };
+        }
+
+        if toggle_fly {
+            self.set_mode(match self.mode {
+                PlayerMode::Walk => PlayerMode::Fly,
+                PlayerMode::Fly => PlayerMode::Walk,
+            });
+        }
+    }
+
+    pub fn get_settings(&self) -> &PlayerSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: PlayerSettings) {
+        self.settings = settings;
+    }
+
+    pub fn controls_mut(&mut self) -> &mut InputMap {
+        &mut self.controls
+    }
+
+    pub fn bindings(&self, action: PlayerAction) -> &[InputBinding] {
+        self.controls.bindings(action.name())
+    }
+
+    /// Makes `key` the primary binding of `action`, other bindings of the
+    /// action stay. If `key` was used by another action, the two actions swap
+    /// keys and that action is returned.
+    pub fn rebind(&mut self, action: PlayerAction, key: VirtualKeyCode) -> Option<PlayerAction> {
+        let binding = InputBinding::Key(key);
+        let previous = self.bindings(action).first().copied();
+        if previous == Some(binding) {
+            return None;
+        }
+
+        let conflict = PlayerAction::ALL
+            .iter()
+            .copied()
+            .find(|other| *other != action && self.bindings(*other).contains(&binding));
+        if let Some(other) = conflict {
+            let mut bindings = self.bindings(other).to_vec();
+            match previous {
+                Some(previous) if !bindings.contains(&previous) => {
+                    for b in bindings.iter_mut().filter(|b| **b == binding) {
+                        *b = previous;
+                    }
+                }
+                _ => bindings.retain(|b| *b != binding),
+            }
+            self.controls.set_bindings(other.name(), bindings);
+        }
+
+        let mut bindings = vec![binding];
+        bindings.extend(
+            self.bindings(action)
+                .iter()
+                .skip(1)
+                .filter(|b| **b != binding),
+        );
+        self.controls.set_bindings(action.name(), bindings);
+        conflict
+    }
+
+    /// Adds `key` as extra binding of `action`. If `key` was used by another
+    /// action, it is taken away from it and that action is returned.
+    pub fn bind(&mut self, action: PlayerAction, key: VirtualKeyCode) -> Option<PlayerAction> {
+        let conflict = self
+            .controls
+            .find_action(key)
+            .and_then(PlayerAction::from_name)
+            .filter(|other| *other != action);
+        if let Some(other) = conflict {
+            self.controls.unbind(other.name(), key);
+        }
+        self.controls.bind(action.name(), key);
+        conflict
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use balala::scene::node::Mesh;
+    use winit::event::{Event, WindowEvent};
+
+    pub fn add_collider_cube(scene: &mut Scene, position: Vector3<f32>, scale: Vector3<f32>) {
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        let mut node = Node::new(NodeKind::Mesh(mesh));
+        node.set_local_position(position);
+        node.set_local_scale(scale);
+        node.set_collider(true);
+        scene.add_node(node);
+    }
+
+    pub fn add_floor(scene: &mut Scene) {
+        add_collider_cube(scene, Vector3::zeros(), Vector3::new(100.0, 0.1, 100.0));
+    }
+
+    #[test]
+    fn player_collides_with_level() {
+        let dt = 1.0 / 60.0;
+        let mut scene = Scene::new();
+        add_floor(&mut scene);
+        // Wall in front of the player, which spawns at z = 20 looking along -Z.
+        add_collider_cube(
+            &mut scene,
+            Vector3::new(0.0, 1.0, 15.0),
+            Vector3::new(10.0, 2.0, 1.0),
+        );
+        // Low step the player walks over on the way.
+        add_collider_cube(
+            &mut scene,
+            Vector3::new(0.0, 0.1, 18.0),
+            Vector3::new(10.0, 0.2, 1.0),
+        );
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        scene.update(1.0, 0.0);
+        let look = scene.borrow_node(&player.pivot).unwrap().get_look_vector();
+        let dir = if look.z < 0.0 { 1.0 } else { -1.0 };
+        player.move_input = Vector2::new(0.0, dir);
+
+        for _ in 0..120 {
+            player.update(&mut scene, dt);
+            scene.update(1.0, dt);
+        }
+        let position = scene
+            .borrow_node(&player.pivot)
+            .unwrap()
+            .get_global_position();
+        // Stopped at the wall face without passing through.
+        assert!((position.z - PLAYER_HALF_EXTENTS.z - 15.5).abs() < 1e-3);
+        assert!(player.vertical.is_grounded());
+        // Standing on the floor again after the step.
+        assert!((position.y - PLAYER_HALF_EXTENTS.y - FLOOR_HEIGHT).abs() < 1e-3);
+
+        // Strafing along the wall is not blocked.
+        player.move_input = Vector2::new(1.0, dir);
+        player.update(&mut scene, dt);
+        scene.update(1.0, dt);
+        let slid = scene
+            .borrow_node(&player.pivot)
+            .unwrap()
+            .get_global_position();
+        assert!((slid.x - position.x).abs() > 0.05);
+        assert!((slid.z - position.z).abs() < 1e-3);
+    }
+
+    #[test]
+    fn player_motion_is_proportional_to_dt() {
+        let travel = |dt: f32, steps: usize| {
+            let mut scene = Scene::new();
+            add_floor(&mut scene);
+            let mut player = Player::new(&mut scene, PlayerSettings::default());
+            player.move_input = Vector2::new(0.0, 1.0);
+            scene.update(1.0, 0.0);
+            let start = scene
+                .borrow_node(&player.pivot)
+                .unwrap()
+                .get_global_position();
+            for _ in 0..steps {
+                player.update(&mut scene, dt);
+                scene.update(1.0, dt);
+            }
+            (scene
+                .borrow_node(&player.pivot)
+                .unwrap()
+                .get_global_position()
+                - start)
+                .norm()
+        };
+
+        let one = travel(0.1, 1);
+        assert!((one - 1.0).abs() < 1e-5);
+        assert!((travel(0.2, 1) - 2.0 * one).abs() < 1e-5);
+        assert!((travel(0.05, 4) - 2.0 * one).abs() < 1e-5);
+    }
+
+    #[test]
+    fn player_look_delta_is_applied_once() {
+        use winit::event::{DeviceEvent, DeviceId};
+
+        let mut scene = Scene::new();
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        let mut input = InputState::new();
+        for _ in 0..2 {
+            input.process_event(&Event::DeviceEvent {
+                device_id: unsafe { DeviceId::dummy() },
+                event: DeviceEvent::MouseMotion { delta: (5.0, 2.0) },
+            });
+        }
+
+        player.process_input(&input);
+        player.update(&mut scene, 0.1);
+        assert!((player.yaw + 3.0).abs() < 1e-5);
+        assert!((player.pitch - 1.2).abs() < 1e-5);
+
+        // Consumed motion does not turn the player again.
+        input.clear_frame();
+        player.process_input(&input);
+        player.update(&mut scene, 0.1);
+        assert!((player.yaw + 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn look_settings() {
+        let delta = Vector2::new(10.0, 4.0);
+        let mut settings = PlayerSettings::default();
+        assert_eq!(settings.mouse_look(delta), Vector2::new(-3.0, 1.2));
+
+        settings.mouse_sensitivity = 0.5;
+        settings.invert_y = true;
+        assert_eq!(settings.mouse_look(delta), Vector2::new(-5.0, -2.0));
+
+        // Full right and up deflection for half a second.
+        settings.invert_y = false;
+        settings.controller_sensitivity = 100.0;
+        assert_eq!(
+            settings.controller_look(Vector2::new(1.0, 1.0), 0.5),
+            Vector2::new(-50.0, -50.0)
+        );
+
+        settings.min_pitch = -85.0;
+        settings.max_pitch = 85.0;
+        assert_eq!(settings.clamp_pitch(120.0), 85.0);
+        assert_eq!(settings.clamp_pitch(-120.0), -85.0);
+
+        let mut scene = Scene::new();
+        let mut player = Player::new(&mut scene, settings);
+        player.look_delta = Vector2::new(0.0, 1000.0);
+        player.update(&mut scene, 0.1);
+        assert_eq!(player.pitch, 85.0);
+
+        let toml = toml::to_string(&PlayerSettings::default()).unwrap();
+        let restored: PlayerSettings = toml::from_str(&toml).unwrap();
+        assert_eq!(restored, PlayerSettings::default());
+        let partial: PlayerSettings = toml::from_str("invert_y = true").unwrap();
+        assert!(partial.invert_y);
+        assert_eq!(partial.mouse_sensitivity, 0.3);
+    }
+
+    impl VerticalMotion {
+        /// Moves over flat ground at `ground` height, returns new height.
+        fn step(&mut self, height: f32, ground: f32, dt: f32) -> f32 {
+            let target = height + self.integrate(dt);
+            let landed = target <= ground;
+            self.resolve(landed, landed, dt);
+            target.max(ground)
+        }
+    }
+
+    #[test]
+    fn jump_arc() {
+        let dt = 1.0 / 60.0;
+        let mut motion = VerticalMotion::default();
+        let mut height = motion.step(1.0, FLOOR_HEIGHT, dt);
+        for _ in 0..60 {
+            height = motion.step(height, FLOOR_HEIGHT, dt);
+        }
+        assert!(motion.is_grounded());
+        assert_eq!(height, FLOOR_HEIGHT);
+
+        motion.request_jump();
+        let mut apex = height;
+        let mut steps = 0;
+        loop {
+            height = motion.step(height, FLOOR_HEIGHT, dt);
+            apex = apex.max(height);
+            steps += 1;
+            if motion.is_grounded() {
+                break;
+            }
+            // Pressing jump again in the air must not double-jump.
+            if steps == 10 {
+                motion.request_jump();
+            }
+            assert!(steps < 120);
+        }
+        let expected = JUMP_SPEED * JUMP_SPEED / (2.0 * GRAVITY);
+        assert!((apex - FLOOR_HEIGHT - expected).abs() < JUMP_SPEED * dt);
+        assert_eq!(height, FLOOR_HEIGHT);
+        // Flight time is about 2 * v / g.
+        let flight = steps as f32 * dt;
+        assert!((flight - 2.0 * JUMP_SPEED / GRAVITY).abs() < 2.0 * dt);
+
+        // Stays on the ground afterwards.
+        for _ in 0..30 {
+            height = motion.step(height, FLOOR_HEIGHT, dt);
+        }
+        assert!(motion.is_grounded());
+        assert_eq!(height, FLOOR_HEIGHT);
+    }
+
+    #[test]
+    fn coyote_time_and_jump_buffer() {
+        let dt = 1.0 / 60.0;
+        let mut motion = VerticalMotion::default();
+        motion.step(0.0, 0.0, dt);
+        assert!(motion.is_grounded());
+
+        // Walked off a ledge, jump is still accepted for a moment.
+        let height = motion.step(0.0, -10.0, dt);
+        assert!(!motion.is_grounded());
+        motion.request_jump();
+        let after = motion.step(height, -10.0, dt);
+        assert!(after > height);
+
+        // Jump pressed right before landing fires on landing.
+        let mut motion = VerticalMotion::default();
+        let mut height = 0.005;
+        motion.request_jump();
+        height = motion.step(height, 0.0, dt);
+        assert!(motion.is_grounded());
+        height = motion.step(height, 0.0, dt);
+        assert!(height > 0.0);
+    }
+
+    #[allow(deprecated)]
+    pub fn key_event(key: VirtualKeyCode, pressed: bool) -> Event<'static, ()> {
+        use winit::event::{DeviceId, ElementState, KeyboardInput, ModifiersState};
+        use winit::window::WindowId;
+
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::KeyboardInput {
+                device_id: unsafe { DeviceId::dummy() },
+                input: KeyboardInput {
+                    scancode: 0,
+                    state: if pressed {
+                        ElementState::Pressed
+                    } else {
+                        ElementState::Released
+                    },
+                    virtual_keycode: Some(key),
+                    modifiers: ModifiersState::empty(),
+                },
+                is_synthetic: false,
+            },
+        }
+    }
+
+    #[test]
+    fn sprint_and_crouch() {
+        let dt = 1.0 / 60.0;
+        let mut scene = Scene::new();
+        add_floor(&mut scene);
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        let mut input = InputState::new();
+        scene.update(1.0, 0.0);
+
+        let mut step = |player: &mut Player, input: &mut InputState| {
+            player.process_input(input);
+            player.update(&mut scene, dt);
+            scene.update(1.0, dt);
+            input.clear_frame();
+        };
+
+        step(&mut player, &mut input);
+        assert_eq!(player.get_speed(), 10.0);
+        assert_eq!(player.get_eye_height(), EYE_HEIGHT);
+
+        input.process_event(&key_event(VirtualKeyCode::LShift, true));
+        step(&mut player, &mut input);
+        assert!((player.get_speed() - 18.0).abs() < 1e-5);
+
+        // Crouch wins over sprint, camera lowers along the easing curve.
+        input.process_event(&key_event(VirtualKeyCode::LControl, true));
+        let mut heights = Vec::new();
+        for _ in 0..12 {
+            step(&mut player, &mut input);
+            heights.push(player.get_eye_height());
+        }
+        assert!((player.get_speed() - 5.0).abs() < 1e-5);
+        assert!(heights.windows(2).all(|pair| pair[1] < pair[0]));
+        let middle = (EYE_HEIGHT + CROUCH_EYE_HEIGHT) * 0.5;
+        assert!((heights[5] - middle).abs() < 1e-4);
+        assert!((heights[11] - CROUCH_EYE_HEIGHT).abs() < 1e-4);
+
+        // Releasing both keys returns to baseline after the transition.
+        input.process_event(&key_event(VirtualKeyCode::LShift, false));
+        input.process_event(&key_event(VirtualKeyCode::LControl, false));
+        for _ in 0..12 {
+            step(&mut player, &mut input);
+        }
+        assert_eq!(player.get_speed(), 10.0);
+        assert!((player.get_eye_height() - EYE_HEIGHT).abs() < 1e-4);
+    }
+
+    #[test]
+    fn crouch_under_low_ceiling() {
+        let dt = 1.0 / 60.0;
+        let mut scene = Scene::new();
+        add_floor(&mut scene);
+        // Ceiling over the spawn point, between crouched and standing height.
+        let bottom = FLOOR_HEIGHT + (CROUCH_HEIGHT + PLAYER_HALF_EXTENTS.y * 2.0) * 0.5;
+        add_collider_cube(
+            &mut scene,
+            Vector3::new(0.0, bottom + 0.5, 20.0),
+            Vector3::new(4.0, 1.0, 4.0),
+        );
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        player.crouched = true;
+        player.crouch_amount = 1.0;
+        scene.update(1.0, 0.0);
+
+        for _ in 0..10 {
+            player.update(&mut scene, dt);
+            scene.update(1.0, dt);
+        }
+        assert!(player.crouched);
+        assert_eq!(player.get_eye_height(), CROUCH_EYE_HEIGHT);
+
+        // Walks out from under the ceiling and stands up.
+        player.move_input = Vector2::new(1.0, 0.0);
+        for _ in 0..60 {
+            player.update(&mut scene, dt);
+            scene.update(1.0, dt);
+        }
+        assert!(!player.crouched);
+    }
+
+    #[test]
+    fn rebind_player_keys() {
+        let mut scene = Scene::new();
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        let mut input = InputState::new();
+
+        // Up arrow belongs to look up, the two actions swap keys.
+        assert_eq!(
+            player.rebind(PlayerAction::MoveForward, VirtualKeyCode::Up),
+            Some(PlayerAction::LookUp)
+        );
+        assert_eq!(
+            player.bindings(PlayerAction::MoveForward),
+            &[InputBinding::Key(VirtualKeyCode::Up)]
+        );
+        assert_eq!(
+            player.bindings(PlayerAction::LookUp),
+            &[InputBinding::Key(VirtualKeyCode::W)]
+        );
+
+        input.process_event(&key_event(VirtualKeyCode::Up, true));
+        player.process_input(&input);
+        assert_eq!(player.move_input, Vector2::new(0.0, 1.0));
+        assert_eq!(player.look_axes, Vector2::zeros());
+
+        input.process_event(&key_event(VirtualKeyCode::Up, false));
+        input.process_event(&key_event(VirtualKeyCode::W, true));
+        player.process_input(&input);
+        assert_eq!(player.move_input, Vector2::zeros());
+        assert_eq!(player.look_axes, Vector2::new(0.0, 1.0));
+        input.process_event(&key_event(VirtualKeyCode::W, false));
+
+        // Rebinding a free key reports no conflict.
+        assert_eq!(player.rebind(PlayerAction::Jump, VirtualKeyCode::J), None);
+        input.process_event(&key_event(VirtualKeyCode::Space, true));
+        player.process_input(&input);
+        assert_eq!(player.vertical.jump_buffer_timer, 0.0);
+        input.process_event(&key_event(VirtualKeyCode::J, true));
+        player.process_input(&input);
+        assert!(player.vertical.jump_buffer_timer > 0.0);
+    }
+
+    #[test]
+    fn multiple_keys_per_player_action() {
+        let mut scene = Scene::new();
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        let mut input = InputState::new();
+
+        // Arrow keys are taken away from looking and work next to WASD.
+        assert_eq!(
+            player.bind(PlayerAction::MoveLeft, VirtualKeyCode::Left),
+            Some(PlayerAction::LookLeft)
+        );
+        assert!(player.bindings(PlayerAction::LookLeft).is_empty());
+        for key in [VirtualKeyCode::A, VirtualKeyCode::Left] {
+            input.process_event(&key_event(key, true));
+            player.process_input(&input);
+            assert_eq!(player.move_input, Vector2::new(1.0, 0.0));
+            assert_eq!(player.look_axes, Vector2::zeros());
+            input.process_event(&key_event(key, false));
+        }
+
+        // Rebind replaces only the primary key.
+        player.rebind(PlayerAction::MoveLeft, VirtualKeyCode::Q);
+        assert_eq!(
+            player.bindings(PlayerAction::MoveLeft),
+            &[
+                InputBinding::Key(VirtualKeyCode::Q),
+                InputBinding::Key(VirtualKeyCode::Left)
+            ]
+        );
+    }
+
+    #[test]
+    fn fly_mode() {
+        let dt = 1.0 / 60.0;
+        let mut scene = Scene::new();
+        add_floor(&mut scene);
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        let mut input = InputState::new();
+        scene.update(1.0, 0.0);
+        let start = scene
+            .borrow_node(&player.pivot)
+            .unwrap()
+            .get_local_position();
+
+        let position = |scene: &Scene, player: &Player| {
+            scene
+                .borrow_node(&player.pivot)
+                .unwrap()
+                .get_local_position()
+        };
+        let run = |scene: &mut Scene, player: &mut Player, steps: usize| {
+            for _ in 0..steps {
+                player.update(scene, dt);
+                scene.update(1.0, dt);
+            }
+        };
+
+        // Walk ignores pitch, player stays on the floor.
+        player.pitch = -45.0;
+        player.move_input = Vector2::new(0.0, 1.0);
+        run(&mut scene, &mut player, 10);
+        let walked = position(&scene, &player);
+        assert!((walked.y - start.y).abs() < 1e-4);
+
+        input.process_event(&key_event(VirtualKeyCode::V, true));
+        player.process_input(&input);
+        assert_eq!(player.get_mode(), PlayerMode::Fly);
+        input.clear_frame();
+        input.process_event(&key_event(VirtualKeyCode::V, false));
+
+        // Fly follows pitched look direction at fly speed.
+        player.move_input = Vector2::new(0.0, 1.0);
+        run(&mut scene, &mut player, 10);
+        let flown = position(&scene, &player) - walked;
+        let look = player.get_look_rotation() * Vector3::z();
+        assert!(look.y > 0.5);
+        assert!((flown - look.scale(FLY_SPEED * dt * 10.0)).norm() < 1e-3);
+
+        // Vertical movement on E and wheel speed multiplier.
+        player.move_input = Vector2::zeros();
+        input.process_event(&key_event(VirtualKeyCode::E, true));
+        player.process_input(&input);
+        assert_eq!(player.fly_input, 1.0);
+        input.process_event(&key_event(VirtualKeyCode::E, false));
+
+        // Toggling back restores grounded movement from rest.
+        player.vertical.velocity = 5.0;
+        input.clear_frame();
+        input.process_event(&key_event(VirtualKeyCode::V, true));
+        player.process_input(&input);
+        assert_eq!(player.get_mode(), PlayerMode::Walk);
+        assert_eq!(player.vertical.velocity, 0.0);
+        player.move_input = Vector2::zeros();
+        run(&mut scene, &mut player, 120);
+        assert!(player.vertical.is_grounded());
+        assert!((position(&scene, &player).y - start.y).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fly_speed_wheel() {
+        use winit::event::MouseScrollDelta;
+
+        let mut scene = Scene::new();
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        player.set_mode(PlayerMode::Fly);
+        let mut input = InputState::new();
+        for _ in 0..100 {
+            input.process_event(&wheel_event(MouseScrollDelta::LineDelta(0.0, 1.0)));
+        }
+        player.process_input(&input);
+        assert_eq!(player.fly_speed_multiplier, MAX_FLY_SPEED_MULTIPLIER);
+    }
+
+    #[test]
+    fn third_person_offset() {
+        let rotation = |yaw: f32, pitch: f32| {
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw.to_radians())
+                * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), pitch.to_radians())
+        };
+
+        let offset = orbit_offset(&rotation(0.0, 0.0), 4.0, 0.5);
+        assert!((offset - Vector3::new(0.0, 0.5, -4.0)).norm() < 1e-5);
+        let offset = orbit_offset(&rotation(90.0, 0.0), 4.0, 0.0);
+        assert!((offset - Vector3::new(-4.0, 0.0, 0.0)).norm() < 1e-5);
+        // Looking up moves the camera down behind the player.
+        let offset = orbit_offset(&rotation(0.0, -30.0), 4.0, 0.0);
+        assert!((offset.norm() - 4.0).abs() < 1e-5);
+        assert!((offset.y - -2.0).abs() < 1e-5);
+
+        // Wall between eye and camera.
+        let blocker = AxisAlignedBoundingBox::new(
+            Vector3::new(-5.0, -5.0, -3.0),
+            Vector3::new(5.0, 5.0, -2.0),
+        );
+        let clipped =
+            clip_camera_offset(Vector3::zeros(), Vector3::new(0.0, 0.0, -4.0), &[blocker]);
+        assert!((clipped - Vector3::new(0.0, 0.0, -2.0 + CAMERA_WALL_MARGIN)).norm() < 1e-5);
+        // Wall behind the camera does not matter.
+        let clipped =
+            clip_camera_offset(Vector3::zeros(), Vector3::new(0.0, 0.0, -1.0), &[blocker]);
+        assert_eq!(clipped, Vector3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn toggle_third_person() {
+        let dt = 1.0 / 60.0;
+        let mut scene = Scene::new();
+        add_floor(&mut scene);
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        let mut input = InputState::new();
+        scene.update(1.0, 0.0);
+
+        input.process_event(&key_event(VirtualKeyCode::F5, true));
+        player.process_input(&input);
+        let mut distances = Vec::new();
+        for _ in 0..15 {
+            player.update(&mut scene, dt);
+            scene.update(1.0, dt);
+            let camera = scene.borrow_node(&player.camera).unwrap();
+            distances
+                .push((camera.get_local_position() - Vector3::new(0.0, EYE_HEIGHT, 0.0)).norm());
+        }
+        assert!(distances.windows(2).all(|pair| pair[1] >= pair[0]));
+        let full = Vector2::new(4.0f32, 0.5).norm();
+        assert!(distances[0] < full * 0.1);
+        assert!((distances[14] - full).abs() < 1e-4);
+    }
+
+    pub fn wheel_event(delta: winit::event::MouseScrollDelta) -> Event<'static, ()> {
+        use winit::event::{DeviceId, TouchPhase};
+        use winit::window::WindowId;
+
+        #[allow(deprecated)]
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::MouseWheel {
+                device_id: unsafe { DeviceId::dummy() },
+                delta,
+                phase: TouchPhase::Moved,
+                modifiers: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn zoom_targets() {
+        let settings = PlayerSettings::default();
+        assert_eq!(settings.zoom_fov(45.0, 1.0), 40.0);
+        assert_eq!(settings.zoom_fov(45.0, -2.0), 55.0);
+        // Clamped rather than inverted past the limits.
+        assert_eq!(settings.zoom_fov(45.0, 100.0), settings.min_fov);
+        assert_eq!(settings.zoom_fov(45.0, -100.0), settings.max_fov);
+        assert_eq!(settings.zoom_distance(4.0, 1.0), 3.5);
+        assert_eq!(
+            settings.zoom_distance(4.0, 100.0),
+            settings.min_third_person_distance
+        );
+        assert_eq!(
+            settings.zoom_distance(4.0, -100.0),
+            settings.max_third_person_distance
+        );
+    }
+
+    #[test]
+    fn wheel_zoom() {
+        use winit::dpi::PhysicalPosition;
+        use winit::event::MouseScrollDelta;
+
+        let dt = 1.0 / 60.0;
+        let mut scene = Scene::new();
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        let mut input = InputState::new();
+
+        // Large pixel deltas of touchpads are scaled down to lines.
+        input.process_event(&wheel_event(MouseScrollDelta::PixelDelta(
+            PhysicalPosition::new(0.0, 40.0),
+        )));
+        player.process_input(&input);
+        assert_eq!(player.fov_target, 35.0);
+        input.clear_frame();
+
+        // Animated rather than instant.
+        player.update(&mut scene, dt);
+        assert!(player.fov < 45.0 && player.fov > 35.0);
+        for _ in 0..9 {
+            player.update(&mut scene, dt);
+        }
+        assert!((player.fov - 35.0).abs() < 0.5);
+        let camera = scene.borrow_node(&player.camera).unwrap();
+        if let NodeKind::Camera(camera) = camera.borrow_kind() {
+            assert_eq!(camera.get_fov(), player.fov);
+        }
+
+        // Third person adjusts distance instead.
+        player.view = CameraView::ThirdPerson;
+        input.process_event(&wheel_event(MouseScrollDelta::LineDelta(0.0, -2.0)));
+        player.process_input(&input);
+        assert_eq!(player.fov_target, 35.0);
+        assert_eq!(player.distance_target, 5.0);
+    }
+
+    #[test]
+    fn spawn_selection() {
+        let spawns: Vec<_> = [[0.0, 0.0, 0.0], [10.0, 0.0, 0.0], [0.0, 0.0, -10.0]]
+            .into_iter()
+            .map(|position| Matrix4::new_translation(&Vector3::from(position)))
+            .collect();
+        let near_second = Vector3::new(8.0, -60.0, 1.0);
+        assert_eq!(SpawnPolicy::First.select(&spawns, near_second, 7), Some(0));
+        assert_eq!(
+            SpawnPolicy::Nearest.select(&spawns, near_second, 7),
+            Some(1)
+        );
+        assert_eq!(
+            SpawnPolicy::Nearest.select(&spawns, Vector3::new(1.0, 0.0, -7.0), 7),
+            Some(2)
+        );
+        assert_eq!(SpawnPolicy::Random.select(&spawns, near_second, 7), Some(1));
+        assert_eq!(SpawnPolicy::Random.select(&spawns, near_second, 9), Some(0));
+        for policy in [
+            SpawnPolicy::First,
+            SpawnPolicy::Nearest,
+            SpawnPolicy::Random,
+        ] {
+            assert_eq!(policy.select(&[], near_second, 7), None);
+        }
+    }
+
+    #[test]
+    fn spawn_orientation_from_transform() {
+        let close = |(yaw, pitch): (f32, f32), expected: (f32, f32)| {
+            (yaw - expected.0).abs() < 1e-3 && (pitch - expected.1).abs() < 1e-3
+        };
+        let rotation = |yaw: f32, pitch: f32| {
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw.to_radians())
+                * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), pitch.to_radians())
+        };
+        for (yaw, pitch) in [(0.0, 0.0), (90.0, 0.0), (-135.0, 30.0), (45.0, -60.0)] {
+            let transform = Matrix4::new_translation(&Vector3::new(1.0, 2.0, 3.0))
+                * rotation(yaw, pitch).to_homogeneous()
+                * Matrix4::new_scaling(2.0);
+            let result = spawn_orientation(&transform);
+            assert!(
+                close(result, (yaw, pitch)),
+                "{:?} {:?}",
+                result,
+                (yaw, pitch)
+            );
+        }
+        // Looking straight down has no heading.
+        let down = rotation(70.0, 90.0).to_homogeneous();
+        assert!(close(spawn_orientation(&down), (0.0, 90.0)));
+
+        let mut scene = Scene::new();
+        let mut player = Player::new(&mut scene, PlayerSettings::default());
+        player.vertical.velocity = -30.0;
+        let transform = Matrix4::new_translation(&Vector3::new(4.0, 1.0, -2.0))
+            * rotation(90.0, 20.0).to_homogeneous();
+        player.spawn_at(&mut scene, &transform);
+        assert!(close((player.yaw, player.pitch), (90.0, 20.0)));
+        assert_eq!(player.vertical.velocity, 0.0);
+        assert!(
+            (player.get_feet_position(&scene).unwrap() - Vector3::new(4.0, 1.0, -2.0)).norm()
+                < 1e-5
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::mem::size_of;
+
+use glow::{HasContext, NativeBuffer, NativeVertexArray};
+use nalgebra::{Matrix4, Vector3};
+
+use crate::math::{aabb::AxisAlignedBoundingBox, color::Color};
+
+use super::{
+    deletion::{self, GpuObject},
+    renderer::{GpuProgram, GL},
+};
+
+/// Appends 12 edges of the box to a line list, two points per edge.
+pub fn push_aabb_lines(aabb: &AxisAlignedBoundingBox, lines: &mut Vec<Vector3<f32>>) {
+    let corners = aabb.corners();
+    for i in 0..8 {
+        // Edges connect corners that differ along exactly one axis.
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                lines.push(corners[i]);
+                lines.push(corners[i | bit]);
+            }
+        }
+    }
+}
+
+/// Collects world space lines during a frame and draws them on top of the
+/// scene with a single draw call.
+pub struct DebugRenderer {
+    shader: GpuProgram,
+    vbo: Option<NativeBuffer>,
+    vao: Option<NativeVertexArray>,
+    lines: Vec<Vector3<f32>>,
+}
+
+impl DebugRenderer {
+    pub fn new() -> Result<Self, String> {
+        let vertex_source = include_str!("./glsl/debug_vertex.glsl");
+        let fragment_source = include_str!("./glsl/debug_fragment.glsl");
+        Ok(Self {
+            shader: GpuProgram::from_source(vertex_source, fragment_source)?,
+            vbo: None,
+            vao: None,
+            lines: Vec::new(),
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn add_aabb(&mut self, aabb: &AxisAlignedBoundingBox) {
+        push_aabb_lines(aabb, &mut self.lines);
+    }
+
+    /// Draws collected lines, returns number of draw calls made.
+    pub fn draw(&mut self, view_projection: &Matrix4<f32>, color: Color) -> usize {
+        if self.lines.is_empty() {
+            return 0;
+        }
+        let u_view_projection = self.shader.get_uniform_location("viewProjection");
+        let u_color = self.shader.get_uniform_location("color");
+        unsafe {
+            let gl = GL.get().unwrap();
+            if self.vao.is_none() {
+                self.vbo = gl.create_buffer().ok();
+                self.vao = gl.create_vertex_array().ok();
+                gl.bind_vertex_array(self.vao);
+                gl.bind_buffer(glow::ARRAY_BUFFER, self.vbo);
+                gl.vertex_attrib_pointer_f32(
+                    0,
+                    3,
+                    glow::FLOAT,
+                    false,
+                    size_of::<Vector3<f32>>() as i32,
+                    0,
+                );
+                gl.enable_vertex_attrib_array(0);
+            }
+            gl.bind_vertex_array(self.vao);
+            gl.bind_buffer(glow::ARRAY_BUFFER, self.vbo);
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&self.lines),
+                glow::STREAM_DRAW,
+            );
+
+            gl.use_program(Some(self.shader.id));
+            gl.uniform_matrix_4_f32_slice(
+                u_view_projection.as_ref(),
+                false,
+                view_projection.as_slice(),
+            );
+            gl.uniform_4_f32(u_color.as_ref(), color.r, color.g, color.b, color.a);
+            gl.draw_arrays(glow::LINES, 0, self.lines.len() as i32);
+            gl.bind_vertex_array(None);
+        }
+        1
+    }
+}
+
+impl Drop for DebugRenderer {
+    fn drop(&mut self) {
+        if let Some(vbo) = self.vbo.take() {
+            deletion::schedule(GpuObject::Buffer(vbo));
+        }
+        if let Some(vao) = self.vao.take() {
+            deletion::schedule(GpuObject::VertexArray(vao));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::push_aabb_lines;
+    use crate::math::aabb::AxisAlignedBoundingBox;
+
+    #[test]
+    fn aabb_lines_are_box_edges() {
+        let aabb = AxisAlignedBoundingBox::new(Vector3::zeros(), Vector3::new(1.0, 2.0, 3.0));
+        let mut lines = Vec::new();
+        push_aabb_lines(&aabb, &mut lines);
+        assert_eq!(lines.len(), 24);
+        let lengths: Vec<f32> = lines.chunks(2).map(|l| (l[1] - l[0]).norm()).collect();
+        for length in [1.0, 2.0, 3.0] {
+            assert_eq!(lengths.iter().filter(|l| **l == length).count(), 4);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::Mutex;
+
+use glow::{HasContext, NativeBuffer, NativeProgram, NativeTexture, NativeVertexArray};
+
+/// GPU object waiting for deletion.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GpuObject {
+    Buffer(NativeBuffer),
+    VertexArray(NativeVertexArray),
+    Texture(NativeTexture),
+    Program(NativeProgram),
+}
+
+impl GpuObject {
+    /// # Safety
+    ///
+    /// GL context owning the object must be current.
+    pub unsafe fn delete(self, gl: &glow::Context) {
+        match self {
+            GpuObject::Buffer(buffer) => gl.delete_buffer(buffer),
+            GpuObject::VertexArray(vao) => gl.delete_vertex_array(vao),
+            GpuObject::Texture(texture) => gl.delete_texture(texture),
+            GpuObject::Program(program) => gl.delete_program(program),
+        }
+    }
+}
+
+/// GPU objects are not deleted in `Drop` impls, because they may run when
+/// there is no current context. Instead they are queued here and deleted by
+/// the renderer. Once the queue is closed (context destroyed) objects are
+/// dropped without deletion.
+#[derive(Debug)]
+pub struct DeletionQueue {
+    objects: Vec<GpuObject>,
+    closed: bool,
+}
+
+impl Default for DeletionQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeletionQueue {
+    pub const fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            closed: false,
+        }
+    }
+
+    pub fn push(&mut self, object: GpuObject) {
+        if !self.closed {
+            self.objects.push(object);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Passes every queued object to `delete`, returns amount of deleted objects.
+    pub fn drain(&mut self, mut delete: impl FnMut(GpuObject)) -> usize {
+        let count = self.objects.len();
+        for object in self.objects.drain(..) {
+            delete(object);
+        }
+        count
+    }
+
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.objects.clear();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+static QUEUE: Mutex<DeletionQueue> = Mutex::new(DeletionQueue::new());
+
+fn with_queue<R>(func: impl FnOnce(&mut DeletionQueue) -> R) -> R {
+    let mut queue = QUEUE.lock().unwrap_or_else(|err| err.into_inner());
+    func(&mut queue)
+}
+
+/// Schedules object for deletion on the next `drain`.
+pub fn schedule(object: GpuObject) {
+    with_queue(|queue| queue.push(object));
+}
+
+/// Deletes all scheduled objects.
+///
+/// # Safety
+///
+/// GL context owning the objects must be current.
+pub unsafe fn drain(gl: &glow::Context) -> usize {
+    with_queue(|queue| queue.drain(|object| object.delete(gl)))
+}
+
+/// Closes the queue, called right before GL context is destroyed.
+pub fn close() {
+    with_queue(|queue| queue.close());
+}
+
+pub fn pending() -> usize {
+    with_queue(|queue| queue.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use glow::NativeBuffer;
+
+    use super::{DeletionQueue, GpuObject};
+
+    fn buffer(id: u32) -> GpuObject {
+        GpuObject::Buffer(NativeBuffer(NonZeroU32::new(id).unwrap()))
+    }
+
+    #[test]
+    fn drains_and_closes() {
+        let mut queue = DeletionQueue::new();
+        queue.push(buffer(1));
+        queue.push(buffer(2));
+        let mut deleted = Vec::new();
+        assert_eq!(queue.drain(|object| deleted.push(object)), 2);
+        assert_eq!(deleted, vec![buffer(1), buffer(2)]);
+        assert!(queue.is_empty());
+
+        queue.push(buffer(3));
+        queue.close();
+        assert!(queue.is_empty());
+        // Objects dropped after the context is gone are ignored.
+        queue.push(buffer(4));
+        assert!(queue.is_empty());
+        assert_eq!(queue.drain(|_| panic!("nothing to delete")), 0);
+        queue.close();
+        assert!(queue.is_closed());
+    }
+}
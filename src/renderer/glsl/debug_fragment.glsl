@@ -0,0 +1,6 @@
+#version 460 core
+uniform vec4 color;
+out vec4 FragColor;
+void main() {
+    FragColor = color;
+}
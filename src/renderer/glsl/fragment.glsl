@@ -1,7 +1,69 @@
 #version 460 core
+
+// Same as MAX_LIGHTS of the renderer.
+#define MAX_LIGHTS 8
+#define LIGHT_POINT 0
+#define LIGHT_DIRECTIONAL 1
+#define LIGHT_SPOT 2
+
 uniform sampler2D diffuseTexture;
-out vec4 FragColor;
+uniform vec4 tint;
+// Texture is sampled only for textured surfaces.
+uniform bool textured;
+// Strength and exponent of Blinn-Phong highlights.
+uniform float specular;
+uniform float shininess;
+uniform vec3 eyePosition;
+uniform vec3 ambient;
+uniform int lightCount;
+uniform int lightKinds[MAX_LIGHTS];
+uniform vec3 lightDirections[MAX_LIGHTS];
+uniform vec3 lightPositions[MAX_LIGHTS];
+uniform vec3 lightColors[MAX_LIGHTS];
+// Radius of point lights and distance of spots.
+uniform float lightRadii[MAX_LIGHTS];
+// Cosines of inner and outer cone angles of spots.
+uniform vec2 lightCones[MAX_LIGHTS];
+
 in vec2 texCoord;
+in vec3 worldPosition;
+in vec3 worldNormal;
+in vec4 color;
+out vec4 FragColor;
+
 void main() {
-    FragColor = texture(diffuseTexture, texCoord);
-}
\ No newline at end of file
+    vec3 normal = normalize(worldNormal);
+    vec3 toEye = normalize(eyePosition - worldPosition);
+    vec3 lighting = ambient;
+    vec3 highlights = vec3(0.0);
+    for (int i = 0; i < lightCount; ++i) {
+        vec3 direction;
+        float attenuation;
+        if (lightKinds[i] == LIGHT_DIRECTIONAL) {
+            direction = -lightDirections[i];
+            attenuation = 1.0;
+        } else {
+            vec3 toLight = lightPositions[i] - worldPosition;
+            float distance = length(toLight);
+            direction = toLight / max(distance, 0.0001);
+            attenuation = clamp(1.0 - distance / lightRadii[i], 0.0, 1.0);
+            attenuation *= attenuation;
+            if (lightKinds[i] == LIGHT_SPOT) {
+                float cosAngle = dot(-direction, lightDirections[i]);
+                vec2 cone = lightCones[i];
+                float edge = clamp((cosAngle - cone.y) / max(cone.x - cone.y, 0.0001), 0.0, 1.0);
+                attenuation *= edge * edge * (3.0 - 2.0 * edge);
+            }
+        }
+        float lambert = max(dot(normal, direction), 0.0);
+        lighting += lightColors[i] * lambert * attenuation;
+        if (specular > 0.0 && lambert > 0.0) {
+            vec3 halfway = normalize(direction + toEye);
+            float highlight = pow(max(dot(normal, halfway), 0.0), shininess);
+            highlights += lightColors[i] * highlight * specular * attenuation;
+        }
+    }
+    vec4 texel = textured ? texture(diffuseTexture, texCoord) : vec4(1.0);
+    vec4 albedo = texel * tint * color;
+    FragColor = vec4(albedo.rgb * lighting + highlights, albedo.a);
+}
@@ -2,12 +2,23 @@
 
 layout(location = 0) in vec3 vertexPosition;
 layout(location = 1) in vec2 vertexTexCoord;
+layout(location = 2) in vec3 vertexNormal;
+// White for surfaces without colors.
+layout(location = 4) in vec4 vertexColor;
 
 uniform mat4 worldViewProjection;
+uniform mat4 world;
+uniform mat3 normalMatrix;
 
 out vec2 texCoord;
+out vec3 worldPosition;
+out vec3 worldNormal;
+out vec4 color;
 
 void main() {
     texCoord = vertexTexCoord;
+    worldPosition = (world * vec4(vertexPosition, 1.0)).xyz;
+    worldNormal = normalMatrix * vertexNormal;
+    color = vertexColor;
     gl_Position = worldViewProjection * vec4(vertexPosition, 1.0);
-}
\ No newline at end of file
+}
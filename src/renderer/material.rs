@@ -0,0 +1,110 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    math::color::Color,
+    resource::{Resource, ResourceKind},
+};
+
+/// Shininess of new materials, moderately sharp highlights.
+pub const DEFAULT_SHININESS: f32 = 32.0;
+
+/// Surface appearance. Diffuse color multiplies the texture and the tint of
+/// the mesh, specular highlights are white light scaled by `specular`.
+#[derive(Debug, Clone)]
+pub struct Material {
+    diffuse: Color,
+    diffuse_texture: Option<Rc<RefCell<Resource>>>,
+    specular: f32,
+    shininess: f32,
+    /// Back faces are culled unless the material is two-sided.
+    two_sided: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            diffuse: Color::WHITE,
+            diffuse_texture: None,
+            specular: 0.0,
+            shininess: DEFAULT_SHININESS,
+            two_sided: false,
+        }
+    }
+}
+
+impl Material {
+    pub fn set_diffuse(&mut self, diffuse: Color) {
+        self.diffuse = diffuse;
+    }
+
+    pub fn get_diffuse(&self) -> Color {
+        self.diffuse
+    }
+
+    /// Resources other than textures clear the texture, surfaces without one
+    /// are drawn with plain diffuse color.
+    pub fn set_diffuse_texture(&mut self, texture: Option<Rc<RefCell<Resource>>>) {
+        self.diffuse_texture = texture
+            .filter(|texture| matches!(texture.borrow().borrow_kind(), ResourceKind::Texture(_)));
+    }
+
+    pub fn get_diffuse_texture(&self) -> Option<&Rc<RefCell<Resource>>> {
+        self.diffuse_texture.as_ref()
+    }
+
+    /// Negative strengths are rejected, zero turns highlights off.
+    pub fn set_specular(&mut self, specular: f32) {
+        if specular >= 0.0 && specular.is_finite() {
+            self.specular = specular;
+        } else {
+            println!("Invalid specular strength {}", specular);
+        }
+    }
+
+    pub fn get_specular(&self) -> f32 {
+        self.specular
+    }
+
+    /// Exponent of highlights, larger ones are smaller and sharper. Values
+    /// below one are rejected.
+    pub fn set_shininess(&mut self, shininess: f32) {
+        if shininess >= 1.0 && shininess.is_finite() {
+            self.shininess = shininess;
+        } else {
+            println!("Invalid shininess {}", shininess);
+        }
+    }
+
+    pub fn get_shininess(&self) -> f32 {
+        self.shininess
+    }
+
+    /// Two-sided surfaces are seen from behind as well, such as foliage
+    /// cards or planes without thickness.
+    pub fn set_two_sided(&mut self, two_sided: bool) {
+        self.two_sided = two_sided;
+    }
+
+    pub fn is_two_sided(&self) -> bool {
+        self.two_sided
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Material, DEFAULT_SHININESS};
+
+    #[test]
+    fn validation() {
+        let mut material = Material::default();
+        material.set_specular(0.5);
+        material.set_specular(-1.0);
+        material.set_specular(f32::NAN);
+        assert_eq!(material.get_specular(), 0.5);
+
+        material.set_shininess(0.5);
+        assert_eq!(material.get_shininess(), DEFAULT_SHININESS);
+        material.set_shininess(64.0);
+        assert_eq!(material.get_shininess(), 64.0);
+    }
+}
@@ -1,2 +1,6 @@
+pub mod debug;
+pub mod deletion;
+pub mod material;
+#[allow(clippy::module_inception)]
 pub mod renderer;
 pub mod surface;
@@ -1,40 +1,53 @@
-use std::{cell::RefCell, num::NonZeroU32, rc::Rc};
+use std::{
+    cell::RefCell,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use glow::{
-    Context, HasContext, NativeProgram, NativeShader, NativeUniformLocation, UniformLocation,
+    Context, HasContext, NativeProgram, NativeShader, NativeTexture, NativeUniformLocation,
 };
 use glutin::{
-    config::ConfigTemplateBuilder,
-    context::{ContextApi, ContextAttributesBuilder, GlContext, PossiblyCurrentContext, Version},
+    config::{Config, ConfigTemplateBuilder},
+    context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext, Version},
     display::GetGlDisplay,
-    prelude::{GlConfig, GlDisplay, NotCurrentGlContextSurfaceAccessor},
+    prelude::{
+        GlConfig, GlDisplay, NotCurrentGlContextSurfaceAccessor,
+        PossiblyCurrentContextGlSurfaceAccessor,
+    },
     surface::{GlSurface, Surface as glutinSurface, SwapInterval, WindowSurface},
 };
 use glutin_winit::{DisplayBuilder, GlWindow};
-use nalgebra::Vector2;
+use nalgebra::{Matrix3, Matrix4, Vector2, Vector3};
 use once_cell::sync::OnceCell;
 use raw_window_handle::HasRawWindowHandle;
+use serde::{Deserialize, Serialize};
 use winit::{
     dpi::LogicalSize,
     event_loop::EventLoop,
-    window::{Window, WindowBuilder},
+    window::{Fullscreen, Window, WindowBuilder},
 };
 
+use super::{
+    debug::DebugRenderer,
+    deletion::{self, GpuObject},
+    surface::{self, Surface},
+};
 use crate::{
+    math::{color::Color, frustum::Frustum},
     resource::{Resource, ResourceKind},
     scene::{
-        node::{Node, NodeKind},
+        node::{LightKind, Mesh, Node, NodeKind},
         Scene,
     },
     utils::pool::Handle,
 };
 
-use super::surface::Surface;
-
 pub static GL: OnceCell<Context> = OnceCell::new();
 
 pub struct GpuProgram {
-    id: NativeProgram,
+    pub(crate) id: NativeProgram,
 }
 impl GpuProgram {
     pub fn create_shader(shader_type: u32, shader_source: &str) -> Result<NativeShader, String> {
@@ -74,44 +87,358 @@ impl GpuProgram {
 
 impl Drop for GpuProgram {
     fn drop(&mut self) {
-        unsafe {
-            let gl = GL.get().unwrap();
-            gl.delete_program(self.id);
+        deletion::schedule(GpuObject::Program(self.id));
+    }
+}
+
+/// How polygons are rasterized.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PolygonMode {
+    #[default]
+    Fill,
+    Wireframe,
+    Points,
+}
+
+impl PolygonMode {
+    /// Next mode in Fill -> Wireframe -> Points -> Fill order.
+    pub fn next(self) -> Self {
+        match self {
+            PolygonMode::Fill => PolygonMode::Wireframe,
+            PolygonMode::Wireframe => PolygonMode::Points,
+            PolygonMode::Points => PolygonMode::Fill,
+        }
+    }
+
+    fn gl_mode(self) -> u32 {
+        match self {
+            PolygonMode::Fill => glow::FILL,
+            PolygonMode::Wireframe => glow::LINE,
+            PolygonMode::Points => glow::POINT,
+        }
+    }
+}
+
+/// Window and swap chain options applied when the renderer is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    /// Client area size in logical pixels.
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// Samples per pixel, `None` picks the config with most samples.
+    pub msaa: Option<u8>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            fullscreen: false,
+            vsync: true,
+            msaa: None,
+        }
+    }
+}
+
+/// Color of the window behind all viewports.
+const BACKGROUND_COLOR: Color = Color::opaque(0.0, 0.63, 0.91);
+
+/// Color of bounding boxes drawn by `set_draw_bounds`.
+const BOUNDS_COLOR: Color = Color::opaque(1.0, 1.0, 0.0);
+
+/// Lights shading a scene at once, further lights in scene order are
+/// ignored. Must match `MAX_LIGHTS` of the fragment shader.
+pub const MAX_LIGHTS: usize = 8;
+
+/// Counters collected while rendering a frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RenderStats {
+    pub draw_calls: usize,
+    pub triangles: usize,
+    pub meshes: usize,
+    pub lights: usize,
+    pub cameras: usize,
+    /// Meshes drawn and skipped by frustum culling, summed over cameras.
+    pub drawn: usize,
+    pub culled: usize,
+    /// Surfaces that got GPU buffers created, shared ones count once.
+    pub surfaces_allocated: usize,
+    /// Face culling switches for two-sided surfaces.
+    pub cull_toggles: usize,
+}
+
+/// Face culling as last set on the GL context, on at the start of a frame.
+/// Switches are only made when a surface needs the other state.
+#[derive(Debug)]
+struct CullState {
+    enabled: bool,
+    toggles: usize,
+}
+
+impl Default for CullState {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            toggles: 0,
+        }
+    }
+}
+
+impl CullState {
+    /// Returns culling state to set before drawing a surface, None if it is
+    /// set already.
+    fn set_two_sided(&mut self, two_sided: bool) -> Option<bool> {
+        let enabled = !two_sided;
+        if self.enabled == enabled {
+            return None;
         }
+        self.enabled = enabled;
+        self.toggles += 1;
+        Some(enabled)
     }
 }
 
+/// Owns the window and its GL context. Presentation is done by the engine, so
+/// glutin types stay private:
+///
+/// ```compile_fail
+/// fn present(renderer: &balala::renderer::renderer::Renderer) {
+///     let _ = &renderer.gl_surface;
+/// }
+/// ```
 pub struct Renderer {
     pub context: Window,
-    pub gl_surface: glutinSurface<WindowSurface>,
-    pub gl_context: PossiblyCurrentContext,
+    gl_surface: glutinSurface<WindowSurface>,
+    gl_context: PossiblyCurrentContext,
+    gl_config: Config,
     flat_shader: GpuProgram,
+    lists: RenderLists,
+    debug: DebugRenderer,
+    /// 1x1 white texture bound for surfaces without one, so their tint shows.
+    white_texture: Option<NativeTexture>,
+    vsync: bool,
+    polygon_mode: PolygonMode,
+    draw_bounds: bool,
+    culling_frozen: bool,
+    cull: CullState,
+    /// Frustums of cameras in order they were rendered in the last frame
+    /// before culling was frozen.
+    frozen_frustums: Vec<Frustum>,
+    /// Path to save next rendered frame to.
+    screenshot: Option<PathBuf>,
+    stats: RenderStats,
+}
+
+/// Nodes of a scene taking part in rendering, collected every frame.
+#[derive(Default)]
+struct RenderLists {
     cameras: Vec<Handle<Node>>,
     lights: Vec<Handle<Node>>,
     meshes: Vec<Handle<Node>>,
+    /// Meshes drawn by the current camera.
+    draw: Vec<Handle<Node>>,
+}
+
+/// Light as uploaded to the flat shader.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct LightUniform {
+    kind: LightKind,
+    position: Vector3<f32>,
+    /// Normalized look vector of the light node.
+    direction: Vector3<f32>,
+    /// Color multiplied by intensity.
+    color: Vector3<f32>,
+    /// Radius of point lights and distance of spots.
+    radius: f32,
+    /// Cosines of inner and outer cone angles of spots.
+    cone: Vector2<f32>,
+}
+
+impl LightUniform {
+    /// Kind constant of the fragment shader.
+    fn shader_kind(&self) -> i32 {
+        match self.kind {
+            LightKind::Point => 0,
+            LightKind::Directional => 1,
+            LightKind::Spot { .. } => 2,
+        }
+    }
+}
+
+/// Material of a surface as uploaded to the flat shader.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct MaterialUniform {
+    /// Diffuse color multiplied by the tint of the mesh.
+    diffuse: Color,
+    specular: f32,
+    shininess: f32,
+    /// False for surfaces without texture, the shader skips sampling.
+    textured: bool,
+}
+
+impl MaterialUniform {
+    fn new(mesh: &Mesh, surface: &Surface) -> Self {
+        let material = surface.get_material();
+        Self {
+            diffuse: mesh.get_tint() * material.get_diffuse(),
+            specular: material.get_specular(),
+            shininess: material.get_shininess(),
+            textured: material.get_diffuse_texture().is_some(),
+        }
+    }
+}
+
+impl RenderLists {
+    /// Hidden nodes are skipped along with their whole subtree, visibility
+    /// is the one of the last scene update.
+    fn collect(&mut self, scene: &Scene) {
+        self.meshes.clear();
+        self.lights.clear();
+        self.cameras.clear();
+        for (node_handle, node) in scene.iter() {
+            if !node.is_globally_visible() {
+                continue;
+            }
+            match node.borrow_kind() {
+                NodeKind::Mesh(_) => self.meshes.push(node_handle),
+                NodeKind::Light(_) => self.lights.push(node_handle),
+                NodeKind::Camera(camera) if camera.is_enabled() => self.cameras.push(node_handle),
+                _ => (),
+            }
+        }
+        if scene.is_single_camera() {
+            let active = scene.active_camera();
+            self.cameras
+                .retain(|camera| active.as_ref() == Some(camera));
+        }
+        // Stable, cameras of the same order keep scene order.
+        self.cameras
+            .sort_by_key(|camera| match scene.borrow_node(camera) {
+                Some(node) => match node.borrow_kind() {
+                    NodeKind::Camera(camera) => camera.get_render_order(),
+                    _ => 0,
+                },
+                None => 0,
+            });
+    }
+
+    /// Fills `draw` with meshes on layers of `cull_mask` inside the frustum,
+    /// returns number of meshes culled by the frustum. Meshes without bounds
+    /// are never culled.
+    fn collect_camera_meshes(
+        &mut self,
+        scene: &Scene,
+        cull_mask: u32,
+        frustum: &Frustum,
+        alpha: f32,
+    ) -> usize {
+        self.draw.clear();
+        let mut culled = 0;
+        for mesh_handle in self.meshes.iter() {
+            let Some(node) = scene.borrow_node(mesh_handle) else {
+                continue;
+            };
+            if node.get_layers() & cull_mask == 0 {
+                continue;
+            }
+            if let NodeKind::Mesh(mesh) = node.borrow_kind() {
+                if let Some(local) = mesh.bounding_box() {
+                    let world = node.get_interpolated_global_transform(alpha);
+                    if !frustum.is_aabb_visible(&local.transform(&world)) {
+                        culled += 1;
+                        continue;
+                    }
+                }
+            }
+            self.draw.push(mesh_handle.clone());
+        }
+        culled
+    }
+
+    /// Ambient light of the scene as uploaded, alpha is dropped.
+    fn ambient_uniform(scene: &Scene) -> Vector3<f32> {
+        scene.get_ambient_color().rgb()
+    }
 
-    /// Scene graph traversal stack
-    traversal_stack: Vec<Handle<Node>>,
+    /// Lights to upload, at most `MAX_LIGHTS` of them. Lights not reaching
+    /// into the frustum are skipped, so they don't take slots of visible ones.
+    fn light_uniforms(&self, scene: &Scene, alpha: f32, frustum: &Frustum) -> Vec<LightUniform> {
+        self.lights
+            .iter()
+            .filter_map(|handle| {
+                let node = scene.borrow_node(handle)?;
+                let NodeKind::Light(light) = node.borrow_kind() else {
+                    return None;
+                };
+                let transform = node.get_interpolated_global_transform(alpha);
+                let position = transform.fixed_view::<3, 1>(0, 3).into_owned();
+                if !frustum.is_sphere_visible(&light.bounding_sphere(position)) {
+                    return None;
+                }
+                let (radius, cone) = match light.get_kind() {
+                    LightKind::Spot {
+                        inner_angle,
+                        outer_angle,
+                        distance,
+                    } => (
+                        distance,
+                        Vector2::new(
+                            inner_angle.to_radians().cos(),
+                            outer_angle.to_radians().cos(),
+                        ),
+                    ),
+                    _ => (light.get_radius(), Vector2::zeros()),
+                };
+                Some(LightUniform {
+                    kind: light.get_kind(),
+                    position,
+                    direction: transform
+                        .fixed_view::<3, 1>(0, 2)
+                        .try_normalize(f32::EPSILON)
+                        .unwrap_or_else(Vector3::z),
+                    color: light.get_color().rgb() * light.get_intensity(),
+                    radius,
+                    cone,
+                })
+            })
+            .take(MAX_LIGHTS)
+            .collect()
+    }
 }
 
 impl Renderer {
     pub fn new(el: &EventLoop<()>) -> Renderer {
+        Self::with_settings(el, &WindowSettings::default())
+    }
+
+    pub fn with_settings(el: &EventLoop<()>, settings: &WindowSettings) -> Renderer {
         //构建窗口
         let window_builder = WindowBuilder::new()
             .with_title("Balala")
-            .with_inner_size(LogicalSize::new(800., 600.))
+            .with_inner_size(LogicalSize::new(settings.width, settings.height))
+            .with_fullscreen(settings.fullscreen.then_some(Fullscreen::Borderless(None)))
             .with_resizable(false);
 
         //构建opnegl context
-        let template = ConfigTemplateBuilder::default();
+        let mut template = ConfigTemplateBuilder::default();
+        if let Some(samples) = settings.msaa.filter(|samples| samples.is_power_of_two()) {
+            template = template.with_multisampling(samples);
+        }
+        let prefer_samples = settings.msaa.is_none();
         let display_builder = DisplayBuilder::new().with_window_builder(Some(window_builder));
-        let (mut window, gl_config) = display_builder
+        let (window, gl_config) = display_builder
             .build(el, template, |configs| {
                 configs
                     .reduce(|accum, config| {
                         let transparency_check = config.supports_transparency().unwrap_or(false)
                             && !accum.supports_transparency().unwrap_or(false);
-                        if transparency_check || config.num_samples() > accum.num_samples() {
+                        let more_samples =
+                            prefer_samples && config.num_samples() > accum.num_samples();
+                        if transparency_check || more_samples {
                             config
                         } else {
                             accum
@@ -143,9 +470,7 @@ impl Renderer {
         };
 
         let gl_context = not_current_context.make_current(&gl_surface).unwrap();
-        gl_surface
-            .set_swap_interval(&gl_context, SwapInterval::Wait(NonZeroU32::new(1).unwrap()))
-            .expect("设置vsync失败");
+        Self::apply_vsync(&gl_surface, &gl_context, settings.vsync);
 
         let context = unsafe {
             glow::Context::from_loader_function_cstr(|s| {
@@ -158,97 +483,298 @@ impl Renderer {
 
         println!("opengl版本：{:?}", context.version());
         GL.set(context).unwrap();
+        let white_texture = Self::create_white_texture();
         let vertex_source = include_str!("./glsl/vertex.glsl");
         let fragment_source = include_str!("./glsl/fragment.glsl");
 
         Renderer {
             context: window,
-            flat_shader: GpuProgram::from_source(&vertex_source, &fragment_source).unwrap(),
-            traversal_stack: Vec::new(),
-            cameras: Vec::new(),
-            lights: Vec::new(),
-            meshes: Vec::new(),
+            flat_shader: GpuProgram::from_source(vertex_source, fragment_source).unwrap(),
+            lists: RenderLists::default(),
+            debug: DebugRenderer::new().unwrap(),
+            white_texture,
+            vsync: settings.vsync,
+            polygon_mode: PolygonMode::Fill,
+            draw_bounds: false,
+            culling_frozen: false,
+            cull: CullState::default(),
+            frozen_frustums: Vec::new(),
+            screenshot: None,
+            stats: RenderStats::default(),
             gl_surface,
             gl_context,
+            gl_config,
         }
     }
 
-    fn draw_surface(&mut self, surf: &Surface) {}
+    fn create_white_texture() -> Option<NativeTexture> {
+        let gl = GL.get().unwrap();
+        unsafe {
+            let texture = gl.create_texture().ok()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA as i32,
+                1,
+                1,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                Some(&[255; 4]),
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            Some(texture)
+        }
+    }
+
+    fn apply_vsync(
+        gl_surface: &glutinSurface<WindowSurface>,
+        gl_context: &PossiblyCurrentContext,
+        enabled: bool,
+    ) -> bool {
+        let interval = if enabled {
+            SwapInterval::Wait(NonZeroU32::new(1).unwrap())
+        } else {
+            SwapInterval::DontWait
+        };
+        if let Err(err) = gl_surface.set_swap_interval(gl_context, interval) {
+            println!("设置vsync失败: {}", err);
+            return false;
+        }
+        true
+    }
+
+    /// Enables or disables vsync. State is kept unchanged if the platform
+    /// refuses the new swap interval.
+    pub fn set_vsync(&mut self, enabled: bool) {
+        if Self::apply_vsync(&self.gl_surface, &self.gl_context, enabled) {
+            self.vsync = enabled;
+        }
+    }
+
+    pub fn is_vsync_enabled(&self) -> bool {
+        self.vsync
+    }
+
+    /// Applied from the next frame.
+    pub fn set_polygon_mode(&mut self, mode: PolygonMode) {
+        self.polygon_mode = mode;
+    }
+
+    pub fn get_polygon_mode(&self) -> PolygonMode {
+        self.polygon_mode
+    }
+
+    /// Draws world bounding boxes of meshes on top of the scene.
+    pub fn set_draw_bounds(&mut self, enabled: bool) {
+        self.draw_bounds = enabled;
+    }
+
+    pub fn is_draw_bounds_enabled(&self) -> bool {
+        self.draw_bounds
+    }
+
+    /// Keeps culling from the camera pose at the moment of freezing, so
+    /// culled objects can be inspected from elsewhere.
+    pub fn set_culling_frozen(&mut self, frozen: bool) {
+        self.culling_frozen = frozen;
+    }
+
+    pub fn is_culling_frozen(&self) -> bool {
+        self.culling_frozen
+    }
+
+    /// Switches window between borderless fullscreen on current monitor and
+    /// windowed mode.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.context
+            .set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.context.fullscreen().is_some()
+    }
+
+    /// Saves the next rendered frame as an image, format is chosen by file
+    /// extension.
+    pub fn request_screenshot(&mut self, path: &Path) {
+        self.screenshot = Some(path.to_path_buf());
+    }
 
-    pub fn upload_resources(&mut self, resources: &Vec<Rc<RefCell<Resource>>>) {
+    fn save_screenshot(&self, path: &Path) -> Result<(), image::ImageError> {
+        let size = self.context.inner_size();
+        let row = size.width as usize * 4;
+        let mut pixels = vec![0u8; row * size.height as usize];
+        unsafe {
+            let gl = GL.get().unwrap();
+            gl.viewport(0, 0, size.width as i32, size.height as i32);
+            gl.read_pixels(
+                0,
+                0,
+                size.width as i32,
+                size.height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+        }
+        // GL rows go bottom to top.
+        let flipped: Vec<u8> = pixels.chunks_exact(row).rev().flatten().copied().collect();
+        image::RgbaImage::from_raw(size.width, size.height, flipped)
+            .expect("buffer matches image size")
+            .save(path)
+    }
+
+    /// Returns counters of the last rendered frame.
+    pub fn get_stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Shows rendered frame on screen and requests next redraw. If swap fails
+    /// (surface lost), tries to recreate the window surface.
+    pub fn present(&mut self) {
+        if let Err(err) = self.gl_surface.swap_buffers(&self.gl_context) {
+            println!("Failed to swap buffers: {}", err);
+            self.recreate_surface();
+        }
+        self.context.request_redraw();
+    }
+
+    /// Deletes GPU objects while the context is still current and only then
+    /// releases the surface and context. Objects dropped later are not
+    /// deleted (the context is gone), their `Drop` impls do nothing.
+    pub fn shutdown(self) {
+        let Renderer {
+            flat_shader,
+            debug,
+            gl_surface,
+            gl_context,
+            ..
+        } = self;
+        drop(flat_shader);
+        drop(debug);
+        if let Some(gl) = GL.get() {
+            let deleted = unsafe { deletion::drain(gl) };
+            println!("Deleted {} GPU objects on shutdown", deleted);
+        }
+        deletion::close();
+        drop(gl_surface);
+        drop(gl_context);
+    }
+
+    /// Resizes window surface, zero sizes (minimized window) are ignored.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if let (Some(width), Some(height)) = (NonZeroU32::new(width), NonZeroU32::new(height)) {
+            self.gl_surface.resize(&self.gl_context, width, height);
+        }
+    }
+
+    fn recreate_surface(&mut self) {
+        let attrs = self.context.build_surface_attributes(Default::default());
+        let gl_surface = match unsafe {
+            self.gl_config
+                .display()
+                .create_window_surface(&self.gl_config, &attrs)
+        } {
+            Ok(gl_surface) => gl_surface,
+            Err(err) => {
+                println!("Failed to recreate window surface: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = self.gl_context.make_current(&gl_surface) {
+            println!("Failed to make recreated surface current: {}", err);
+            return;
+        }
+        Self::apply_vsync(&gl_surface, &self.gl_context, self.vsync);
+        self.gl_surface = gl_surface;
+    }
+
+    pub fn upload_resources(&mut self, resources: &[Rc<RefCell<Resource>>]) {
         unsafe {
             let gl = GL.get().unwrap();
             for resource in resources.iter() {
                 if let ResourceKind::Texture(texture) = resource.borrow_mut().borrow_kind_mut() {
                     if texture.need_upload {
-                        if texture.need_upload {
-                            if texture.gpu_tex == None {
-                                texture.gpu_tex = gl.create_texture().ok();
-                            }
-                            gl.bind_texture(glow::TEXTURE_2D, texture.gpu_tex);
-                            gl.tex_image_2d(
-                                glow::TEXTURE_2D,
-                                0,
-                                glow::RGBA as i32,
-                                texture.width as i32,
-                                texture.height as i32,
-                                0,
-                                glow::RGBA,
-                                glow::UNSIGNED_BYTE,
-                                Some(bytemuck::cast_slice(&texture.pixels)),
-                            );
-                            gl.tex_parameter_i32(
-                                glow::TEXTURE_2D,
-                                glow::TEXTURE_MAG_FILTER,
-                                glow::LINEAR as i32,
-                            );
-                            gl.tex_parameter_i32(
-                                glow::TEXTURE_2D,
-                                glow::TEXTURE_MIN_FILTER,
-                                glow::LINEAR_MIPMAP_LINEAR as i32,
-                            );
-
-                            gl.generate_mipmap(glow::TEXTURE_2D);
-                            texture.need_upload = false;
+                        if texture.gpu_tex.is_none() {
+                            texture.gpu_tex = gl.create_texture().ok();
                         }
+                        gl.bind_texture(glow::TEXTURE_2D, texture.gpu_tex);
+                        gl.tex_image_2d(
+                            glow::TEXTURE_2D,
+                            0,
+                            glow::RGBA as i32,
+                            texture.width as i32,
+                            texture.height as i32,
+                            0,
+                            glow::RGBA,
+                            glow::UNSIGNED_BYTE,
+                            Some(bytemuck::cast_slice(&texture.pixels)),
+                        );
+                        gl.tex_parameter_i32(
+                            glow::TEXTURE_2D,
+                            glow::TEXTURE_MAG_FILTER,
+                            glow::LINEAR as i32,
+                        );
+                        gl.tex_parameter_i32(
+                            glow::TEXTURE_2D,
+                            glow::TEXTURE_MIN_FILTER,
+                            glow::LINEAR_MIPMAP_LINEAR as i32,
+                        );
+
+                        gl.generate_mipmap(glow::TEXTURE_2D);
+                        texture.need_upload = false;
                     }
                 }
             }
         }
     }
 
-    pub fn render(&mut self, scenes: &[&Scene]) {
+    /// Renders scenes, `alpha` is blend factor between previous and current node
+    /// transforms (1.0 renders current state as is).
+    pub fn render(&mut self, scenes: &[&Scene], alpha: f32) {
         let gl = GL.get().unwrap();
+        // Evicted primitives queue their buffers for the drain below.
+        surface::evict_unused_primitives();
+        unsafe {
+            deletion::drain(gl);
+        }
 
         let client_size = self.context.inner_size();
+        self.stats = RenderStats::default();
+        if !self.culling_frozen {
+            self.frozen_frustums.clear();
+        }
+        let mut camera_index = 0;
 
         unsafe {
-            gl.clear_color(0.0, 0.63, 0.91, 1.0);
+            let color = BACKGROUND_COLOR;
+            gl.clear_color(color.r, color.g, color.b, color.a);
             gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+            gl.polygon_mode(glow::FRONT_AND_BACK, self.polygon_mode.gl_mode());
+            // Counter-clockwise faces are the front ones.
+            gl.enable(glow::CULL_FACE);
+            gl.cull_face(glow::BACK);
         }
+        self.cull = CullState::default();
 
         for scene in scenes.iter() {
-            self.meshes.clear();
-            self.lights.clear();
-            self.cameras.clear();
-            self.traversal_stack.clear();
-            self.traversal_stack.push(scene.root.clone());
-            while !self.traversal_stack.is_empty() {
-                if let Some(node_handle) = self.traversal_stack.pop() {
-                    if let Some(node) = scene.borrow_node(&node_handle) {
-                        match node.borrow_kind() {
-                            NodeKind::Mesh(_) => self.meshes.push(node_handle),
-                            NodeKind::Light(_) => self.lights.push(node_handle),
-                            NodeKind::Camera(_) => self.cameras.push(node_handle),
-                            _ => (),
-                        }
+            self.lists.collect(scene);
 
-                        for child_handle in node.children.iter() {
-                            self.traversal_stack.push(child_handle.clone());
-                        }
-                    }
-                }
-            }
+            self.stats.meshes += self.lists.meshes.len();
+            self.stats.lights += self.lists.lights.len();
+            self.stats.cameras += self.lists.cameras.len();
 
             unsafe {
                 gl.use_program(Some(self.flat_shader.id));
@@ -257,11 +783,20 @@ impl Renderer {
                 .flat_shader
                 .get_uniform_location("worldViewProjection")
                 .unwrap();
-
-            for camera_handle in self.cameras.iter() {
+            let u_tint = self.flat_shader.get_uniform_location("tint");
+            let u_specular = self.flat_shader.get_uniform_location("specular");
+            let u_shininess = self.flat_shader.get_uniform_location("shininess");
+            let u_textured = self.flat_shader.get_uniform_location("textured");
+            let u_eye_position = self.flat_shader.get_uniform_location("eyePosition");
+            let u_world = self.flat_shader.get_uniform_location("world");
+            let u_normal_matrix = self.flat_shader.get_uniform_location("normalMatrix");
+            // Indexed, draw list of the lists is refilled for every camera.
+            for i in 0..self.lists.cameras.len() {
+                let camera_handle = self.lists.cameras[i].clone();
                 if let Some(camera_node) = scene.borrow_node(&camera_handle) {
                     if let NodeKind::Camera(camera) = camera_node.borrow_kind() {
-                        // Setup viewport
+                        // Setup viewport, clears are scissored to it so other
+                        // viewports stay intact.
                         unsafe {
                             let viewport = camera.get_viewport_pixels(Vector2::new(
                                 client_size.width as f32,
@@ -269,13 +804,75 @@ impl Renderer {
                             ));
 
                             gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+                            gl.enable(glow::SCISSOR_TEST);
+                            gl.scissor(viewport.x, viewport.y, viewport.width, viewport.height);
+                            let mut mask = glow::DEPTH_BUFFER_BIT;
+                            if let Some(color) = camera.get_clear_color() {
+                                gl.clear_color(color.r, color.g, color.b, color.a);
+                                mask |= glow::COLOR_BUFFER_BIT;
+                            }
+                            gl.clear(mask);
+                            gl.disable(glow::SCISSOR_TEST);
+                        }
+
+                        let view_projection = if alpha < 1.0 {
+                            camera.get_view_projection_matrix_at(
+                                &camera_node.get_interpolated_global_transform(alpha),
+                            )
+                        } else {
+                            camera.get_view_projection_matrix()
+                        };
+
+                        let eye = camera_node
+                            .get_interpolated_global_transform(alpha)
+                            .fixed_view::<3, 1>(0, 3)
+                            .into_owned();
+                        unsafe {
+                            gl.uniform_3_f32(u_eye_position.as_ref(), eye.x, eye.y, eye.z);
+                        }
+
+                        let mut frustum = Frustum::from_view_projection(&view_projection);
+                        if !self.culling_frozen {
+                            self.frozen_frustums.push(frustum);
+                        } else if let Some(frozen) = self.frozen_frustums.get(camera_index) {
+                            frustum = *frozen;
                         }
+                        camera_index += 1;
+                        self.upload_lights(scene, alpha, &frustum);
 
-                        let view_projection = camera.get_view_projection_matrix();
+                        self.stats.culled += self.lists.collect_camera_meshes(
+                            scene,
+                            camera.get_cull_mask(),
+                            &frustum,
+                            alpha,
+                        );
+                        self.stats.drawn += self.lists.draw.len();
 
-                        for mesh_handle in self.meshes.iter() {
-                            if let Some(node) = scene.borrow_node(&mesh_handle) {
-                                let mvp = view_projection * node.global_transform;
+                        // Two-sided surfaces go last, so culling switches at
+                        // most twice per camera.
+                        for two_sided in [false, true] {
+                            for mesh_handle in self.lists.draw.iter() {
+                                let Some(node) = scene.borrow_node(mesh_handle) else {
+                                    continue;
+                                };
+                                let NodeKind::Mesh(mesh) = node.borrow_kind() else {
+                                    continue;
+                                };
+                                let is_in_pass = |surface: &&Surface| {
+                                    surface.get_material().is_two_sided() == two_sided
+                                };
+                                if !mesh.surfaces.iter().any(|surface| is_in_pass(&surface)) {
+                                    continue;
+                                }
+                                let world = node.get_interpolated_global_transform(alpha);
+                                let mvp = view_projection * world;
+                                // Inverse transpose keeps normals perpendicular
+                                // under non-uniform scale.
+                                let normal_matrix = world
+                                    .fixed_view::<3, 3>(0, 0)
+                                    .try_inverse()
+                                    .map(|inverse| inverse.transpose())
+                                    .unwrap_or_else(Matrix3::identity);
                                 unsafe {
                                     gl.use_program(Some(self.flat_shader.id));
                                     gl.uniform_matrix_4_f32_slice(
@@ -283,19 +880,539 @@ impl Renderer {
                                         false,
                                         mvp.as_slice(),
                                     );
+                                    gl.uniform_matrix_4_f32_slice(
+                                        u_world.as_ref(),
+                                        false,
+                                        world.as_slice(),
+                                    );
+                                    gl.uniform_matrix_3_f32_slice(
+                                        u_normal_matrix.as_ref(),
+                                        false,
+                                        normal_matrix.as_slice(),
+                                    );
                                 }
 
-                                if let NodeKind::Mesh(mesh) = node.borrow_kind() {
-                                    for surface in mesh.surfaces.iter() {
-                                        surface.draw();
+                                for surface in mesh.surfaces.iter().filter(is_in_pass) {
+                                    let material = MaterialUniform::new(mesh, surface);
+                                    let tint = material.diffuse;
+                                    unsafe {
+                                        if let Some(enable) = self.cull.set_two_sided(two_sided) {
+                                            if enable {
+                                                gl.enable(glow::CULL_FACE);
+                                            } else {
+                                                gl.disable(glow::CULL_FACE);
+                                            }
+                                        }
+                                        gl.uniform_4_f32(
+                                            u_tint.as_ref(),
+                                            tint.r,
+                                            tint.g,
+                                            tint.b,
+                                            tint.a,
+                                        );
+                                        gl.uniform_1_f32(u_specular.as_ref(), material.specular);
+                                        gl.uniform_1_f32(u_shininess.as_ref(), material.shininess);
+                                        gl.uniform_1_i32(
+                                            u_textured.as_ref(),
+                                            material.textured as i32,
+                                        );
+                                    }
+                                    if !surface.data.borrow().has_gpu_buffers() {
+                                        self.stats.surfaces_allocated += 1;
                                     }
+                                    self.stats.triangles += surface.draw(self.white_texture);
+                                    self.stats.draw_calls += 1;
                                 }
                             }
                         }
+
+                        if self.draw_bounds {
+                            self.stats.draw_calls += Self::draw_mesh_bounds(
+                                &mut self.debug,
+                                &self.lists.meshes,
+                                scene,
+                                &view_projection,
+                                alpha,
+                            );
+                            unsafe {
+                                gl.polygon_mode(glow::FRONT_AND_BACK, self.polygon_mode.gl_mode());
+                            }
+                        }
                     }
                 }
             }
         }
 
+        self.stats.cull_toggles = self.cull.toggles;
+
+        if let Some(path) = self.screenshot.take() {
+            match self.save_screenshot(&path) {
+                Ok(()) => println!("Saved screenshot to {:?}", path),
+                Err(err) => println!("Failed to save screenshot {:?}: {}", path, err),
+            }
+        }
+    }
+
+    /// Uploads ambient and lights of the collected scene seen by `frustum` to
+    /// the flat shader, which has to be in use.
+    fn upload_lights(&mut self, scene: &Scene, alpha: f32, frustum: &Frustum) {
+        let ambient = RenderLists::ambient_uniform(scene);
+        let lights = self.lists.light_uniforms(scene, alpha, frustum);
+        let positions: Vec<f32> = lights
+            .iter()
+            .flat_map(|l| l.position.iter().copied())
+            .collect();
+        let colors: Vec<f32> = lights
+            .iter()
+            .flat_map(|l| l.color.iter().copied())
+            .collect();
+        let directions: Vec<f32> = lights
+            .iter()
+            .flat_map(|l| l.direction.iter().copied())
+            .collect();
+        let radii: Vec<f32> = lights.iter().map(|l| l.radius).collect();
+        let cones: Vec<f32> = lights.iter().flat_map(|l| l.cone.iter().copied()).collect();
+        let kinds: Vec<i32> = lights.iter().map(LightUniform::shader_kind).collect();
+        let shader = &mut self.flat_shader;
+        let gl = GL.get().unwrap();
+        unsafe {
+            gl.uniform_3_f32(
+                shader.get_uniform_location("ambient").as_ref(),
+                ambient.x,
+                ambient.y,
+                ambient.z,
+            );
+            gl.uniform_1_i32(
+                shader.get_uniform_location("lightCount").as_ref(),
+                lights.len() as i32,
+            );
+            if !lights.is_empty() {
+                gl.uniform_1_i32_slice(shader.get_uniform_location("lightKinds").as_ref(), &kinds);
+                gl.uniform_3_f32_slice(
+                    shader.get_uniform_location("lightDirections").as_ref(),
+                    &directions,
+                );
+                gl.uniform_3_f32_slice(
+                    shader.get_uniform_location("lightPositions").as_ref(),
+                    &positions,
+                );
+                gl.uniform_3_f32_slice(
+                    shader.get_uniform_location("lightColors").as_ref(),
+                    &colors,
+                );
+                gl.uniform_1_f32_slice(shader.get_uniform_location("lightRadii").as_ref(), &radii);
+                gl.uniform_2_f32_slice(shader.get_uniform_location("lightCones").as_ref(), &cones);
+            }
+        }
+    }
+
+    /// Draws world bounds of meshes with fill polygon mode, returns number of
+    /// draw calls.
+    fn draw_mesh_bounds(
+        debug: &mut DebugRenderer,
+        meshes: &[Handle<Node>],
+        scene: &Scene,
+        view_projection: &Matrix4<f32>,
+        alpha: f32,
+    ) -> usize {
+        debug.clear();
+        for mesh_handle in meshes.iter() {
+            if let Some(node) = scene.borrow_node(mesh_handle) {
+                if let NodeKind::Mesh(mesh) = node.borrow_kind() {
+                    if let Some(local) = mesh.bounding_box() {
+                        debug.add_aabb(
+                            &local.transform(&node.get_interpolated_global_transform(alpha)),
+                        );
+                    }
+                }
+            }
+        }
+        unsafe {
+            GL.get()
+                .unwrap()
+                .polygon_mode(glow::FRONT_AND_BACK, glow::FILL);
+        }
+        debug.draw(view_projection, BOUNDS_COLOR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::Path, rc::Rc};
+
+    use nalgebra::{Matrix4, UnitQuaternion, Vector2, Vector3};
+
+    use super::{CullState, MaterialUniform, RenderLists, MAX_LIGHTS};
+    use crate::{
+        math::{color::Color, frustum::Frustum},
+        renderer::surface::{Surface, SurfaceSharedData},
+        resource::{texture::Texture, Resource, ResourceKind},
+        scene::{
+            node::{Camera, Light, LightKind, Mesh, Node, NodeKind},
+            Scene,
+        },
+        utils::pool::Handle,
+    };
+
+    #[test]
+    fn material_uniforms() {
+        let mut mesh = Mesh::default();
+        mesh.set_tint(Color::opaque(0.5, 1.0, 1.0));
+        let mut surface = Surface::new(&SurfaceSharedData::cached_cube());
+        let material = surface.material_mut();
+        material.set_diffuse(Color::opaque(1.0, 0.0, 0.0));
+        material.set_specular(0.25);
+        material.set_shininess(8.0);
+        assert_eq!(
+            MaterialUniform::new(&mesh, &surface),
+            MaterialUniform {
+                diffuse: Color::opaque(0.5, 0.0, 0.0),
+                specular: 0.25,
+                shininess: 8.0,
+                textured: false,
+            }
+        );
+
+        let texture = Texture {
+            width: 1,
+            height: 1,
+            gpu_tex: None,
+            need_upload: true,
+            pixels: vec![255; 4],
+        };
+        let texture = Resource::new(Path::new("white.png"), ResourceKind::Texture(texture));
+        surface.set_texture(Rc::new(RefCell::new(texture)));
+        let uniform = MaterialUniform::new(&mesh, &surface);
+        assert!(uniform.textured);
+        assert_eq!(uniform.diffuse, Color::opaque(0.5, 0.0, 0.0));
+
+        // Other resources are not textures.
+        let base = Resource::new(Path::new("base"), ResourceKind::Base);
+        surface.set_texture(Rc::new(RefCell::new(base)));
+        assert!(!MaterialUniform::new(&mesh, &surface).textured);
+    }
+
+    #[test]
+    fn cull_toggles() {
+        // Single-sided pass keeps culling on, two-sided pass switches it off
+        // once for all its surfaces.
+        let mut cull = CullState::default();
+        for two_sided in [false, false, true, true, true] {
+            cull.set_two_sided(two_sided);
+        }
+        assert_eq!(cull.toggles, 1);
+        assert_eq!(cull.set_two_sided(false), Some(true));
+        assert_eq!(cull.set_two_sided(false), None);
+        assert_eq!(cull.toggles, 2);
+    }
+
+    #[test]
+    fn offscreen_lights() {
+        // Lights behind the camera come first in scene order, but only the
+        // one in front and the one reaching into view take slots.
+        let mut scene = Scene::new();
+        for _ in 0..MAX_LIGHTS {
+            let mut light = Node::new(NodeKind::Light(Light::new(10.0, Color::WHITE)));
+            light.set_local_position(Vector3::new(0.0, 0.0, 50.0));
+            scene.add_node(light);
+        }
+        let mut reaching = Node::new(NodeKind::Light(Light::new(10.0, Color::WHITE)));
+        reaching.set_local_position(Vector3::new(0.0, 0.0, 5.0));
+        scene.add_node(reaching);
+        let mut front = Node::new(NodeKind::Light(Light::new(1.0, Color::WHITE)));
+        front.set_local_position(Vector3::new(0.0, 0.0, -5.0));
+        scene.add_node(front);
+        scene.add_node(Node::new(NodeKind::Light(Light::directional(Color::WHITE))));
+        scene.update(1.0, 0.0);
+
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        let projection = Matrix4::new_perspective(1.0, 90f32.to_radians(), 1.0, 10.0);
+        let lights = lists.light_uniforms(&scene, 1.0, &Frustum::from_view_projection(&projection));
+        let positions: Vec<_> = lights.iter().map(|light| light.position.z).collect();
+        assert_eq!(positions, [5.0, -5.0, 0.0]);
+        assert_eq!(lights[2].shader_kind(), 1);
+    }
+
+    #[test]
+    fn hidden_subtree() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(Node::new(NodeKind::Base));
+        let child = scene.add_node(Node::new(NodeKind::Mesh(Mesh::default())));
+        let light = scene.add_node(Node::new(NodeKind::Light(Light::default())));
+        let visible = scene.add_node(Node::new(NodeKind::Mesh(Mesh::default())));
+        scene.link_nodes(&child, &parent);
+        scene.link_nodes(&light, &parent);
+        scene
+            .borrow_node_mut(&parent)
+            .unwrap()
+            .set_visibility(false);
+        scene.update(1.0, 0.0);
+
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        assert!(lists.meshes == [visible]);
+        assert!(lists.lights.is_empty());
+        // Child keeps its own flag, hidden only through the parent.
+        let child = scene.borrow_node(&child).unwrap();
+        assert!(child.is_visible());
+        assert!(!child.is_globally_visible());
+    }
+
+    #[test]
+    fn disabled_cameras() {
+        let mut scene = Scene::new();
+        let main = scene.add_node(Node::new(NodeKind::Camera(Camera::default())));
+        let mut cutscene = Camera::default();
+        cutscene.set_enabled(false);
+        let cutscene = scene.add_node(Node::new(NodeKind::Camera(cutscene)));
+        scene.update(1.0, 0.0);
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        assert!(lists.cameras == [main.clone()]);
+
+        let mut switch = |handle: &Handle<Node>, enabled| {
+            if let NodeKind::Camera(camera) =
+                scene.borrow_node_mut(handle).unwrap().borrow_kind_mut()
+            {
+                camera.set_enabled(enabled);
+            }
+        };
+        switch(&main, false);
+        switch(&cutscene, true);
+        lists.collect(&scene);
+        assert!(lists.cameras == [cutscene]);
+    }
+
+    #[test]
+    fn camera_order() {
+        let mut scene = Scene::new();
+        let add_camera = |scene: &mut Scene, order| {
+            let mut camera = Camera::default();
+            camera.set_render_order(order);
+            scene.add_node(Node::new(NodeKind::Camera(camera)))
+        };
+        let minimap = add_camera(&mut scene, 1);
+        let first = add_camera(&mut scene, 0);
+        let overlay = add_camera(&mut scene, 1);
+        let second = add_camera(&mut scene, 0);
+        scene.update(1.0, 0.0);
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        assert!(lists.cameras == [first.clone(), second, minimap.clone(), overlay]);
+
+        scene.set_single_camera(true);
+        lists.collect(&scene);
+        assert!(lists.cameras == [first]);
+        scene.set_active_camera(&minimap);
+        lists.collect(&scene);
+        assert!(lists.cameras == [minimap]);
+    }
+
+    /// Frustum containing the whole test scene.
+    fn everywhere() -> Frustum {
+        Frustum::from_view_projection(&Matrix4::new_orthographic(
+            -1000.0, 1000.0, -1000.0, 1000.0, -1000.0, 1000.0,
+        ))
+    }
+
+    #[test]
+    fn light_uniforms() {
+        let mut scene = Scene::new();
+        let mut first = Node::new(NodeKind::Light(Light::new(
+            5.0,
+            Color::opaque(1.0, 0.5, 0.0),
+        )));
+        first.set_local_position(Vector3::new(1.0, 2.0, 3.0));
+        let first = scene.add_node(first);
+        let parent = scene.add_node(Node::new(NodeKind::Base));
+        scene
+            .borrow_node_mut(&parent)
+            .unwrap()
+            .set_local_position(Vector3::new(0.0, 10.0, 0.0));
+        let child = scene.add_node(Node::new(NodeKind::Light(Light::default())));
+        scene.link_nodes(&child, &parent);
+        scene.update(1.0, 0.0);
+
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        let lights = lists.light_uniforms(&scene, 1.0, &everywhere());
+        assert_eq!(lights.len(), 2);
+        assert_eq!(lights[0].position, Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(lights[0].color, Vector3::new(1.0, 0.5, 0.0));
+        assert_eq!(lights[0].radius, 5.0);
+        assert_eq!(lights[0].shader_kind(), 0);
+        // Position is global.
+        assert_eq!(lights[1].position, Vector3::new(0.0, 10.0, 0.0));
+        assert!(lists.lights.contains(&first));
+
+        // Changes show up in the next upload.
+        if let NodeKind::Light(light) = scene.borrow_node_mut(&first).unwrap().borrow_kind_mut() {
+            light.set_color(Color::from_rgb8(0, 0, 255));
+            light.set_intensity(2.0);
+            light.set_radius(-1.0);
+        }
+        let lights = lists.light_uniforms(&scene, 1.0, &everywhere());
+        assert_eq!(lights[0].color, Vector3::new(0.0, 0.0, 2.0));
+        assert_eq!(lights[0].radius, 5.0);
+
+        // Lights over the limit are not uploaded.
+        for _ in 0..MAX_LIGHTS {
+            scene.add_node(Node::new(NodeKind::Light(Light::default())));
+        }
+        scene.update(1.0, 0.0);
+        lists.collect(&scene);
+        assert_eq!(lists.lights.len(), MAX_LIGHTS + 2);
+        assert_eq!(
+            lists.light_uniforms(&scene, 1.0, &everywhere()).len(),
+            MAX_LIGHTS
+        );
+    }
+
+    #[test]
+    fn directional_light_uniform() {
+        let mut scene = Scene::new();
+        let mut sun = Node::new(NodeKind::Light(Light::directional(Color::WHITE)));
+        sun.set_local_rotation(UnitQuaternion::from_euler_angles(0.7, 0.4, 0.0));
+        sun.set_local_scale(Vector3::repeat(3.0));
+        let sun = scene.add_node(sun);
+        let parent = scene.add_node(Node::new(NodeKind::Base));
+        scene
+            .borrow_node_mut(&parent)
+            .unwrap()
+            .set_local_rotation(UnitQuaternion::from_euler_angles(0.0, 0.0, 1.2));
+        scene.link_nodes(&sun, &parent);
+        scene.update(1.0, 0.0);
+
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        let lights = lists.light_uniforms(&scene, 1.0, &everywhere());
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].kind, LightKind::Directional);
+        assert_eq!(lights[0].shader_kind(), 1);
+        let look = scene
+            .borrow_node(&sun)
+            .unwrap()
+            .get_look_vector()
+            .normalize();
+        assert!((lights[0].direction - look).norm() < 1e-5);
+    }
+
+    #[test]
+    fn spot_light_uniform() {
+        let mut scene = Scene::new();
+        let mut flashlight = Node::new(NodeKind::Light(Light::spot(
+            Color::opaque(1.0, 1.0, 0.5),
+            20.0,
+            30.0,
+            15.0,
+        )));
+        flashlight.set_local_position(Vector3::new(0.0, 2.0, 0.0));
+        // Look vector +Z turned to +X.
+        flashlight.set_local_rotation(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            std::f32::consts::FRAC_PI_2,
+        ));
+        flashlight.set_local_scale(Vector3::repeat(2.0));
+        scene.add_node(flashlight);
+        scene.update(1.0, 0.0);
+
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        let light = lists.light_uniforms(&scene, 1.0, &everywhere())[0];
+        assert_eq!(light.shader_kind(), 2);
+        assert_eq!(light.position, Vector3::new(0.0, 2.0, 0.0));
+        assert!((light.direction - Vector3::x()).norm() < 1e-5);
+        assert_eq!(light.radius, 15.0);
+        let expected = Vector2::new(20f32.to_radians().cos(), 30f32.to_radians().cos());
+        assert!((light.cone - expected).norm() < 1e-6);
+    }
+
+    #[test]
+    fn scene_ambient() {
+        let mut menu = Scene::new();
+        let level = Scene::new();
+        assert_eq!(
+            RenderLists::ambient_uniform(&level),
+            Vector3::new(0.2, 0.2, 0.2)
+        );
+        menu.set_ambient_color(Color::new(0.5, 0.25, 1.0, 0.0));
+        assert_eq!(
+            RenderLists::ambient_uniform(&menu),
+            Vector3::new(0.5, 0.25, 1.0)
+        );
+        assert_eq!(
+            RenderLists::ambient_uniform(&level),
+            Vector3::new(0.2, 0.2, 0.2)
+        );
+    }
+
+    #[test]
+    fn camera_layers() {
+        const WORLD: u32 = 1;
+        const WEAPON: u32 = 2;
+        const GIZMO: u32 = 4;
+        let mut scene = Scene::new();
+        let mut add_mesh = |layers, position: Vector3<f32>| {
+            let mut mesh = Mesh::default();
+            mesh.make_cube();
+            let mut node = Node::new(NodeKind::Mesh(mesh));
+            node.set_layers(layers);
+            node.set_local_position(position);
+            scene.add_node(node)
+        };
+        let ahead = Vector3::new(0.0, 0.0, 10.0);
+        let level = add_mesh(WORLD, ahead);
+        let weapon = add_mesh(WEAPON, ahead);
+        let gizmo = add_mesh(GIZMO, ahead);
+        let shared = add_mesh(WORLD | GIZMO, ahead);
+        // Behind both cameras, culled by the frustum.
+        add_mesh(WORLD, -ahead);
+        let hidden = add_mesh(WEAPON, ahead);
+        scene
+            .borrow_node_mut(&hidden)
+            .unwrap()
+            .set_visibility(false);
+        let mut camera = Camera::default();
+        camera.set_cull_mask(WORLD | WEAPON);
+        let player = scene.add_node(Node::new(NodeKind::Camera(camera)));
+        let mut camera = Camera::default();
+        camera.set_cull_mask(GIZMO);
+        let editor = scene.add_node(Node::new(NodeKind::Camera(camera)));
+        scene.update(1.0, 0.0);
+
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        let mut draw_list = |handle: &Handle<Node>| {
+            let NodeKind::Camera(camera) = scene.borrow_node(handle).unwrap().borrow_kind() else {
+                unreachable!();
+            };
+            let frustum = Frustum::from_view_projection(&camera.get_view_projection_matrix());
+            let culled = lists.collect_camera_meshes(&scene, camera.get_cull_mask(), &frustum, 1.0);
+            (lists.draw.clone(), culled)
+        };
+        assert_eq!(draw_list(&player), (vec![level, weapon, shared.clone()], 1));
+        assert_eq!(draw_list(&editor), (vec![gizmo, shared], 0));
+    }
+
+    #[test]
+    fn unlinked_nodes() {
+        let mut scene = Scene::new();
+        let mut node = Node::new(NodeKind::Mesh(Mesh::default()));
+        node.set_local_position(Vector3::new(0.0, 1.0, 0.0));
+        let mesh = scene.add_node_unlinked(node);
+        scene.update(1.0, 0.0);
+        let mut lists = RenderLists::default();
+        lists.collect(&scene);
+        assert!(lists.meshes.is_empty());
+        let position = |scene: &Scene| scene.borrow_node(&mesh).unwrap().get_global_position();
+        assert_eq!(position(&scene), Vector3::zeros());
+
+        let root = scene.root();
+        scene.link_nodes(&mesh, &root);
+        scene.update(1.0, 0.0);
+        lists.collect(&scene);
+        assert_eq!(position(&scene), Vector3::new(0.0, 1.0, 0.0));
+        assert!(lists.meshes == [mesh]);
     }
 }
@@ -1,151 +1,955 @@
-use std::{cell::RefCell, mem::size_of, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    mem::size_of,
+    rc::Rc,
+};
 
-use glow::{HasContext, NativeBuffer, NativeVertexArray};
+use glow::{HasContext, NativeBuffer, NativeTexture, NativeVertexArray};
 use nalgebra::{Vector2, Vector3, Vector4};
 
-use crate::resource::{Resource, ResourceKind};
+use crate::{
+    math::{aabb::AxisAlignedBoundingBox, sphere::BoundingSphere},
+    resource::{texture::Texture, Resource, ResourceKind},
+};
 
-use super::renderer::GL;
+use super::{
+    deletion::{self, GpuObject},
+    material::Material,
+    renderer::GL,
+};
+
+/// Vertex data rejected by `SurfaceSharedData::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SurfaceDataError {
+    /// No vertices or no triangles.
+    Empty,
+    /// Attribute has other number of entries than there are positions.
+    AttributeLength {
+        attribute: &'static str,
+        len: usize,
+        expected: usize,
+    },
+    /// Index is not less than number of vertices.
+    IndexOutOfRange { index: u32, vertex_count: usize },
+    /// Index count is not a multiple of three.
+    PartialTriangle(usize),
+    /// Draw range is not whole triangles inside the index buffer.
+    InvalidRange { range: DrawRange, len: usize },
+}
+
+impl fmt::Display for SurfaceDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SurfaceDataError::Empty => write!(f, "surface has no triangles"),
+            SurfaceDataError::AttributeLength {
+                attribute,
+                len,
+                expected,
+            } => write!(
+                f,
+                "{} has {} entries, expected one per vertex ({})",
+                attribute, len, expected
+            ),
+            SurfaceDataError::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "index {} is out of range of {} vertices",
+                index, vertex_count
+            ),
+            SurfaceDataError::PartialTriangle(count) => {
+                write!(f, "{} indices don't make whole triangles", count)
+            }
+            SurfaceDataError::InvalidRange { range, len } => write!(
+                f,
+                "indices {}..{} are not whole triangles of {} indices",
+                range.first_index,
+                range.end(),
+                len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SurfaceDataError {}
+
+/// Part of the index buffer drawn by a surface. Surfaces sharing one buffer
+/// may draw disjoint ranges with different materials.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DrawRange {
+    pub first_index: usize,
+    pub index_count: usize,
+}
+
+impl DrawRange {
+    pub fn new(first_index: usize, index_count: usize) -> Self {
+        Self {
+            first_index,
+            index_count,
+        }
+    }
+
+    /// Index past the last one of the range.
+    pub fn end(&self) -> usize {
+        self.first_index + self.index_count
+    }
+}
+
+/// How often vertices change, hints the driver where to keep them.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum BufferUsage {
+    /// Set once and drawn many times, such as loaded models.
+    #[default]
+    Static,
+    /// Modified every few frames, such as geometry animated on the CPU.
+    Dynamic,
+}
+
+impl BufferUsage {
+    fn gl_usage(self) -> u32 {
+        match self {
+            BufferUsage::Static => glow::STATIC_DRAW,
+            BufferUsage::Dynamic => glow::DYNAMIC_DRAW,
+        }
+    }
+}
+
+/// Sizes of GPU buffers in bytes. Vertex attributes follow each other in
+/// the vertex buffer in the order they are listed, absent ones take no
+/// space.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct BufferLayout {
+    positions: usize,
+    tex_coords: usize,
+    normals: usize,
+    tangents: usize,
+    colors: usize,
+    bone_indices: usize,
+    bone_weights: usize,
+    indices: usize,
+    usage: BufferUsage,
+}
+
+impl BufferLayout {
+    /// Offsets of vertex attributes in the order of fields.
+    fn offsets(&self) -> [usize; 7] {
+        let sizes = [
+            self.positions,
+            self.tex_coords,
+            self.normals,
+            self.tangents,
+            self.colors,
+            self.bone_indices,
+            self.bone_weights,
+        ];
+        let mut offset = 0;
+        sizes.map(|size| {
+            offset += size;
+            offset - size
+        })
+    }
+
+    fn vertex_bytes(&self) -> usize {
+        self.offsets()[6] + self.bone_weights
+    }
+}
+
+/// Triangle corners as indices of vertices. Surfaces with vertices that fit
+/// use 16-bit indices, halving index memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexStorage {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Default for IndexStorage {
+    fn default() -> Self {
+        IndexStorage::U16(Vec::new())
+    }
+}
+
+impl IndexStorage {
+    /// Picks the smallest storage able to index `vertex_count` vertices.
+    pub fn new(indices: Vec<u32>, vertex_count: usize) -> Self {
+        let storage = IndexStorage::U32(indices);
+        if vertex_count <= u16::MAX as usize + 1 {
+            storage.to_u16().map_or(storage, IndexStorage::U16)
+        } else {
+            storage
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IndexStorage::U16(indices) => indices.len(),
+            IndexStorage::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            IndexStorage::U16(indices) => indices[i] as u32,
+            IndexStorage::U32(indices) => indices[i],
+        }
+    }
+
+    /// Widens the storage when the index doesn't fit 16 bits.
+    pub fn set(&mut self, i: usize, index: u32) {
+        if let IndexStorage::U16(indices) = self {
+            match u16::try_from(index) {
+                Ok(index) => {
+                    indices[i] = index;
+                    return;
+                }
+                Err(_) => self.widen(),
+            }
+        }
+        if let IndexStorage::U32(indices) = self {
+            indices[i] = index;
+        }
+    }
+
+    /// Widens the storage when the index doesn't fit 16 bits.
+    pub fn push(&mut self, index: u32) {
+        if let IndexStorage::U16(indices) = self {
+            match u16::try_from(index) {
+                Ok(index) => {
+                    indices.push(index);
+                    return;
+                }
+                Err(_) => self.widen(),
+            }
+        }
+        if let IndexStorage::U32(indices) = self {
+            indices.push(index);
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            IndexStorage::U16(indices) => indices.truncate(len),
+            IndexStorage::U32(indices) => indices.truncate(len),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        self.iter().collect()
+    }
+
+    /// None if some index doesn't fit 16 bits.
+    pub fn to_u16(&self) -> Option<Vec<u16>> {
+        self.iter().map(|index| u16::try_from(index).ok()).collect()
+    }
+
+    /// Turns 16-bit storage into 32-bit one, so any index can be added.
+    pub fn widen(&mut self) {
+        if let IndexStorage::U16(indices) = self {
+            *self = IndexStorage::U32(indices.iter().map(|&index| index as u32).collect());
+        }
+    }
+
+    /// Size of one index in bytes.
+    pub fn index_size(&self) -> usize {
+        match self {
+            IndexStorage::U16(_) => size_of::<u16>(),
+            IndexStorage::U32(_) => size_of::<u32>(),
+        }
+    }
+
+    /// `glow::UNSIGNED_SHORT` or `glow::UNSIGNED_INT`.
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            IndexStorage::U16(_) => glow::UNSIGNED_SHORT,
+            IndexStorage::U32(_) => glow::UNSIGNED_INT,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            IndexStorage::U16(indices) => bytemuck::cast_slice(indices),
+            IndexStorage::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct SurfaceSharedData {
     need_upload: bool,
-    vbo: NativeBuffer,
-    vao: NativeVertexArray,
-    ebo: NativeBuffer,
+    /// GPU objects are created on first upload, so surfaces can be built
+    /// without GL context (headless engine).
+    vbo: Option<NativeBuffer>,
+    vao: Option<NativeVertexArray>,
+    ebo: Option<NativeBuffer>,
     positions: Vec<Vector3<f32>>,
     normals: Vec<Vector3<f32>>,
     tex_coords: Vec<Vector2<f32>>,
     tangents: Vec<Vector4<f32>>,
-    indices: Vec<i32>,
+    /// Linear RGBA multiplied with the texture, empty for white vertices.
+    colors: Vec<Vector4<f32>>,
+    /// Skinning channels, empty for rigid surfaces. Indices point into bone
+    /// matrices of the mesh, weights of a vertex sum up to one.
+    bone_indices: Vec<[u16; 4]>,
+    bone_weights: Vec<Vector4<f32>>,
+    indices: IndexStorage,
+    /// Box of positions, calculated on first request.
+    bounds: Cell<Option<AxisAlignedBoundingBox>>,
+    usage: BufferUsage,
+    /// Buffers as allocated by the last upload, None before the first one.
+    allocated: Option<BufferLayout>,
+    /// Number of uploads that allocated buffers instead of updating them.
+    reallocations: usize,
+    /// Error of data waiting for upload, already logged.
+    logged_error: Option<SurfaceDataError>,
 }
 
 impl SurfaceSharedData {
     fn new() -> Self {
-        unsafe {
-            let gl = GL.get().unwrap();
-            let mut vbo = gl.create_buffer().unwrap();
-            let mut ebo = gl.create_buffer().unwrap();
-            let mut vao = gl.create_vertex_array().unwrap();
+        Self {
+            need_upload: true,
+            vbo: None,
+            vao: None,
+            ebo: None,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            tex_coords: Vec::new(),
+            tangents: Vec::new(),
+            colors: Vec::new(),
+            bone_indices: Vec::new(),
+            bone_weights: Vec::new(),
+            indices: IndexStorage::default(),
+            bounds: Cell::new(None),
+            usage: BufferUsage::Static,
+            allocated: None,
+            reallocations: 0,
+            logged_error: None,
+        }
+    }
 
-            Self {
-                need_upload: true,
-                vbo,
-                vao,
-                ebo,
-                positions: Vec::new(),
-                normals: Vec::new(),
-                tex_coords: Vec::new(),
-                tangents: Vec::new(),
-                indices: Vec::new(),
-            }
+    fn layout(&self) -> BufferLayout {
+        BufferLayout {
+            positions: self.positions.len() * size_of::<Vector3<f32>>(),
+            tex_coords: self.tex_coords.len() * size_of::<Vector2<f32>>(),
+            normals: self.normals.len() * size_of::<Vector3<f32>>(),
+            tangents: self.tangents.len() * size_of::<Vector4<f32>>(),
+            colors: self.colors.len() * size_of::<Vector4<f32>>(),
+            bone_indices: self.bone_indices.len() * size_of::<[u16; 4]>(),
+            bone_weights: self.bone_weights.len() * size_of::<Vector4<f32>>(),
+            indices: self.indices.as_bytes().len(),
+            usage: self.usage,
+        }
+    }
+
+    /// Returns layout to upload and whether buffers must be allocated for it.
+    /// Buffers are only updated in place while sizes of all attributes and
+    /// usage stay the same.
+    fn begin_upload(&mut self) -> (BufferLayout, bool) {
+        let layout = self.layout();
+        let reallocate = self.allocated != Some(layout);
+        if reallocate {
+            self.allocated = Some(layout);
+            self.reallocations += 1;
         }
+        self.need_upload = false;
+        (layout, reallocate)
     }
 
     pub fn upload(&mut self) {
         unsafe {
             let gl = GL.get().unwrap();
+            if self.vao.is_none() {
+                self.vbo = gl.create_buffer().ok();
+                self.ebo = gl.create_buffer().ok();
+                self.vao = gl.create_vertex_array().ok();
+            }
+            let (layout, reallocate) = self.begin_upload();
 
-            let positions_bytes = self.positions.len() * size_of::<Vector3<f32>>();
-            let tex_coords_bytes = self.tex_coords.len() * size_of::<Vector2<f32>>();
-            let normals_bytes = self.normals.len() * size_of::<Vector3<f32>>();
-            let tangents_bytes = self.tangents.len() * size_of::<Vector4<f32>>();
+            gl.bind_vertex_array(self.vao);
+            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, self.ebo);
+            if reallocate {
+                gl.buffer_data_u8_slice(
+                    glow::ELEMENT_ARRAY_BUFFER,
+                    self.indices.as_bytes(),
+                    layout.usage.gl_usage(),
+                );
+            } else {
+                gl.buffer_sub_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, 0, self.indices.as_bytes());
+            }
+            gl.bind_buffer(glow::ARRAY_BUFFER, self.vbo);
+            if reallocate {
+                gl.buffer_data_size(
+                    glow::ARRAY_BUFFER,
+                    layout.vertex_bytes() as i32,
+                    layout.usage.gl_usage(),
+                );
+            }
 
-            let total_size_bytes =
-                positions_bytes + normals_bytes + tex_coords_bytes + tangents_bytes;
+            let [pos_offset, tex_coord_offset, normals_offset, tangents_offset, colors_offset, bone_indices_offset, bone_weights_offset] =
+                layout.offsets();
+            let attributes: [(usize, &[u8]); 7] = [
+                (pos_offset, bytemuck::cast_slice(&self.positions)),
+                (tex_coord_offset, bytemuck::cast_slice(&self.tex_coords)),
+                (normals_offset, bytemuck::cast_slice(&self.normals)),
+                (tangents_offset, bytemuck::cast_slice(&self.tangents)),
+                (colors_offset, bytemuck::cast_slice(&self.colors)),
+                (
+                    bone_indices_offset,
+                    bytemuck::cast_slice(&self.bone_indices),
+                ),
+                (
+                    bone_weights_offset,
+                    bytemuck::cast_slice(&self.bone_weights),
+                ),
+            ];
+            for (offset, bytes) in attributes {
+                if !bytes.is_empty() {
+                    gl.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, offset as i32, bytes);
+                }
+            }
 
-            gl.bind_vertex_array(Some(self.vao));
-            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ebo));
-            gl.buffer_data_u8_slice(
-                glow::ELEMENT_ARRAY_BUFFER,
-                bytemuck::cast_slice(&self.indices),
-                glow::STATIC_DRAW,
-            );
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-            gl.buffer_data_size(
-                glow::ARRAY_BUFFER,
-                total_size_bytes as i32,
-                glow::STATIC_DRAW,
-            );
+            // Attribute offsets only move with sizes, which reallocates.
+            if reallocate {
+                gl.vertex_attrib_pointer_f32(
+                    0,
+                    3,
+                    glow::FLOAT,
+                    false,
+                    size_of::<Vector3<f32>>() as i32,
+                    pos_offset as i32,
+                );
+                gl.enable_vertex_attrib_array(0);
 
-            let pos_offset = 0usize;
-            gl.buffer_sub_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                pos_offset as i32,
-                bytemuck::cast_slice(&self.positions),
-            );
+                gl.vertex_attrib_pointer_f32(
+                    1,
+                    2,
+                    glow::FLOAT,
+                    false,
+                    size_of::<Vector2<f32>>() as i32,
+                    tex_coord_offset as i32,
+                );
+                gl.enable_vertex_attrib_array(1);
 
-            let tex_coord_offset = pos_offset + positions_bytes;
+                gl.vertex_attrib_pointer_f32(
+                    2,
+                    3,
+                    glow::FLOAT,
+                    false,
+                    size_of::<Vector3<f32>>() as i32,
+                    normals_offset as i32,
+                );
+                gl.enable_vertex_attrib_array(2);
 
-            gl.buffer_sub_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                tex_coord_offset as i32,
-                bytemuck::cast_slice(&self.tex_coords),
-            );
+                if self.tangents.is_empty() {
+                    gl.disable_vertex_attrib_array(3);
+                } else {
+                    gl.vertex_attrib_pointer_f32(
+                        3,
+                        4,
+                        glow::FLOAT,
+                        false,
+                        size_of::<Vector4<f32>>() as i32,
+                        tangents_offset as i32,
+                    );
+                    gl.enable_vertex_attrib_array(3);
+                }
 
-            let normals_offset = tex_coord_offset + tex_coords_bytes;
+                if self.has_colors() {
+                    gl.vertex_attrib_pointer_f32(
+                        4,
+                        4,
+                        glow::FLOAT,
+                        false,
+                        size_of::<Vector4<f32>>() as i32,
+                        colors_offset as i32,
+                    );
+                    gl.enable_vertex_attrib_array(4);
+                } else {
+                    // Disabled array reads the generic value, shared by all
+                    // surfaces without colors.
+                    gl.disable_vertex_attrib_array(4);
+                    gl.vertex_attrib_4_f32(4, 1.0, 1.0, 1.0, 1.0);
+                }
 
-            gl.buffer_sub_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                normals_offset as i32,
-                bytemuck::cast_slice(&self.normals),
-            );
+                if self.is_skinned() {
+                    gl.vertex_attrib_pointer_i32(
+                        5,
+                        4,
+                        glow::UNSIGNED_SHORT,
+                        size_of::<[u16; 4]>() as i32,
+                        bone_indices_offset as i32,
+                    );
+                    gl.enable_vertex_attrib_array(5);
 
-            let tangents_offset = normals_offset + normals_bytes;
+                    gl.vertex_attrib_pointer_f32(
+                        6,
+                        4,
+                        glow::FLOAT,
+                        false,
+                        size_of::<Vector4<f32>>() as i32,
+                        bone_weights_offset as i32,
+                    );
+                    gl.enable_vertex_attrib_array(6);
+                } else {
+                    gl.disable_vertex_attrib_array(5);
+                    gl.disable_vertex_attrib_array(6);
+                }
+            }
 
-            gl.buffer_sub_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                tangents_offset as i32,
-                bytemuck::cast_slice(&self.tangents),
-            );
+            gl.bind_vertex_array(None);
+        }
+    }
 
-            gl.vertex_attrib_pointer_f32(
-                0,
-                3,
-                glow::FLOAT,
-                false,
-                size_of::<Vector3<f32>>() as i32,
-                pos_offset as i32,
-            );
-            gl.enable_vertex_attrib_array(0);
-
-            gl.vertex_attrib_pointer_f32(
-                1,
-                2,
-                glow::FLOAT,
-                false,
-                size_of::<Vector2<f32>>() as i32,
-                tex_coord_offset as i32,
-            );
-            gl.enable_vertex_attrib_array(1);
-
-            gl.vertex_attrib_pointer_f32(
-                2,
-                3,
-                glow::FLOAT,
-                false,
-                size_of::<Vector3<f32>>() as i32,
-                normals_offset as i32,
-            );
-            gl.enable_vertex_attrib_array(2);
-
-            gl.vertex_attrib_pointer_f32(
-                3,
-                4,
-                glow::FLOAT,
-                false,
-                size_of::<Vector4<f32>>() as i32,
-                tangents_offset as i32,
-            );
-            gl.enable_vertex_attrib_array(3);
+    /// Creates surface data from triangle list. Texture coordinates are zeroed
+    /// and normals are calculated from geometry.
+    pub fn from_triangles(positions: Vec<Vector3<f32>>, indices: Vec<u32>) -> Self {
+        let mut data = Self::new();
+        data.tex_coords = vec![Vector2::zeros(); positions.len()];
+        data.indices = IndexStorage::new(indices, positions.len());
+        data.positions = positions;
+        data.calculate_normals(true);
+        data.calculate_tangents();
+        data
+    }
 
-            gl.bind_vertex_array(None);
+    /// Creates surface data from indexed vertices with all attributes given,
+    /// tangents are calculated from texture coordinates.
+    pub fn from_vertices(
+        positions: Vec<Vector3<f32>>,
+        normals: Vec<Vector3<f32>>,
+        tex_coords: Vec<Vector2<f32>>,
+        indices: Vec<u32>,
+    ) -> Self {
+        let mut data = Self::new();
+        data.indices = IndexStorage::new(indices, positions.len());
+        data.positions = positions;
+        data.normals = normals;
+        data.tex_coords = tex_coords;
+        data.calculate_tangents();
+        data
+    }
+
+    /// Creates surface data from triangle list with validated attributes.
+    /// Texture coordinates are required, empty normals and tangents are
+    /// calculated, smooth normals are used. Colors may be empty.
+    pub fn from_raw(
+        positions: Vec<Vector3<f32>>,
+        tex_coords: Vec<Vector2<f32>>,
+        normals: Vec<Vector3<f32>>,
+        tangents: Vec<Vector4<f32>>,
+        colors: Vec<Vector4<f32>>,
+        indices: Vec<u32>,
+    ) -> Result<Self, SurfaceDataError> {
+        let mut data = Self::new();
+        data.indices = IndexStorage::new(indices, positions.len());
+        data.positions = positions;
+        data.tex_coords = tex_coords;
+        data.normals = normals;
+        data.tangents = tangents;
+        data.colors = colors;
+        // Both skip triangles with indices out of range.
+        if data.normals.is_empty() {
+            data.calculate_normals(true);
+        }
+        if data.tangents.is_empty() {
+            data.calculate_tangents();
+        }
+        data.validate()?;
+        Ok(data)
+    }
+
+    /// Checks that there are triangles, every attribute has an entry per
+    /// vertex and indices are in range. Colors and skinning channels may be
+    /// empty.
+    pub fn validate(&self) -> Result<(), SurfaceDataError> {
+        let expected = self.positions.len();
+        if expected == 0 || self.indices.is_empty() {
+            return Err(SurfaceDataError::Empty);
+        }
+        let lengths = [
+            ("tex_coords", self.tex_coords.len(), false),
+            ("normals", self.normals.len(), false),
+            ("tangents", self.tangents.len(), true),
+            ("colors", self.colors.len(), true),
+            ("bone_indices", self.bone_indices.len(), true),
+            ("bone_weights", self.bone_weights.len(), true),
+        ];
+        for (attribute, len, optional) in lengths {
+            if len != expected && !(optional && len == 0) {
+                return Err(SurfaceDataError::AttributeLength {
+                    attribute,
+                    len,
+                    expected,
+                });
+            }
+        }
+        if self.bone_indices.len() != self.bone_weights.len() {
+            return Err(SurfaceDataError::AttributeLength {
+                attribute: "bone_weights",
+                len: self.bone_weights.len(),
+                expected,
+            });
+        }
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(SurfaceDataError::PartialTriangle(self.indices.len()));
+        }
+        if let Some(index) = self
+            .indices
+            .iter()
+            .find(|&index| index as usize >= expected)
+        {
+            return Err(SurfaceDataError::IndexOutOfRange {
+                index,
+                vertex_count: expected,
+            });
+        }
+        Ok(())
+    }
+
+    /// Validates data waiting for upload. Errors are returned to be logged
+    /// once, repeating the last one gives None until data becomes valid or
+    /// fails otherwise.
+    fn check_upload(&mut self) -> Result<(), Option<SurfaceDataError>> {
+        match self.validate() {
+            Ok(()) => {
+                self.logged_error = None;
+                Ok(())
+            }
+            Err(error) if self.logged_error.as_ref() == Some(&error) => Err(None),
+            Err(error) => {
+                self.logged_error = Some(error.clone());
+                Err(Some(error))
+            }
+        }
+    }
+
+    /// Calculates vertex normals. Smooth normals are area-weighted sums of
+    /// normals of triangles around a position, so vertices split by texture
+    /// seams still get the same normal. Flat normals are the ones of the
+    /// triangles, vertices shared by triangles facing different ways are
+    /// duplicated. Vertices of degenerate triangles only get up vector.
+    pub fn calculate_normals(&mut self, smooth: bool) {
+        if smooth {
+            self.calculate_smooth_normals();
+        } else {
+            self.calculate_flat_normals();
+        }
+        self.need_upload = true;
+    }
+
+    /// Corners of a triangle if all of them are in range.
+    fn triangle_at(&self, first: usize) -> Option<[usize; 3]> {
+        let corners = [0, 1, 2].map(|i| self.indices.get(first + i) as usize);
+        corners
+            .iter()
+            .all(|&i| i < self.positions.len())
+            .then_some(corners)
+    }
+
+    /// Cross product of triangle edges, its length is twice the area.
+    fn face_normal(&self, [a, b, c]: [usize; 3]) -> Vector3<f32> {
+        (self.positions[b] - self.positions[a]).cross(&(self.positions[c] - self.positions[a]))
+    }
+
+    fn calculate_smooth_normals(&mut self) {
+        // Adding zero turns negative zeros positive, so both map to one key.
+        let key = |p: &Vector3<f32>| [p.x + 0.0, p.y + 0.0, p.z + 0.0].map(f32::to_bits);
+        let mut sums: HashMap<[u32; 3], Vector3<f32>> = HashMap::new();
+        for first in (0..self.indices.len() / 3).map(|i| i * 3) {
+            let Some(triangle) = self.triangle_at(first) else {
+                continue;
+            };
+            let normal = self.face_normal(triangle);
+            for i in triangle {
+                *sums.entry(key(&self.positions[i])).or_default() += normal;
+            }
+        }
+        self.normals = self
+            .positions
+            .iter()
+            .map(|position| {
+                sums.get(&key(position))
+                    .and_then(|sum| sum.try_normalize(f32::EPSILON))
+                    .unwrap_or_else(Vector3::y)
+            })
+            .collect();
+    }
+
+    fn calculate_flat_normals(&mut self) {
+        let mut normals: Vec<Option<Vector3<f32>>> = vec![None; self.positions.len()];
+        // Copies of a vertex made for other normals.
+        let mut copies: HashMap<usize, Vec<usize>> = HashMap::new();
+        let matches = |a: Vector3<f32>, b: Vector3<f32>| (a - b).norm() <= 1e-5;
+        for first in (0..self.indices.len() / 3).map(|i| i * 3) {
+            let Some(triangle) = self.triangle_at(first) else {
+                continue;
+            };
+            let Some(normal) = self.face_normal(triangle).try_normalize(f32::EPSILON) else {
+                continue;
+            };
+            for (corner, vertex) in triangle.into_iter().enumerate() {
+                match normals[vertex] {
+                    None => normals[vertex] = Some(normal),
+                    Some(existing) if matches(existing, normal) => (),
+                    Some(_) => {
+                        let existing = copies.get(&vertex).and_then(|copies| {
+                            copies
+                                .iter()
+                                .copied()
+                                .find(|&copy| normals[copy].is_some_and(|n| matches(n, normal)))
+                        });
+                        let copy = existing.unwrap_or_else(|| {
+                            let copy = self.duplicate_vertex(vertex);
+                            normals.push(Some(normal));
+                            copies.entry(vertex).or_default().push(copy);
+                            copy
+                        });
+                        self.indices.set(first + corner, copy as u32);
+                    }
+                }
+            }
+        }
+        self.normals = normals
+            .into_iter()
+            .map(|normal| normal.unwrap_or_else(Vector3::y))
+            .collect();
+    }
+
+    /// Appends copy of a vertex with all its attributes except normal,
+    /// returns its index.
+    fn duplicate_vertex(&mut self, vertex: usize) -> usize {
+        let count = self.positions.len();
+        self.positions.push(self.positions[vertex]);
+        if self.tex_coords.len() == count {
+            self.tex_coords.push(self.tex_coords[vertex]);
+        }
+        if self.tangents.len() == count {
+            self.tangents.push(self.tangents[vertex]);
+        }
+        if self.colors.len() == count {
+            self.colors.push(self.colors[vertex]);
+        }
+        if self.bone_indices.len() == count && self.bone_weights.len() == count {
+            self.bone_indices.push(self.bone_indices[vertex]);
+            self.bone_weights.push(self.bone_weights[vertex]);
+        }
+        count
+    }
+
+    /// Calculates per-vertex tangents along the U texture direction. W holds
+    /// handedness of the bitangent, so it can be restored as
+    /// `cross(normal, tangent) * w`.
+    pub fn calculate_tangents(&mut self) {
+        let count = self.positions.len();
+        let mut tangents = vec![Vector3::<f32>::zeros(); count];
+        let mut bitangents = vec![Vector3::<f32>::zeros(); count];
+        for first in (0..self.indices.len() / 3).map(|i| i * 3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.indices.get(first + i) as usize);
+            if a >= count || b >= count || c >= count || self.tex_coords.len() < count {
+                continue;
+            }
+            let edge1 = self.positions[b] - self.positions[a];
+            let edge2 = self.positions[c] - self.positions[a];
+            let uv1 = self.tex_coords[b] - self.tex_coords[a];
+            let uv2 = self.tex_coords[c] - self.tex_coords[a];
+            let determinant = uv1.x * uv2.y - uv2.x * uv1.y;
+            if determinant.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * uv2.y - edge2 * uv1.y) / determinant;
+            let bitangent = (edge2 * uv1.x - edge1 * uv2.x) / determinant;
+            for index in [a, b, c] {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        self.tangents = (0..count)
+            .map(|i| {
+                let normal = self.normals.get(i).copied().unwrap_or_else(Vector3::y);
+                // Gram-Schmidt against the normal, any perpendicular
+                // direction will do for vertices without usable UVs.
+                let tangent = (tangents[i] - normal * normal.dot(&tangents[i]))
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(|| {
+                        let axis = if normal.x.abs() < 0.9 {
+                            Vector3::x()
+                        } else {
+                            Vector3::y()
+                        };
+                        normal.cross(&axis).normalize()
+                    });
+                let w = if normal.cross(&tangent).dot(&bitangents[i]) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                Vector4::new(tangent.x, tangent.y, tangent.z, w)
+            })
+            .collect();
+        self.need_upload = true;
+    }
+
+    pub fn get_vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn get_triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn get_positions(&self) -> &[Vector3<f32>] {
+        &self.positions
+    }
+
+    pub fn get_normals(&self) -> &[Vector3<f32>] {
+        &self.normals
+    }
+
+    pub fn get_tex_coords(&self) -> &[Vector2<f32>] {
+        &self.tex_coords
+    }
+
+    pub fn get_tangents(&self) -> &[Vector4<f32>] {
+        &self.tangents
+    }
+
+    /// Mutable accessors below schedule upload of the whole surface. Every
+    /// attribute must keep an entry per vertex, changing the vertex count
+    /// reallocates buffers on the next upload.
+    pub fn positions_mut(&mut self) -> &mut Vec<Vector3<f32>> {
+        self.need_upload = true;
+        self.bounds.set(None);
+        &mut self.positions
+    }
+
+    pub fn normals_mut(&mut self) -> &mut Vec<Vector3<f32>> {
+        self.need_upload = true;
+        &mut self.normals
+    }
+
+    pub fn tex_coords_mut(&mut self) -> &mut Vec<Vector2<f32>> {
+        self.need_upload = true;
+        &mut self.tex_coords
+    }
+
+    pub fn tangents_mut(&mut self) -> &mut Vec<Vector4<f32>> {
+        self.need_upload = true;
+        &mut self.tangents
+    }
+
+    pub fn colors_mut(&mut self) -> &mut Vec<Vector4<f32>> {
+        self.need_upload = true;
+        &mut self.colors
+    }
+
+    /// Indices are not validated, they must stay in range of vertices.
+    pub fn indices_mut(&mut self) -> &mut IndexStorage {
+        self.need_upload = true;
+        &mut self.indices
+    }
+
+    /// Checks that the range starts at a triangle and covers whole triangles
+    /// inside the index buffer.
+    pub fn check_range(&self, range: DrawRange) -> Result<(), SurfaceDataError> {
+        let len = self.indices.len();
+        let whole = range.first_index.is_multiple_of(3) && range.index_count.is_multiple_of(3);
+        if !whole || range.end() > len {
+            return Err(SurfaceDataError::InvalidRange { range, len });
+        }
+        Ok(())
+    }
 
-            self.need_upload = false;
+    /// Surfaces modified every frame should be dynamic. Changing usage
+    /// reallocates buffers on the next upload.
+    pub fn set_usage(&mut self, usage: BufferUsage) {
+        if self.usage != usage {
+            self.usage = usage;
+            self.need_upload = true;
         }
     }
 
-    pub fn calculate_tangents(&self) {}
+    pub fn get_usage(&self) -> BufferUsage {
+        self.usage
+    }
+
+    pub fn need_upload(&self) -> bool {
+        self.need_upload
+    }
+
+    /// False until the first upload creates GPU objects.
+    pub fn has_gpu_buffers(&self) -> bool {
+        self.vao.is_some()
+    }
+
+    /// Number of uploads that allocated GPU buffers, the first upload
+    /// included. Uploads of modified vertices of unchanged count update
+    /// buffers in place and are not counted.
+    pub fn get_reallocation_count(&self) -> usize {
+        self.reallocations
+    }
+
+    /// Sets color of every vertex, empty colors make the surface white.
+    /// Surfaces with other number of colors than vertices are not drawn.
+    pub fn set_colors(&mut self, colors: Vec<Vector4<f32>>) {
+        self.colors = colors;
+        self.need_upload = true;
+    }
+
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+
+    pub fn get_colors(&self) -> &[Vector4<f32>] {
+        &self.colors
+    }
+
+    /// Sets up to four bone influences of every vertex, both channels must
+    /// have an entry per vertex or the surface is not drawn.
+    pub fn set_skin(&mut self, bone_indices: Vec<[u16; 4]>, bone_weights: Vec<Vector4<f32>>) {
+        self.bone_indices = bone_indices;
+        self.bone_weights = bone_weights;
+        self.need_upload = true;
+    }
+
+    pub fn is_skinned(&self) -> bool {
+        !self.bone_indices.is_empty()
+    }
+
+    pub fn get_bone_indices(&self) -> &[[u16; 4]] {
+        &self.bone_indices
+    }
+
+    pub fn get_bone_weights(&self) -> &[Vector4<f32>] {
+        &self.bone_weights
+    }
+
+    pub fn get_indices(&self) -> &IndexStorage {
+        &self.indices
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from_points(&self.positions)
+    }
+
+    /// Box of positions, invalid for surfaces without vertices.
+    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        if let Some(bounds) = self.bounds.get() {
+            return bounds;
+        }
+        let bounds = AxisAlignedBoundingBox::from_points(&self.positions);
+        self.bounds.set(Some(bounds));
+        bounds
+    }
 
     pub fn make_cube() -> Self {
         let mut data = Self::new();
@@ -247,71 +1051,932 @@ impl SurfaceSharedData {
             Vector2::new(1.0, 1.0),
             Vector2::new(1.0, 0.0),
         ];
-        data.indices = vec![
+        data.indices = IndexStorage::U16(vec![
             2, 1, 0, 3, 2, 0, 4, 5, 6, 4, 6, 7, 10, 9, 8, 11, 10, 8, 12, 13, 14, 12, 14, 15, 18,
             17, 16, 19, 18, 16, 20, 21, 22, 20, 22, 23,
-        ];
+        ]);
+
+        data
+    }
+
+    /// Cylinder along Y centered at the origin, with `sides` faces around at
+    /// least three. Side texture wraps around once, caps are mapped from
+    /// above.
+    pub fn make_cylinder(sides: usize, height: f32, radius: f32, caps: bool) -> Self {
+        let sides = sides.max(3);
+        let half = height * 0.5;
+        let mut geometry = Geometry::default();
+        for i in 0..=sides {
+            // Seam vertices repeat the first ones exactly, only U differs.
+            let (sin, cos) = ((i % sides) as f32 / sides as f32 * std::f32::consts::TAU).sin_cos();
+            let normal = Vector3::new(sin, 0.0, cos);
+            let u = i as f32 / sides as f32;
+            for (y, v) in [(-half, 0.0), (half, 1.0)] {
+                geometry.push(
+                    Vector3::new(sin * radius, y, cos * radius),
+                    normal,
+                    Vector2::new(u, v),
+                );
+            }
+        }
+        for i in 0..sides as u32 {
+            let (bottom, top) = (i * 2, i * 2 + 1);
+            let (next_bottom, next_top) = (bottom + 2, top + 2);
+            geometry.indices.extend([bottom, next_bottom, top]);
+            geometry.indices.extend([top, next_bottom, next_top]);
+        }
+        if caps {
+            geometry.push_cap(sides, half, radius, true);
+            geometry.push_cap(sides, -half, radius, false);
+        }
+        geometry.build()
+    }
+
+    /// Cone along Y centered at the origin with apex on top, see
+    /// `make_cylinder`. Side normals are perpendicular to the slope, apex is
+    /// split per side so its normals follow the faces.
+    pub fn make_cone(sides: usize, height: f32, radius: f32) -> Self {
+        let sides = sides.max(3);
+        let half = height * 0.5;
+        let mut geometry = Geometry::default();
+        let normal_at = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            Vector3::new(sin * height, radius, cos * height)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y)
+        };
+        for i in 0..=sides {
+            let angle = (i % sides) as f32 / sides as f32 * std::f32::consts::TAU;
+            let (sin, cos) = angle.sin_cos();
+            geometry.push(
+                Vector3::new(sin * radius, -half, cos * radius),
+                normal_at(angle),
+                Vector2::new(i as f32 / sides as f32, 0.0),
+            );
+        }
+        let apex = geometry.positions.len() as u32;
+        for i in 0..sides {
+            let t = (i as f32 + 0.5) / sides as f32;
+            geometry.push(
+                Vector3::new(0.0, half, 0.0),
+                normal_at(t * std::f32::consts::TAU),
+                Vector2::new(t, 1.0),
+            );
+        }
+        for i in 0..sides as u32 {
+            geometry.indices.extend([i, i + 1, apex + i]);
+        }
+        geometry.push_cap(sides, -half, radius, false);
+        geometry.build()
+    }
 
+    /// Terrain in XZ plane centered at the origin, `size` long along X and
+    /// Z, with `resolution` cells along each side, at least one. Heights are
+    /// luminance of `heightmap` times `height_scale` sampled bilinearly, so
+    /// images of any size and aspect fit any resolution. The texture covers
+    /// the terrain once with its top row at -Z.
+    pub fn make_terrain(
+        heightmap: &Texture,
+        size: Vector2<f32>,
+        height_scale: f32,
+        resolution: usize,
+    ) -> Self {
+        let resolution = resolution.max(1);
+        let side = resolution + 1;
+        let mut positions = Vec::with_capacity(side * side);
+        let mut tex_coords = Vec::with_capacity(side * side);
+        for row in 0..side {
+            for column in 0..side {
+                let uv = Vector2::new(column as f32, row as f32) / resolution as f32;
+                let height = heightmap.sample_luminance(uv) * height_scale;
+                positions.push(Vector3::new(
+                    (uv.x - 0.5) * size.x,
+                    height,
+                    (uv.y - 0.5) * size.y,
+                ));
+                tex_coords.push(uv);
+            }
+        }
+        let side = side as u32;
+        let mut indices = Vec::with_capacity(resolution * resolution * 6);
+        for row in 0..resolution as u32 {
+            for column in 0..resolution as u32 {
+                let top_left = row * side + column;
+                let bottom_left = top_left + side;
+                indices.extend([top_left, bottom_left, top_left + 1]);
+                indices.extend([top_left + 1, bottom_left, bottom_left + 1]);
+            }
+        }
+        let mut data = Self::new();
+        data.indices = IndexStorage::new(indices, positions.len());
+        data.positions = positions;
+        data.tex_coords = tex_coords;
+        data.calculate_normals(true);
+        data.calculate_tangents();
         data
     }
 }
 
+/// Vertices collected by procedural shapes.
+#[derive(Default)]
+struct Geometry {
+    positions: Vec<Vector3<f32>>,
+    normals: Vec<Vector3<f32>>,
+    tex_coords: Vec<Vector2<f32>>,
+    indices: Vec<u32>,
+}
+
+impl Geometry {
+    fn push(&mut self, position: Vector3<f32>, normal: Vector3<f32>, tex_coord: Vector2<f32>) {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.tex_coords.push(tex_coord);
+    }
+
+    /// Disk at height `y` facing up or down, as a fan around its center.
+    fn push_cap(&mut self, sides: usize, y: f32, radius: f32, up: bool) {
+        let normal = if up { Vector3::y() } else { -Vector3::y() };
+        let center = self.positions.len() as u32;
+        self.push(Vector3::new(0.0, y, 0.0), normal, Vector2::new(0.5, 0.5));
+        for i in 0..sides {
+            let (sin, cos) = (i as f32 / sides as f32 * std::f32::consts::TAU).sin_cos();
+            self.push(
+                Vector3::new(sin * radius, y, cos * radius),
+                normal,
+                Vector2::new(0.5 + sin * 0.5, 0.5 - cos * 0.5),
+            );
+        }
+        for i in 0..sides as u32 {
+            let current = center + 1 + i;
+            let next = center + 1 + (i + 1) % sides as u32;
+            if up {
+                self.indices.extend([center, current, next]);
+            } else {
+                self.indices.extend([center, next, current]);
+            }
+        }
+    }
+
+    fn build(self) -> SurfaceSharedData {
+        SurfaceSharedData::from_vertices(
+            self.positions,
+            self.normals,
+            self.tex_coords,
+            self.indices,
+        )
+    }
+}
+
 impl Drop for SurfaceSharedData {
     fn drop(&mut self) {
-        unsafe {
-            let gl = GL.get().unwrap();
-            gl.delete_buffer(self.vbo);
-            gl.delete_buffer(self.ebo);
-            gl.delete_vertex_array(self.vao);
+        if let Some(vbo) = self.vbo.take() {
+            deletion::schedule(GpuObject::Buffer(vbo));
+        }
+        if let Some(ebo) = self.ebo.take() {
+            deletion::schedule(GpuObject::Buffer(ebo));
+        }
+        if let Some(vao) = self.vao.take() {
+            deletion::schedule(GpuObject::VertexArray(vao));
         }
     }
 }
 
-type SurfaceSharedDataRef = Rc<RefCell<SurfaceSharedData>>;
+pub type SurfaceSharedDataRef = Rc<RefCell<SurfaceSharedData>>;
 
-#[derive(Debug)]
+/// Procedural surface with its parameters, floats are kept as bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum PrimitiveKey {
+    Cube,
+    Cylinder {
+        sides: usize,
+        height: u32,
+        radius: u32,
+        caps: bool,
+    },
+    Cone {
+        sides: usize,
+        height: u32,
+        radius: u32,
+    },
+}
+
+thread_local! {
+    /// Procedural surfaces shared by meshes, GL objects can't leave the
+    /// thread of their context anyway.
+    static PRIMITIVES: RefCell<HashMap<PrimitiveKey, SurfaceSharedDataRef>> =
+        RefCell::new(HashMap::new());
+}
+
+fn cached(key: PrimitiveKey, make: impl FnOnce() -> SurfaceSharedData) -> SurfaceSharedDataRef {
+    PRIMITIVES.with(|primitives| {
+        primitives
+            .borrow_mut()
+            .entry(key)
+            .or_insert_with(|| Rc::new(RefCell::new(make())))
+            .clone()
+    })
+}
+
+/// Drops cached primitives used by nothing but the cache, so their GPU
+/// buffers get deleted. Returns number of dropped ones.
+pub fn evict_unused_primitives() -> usize {
+    PRIMITIVES.with(|primitives| {
+        let mut primitives = primitives.borrow_mut();
+        let count = primitives.len();
+        primitives.retain(|_, data| Rc::strong_count(data) > 1);
+        count - primitives.len()
+    })
+}
+
+impl SurfaceSharedData {
+    /// Cached versions of procedural surfaces return the same data for same
+    /// parameters, modifying it changes every user.
+    pub fn cached_cube() -> SurfaceSharedDataRef {
+        cached(PrimitiveKey::Cube, Self::make_cube)
+    }
+
+    pub fn cached_cylinder(
+        sides: usize,
+        height: f32,
+        radius: f32,
+        caps: bool,
+    ) -> SurfaceSharedDataRef {
+        let key = PrimitiveKey::Cylinder {
+            sides: sides.max(3),
+            height: height.to_bits(),
+            radius: radius.to_bits(),
+            caps,
+        };
+        cached(key, || Self::make_cylinder(sides, height, radius, caps))
+    }
+
+    pub fn cached_cone(sides: usize, height: f32, radius: f32) -> SurfaceSharedDataRef {
+        let key = PrimitiveKey::Cone {
+            sides: sides.max(3),
+            height: height.to_bits(),
+            radius: radius.to_bits(),
+        };
+        cached(key, || Self::make_cone(sides, height, radius))
+    }
+}
+
+/// Cloned surfaces share geometry and texture.
+#[derive(Debug, Clone)]
 pub struct Surface {
     pub(crate) data: SurfaceSharedDataRef,
-    pub(crate) texture: Option<Rc<RefCell<Resource>>>,
+    material: Material,
+    /// Whole index buffer is drawn without a range.
+    range: Option<DrawRange>,
+    /// Range no longer fitting changed data is reported once.
+    range_error_logged: Cell<bool>,
 }
 
 impl Surface {
     pub fn new(data: &SurfaceSharedDataRef) -> Self {
         Self {
             data: data.clone(),
-            texture: None,
+            material: Material::default(),
+            range: None,
+            range_error_logged: Cell::new(false),
         }
     }
+
+    /// Sets diffuse texture of the material.
     pub fn set_texture(&mut self, tex: Rc<RefCell<Resource>>) {
-        if let ResourceKind::Texture(_) = tex.borrow_mut().borrow_kind() {
-            self.texture = Some(tex.clone());
-        } else {
-            self.texture = None;
+        self.material.set_diffuse_texture(Some(tex));
+    }
+
+    pub fn get_data(&self) -> &SurfaceSharedDataRef {
+        &self.data
+    }
+
+    pub fn get_texture(&self) -> Option<&Rc<RefCell<Resource>>> {
+        self.material.get_diffuse_texture()
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn get_material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    /// Limits drawing to a part of the index buffer, None draws all of it.
+    /// Ranges outside the current buffer are rejected.
+    pub fn set_draw_range(&mut self, range: Option<DrawRange>) {
+        if let Some(range) = range {
+            if let Err(error) = self.data.borrow().check_range(range) {
+                println!("Invalid draw range: {}", error);
+                return;
+            }
+        }
+        self.range = range;
+        self.range_error_logged.set(false);
+    }
+
+    pub fn get_draw_range(&self) -> Option<DrawRange> {
+        self.range
+    }
+
+    /// Indices to draw from `data`, None if the range does not fit indices
+    /// changed after it was set.
+    fn indices_to_draw(&self, data: &SurfaceSharedData) -> Option<DrawRange> {
+        let Some(range) = self.range else {
+            return Some(DrawRange::new(0, data.indices.len()));
+        };
+        match data.check_range(range) {
+            Ok(()) => Some(range),
+            Err(error) => {
+                if !self.range_error_logged.replace(true) {
+                    println!("Surface is not drawn: {}", error);
+                }
+                None
+            }
         }
     }
 
-    pub fn draw(&self) {
+    /// Draws surface, returns number of rendered triangles. Surfaces without
+    /// texture are drawn with `fallback` one.
+    pub fn draw(&self, fallback: Option<NativeTexture>) -> usize {
         unsafe {
             let gl = GL.get().unwrap();
 
             let mut data = self.data.borrow_mut();
             if data.need_upload {
-                data.upload();
+                // Invalid data stays waiting for upload and is not drawn.
+                match data.check_upload() {
+                    Ok(()) => data.upload(),
+                    Err(Some(error)) => {
+                        println!("Surface is not drawn: {}", error);
+                        return 0;
+                    }
+                    Err(None) => return 0,
+                }
             }
-            if let Some(ref resource) = self.texture {
+            if let Some(resource) = self.material.get_diffuse_texture() {
                 if let ResourceKind::Texture(texture) = &resource.borrow_mut().borrow_kind() {
                     gl.bind_texture(glow::TEXTURE_2D, texture.gpu_tex);
                 }
             } else {
-                gl.bind_texture(glow::TEXTURE_2D, None);
+                gl.bind_texture(glow::TEXTURE_2D, fallback);
             }
-            gl.bind_vertex_array(Some(data.vao));
+            let Some(range) = self.indices_to_draw(&data) else {
+                return 0;
+            };
+            gl.bind_vertex_array(data.vao);
             gl.draw_elements(
                 glow::TRIANGLES,
-                data.indices.len() as i32,
-                glow::UNSIGNED_INT,
-                0,
+                range.index_count as i32,
+                data.indices.gl_type(),
+                (range.first_index * data.indices.index_size()) as i32,
+            );
+            range.index_count / 3
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use nalgebra::{Vector2, Vector3, Vector4};
+
+    use super::{
+        evict_unused_primitives, BufferUsage, DrawRange, IndexStorage, Surface, SurfaceDataError,
+        SurfaceSharedData,
+    };
+    use crate::resource::texture::Texture;
+
+    /// Every triangle has area and faces the same way as its vertex normals.
+    fn check_triangles(data: &SurfaceSharedData) {
+        let positions = data.get_positions();
+        let normals = data.get_normals();
+        for triangle in data.get_indices().to_u32().chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+            let face = (positions[b] - positions[a]).cross(&(positions[c] - positions[a]));
+            assert!(face.norm() > 1e-6, "{:?} is degenerate", triangle);
+            for i in [a, b, c] {
+                assert!(face.dot(&normals[i]) > 0.0, "{:?} is flipped", triangle);
+            }
+        }
+    }
+
+    #[test]
+    fn cylinder() {
+        let data = SurfaceSharedData::make_cylinder(8, 2.0, 0.5, true);
+        assert_eq!(data.get_vertex_count(), 4 * 9);
+        assert_eq!(data.get_triangle_count(), 4 * 8);
+        check_triangles(&data);
+        let bounds = data.bounding_box();
+        assert!((bounds.max - Vector3::new(0.5, 1.0, 0.5)).norm() < 1e-6);
+        assert!((bounds.min + Vector3::new(0.5, 1.0, 0.5)).norm() < 1e-6);
+        // Side normals are radial, cap normals axial.
+        for (position, normal) in data.get_positions().iter().zip(data.get_normals()) {
+            if normal.y == 0.0 {
+                let radial = Vector3::new(position.x, 0.0, position.z) * 2.0;
+                assert!((normal - radial).norm() < 1e-5);
+            } else {
+                assert_eq!(normal.y, position.y.signum());
+            }
+        }
+        // Side wraps around once.
+        let u: Vec<f32> = data.get_tex_coords().iter().map(|uv| uv.x).collect();
+        assert_eq!((u[0], u[17]), (0.0, 1.0));
+
+        let open = SurfaceSharedData::make_cylinder(2, 1.0, 1.0, false);
+        assert_eq!(open.get_vertex_count(), 2 * 4);
+        assert_eq!(open.get_triangle_count(), 2 * 3);
+        check_triangles(&open);
+    }
+
+    /// Regular tetrahedron centered at the origin, vertices shared by faces.
+    fn tetrahedron() -> SurfaceSharedData {
+        SurfaceSharedData::from_vertices(
+            vec![
+                Vector3::new(1.0, 1.0, 1.0),
+                Vector3::new(1.0, -1.0, -1.0),
+                Vector3::new(-1.0, 1.0, -1.0),
+                Vector3::new(-1.0, -1.0, 1.0),
+            ],
+            Vec::new(),
+            vec![Vector2::zeros(); 4],
+            vec![0, 1, 2, 0, 3, 1, 0, 2, 3, 1, 3, 2],
+        )
+    }
+
+    #[test]
+    fn smooth_normals() {
+        let mut data = tetrahedron();
+        data.calculate_normals(true);
+        assert_eq!(data.get_vertex_count(), 4);
+        for (position, normal) in data.get_positions().iter().zip(data.get_normals()) {
+            assert!((normal - position.normalize()).norm() < 1e-5);
+        }
+
+        // Vertices split by a seam share the normal.
+        let mut data = SurfaceSharedData::make_cylinder(4, 1.0, 1.0, false);
+        data.calculate_normals(true);
+        let normals = data.get_normals();
+        assert_eq!(normals[0], normals[8]);
+        assert_eq!(normals[1], normals[9]);
+        assert!(normals.iter().all(|normal| normal.y == 0.0));
+    }
+
+    #[test]
+    fn flat_normals() {
+        let cube = SurfaceSharedData::make_cube();
+        let mut data = SurfaceSharedData::make_cube();
+        data.calculate_normals(false);
+        assert_eq!(data.get_normals(), cube.get_normals());
+        assert_eq!(data.get_indices(), cube.get_indices());
+
+        let mut data = tetrahedron();
+        data.calculate_tangents();
+        data.calculate_normals(false);
+        assert_eq!(data.get_vertex_count(), 12);
+        assert_eq!(data.get_tex_coords().len(), 12);
+        assert_eq!(data.get_tangents().len(), 12);
+        check_triangles(&data);
+        for triangle in data.get_indices().to_u32().chunks_exact(3) {
+            let normals = triangle.iter().map(|&i| data.get_normals()[i as usize]);
+            for normal in normals {
+                assert_eq!(normal, data.get_normals()[triangle[0] as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_normals() {
+        let positions = vec![
+            Vector3::zeros(),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+        ];
+        for smooth in [true, false] {
+            let mut data = SurfaceSharedData::from_vertices(
+                positions.clone(),
+                Vec::new(),
+                Vec::new(),
+                vec![0, 1, 2],
+            );
+            data.calculate_normals(smooth);
+            assert!(data.get_normals().iter().all(|n| *n == Vector3::y()));
+        }
+    }
+
+    #[test]
+    fn raw_data_validation() {
+        let positions = vec![Vector3::zeros(), Vector3::x(), Vector3::y()];
+        let tex_coords = vec![Vector2::zeros(); 3];
+        let raw = |tex_coords: &[Vector2<f32>], normals: &[Vector3<f32>], indices: &[u32]| {
+            SurfaceSharedData::from_raw(
+                positions.clone(),
+                tex_coords.to_vec(),
+                normals.to_vec(),
+                Vec::new(),
+                Vec::new(),
+                indices.to_vec(),
+            )
+        };
+        assert_eq!(
+            raw(&tex_coords[..2], &[], &[0, 1, 2]).unwrap_err(),
+            SurfaceDataError::AttributeLength {
+                attribute: "tex_coords",
+                len: 2,
+                expected: 3
+            }
+        );
+        assert_eq!(
+            raw(&tex_coords, &[Vector3::z()], &[0, 1, 2]).unwrap_err(),
+            SurfaceDataError::AttributeLength {
+                attribute: "normals",
+                len: 1,
+                expected: 3
+            }
+        );
+        assert_eq!(
+            raw(&tex_coords, &[], &[0, 1, 2, 0]).unwrap_err(),
+            SurfaceDataError::PartialTriangle(4)
+        );
+        for index in [3, u32::MAX] {
+            assert_eq!(
+                raw(&tex_coords, &[], &[0, 1, index]).unwrap_err(),
+                SurfaceDataError::IndexOutOfRange {
+                    index,
+                    vertex_count: 3
+                }
             );
         }
+        let tangents = SurfaceSharedData::from_raw(
+            positions.clone(),
+            tex_coords.clone(),
+            Vec::new(),
+            vec![Vector4::zeros(); 2],
+            Vec::new(),
+            vec![0, 1, 2],
+        );
+        assert!(matches!(
+            tangents,
+            Err(SurfaceDataError::AttributeLength {
+                attribute: "tangents",
+                ..
+            })
+        ));
+
+        // Missing normals and tangents are calculated.
+        let data = raw(&tex_coords, &[], &[0, 1, 2]).unwrap();
+        assert_eq!(data.get_normals(), [Vector3::z(); 3]);
+        assert_eq!(data.get_tangents().len(), 3);
+        assert_eq!(data.get_triangle_count(), 1);
+    }
+
+    #[test]
+    fn validation() {
+        let empty = SurfaceSharedData::from_triangles(Vec::new(), Vec::new());
+        assert_eq!(empty.validate(), Err(SurfaceDataError::Empty));
+        assert_eq!(
+            SurfaceSharedData::from_raw(
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new()
+            )
+            .unwrap_err(),
+            SurfaceDataError::Empty
+        );
+
+        let mut cube = SurfaceSharedData::make_cube();
+        assert_eq!(cube.validate(), Ok(()));
+        cube.normals_mut().pop();
+        assert_eq!(
+            cube.validate(),
+            Err(SurfaceDataError::AttributeLength {
+                attribute: "normals",
+                len: 23,
+                expected: 24
+            })
+        );
+        cube.normals_mut().push(Vector3::y());
+        cube.indices_mut().push(24);
+        assert_eq!(cube.validate(), Err(SurfaceDataError::PartialTriangle(37)));
+        cube.indices_mut().push(0);
+        cube.indices_mut().push(1);
+        assert_eq!(
+            cube.validate(),
+            Err(SurfaceDataError::IndexOutOfRange {
+                index: 24,
+                vertex_count: 24
+            })
+        );
+    }
+
+    #[test]
+    fn error_logged_once() {
+        let mut cube = SurfaceSharedData::make_cube();
+        cube.tex_coords_mut().clear();
+        let missing = cube.validate().unwrap_err();
+        assert_eq!(cube.check_upload(), Err(Some(missing.clone())));
+        assert_eq!(cube.check_upload(), Err(None));
+        assert_eq!(cube.check_upload(), Err(None));
+
+        // Other error is logged, so is the same one after a fix.
+        *cube.tex_coords_mut() = vec![Vector2::zeros(); 24];
+        cube.indices_mut().push(0);
+        assert_eq!(
+            cube.check_upload(),
+            Err(Some(SurfaceDataError::PartialTriangle(37)))
+        );
+        cube.indices_mut().truncate(36);
+        assert_eq!(cube.check_upload(), Ok(()));
+        cube.tex_coords_mut().clear();
+        assert_eq!(cube.check_upload(), Err(Some(missing)));
+    }
+
+    #[test]
+    fn mismatched_setters() {
+        // Bad lengths are reported by validation and wait for upload, so the
+        // surface is skipped instead of panicking.
+        let mut cube = SurfaceSharedData::make_cube();
+        cube.set_colors(vec![Vector4::repeat(1.0); 3]);
+        assert!(cube.need_upload());
+        assert_eq!(
+            cube.check_upload(),
+            Err(Some(SurfaceDataError::AttributeLength {
+                attribute: "colors",
+                len: 3,
+                expected: 24,
+            }))
+        );
+        assert_eq!(cube.check_upload(), Err(None));
+        assert!(cube.need_upload());
+
+        cube.set_colors(Vec::new());
+        cube.set_skin(vec![[0; 4]; 24], vec![Vector4::x(); 23]);
+        assert_eq!(
+            cube.check_upload(),
+            Err(Some(SurfaceDataError::AttributeLength {
+                attribute: "bone_weights",
+                len: 23,
+                expected: 24,
+            }))
+        );
+        cube.set_skin(vec![[0; 4]; 24], vec![Vector4::x(); 24]);
+        assert_eq!(cube.check_upload(), Ok(()));
+    }
+
+    #[test]
+    fn dynamic_updates() {
+        // Plane of 4x4 quads in XZ.
+        let positions = (0..25)
+            .map(|i| Vector3::new((i % 5) as f32, 0.0, (i / 5) as f32))
+            .collect();
+        let indices = (0..16)
+            .flat_map(|quad| {
+                let corner = quad / 4 * 5 + quad % 4;
+                [
+                    corner,
+                    corner + 5,
+                    corner + 1,
+                    corner + 1,
+                    corner + 5,
+                    corner + 6,
+                ]
+            })
+            .collect();
+        let mut data = SurfaceSharedData::from_triangles(positions, indices);
+        data.set_usage(BufferUsage::Dynamic);
+        assert_eq!(data.begin_upload().0.usage, BufferUsage::Dynamic);
+        assert!(!data.need_upload());
+
+        for frame in 0..10 {
+            let time = frame as f32 * 0.1;
+            for position in data.positions_mut().iter_mut() {
+                position.y = (position.x + time).sin();
+            }
+            assert!(data.need_upload());
+            assert!(!data.begin_upload().1);
+        }
+        assert_eq!(data.get_reallocation_count(), 1);
+        assert!(data.bounding_box().max.y > 0.9);
+
+        // Vertex count changes reallocate, so does usage.
+        data.positions_mut().push(Vector3::zeros());
+        data.normals_mut().push(Vector3::y());
+        data.tex_coords_mut().push(Vector2::zeros());
+        data.tangents_mut().push(Vector4::x());
+        assert!(data.begin_upload().1);
+        data.set_usage(BufferUsage::Static);
+        assert!(data.begin_upload().1);
+        assert!(!data.begin_upload().1);
+        assert_eq!(data.get_reallocation_count(), 3);
+    }
+
+    #[test]
+    fn primitive_cache() {
+        let a = SurfaceSharedData::cached_cube();
+        let b = SurfaceSharedData::cached_cube();
+        assert!(Rc::ptr_eq(&a, &b));
+        // Cache holds the third reference.
+        assert_eq!(Rc::strong_count(&a), 3);
+
+        let cone = SurfaceSharedData::cached_cone(8, 1.0, 0.5);
+        assert!(Rc::ptr_eq(
+            &cone,
+            &SurfaceSharedData::cached_cone(8, 1.0, 0.5)
+        ));
+        assert!(!Rc::ptr_eq(
+            &cone,
+            &SurfaceSharedData::cached_cone(8, 2.0, 0.5)
+        ));
+        // Sides are clamped before keying.
+        assert!(Rc::ptr_eq(
+            &SurfaceSharedData::cached_cylinder(2, 1.0, 1.0, true),
+            &SurfaceSharedData::cached_cylinder(3, 1.0, 1.0, true)
+        ));
+
+        // Unused cone of height 2 and the cylinder go.
+        assert_eq!(evict_unused_primitives(), 2);
+        drop(b);
+        assert_eq!(evict_unused_primitives(), 0);
+        drop((a, cone));
+        assert_eq!(evict_unused_primitives(), 2);
+    }
+
+    #[test]
+    fn color_layout() {
+        let mut data = SurfaceSharedData::make_cube();
+        let plain = data.layout();
+        // Cube has no tangents.
+        assert_eq!(plain.offsets(), [0, 288, 480, 768, 768, 768, 768]);
+        assert_eq!(plain.vertex_bytes(), 768);
+
+        // Corners from black to white, so every face has a gradient.
+        let colors = data
+            .get_positions()
+            .iter()
+            .map(|p| p.add_scalar(0.5).push(1.0))
+            .collect();
+        data.set_colors(colors);
+        let colored = data.layout();
+        assert_eq!(colored.offsets(), [0, 288, 480, 768, 768, 1152, 1152]);
+        assert_eq!(colored.vertex_bytes(), 1152);
+        assert_eq!(data.get_colors()[2], Vector4::new(1.0, 1.0, 1.0, 1.0));
+
+        // Flat normals duplicate colors along with vertices.
+        data.calculate_normals(false);
+        assert_eq!(data.get_colors().len(), data.get_vertex_count());
+        assert!(SurfaceSharedData::from_raw(
+            vec![Vector3::zeros(); 3],
+            vec![Vector2::zeros(); 3],
+            Vec::new(),
+            Vec::new(),
+            vec![Vector4::x(); 2],
+            vec![0, 1, 2],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn index_storage() {
+        let cube = SurfaceSharedData::make_cube();
+        assert_eq!(cube.get_indices().gl_type(), glow::UNSIGNED_SHORT);
+        assert_eq!(cube.layout().indices, 72);
+
+        assert!(matches!(
+            IndexStorage::new(vec![0, 1, 65535], 65536),
+            IndexStorage::U16(_)
+        ));
+        let indices = IndexStorage::new(vec![0, 1, 2], 65537);
+        assert_eq!(indices.gl_type(), glow::UNSIGNED_INT);
+        assert_eq!(indices.to_u16(), Some(vec![0, 1, 2]));
+
+        // Indices over 16 bits widen the storage.
+        let mut indices = IndexStorage::U16(vec![0, 1, 2]);
+        indices.push(3);
+        assert!(matches!(indices, IndexStorage::U16(_)));
+        indices.set(0, 70000);
+        assert_eq!(indices, IndexStorage::U32(vec![70000, 1, 2, 3]));
+        assert_eq!(indices.to_u16(), None);
+        assert_eq!(indices.as_bytes().len(), 16);
+    }
+
+    #[test]
+    fn bounding_box() {
+        let cube = SurfaceSharedData::make_cube().bounding_box();
+        assert_eq!(cube.min, Vector3::repeat(-0.5));
+        assert_eq!(cube.max, Vector3::repeat(0.5));
+        let empty = SurfaceSharedData::from_triangles(Vec::new(), Vec::new());
+        assert!(!empty.bounding_box().is_valid());
+    }
+
+    #[test]
+    fn cone() {
+        let data = SurfaceSharedData::make_cone(6, 1.0, 1.0);
+        assert_eq!(data.get_vertex_count(), 3 * 6 + 2);
+        assert_eq!(data.get_triangle_count(), 2 * 6);
+        check_triangles(&data);
+        // Slope of 45 degrees.
+        let normal = data.get_normals()[0];
+        assert!((normal - Vector3::new(0.0, 1.0, 1.0).normalize()).norm() < 1e-5);
+    }
+
+    #[test]
+    fn draw_ranges() {
+        let data = SurfaceSharedData::cached_cube();
+        let full = Surface::new(&data);
+        let mut halves = [Surface::new(&data), Surface::new(&data)];
+        halves[0].set_draw_range(Some(DrawRange::new(0, 18)));
+        halves[1].set_draw_range(Some(DrawRange::new(18, 18)));
+        let drawn = |surface: &Surface| {
+            let data = data.borrow();
+            let range = surface.indices_to_draw(&data).unwrap();
+            (range.first_index..range.end())
+                .map(|i| data.get_indices().get(i))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(drawn(&halves[0]).len(), 18);
+        assert_eq!(
+            [drawn(&halves[0]), drawn(&halves[1])].concat(),
+            drawn(&full)
+        );
+        // Second half starts 36 bytes into the 16 bit index buffer.
+        assert_eq!(18 * data.borrow().get_indices().index_size(), 36);
+
+        // Ranges past the end or splitting triangles keep the previous one.
+        for range in [DrawRange::new(30, 12), DrawRange::new(1, 18)] {
+            halves[1].set_draw_range(Some(range));
+            assert_eq!(halves[1].get_draw_range(), Some(DrawRange::new(18, 18)));
+            assert_eq!(
+                data.borrow().check_range(range),
+                Err(SurfaceDataError::InvalidRange { range, len: 36 })
+            );
+        }
+
+        // Shrunk indices leave the second half out of range.
+        let shrunk = Rc::new(RefCell::new(SurfaceSharedData::make_cube()));
+        let mut surface = Surface::new(&shrunk);
+        surface.set_draw_range(Some(DrawRange::new(18, 18)));
+        shrunk.borrow_mut().indices_mut().truncate(18);
+        assert_eq!(surface.indices_to_draw(&shrunk.borrow()), None);
+        surface.set_draw_range(None);
+        assert_eq!(
+            surface.indices_to_draw(&shrunk.borrow()),
+            Some(DrawRange::new(0, 18))
+        );
+    }
+
+    #[test]
+    fn terrain() {
+        // Gray gradient of 3x2 texels, brightness grows by 100 to the right
+        // and by 50 downwards.
+        let mut pixels = Vec::new();
+        for y in 0..2u8 {
+            for x in 0..3u8 {
+                let v = x * 100 + y * 50;
+                pixels.extend([v, v, v, 255]);
+            }
+        }
+        let heightmap = Texture {
+            width: 3,
+            height: 2,
+            gpu_tex: None,
+            need_upload: true,
+            pixels,
+        };
+        assert!((heightmap.get_luminance(2, 1) - 250.0 / 255.0).abs() < 1e-6);
+
+        // Columns hit texels, rows fall halfway between them.
+        let size = Vector2::new(4.0, 2.0);
+        let data = SurfaceSharedData::make_terrain(&heightmap, size, 2.55, 2);
+        assert_eq!(data.get_vertex_count(), 9);
+        assert_eq!(data.get_indices().len(), 24);
+        for row in 0..3 {
+            for column in 0..3 {
+                let position = data.get_positions()[row * 3 + column];
+                let height = column as f32 + row as f32 * 0.25;
+                assert!((position.y - height).abs() < 1e-5, "{:?}", position);
+            }
+        }
+        assert_eq!(data.get_positions()[0].xz(), Vector2::new(-2.0, -1.0));
+        assert_eq!(data.get_tex_coords()[8], Vector2::new(1.0, 1.0));
+        assert!(data.validate().is_ok());
+        check_triangles(&data);
+        assert!(data.get_normals().iter().all(|normal| normal.y > 0.0));
+
+        // Finer grid than the image interpolates between texels.
+        let data = SurfaceSharedData::make_terrain(&heightmap, size, 2.55, 8);
+        assert_eq!(data.get_indices().len(), 8 * 8 * 6);
+        assert!((data.get_positions()[4 * 9 + 4].y - 1.25).abs() < 1e-5);
+        // Bounds used by frustum culling cover the heights.
+        let bounds = data.bounding_box();
+        assert_eq!(bounds.min, Vector3::new(-2.0, 0.0, -1.0));
+        assert!((bounds.max - Vector3::new(2.0, 2.5, 1.0)).norm() < 1e-5);
     }
 }
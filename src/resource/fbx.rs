@@ -0,0 +1,2536 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    error::Error,
+    f32::consts::FRAC_PI_2,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use fbxcel_dom::{
+    any::{self, AnyDocument},
+    fbxcel::{
+        pull_parser,
+        tree::{self, v7400::NodeHandle},
+    },
+    v7400::{
+        object::{
+            geometry,
+            material::MaterialHandle,
+            model::TypedModelHandle,
+            property::{
+                loaders::{F64Arr3Loader, PrimitiveLoader},
+                ObjectProperties, PropertiesHandle,
+            },
+            texture::TextureHandle,
+            ObjectHandle, ObjectId, TypedObjectHandle,
+        },
+        Document,
+    },
+};
+use nalgebra::{Matrix3, Matrix4, UnitQuaternion, Vector2, Vector3, Vector4};
+
+use crate::{
+    math::{color::Color, quat_from_euler, triangulator::triangulate, RotationOrder},
+    renderer::surface::{DrawRange, SurfaceSharedData},
+    resource::{
+        model::{
+            ImportOptions, ImportReport, Model, ModelAnimation, ModelBone, ModelImportError,
+            ModelNode, ModelNodeKind, ModelSurface,
+        },
+        ResourceError,
+    },
+    scene::{
+        animation::{Animation, Keyframe, Track},
+        node::{Camera, Light},
+    },
+    utils::pool::Handle,
+};
+
+/// Loads FBX file as a model. Root models of the file become children of the
+/// root node named after the file, which converts file units and axes to the
+/// engine ones.
+pub fn load(path: &Path, options: &ImportOptions) -> Result<Model, ResourceError> {
+    let root_name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let directory = path.parent().unwrap_or(Path::new(""));
+    load_from_reader(
+        BufReader::new(File::open(path)?),
+        root_name,
+        directory,
+        options,
+    )
+}
+
+/// Loads FBX document from any seekable source, see `load`. Texture files
+/// are looked up relative to `directory`.
+pub fn load_from_reader(
+    reader: impl Read + Seek,
+    root_name: &str,
+    directory: &Path,
+    options: &ImportOptions,
+) -> Result<Model, ResourceError> {
+    let doc = open_document(reader)?;
+
+    // Root converts file units and axes to the engine ones, lengths that
+    // aren't part of node transforms are scaled on import.
+    let mut model = Model::new(root_name);
+    let mut scale = 1.0;
+    if let Some(settings) = doc.global_settings() {
+        let root = &mut model.nodes[0];
+        let properties = settings.raw_properties();
+        root.local_rotation = axis_conversion(&properties);
+        // Scale factor is centimeters per file unit.
+        let unit = f64_setting(&properties, "UnitScaleFactor")
+            .or_else(|| f64_setting(&properties, "OriginalUnitScaleFactor"))
+            .unwrap_or(1.0);
+        scale = unit as f32 * 0.01 / options.unit;
+        root.local_scale = Vector3::new(scale, scale, scale);
+    }
+
+    let mut importer = Importer {
+        model,
+        scale,
+        directory: directory.to_path_buf(),
+        models: HashMap::new(),
+        geometries: HashMap::new(),
+        skins: Vec::new(),
+        ancestors: Vec::new(),
+    };
+    // Objects come in hash order, sort roots to keep node order stable.
+    let mut roots: Vec<_> = doc
+        .objects()
+        .filter_map(|object| match object.get_typed() {
+            TypedObjectHandle::Model(model) if is_root_model(&model) => Some(model),
+            _ => None,
+        })
+        .collect();
+    roots.sort_by_key(|model| model.object_id());
+    for root in roots {
+        importer.import_model(&root, 0)?;
+    }
+    importer.bind_skins();
+    importer.import_animations(&doc);
+    Ok(importer.model)
+}
+
+/// Magic bytes starting binary FBX files, version follows after two more
+/// bytes.
+const BINARY_MAGIC: &[u8; 21] = b"Kaydara FBX Binary  \0";
+
+/// Reads FBX document, telling ASCII files and unsupported versions apart
+/// from broken ones.
+fn open_document(mut reader: impl Read + Seek) -> Result<Box<Document>, ResourceError> {
+    let mut header = [0; 27];
+    let header_len = read_header(&mut reader, &mut header)?;
+    if header_len < BINARY_MAGIC.len() || !header.starts_with(BINARY_MAGIC) {
+        // ASCII files start with a "; FBX ..." comment, anything else without
+        // the magic is most likely ASCII too.
+        return Err(ModelImportError::AsciiFbx.into());
+    }
+    if header_len < header.len() {
+        return Err(ModelImportError::Malformed("file ends in header".to_owned()).into());
+    }
+    let version = u32::from_le_bytes([header[23], header[24], header[25], header[26]]);
+    reader.seek(SeekFrom::Start(0))?;
+    match AnyDocument::from_seekable_reader(reader) {
+        Ok(AnyDocument::V7400(_, doc)) => Ok(doc),
+        Ok(_) | Err(any::Error::UnsupportedVersion(_)) => {
+            Err(ModelImportError::UnsupportedVersion(version).into())
+        }
+        Err(err) => Err(match io_error_kind(&err) {
+            // Running out of data is a property of the file, not of reading.
+            Some(kind) if kind != io::ErrorKind::UnexpectedEof => {
+                ResourceError::Io(io::Error::new(kind, err.to_string()))
+            }
+            _ if version_unsupported(&err) => ModelImportError::UnsupportedVersion(version).into(),
+            _ => ModelImportError::Malformed(err.to_string()).into(),
+        }),
+    }
+}
+
+/// Fills `header` as far as the source goes, returns number of bytes read.
+fn read_header(reader: &mut impl Read, header: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < header.len() {
+        match reader.read(&mut header[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+/// Kind of I/O error anywhere in the source chain of a parser error.
+fn io_error_kind(err: &(dyn Error + 'static)) -> Option<io::ErrorKind> {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<io::Error>() {
+            return Some(io.kind());
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Whether fbxcel failed to create a parser for the file version.
+fn version_unsupported(err: &any::Error) -> bool {
+    matches!(
+        err,
+        any::Error::Tree(tree::any::Error::ParserCreation(
+            pull_parser::any::Error::UnsupportedVersion(_)
+        ))
+    )
+}
+
+fn f64_setting(properties: &PropertiesHandle, name: &str) -> Option<f64> {
+    properties
+        .get_property(name)
+        .and_then(|property| property.load_value(PrimitiveLoader::<f64>::new()).ok())
+}
+
+/// Rotation from file axes to the engine ones: right is +X, up is +Y and
+/// front, the side facing the viewer, is +Z so the engine forward is -Z.
+fn axis_conversion(properties: &PropertiesHandle) -> UnitQuaternion<f32> {
+    let axis = |name: &str, default: i32| {
+        let setting = |name: &str| {
+            properties
+                .get_property(name)
+                .and_then(|property| property.load_value(PrimitiveLoader::<i32>::new()).ok())
+        };
+        let index = setting(name).unwrap_or(default);
+        let sign = setting(&format!("{}Sign", name)).unwrap_or(1);
+        let mut axis = Vector3::zeros();
+        if let Some(component) = usize::try_from(index).ok().filter(|i| *i < 3) {
+            axis[component] = if sign < 0 { -1.0 } else { 1.0 };
+        }
+        axis
+    };
+    let right = axis("CoordAxis", 0);
+    let up = axis("UpAxis", 1);
+    let mut front = axis("FrontAxis", 2);
+    if right.cross(&up).norm() < 0.5 || front.cross(&up).norm() < 0.5 {
+        println!("Invalid FBX axis settings, axes are kept as is");
+        return UnitQuaternion::identity();
+    }
+    if right.cross(&up).dot(&front) < 0.0 {
+        println!("Left-handed FBX axes are not supported, front axis is flipped");
+        front = -front;
+    }
+    // Rows of the rotation are the file axes.
+    let matrix = Matrix3::from_rows(&[right.transpose(), up.transpose(), front.transpose()]);
+    UnitQuaternion::from_matrix(&matrix)
+}
+
+/// Models connected to the scene root or to no other model start the
+/// hierarchy, a model can also be a child of other models at the same time.
+fn is_root_model(model: &TypedModelHandle) -> bool {
+    model.parent_model().is_none()
+        || model
+            .destination_objects()
+            .any(|object| object.object_id().raw() == 0)
+}
+
+/// FBX `RotationOrder` property values, spheric XYZ is treated as XYZ.
+fn rotation_order(value: i32) -> RotationOrder {
+    match value {
+        1 => RotationOrder::Xzy,
+        2 => RotationOrder::Yzx,
+        3 => RotationOrder::Yxz,
+        4 => RotationOrder::Zxy,
+        5 => RotationOrder::Zyx,
+        _ => RotationOrder::Xyz,
+    }
+}
+
+fn vector_property(
+    properties: &ObjectProperties,
+    name: &str,
+    default: Vector3<f32>,
+) -> Vector3<f32> {
+    properties
+        .get_property(name)
+        .and_then(|property| property.load_value(F64Arr3Loader).ok())
+        .map_or(default, |[x, y, z]| {
+            Vector3::new(x as f32, y as f32, z as f32)
+        })
+}
+
+fn f32_property(properties: &ObjectProperties, name: &str, default: f32) -> f32 {
+    properties
+        .get_property(name)
+        .and_then(|property| property.load_value(PrimitiveLoader::<f64>::new()).ok())
+        .map_or(default, |value| value as f32)
+}
+
+fn bool_property(properties: &ObjectProperties, name: &str, default: bool) -> bool {
+    properties
+        .get_property(name)
+        .and_then(|property| property.load_value(PrimitiveLoader::<bool>::new()).ok())
+        .unwrap_or(default)
+}
+
+fn i32_property(properties: &ObjectProperties, name: &str, default: i32) -> i32 {
+    properties
+        .get_property(name)
+        .and_then(|property| property.load_value(PrimitiveLoader::<i32>::new()).ok())
+        .unwrap_or(default)
+}
+
+/// Model object and the nodes created for it.
+struct ImportedModel {
+    nodes: Vec<usize>,
+    rotation_order: RotationOrder,
+}
+
+struct Importer {
+    model: Model,
+    /// Engine units per file unit.
+    scale: f32,
+    directory: PathBuf,
+    models: HashMap<ObjectId, ImportedModel>,
+    /// Geometry used by several models is imported once and shared.
+    geometries: HashMap<ObjectId, ImportedGeometry>,
+    /// Skinned nodes and clusters of their geometry.
+    skins: Vec<(usize, Vec<Cluster>)>,
+    /// Models on the path from the root, guards against connection cycles.
+    ancestors: Vec<ObjectId>,
+}
+
+impl Importer {
+    /// Adds model and its children under `parent`. A model with several
+    /// parents is visited from each of them and so duplicated, its geometry
+    /// stays shared.
+    fn import_model(
+        &mut self,
+        object: &TypedModelHandle,
+        parent: usize,
+    ) -> Result<(), ResourceError> {
+        let id = object.object_id();
+        if self.ancestors.contains(&id) {
+            return Err(ResourceError::Parse(format!(
+                "model {:?} is its own ancestor",
+                object.name().unwrap_or_default()
+            )));
+        }
+
+        let mut node = ModelNode::new(object.name().unwrap_or_default());
+        let properties = object.properties_by_native_typename("FbxNode");
+        let vector = |name| vector_property(&properties, name, Vector3::zeros());
+        node.local_position = vector("Lcl Translation");
+        node.local_scale = vector_property(&properties, "Lcl Scaling", Vector3::new(1.0, 1.0, 1.0));
+        node.rotation_offset = vector("RotationOffset");
+        node.rotation_pivot = vector("RotationPivot");
+        node.scaling_offset = vector("ScalingOffset");
+        node.scaling_pivot = vector("ScalingPivot");
+        // Rotation order and pre/post rotations are ignored by FBX SDK unless
+        // rotation is active, pre/post rotations are always XYZ.
+        let mut order = RotationOrder::Xyz;
+        if bool_property(&properties, "RotationActive", false) {
+            order = rotation_order(i32_property(&properties, "RotationOrder", 0));
+            node.pre_rotation = quat_from_euler(vector("PreRotation"), RotationOrder::Xyz);
+            node.post_rotation = quat_from_euler(vector("PostRotation"), RotationOrder::Xyz);
+        }
+        node.local_rotation = quat_from_euler(vector("Lcl Rotation"), order);
+        for attribute in sources_of_class(object, "NodeAttribute") {
+            match attribute.subclass() {
+                "Camera" => {
+                    // FBX cameras look along +X with +Y up, engine ones along
+                    // +Z. Only the view is turned, children of the camera
+                    // keep their placement.
+                    let mut camera = self.import_camera(&attribute);
+                    camera.set_view_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::y_axis(),
+                        FRAC_PI_2,
+                    ));
+                    node.kind = ModelNodeKind::Camera(camera);
+                }
+                "Light" => node.kind = ModelNodeKind::Light(self.import_light(&attribute)),
+                _ => {}
+            }
+        }
+        let mut clusters = Vec::new();
+        if let TypedModelHandle::Mesh(mesh) = object {
+            // Mesh models without geometry stay empty nodes.
+            if let Ok(geometry) = mesh.geometry() {
+                let imported = match self.geometries.entry(geometry.object_id()) {
+                    Entry::Occupied(entry) => entry.get().clone(),
+                    Entry::Vacant(entry) => entry
+                        .insert(import_geometry(&geometry, &mut self.model.report)?)
+                        .clone(),
+                };
+                clusters = imported.clusters;
+                // Materials belong to the model, so instances of shared
+                // geometry can look different.
+                let materials: Vec<_> = mesh.materials().collect();
+                // Culling of the model applies to all its surfaces, models
+                // without it are single-sided.
+                let two_sided = string_child(&object.node(), "Culling") == Some("CullingOff");
+                for (material, mut surface) in imported.surfaces {
+                    if let Some(material) = materials.get(material).or(materials.first()) {
+                        self.apply_material(&mut surface, material);
+                    }
+                    surface.two_sided = two_sided;
+                    node.surfaces.push(surface);
+                }
+                self.model
+                    .report
+                    .mesh_surfaces
+                    .push((node.name.clone(), node.surfaces.len()));
+            }
+        }
+
+        let index = self.model.add_node(node, parent);
+        if !clusters.is_empty() {
+            self.skins.push((index, clusters));
+        }
+        let imported = self.models.entry(id).or_insert_with(|| ImportedModel {
+            nodes: Vec::new(),
+            rotation_order: order,
+        });
+        imported.nodes.push(index);
+        self.ancestors.push(id);
+        for child in object.child_models() {
+            self.import_model(&child, index)?;
+        }
+        self.ancestors.pop();
+        Ok(())
+    }
+
+    /// Vertical field of view comes from the field of view property or, in
+    /// focal length mode, from focal length and film height.
+    fn import_camera(&self, attribute: &ObjectHandle) -> Camera {
+        let properties = attribute.properties_by_native_typename("FbxCamera");
+        let number = |name, default| f32_property(&properties, name, default);
+        let film_width = number("FilmWidth", 0.816);
+        let film_height = number("FilmHeight", 0.612);
+        let fov = match i32_property(&properties, "ApertureMode", 2) {
+            // Horizontal and vertical or horizontal only.
+            0 | 1 => {
+                let horizontal = number("FieldOfView", 40.0).to_radians();
+                2.0 * ((horizontal * 0.5).tan() * film_height / film_width).atan()
+            }
+            2 => number("FieldOfView", 25.0).to_radians(),
+            // Film size is in inches, focal length in millimeters.
+            _ => 2.0 * (film_height * 25.4 / (2.0 * number("FocalLength", 34.89))).atan(),
+        };
+        let mut camera = Camera::default();
+        camera.set_fov(fov.to_degrees());
+        camera.set_clip_planes(
+            number("NearPlane", 10.0) * self.scale,
+            number("FarPlane", 4000.0) * self.scale,
+        );
+        camera
+    }
+
+    /// Only point lights are supported, other types become point lights.
+    /// Falloff ends at far attenuation distance when it is enabled.
+    fn import_light(&self, attribute: &ObjectHandle) -> Light {
+        let properties = attribute.properties_by_native_typename("FbxLight");
+        let light_type = i32_property(&properties, "LightType", 0);
+        if light_type != 0 {
+            println!(
+                "{:?}: light type {} is imported as point light",
+                attribute.name().unwrap_or_default(),
+                light_type
+            );
+        }
+        let color = vector_property(&properties, "Color", Vector3::new(1.0, 1.0, 1.0));
+        let radius = if bool_property(&properties, "EnableFarAttenuation", false) {
+            f32_property(&properties, "FarAttenuationEnd", 0.0) * self.scale
+        } else {
+            Light::default().get_radius()
+        };
+        let mut light = Light::new(radius, Color::opaque(color.x, color.y, color.z));
+        // Intensity of 100 is the light color as is.
+        light.set_intensity(f32_property(&properties, "Intensity", 100.0) * 0.01);
+        light
+    }
+
+    /// Links bones of skinned nodes to nodes of the cluster models. A model
+    /// imported several times is bound to its first node.
+    fn bind_skins(&mut self) {
+        for (index, clusters) in std::mem::take(&mut self.skins) {
+            let bones = clusters
+                .iter()
+                .map(|cluster| {
+                    let node = cluster
+                        .link
+                        .and_then(|link| self.models.get(&link))
+                        .map(|imported| imported.nodes[0]);
+                    if cluster.link.is_some() && node.is_none() {
+                        println!(
+                            "{:?}: bone model is not in the hierarchy",
+                            self.model.nodes[index].name
+                        );
+                    }
+                    // Unresolved bones are bound to the skinned node itself
+                    // with identity bind pose, so their vertices stay put.
+                    match node {
+                        Some(node) => ModelBone {
+                            node,
+                            transform_link: cluster.transform_link,
+                            inverse_bind_pose: cluster.inverse_bind_pose,
+                        },
+                        None => ModelBone {
+                            node: index,
+                            transform_link: cluster.transform_link,
+                            inverse_bind_pose: Matrix4::identity(),
+                        },
+                    }
+                })
+                .collect();
+            self.model.nodes[index].bones = bones;
+        }
+    }
+
+    /// Every animation stack becomes an animation, keys of its first layer
+    /// are converted to tracks of the animated nodes.
+    fn import_animations(&mut self, doc: &Document) {
+        let mut stacks: Vec<_> = doc
+            .objects()
+            .filter(|object| object.class() == "AnimationStack")
+            .collect();
+        stacks.sort_by_key(|stack| stack.object_id());
+        for stack in stacks {
+            let name = stack.name().unwrap_or_default();
+            let mut layers = sources_of_class(&stack, "AnimationLayer");
+            layers.sort_by_key(|layer| layer.object_id());
+            let Some(layer) = layers.first() else {
+                continue;
+            };
+            if layers.len() > 1 {
+                println!(
+                    "Animation {:?}: only the first of {} layers is imported",
+                    name,
+                    layers.len()
+                );
+            }
+            let start = stack
+                .direct_properties()
+                .and_then(|properties| properties.get_property("LocalStart"))
+                .and_then(|property| property.load_value(PrimitiveLoader::<i64>::new()).ok())
+                .unwrap_or(0);
+
+            let mut animation = ModelAnimation {
+                animation: Animation::new(name),
+                nodes: Vec::new(),
+            };
+            let mut tracks = Vec::new();
+            let mut curve_nodes = sources_of_class(layer, "AnimationCurveNode");
+            curve_nodes.sort_by_key(|curve_node| curve_node.object_id());
+            for curve_node in curve_nodes {
+                for destination in curve_node.destination_objects() {
+                    let Some(imported) = self.models.get(&destination.object_id()) else {
+                        continue;
+                    };
+                    let channel = destination.label().unwrap_or_default();
+                    let default = if channel == "Lcl Scaling" { 1.0 } else { 0.0 };
+                    let keys = channel_keys(&curve_node, start, default);
+                    for &node in imported.nodes.iter() {
+                        let index = match animation.nodes.iter().position(|n| *n == node) {
+                            Some(index) => index,
+                            None => {
+                                animation.nodes.push(node);
+                                tracks.push(Track::new(Handle::none()));
+                                tracks.len() - 1
+                            }
+                        };
+                        let track = &mut tracks[index];
+                        let vectors = || {
+                            keys.iter()
+                                .map(|(time, value)| Keyframe::new(*time, *value))
+                                .collect()
+                        };
+                        match channel {
+                            "Lcl Translation" => track.set_positions(vectors()),
+                            "Lcl Scaling" => track.set_scales(vectors()),
+                            "Lcl Rotation" => track.set_rotations(
+                                keys.iter()
+                                    .map(|(time, degrees)| {
+                                        Keyframe::new(
+                                            *time,
+                                            quat_from_euler(*degrees, imported.rotation_order),
+                                        )
+                                    })
+                                    .collect(),
+                            ),
+                            _ => (),
+                        }
+                    }
+                }
+            }
+            for track in tracks {
+                animation.animation.add_track(track);
+            }
+            self.model.animations.push(animation);
+        }
+    }
+
+    /// Diffuse texture goes to the surface, diffuse color only when there is
+    /// no texture file to use.
+    fn apply_material(&self, surface: &mut ModelSurface, material: &MaterialHandle) {
+        surface.texture_path = material
+            .diffuse_texture()
+            .and_then(|texture| texture_path(&self.directory, &texture));
+        if surface.texture_path.is_none() {
+            let properties = material.properties();
+            let factor = properties.diffuse_factor_or_default().unwrap_or(1.0);
+            if let Ok(color) = properties.diffuse_color_or_default() {
+                surface.tint = Color::opaque(
+                    (color.r * factor) as f32,
+                    (color.g * factor) as f32,
+                    (color.b * factor) as f32,
+                );
+            }
+        }
+    }
+}
+
+/// FBX time units in a second.
+const KTIME_PER_SECOND: f64 = 46_186_158_000.0;
+
+fn sources_of_class<'a>(object: &ObjectHandle<'a>, class: &str) -> Vec<ObjectHandle<'a>> {
+    object
+        .source_objects()
+        .filter_map(|source| source.object_handle())
+        .filter(|source| source.class() == class)
+        .collect()
+}
+
+/// Keys of one animation curve, time in FBX units.
+struct Curve {
+    times: Vec<i64>,
+    values: Vec<f32>,
+}
+
+impl Curve {
+    fn read(object: &ObjectHandle) -> Option<Curve> {
+        let node = object.node();
+        let array = |name: &str| {
+            node.first_child_by_name(name)
+                .and_then(|child| child.attributes().first().cloned())
+        };
+        let times = array("KeyTime")?.get_arr_i64()?.to_vec();
+        let values = array("KeyValueFloat")?.get_arr_f32()?.to_vec();
+        (times.len() == values.len() && !times.is_empty()).then_some(Curve { times, values })
+    }
+
+    /// Linear interpolation between keys, values outside are held.
+    fn evaluate(&self, time: i64) -> f32 {
+        match self.times.iter().position(|t| *t > time) {
+            None => *self.values.last().unwrap(),
+            Some(0) => self.values[0],
+            Some(next) => {
+                let (t0, t1) = (self.times[next - 1], self.times[next]);
+                let t = (time - t0) as f64 / (t1 - t0) as f64;
+                self.values[next - 1] + (self.values[next] - self.values[next - 1]) * t as f32
+            }
+        }
+    }
+}
+
+/// Keys of a curve node with X, Y and Z curves, resampled at every key time
+/// of any of them. Components without curve keep the value stored in the
+/// curve node, a node without curves gives a single key.
+fn channel_keys(curve_node: &ObjectHandle, start: i64, default: f32) -> Vec<(f32, Vector3<f32>)> {
+    let properties = curve_node.direct_properties();
+    let components = ["d|X", "d|Y", "d|Z"].map(|component| {
+        let curve = curve_node
+            .source_objects()
+            .filter(|source| source.label() == Some(component))
+            .filter_map(|source| source.object_handle())
+            .find_map(|source| Curve::read(&source));
+        let constant = properties
+            .and_then(|properties| properties.get_property(component))
+            .and_then(|property| property.load_value(PrimitiveLoader::<f64>::new()).ok())
+            .map_or(default, |value| value as f32);
+        (curve, constant)
+    });
+
+    let mut times: Vec<i64> = components
+        .iter()
+        .filter_map(|(curve, _)| curve.as_ref())
+        .flat_map(|curve| curve.times.iter().copied())
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+    if times.is_empty() {
+        times.push(start);
+    }
+    times
+        .into_iter()
+        .map(|time| {
+            let value = components.each_ref().map(|(curve, constant)| {
+                curve
+                    .as_ref()
+                    .map_or(*constant, |curve| curve.evaluate(time))
+            });
+            let seconds = ((time - start) as f64 / KTIME_PER_SECOND) as f32;
+            (seconds, Vector3::from(value))
+        })
+        .collect()
+}
+
+fn string_child<'a>(node: &NodeHandle<'a>, name: &str) -> Option<&'a str> {
+    node.first_child_by_name(name)
+        .and_then(|child| child.attributes().first())
+        .and_then(|attribute| attribute.get_string())
+}
+
+/// Finds file of the texture relative to `directory`. Exporters often store
+/// absolute paths from the artist's machine, so when neither stored path
+/// exists the bare file name is looked up next to the model.
+fn texture_path(directory: &Path, texture: &TextureHandle) -> Option<PathBuf> {
+    let node = texture.node();
+    let relative = string_child(&node, "RelativeFilename").filter(|path| !path.is_empty());
+    let absolute = string_child(&node, "FileName").filter(|path| !path.is_empty());
+    let mut candidates = Vec::new();
+    if let Some(relative) = relative {
+        candidates.push(PathBuf::from(relative.replace('\\', "/")));
+    }
+    if let Some(absolute) = absolute {
+        candidates.push(PathBuf::from(absolute));
+    }
+    if let Some(file_name) = relative
+        .or(absolute)
+        .and_then(|path| path.rsplit(['/', '\\']).next())
+    {
+        candidates.push(PathBuf::from(file_name));
+    }
+
+    let found = candidates
+        .into_iter()
+        .find(|candidate| directory.join(candidate).is_file());
+    if found.is_none() {
+        println!(
+            "Texture {:?} of {:?} not found",
+            absolute.or(relative).unwrap_or_default(),
+            texture.name().unwrap_or_default()
+        );
+    }
+    found
+}
+
+/// Corner of a polygon as stored in the geometry.
+struct PolygonVertex {
+    control_point: usize,
+    polygon: usize,
+}
+
+/// How layer element values are assigned to polygon vertices.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Mapping {
+    ByPolygonVertex,
+    ByControlPoint,
+    ByPolygon,
+    AllSame,
+}
+
+/// Surfaces of a geometry with material index of each, and its skin.
+#[derive(Clone)]
+struct ImportedGeometry {
+    surfaces: Vec<(usize, ModelSurface)>,
+    clusters: Vec<Cluster>,
+}
+
+/// Polygons are split into one surface per material, all drawing their
+/// ranges of one buffer. Without normal and UV layers a single surface keeps
+/// positions per control point and triangles index them directly, so
+/// polygons sharing a corner share its vertex. Otherwise polygon corners
+/// with equal attributes are merged.
+fn import_geometry(
+    geometry: &geometry::MeshHandle,
+    report: &mut ImportReport,
+) -> Result<ImportedGeometry, ResourceError> {
+    let polygon_vertices = geometry
+        .polygon_vertices()
+        .map_err(|err| ResourceError::Parse(err.to_string()))?;
+    let positions: Vec<Vector3<f32>> = polygon_vertices
+        .raw_control_points()
+        .map_err(|err| ResourceError::Parse(err.to_string()))?
+        .map(|p| Vector3::new(p.x as f32, p.y as f32, p.z as f32))
+        .collect();
+    let vertices = decode_polygons(polygon_vertices.raw_polygon_vertices(), positions.len())?;
+    let name = geometry.name().unwrap_or_default();
+    let (corners, degenerate) = triangulate_polygons(&vertices, &positions);
+    if degenerate > 0 {
+        println!("{:?}: {} degenerate polygons dropped", name, degenerate);
+        report.degenerate_polygons += degenerate;
+    }
+
+    let node = geometry.node();
+    let normals = node
+        .first_child_by_name("LayerElementNormal")
+        .and_then(|layer| {
+            layer_element::<3>(&layer, "Normals", "NormalsIndex", &vertices)
+                .map_err(|err| println!("{:?}: {}, normals are recalculated", name, err))
+                .ok()
+        });
+    // Only the first UV set is used.
+    let tex_coords = node
+        .first_child_by_name("LayerElementUV")
+        .and_then(|layer| {
+            layer_element::<2>(&layer, "UV", "UVIndex", &vertices)
+                .map_err(|err| println!("{:?}: {}, texture coordinates are dropped", name, err))
+                .ok()
+        });
+
+    // Triangles grouped by material, polygons without usable material
+    // layer use the first one.
+    let materials = polygon_materials(&node, &vertices, name);
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for triangle in corners.chunks_exact(3) {
+        let material = materials[vertices[triangle[0]].polygon];
+        groups
+            .entry(material)
+            .or_default()
+            .extend_from_slice(triangle);
+    }
+
+    let control_point_indices = |corners: &[usize]| {
+        corners
+            .iter()
+            .map(|&corner| vertices[corner].control_point as u32)
+            .collect()
+    };
+    let (clusters, influences) = read_skin(geometry, positions.len());
+    if normals.is_none() && tex_coords.is_none() && groups.len() == 1 {
+        let mut data =
+            SurfaceSharedData::from_triangles(positions, control_point_indices(&corners));
+        if !clusters.is_empty() {
+            apply_influences(&mut data, &influences, 0..influences.len());
+        }
+        let material = *groups.keys().next().unwrap();
+        return Ok(ImportedGeometry {
+            surfaces: vec![(material, ModelSurface::new(data))],
+            clusters,
+        });
+    }
+    // Recalculated normals are smooth over control points, same as above.
+    let smooth_normals;
+    let normals: Vec<Vector3<f32>> = match normals {
+        Some(normals) => normals.into_iter().map(Vector3::from).collect(),
+        None => {
+            smooth_normals = SurfaceSharedData::from_triangles(
+                positions.clone(),
+                control_point_indices(&corners),
+            );
+            vertices
+                .iter()
+                .map(|vertex| smooth_normals.get_normals()[vertex.control_point])
+                .collect()
+        }
+    };
+    // FBX has V going up, textures are stored top row first.
+    let tex_coords: Vec<Vector2<f32>> = match tex_coords {
+        Some(tex_coords) => tex_coords
+            .into_iter()
+            .map(|[u, v]| Vector2::new(u, 1.0 - v))
+            .collect(),
+        None => vec![Vector2::zeros(); vertices.len()],
+    };
+
+    // Material groups follow each other in the index buffer, corners with
+    // equal attributes are merged across groups.
+    let mut merged = HashMap::new();
+    let mut surface_positions = Vec::new();
+    let mut surface_normals = Vec::new();
+    let mut surface_tex_coords = Vec::new();
+    let mut surface_control_points = Vec::new();
+    let mut indices = Vec::with_capacity(corners.len());
+    let mut ranges = Vec::with_capacity(groups.len());
+    for (material, corners) in groups {
+        ranges.push((material, DrawRange::new(indices.len(), corners.len())));
+        for corner in corners {
+            let control_point = vertices[corner].control_point;
+            let normal = normals[corner];
+            let tex_coord = tex_coords[corner];
+            let key = (
+                control_point,
+                normal.map(f32::to_bits),
+                tex_coord.map(f32::to_bits),
+            );
+            indices.push(*merged.entry(key).or_insert_with(|| {
+                surface_positions.push(positions[control_point]);
+                surface_normals.push(normal);
+                surface_tex_coords.push(tex_coord);
+                surface_control_points.push(control_point);
+                surface_positions.len() as u32 - 1
+            }));
+        }
+    }
+    let mut data = SurfaceSharedData::from_vertices(
+        surface_positions,
+        surface_normals,
+        surface_tex_coords,
+        indices,
+    );
+    if !clusters.is_empty() {
+        apply_influences(&mut data, &influences, surface_control_points);
+    }
+    let shared = ModelSurface::new(data);
+    // Single group draws the whole buffer.
+    let surfaces = match ranges.as_slice() {
+        [(material, _)] => vec![(*material, shared)],
+        _ => ranges
+            .into_iter()
+            .map(|(material, range)| {
+                let mut surface = shared.clone();
+                surface.range = Some(range);
+                (material, surface)
+            })
+            .collect(),
+    };
+    Ok(ImportedGeometry { surfaces, clusters })
+}
+
+/// Material index of every polygon. Only per polygon and whole mesh
+/// mappings are supported, polygons use the first material otherwise.
+fn polygon_materials(node: &NodeHandle, vertices: &[PolygonVertex], name: &str) -> Vec<usize> {
+    let polygon_count = vertices.last().map_or(0, |vertex| vertex.polygon + 1);
+    let Some(layer) = node.first_child_by_name("LayerElementMaterial") else {
+        return vec![0; polygon_count];
+    };
+    let indices = layer
+        .first_child_by_name("Materials")
+        .and_then(|child| child.attributes().first())
+        .and_then(|attribute| attribute.get_arr_i32())
+        .unwrap_or_default();
+    let index = |i: usize| {
+        indices
+            .get(i)
+            .and_then(|&index| usize::try_from(index).ok())
+            .unwrap_or(0)
+    };
+    match string_child(&layer, "MappingInformationType") {
+        Some("AllSame") => vec![index(0); polygon_count],
+        Some("ByPolygon") => {
+            if indices.len() < polygon_count {
+                println!("{:?}: material layer is shorter than polygon list", name);
+            }
+            (0..polygon_count).map(index).collect()
+        }
+        mapping => {
+            println!(
+                "{:?}: material mapping {:?} is not supported, using the first material",
+                name, mapping
+            );
+            vec![0; polygon_count]
+        }
+    }
+}
+
+/// Bone of a skinned geometry. Linked model is resolved to nodes after the
+/// whole hierarchy is imported.
+#[derive(Debug, Clone)]
+struct Cluster {
+    link: Option<ObjectId>,
+    transform_link: Matrix4<f32>,
+    inverse_bind_pose: Matrix4<f32>,
+}
+
+/// Reads clusters of the first skin deformer of a geometry and bone
+/// influences of every control point, as (cluster index, weight) pairs.
+fn read_skin(
+    geometry: &geometry::MeshHandle,
+    control_point_count: usize,
+) -> (Vec<Cluster>, Vec<Vec<(u16, f32)>>) {
+    let name = geometry.name().unwrap_or_default();
+    let mut clusters = Vec::new();
+    let mut influences = vec![Vec::new(); control_point_count];
+    let mut skins = geometry.skins();
+    let Some(skin) = skins.next() else {
+        return (clusters, influences);
+    };
+    if skins.next().is_some() {
+        println!("{:?}: only the first skin deformer is used", name);
+    }
+    for cluster in skin.clusters() {
+        let node = cluster.node();
+        let array = |name| {
+            node.first_child_by_name(name)
+                .and_then(|child| child.attributes().first())
+        };
+        let matrix = |name| {
+            array(name)
+                .and_then(|attribute| attribute.get_arr_f64())
+                .filter(|values| values.len() == 16)
+                .map(|values| Matrix4::from_iterator(values.iter().map(|&v| v as f32)))
+                .unwrap_or_else(Matrix4::identity)
+        };
+        // Transform is global transform of the mesh at bind time.
+        let transform = matrix("Transform");
+        let transform_link = matrix("TransformLink");
+        let link = sources_of_class(&cluster, "Model")
+            .first()
+            .map(|model| model.object_id());
+        if link.is_none() {
+            println!(
+                "{:?}: cluster {:?} has no linked bone",
+                name,
+                cluster.name().unwrap_or_default()
+            );
+        }
+        let bone = clusters.len() as u16;
+        clusters.push(Cluster {
+            link,
+            transform_link,
+            inverse_bind_pose: transform_link
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity)
+                * transform,
+        });
+
+        let indexes = array("Indexes").and_then(|attribute| attribute.get_arr_i32());
+        let weights = array("Weights").and_then(|attribute| attribute.get_arr_f64());
+        if let (Some(indexes), Some(weights)) = (indexes, weights) {
+            for (&index, &weight) in indexes.iter().zip(weights.iter()) {
+                if let Some(influence) = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| influences.get_mut(index))
+                {
+                    if weight > 0.0 {
+                        influence.push((bone, weight as f32));
+                    }
+                }
+            }
+        }
+    }
+    (clusters, influences)
+}
+
+/// Keeps four strongest influences, ties go to the lower bone index, and
+/// scales their weights to sum up to one.
+fn strongest_influences(influences: &[(u16, f32)]) -> ([u16; 4], Vector4<f32>) {
+    let mut sorted = influences.to_vec();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    sorted.truncate(4);
+    let total: f32 = sorted.iter().map(|&(_, weight)| weight).sum();
+    let mut bones = [0; 4];
+    let mut weights = Vector4::zeros();
+    for (i, &(bone, weight)) in sorted.iter().enumerate() {
+        bones[i] = bone;
+        weights[i] = weight / total;
+    }
+    (bones, weights)
+}
+
+/// Sets skinning channels of surface vertices made of given control points.
+fn apply_influences(
+    data: &mut SurfaceSharedData,
+    influences: &[Vec<(u16, f32)>],
+    control_points: impl IntoIterator<Item = usize>,
+) {
+    let (bones, weights) = control_points
+        .into_iter()
+        .map(|control_point| strongest_influences(&influences[control_point]))
+        .unzip();
+    data.set_skin(bones, weights);
+}
+
+/// Reads values of a layer element and expands them to every polygon vertex.
+/// `values` and `indices` name the arrays of values and of their indices.
+fn layer_element<const N: usize>(
+    layer: &NodeHandle,
+    values: &str,
+    indices: &str,
+    vertices: &[PolygonVertex],
+) -> Result<Vec<[f32; N]>, String> {
+    let mapping = match string_child(layer, "MappingInformationType") {
+        Some("ByPolygonVertex") => Mapping::ByPolygonVertex,
+        Some("ByControlPoint" | "ByVertex" | "ByVertice") => Mapping::ByControlPoint,
+        Some("ByPolygon") => Mapping::ByPolygon,
+        Some("AllSame") => Mapping::AllSame,
+        other => return Err(format!("unsupported {} mapping {:?}", values, other)),
+    };
+    let array = |name: &str| {
+        layer
+            .first_child_by_name(name)
+            .and_then(|child| child.attributes().first())
+    };
+    let value_array = array(values)
+        .and_then(|attribute| attribute.get_arr_f64())
+        .ok_or_else(|| format!("{} are missing", values))?;
+    let index_array = match string_child(layer, "ReferenceInformationType") {
+        Some("Direct") => None,
+        Some("IndexToDirect" | "Index") => Some(
+            array(indices)
+                .and_then(|attribute| attribute.get_arr_i32())
+                .ok_or_else(|| format!("{} are missing", indices))?,
+        ),
+        other => return Err(format!("unsupported {} reference {:?}", values, other)),
+    };
+
+    vertices
+        .iter()
+        .enumerate()
+        .map(|(i, vertex)| {
+            let key = match mapping {
+                Mapping::ByPolygonVertex => i,
+                Mapping::ByControlPoint => vertex.control_point,
+                Mapping::ByPolygon => vertex.polygon,
+                Mapping::AllSame => 0,
+            };
+            let index = match index_array {
+                Some(index_array) => index_array
+                    .get(key)
+                    .and_then(|&index| usize::try_from(index).ok()),
+                None => Some(key),
+            };
+            index
+                .and_then(|index| value_array.get(index * N..(index + 1) * N))
+                .map(|value| std::array::from_fn(|component| value[component] as f32))
+                .ok_or_else(|| format!("{} index out of range", values))
+        })
+        .collect()
+}
+
+/// Last vertex of every polygon is stored as bitwise negation of control
+/// point index.
+fn decode_polygons(
+    polygon_vertices: &[i32],
+    control_point_count: usize,
+) -> Result<Vec<PolygonVertex>, ResourceError> {
+    let mut vertices = Vec::with_capacity(polygon_vertices.len());
+    let mut polygon = 0;
+    let mut is_complete = true;
+    for &raw in polygon_vertices {
+        let (index, is_end) = if raw < 0 { (!raw, true) } else { (raw, false) };
+        if index as usize >= control_point_count {
+            return Err(ResourceError::Parse(format!(
+                "control point index {} out of range",
+                index
+            )));
+        }
+        vertices.push(PolygonVertex {
+            control_point: index as usize,
+            polygon,
+        });
+        is_complete = is_end;
+        if is_end {
+            polygon += 1;
+        }
+    }
+    if !is_complete {
+        return Err(ResourceError::Parse("incomplete polygon".to_owned()));
+    }
+    Ok(vertices)
+}
+
+/// Splits polygons into triangles, returns triangle corners as indices of
+/// polygon vertices and number of degenerate polygons left out.
+fn triangulate_polygons(
+    vertices: &[PolygonVertex],
+    positions: &[Vector3<f32>],
+) -> (Vec<usize>, usize) {
+    let mut corners = Vec::new();
+    let mut degenerate = 0;
+    let mut start = 0;
+    while start < vertices.len() {
+        let polygon = vertices[start].polygon;
+        let end = vertices[start..]
+            .iter()
+            .position(|vertex| vertex.polygon != polygon)
+            .map_or(vertices.len(), |length| start + length);
+        let points: Vec<_> = vertices[start..end]
+            .iter()
+            .map(|vertex| positions[vertex.control_point])
+            .collect();
+        let triangles = triangulate(&points);
+        if triangles.is_empty() {
+            degenerate += 1;
+        }
+        corners.extend(triangles.iter().flatten().map(|&i| start + i));
+        start = end;
+    }
+    (corners, degenerate)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::Cursor,
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+
+    use fbxcel::{
+        low::{v7400::AttributeValue, FbxVersion},
+        writer::v7400::binary::Writer,
+    };
+
+    use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector2, Vector3, Vector4};
+
+    use super::{
+        decode_polygons, load, load_from_reader, strongest_influences, triangulate_polygons,
+    };
+    use crate::{
+        math::{color::Color, quat_from_euler, RotationOrder},
+        renderer::surface::DrawRange,
+        resource::{
+            model::{ImportOptions, Model, ModelHandle, ModelImportError, ModelSurface},
+            ResourceError,
+        },
+        scene::{node::NodeKind, Scene},
+    };
+
+    /// Node of a test document written with the FBX binary writer.
+    pub(crate) struct FbxNode {
+        name: &'static str,
+        attributes: Vec<AttributeValue>,
+        children: Vec<FbxNode>,
+    }
+
+    impl FbxNode {
+        pub(crate) fn new(name: &'static str, attributes: Vec<AttributeValue>) -> Self {
+            Self {
+                name,
+                attributes,
+                children: Vec::new(),
+            }
+        }
+
+        pub(crate) fn with_child(mut self, child: FbxNode) -> Self {
+            self.children.push(child);
+            self
+        }
+
+        fn write<W: std::io::Write + std::io::Seek>(&self, writer: &mut Writer<W>) {
+            let mut attributes = writer.new_node(self.name).unwrap();
+            for attribute in self.attributes.iter().cloned() {
+                match attribute {
+                    AttributeValue::I32(v) => attributes.append_i32(v),
+                    AttributeValue::I64(v) => attributes.append_i64(v),
+                    AttributeValue::F64(v) => attributes.append_f64(v),
+                    AttributeValue::ArrI32(v) => attributes.append_arr_i32_from_iter(None, v),
+                    AttributeValue::ArrI64(v) => attributes.append_arr_i64_from_iter(None, v),
+                    AttributeValue::ArrF32(v) => attributes.append_arr_f32_from_iter(None, v),
+                    AttributeValue::ArrF64(v) => attributes.append_arr_f64_from_iter(None, v),
+                    AttributeValue::String(v) => attributes.append_string_direct(&v),
+                    other => panic!("unsupported test attribute {:?}", other),
+                }
+                .unwrap();
+            }
+            for child in self.children.iter() {
+                child.write(writer);
+            }
+            writer.close_node().unwrap();
+        }
+    }
+
+    /// Object node, FBX stores name and class as "name\0\x01class".
+    pub(crate) fn object(class: &'static str, subclass: &str, id: i64, name: &str) -> FbxNode {
+        let node_name = if class == "SubDeformer" {
+            "Deformer"
+        } else {
+            class
+        };
+        FbxNode::new(
+            node_name,
+            vec![
+                AttributeValue::I64(id),
+                AttributeValue::String(format!("{}\u{0}\u{1}{}", name, class)),
+                AttributeValue::String(subclass.to_owned()),
+            ],
+        )
+    }
+
+    /// Mesh geometry with control points and raw polygon vertex indices.
+    pub(crate) fn mesh_geometry(id: i64, points: &[[f64; 3]], polygon_vertices: &[i32]) -> FbxNode {
+        object("Geometry", "Mesh", id, "Geometry")
+            .with_child(FbxNode::new(
+                "Vertices",
+                vec![AttributeValue::ArrF64(
+                    points.iter().flatten().copied().collect(),
+                )],
+            ))
+            .with_child(FbxNode::new(
+                "PolygonVertexIndex",
+                vec![AttributeValue::ArrI32(polygon_vertices.to_vec())],
+            ))
+    }
+
+    /// `Properties70` node of an object.
+    pub(crate) fn properties(properties: Vec<FbxNode>) -> FbxNode {
+        let mut node = FbxNode::new("Properties70", vec![]);
+        node.children = properties;
+        node
+    }
+
+    /// Property node, values follow name, type, label and flags.
+    pub(crate) fn property(name: &str, data_type: &str, values: Vec<AttributeValue>) -> FbxNode {
+        let string = |s: &str| AttributeValue::String(s.to_owned());
+        let mut attributes = vec![string(name), string(data_type), string(""), string("A")];
+        attributes.extend(values);
+        FbxNode::new("P", attributes)
+    }
+
+    pub(crate) fn vector_property(name: &str, value: [f64; 3]) -> FbxNode {
+        property(name, name, value.map(AttributeValue::F64).to_vec())
+    }
+
+    /// Writes binary FBX 7.4 document with given objects and object to
+    /// object connections (source, destination), 0 is the scene root.
+    pub(crate) fn write_document(objects: Vec<FbxNode>, connections: &[(i64, i64)]) -> Vec<u8> {
+        write_labeled_document(objects, connections, &[])
+    }
+
+    /// Same as `write_document` with extra object to property connections
+    /// (source, destination, property name).
+    pub(crate) fn write_labeled_document(
+        objects: Vec<FbxNode>,
+        connections: &[(i64, i64)],
+        property_connections: &[(i64, i64, &str)],
+    ) -> Vec<u8> {
+        write_document_with(vec![], objects, connections, property_connections)
+    }
+
+    /// Writes document with top level `header` nodes before the objects.
+    pub(crate) fn write_document_with(
+        header: Vec<FbxNode>,
+        objects: Vec<FbxNode>,
+        connections: &[(i64, i64)],
+        property_connections: &[(i64, i64, &str)],
+    ) -> Vec<u8> {
+        let mut writer = Writer::new(Cursor::new(Vec::new()), FbxVersion::V7_4).unwrap();
+        let string = |s: &str| AttributeValue::String(s.to_owned());
+        let document = FbxNode::new("Documents", vec![]).with_child(FbxNode::new(
+            "Document",
+            vec![
+                AttributeValue::I64(1),
+                string("Scene\u{0}\u{1}Scene"),
+                string("Scene"),
+            ],
+        ));
+        document.write(&mut writer);
+        for node in header.iter() {
+            node.write(&mut writer);
+        }
+        let mut objects_node = FbxNode::new("Objects", vec![]);
+        objects_node.children = objects;
+        objects_node.write(&mut writer);
+        let mut connections_node = FbxNode::new("Connections", vec![]);
+        for (source, destination) in connections {
+            connections_node.children.push(FbxNode::new(
+                "C",
+                vec![
+                    string("OO"),
+                    AttributeValue::I64(*source),
+                    AttributeValue::I64(*destination),
+                ],
+            ));
+        }
+        for (source, destination, label) in property_connections {
+            connections_node.children.push(FbxNode::new(
+                "C",
+                vec![
+                    string("OP"),
+                    AttributeValue::I64(*source),
+                    AttributeValue::I64(*destination),
+                    string(label),
+                ],
+            ));
+        }
+        connections_node.write(&mut writer);
+        writer
+            .finalize_and_flush(&Default::default())
+            .unwrap()
+            .into_inner()
+    }
+
+    /// Material layer of a geometry mapping polygons to model materials.
+    pub(crate) fn material_layer(mapping: &str, materials: &[i32]) -> FbxNode {
+        let string = |s: &str| vec![AttributeValue::String(s.to_owned())];
+        FbxNode::new("LayerElementMaterial", vec![AttributeValue::I32(0)])
+            .with_child(FbxNode::new("MappingInformationType", string(mapping)))
+            .with_child(FbxNode::new(
+                "ReferenceInformationType",
+                string("IndexToDirect"),
+            ))
+            .with_child(FbxNode::new(
+                "Materials",
+                vec![AttributeValue::ArrI32(materials.to_vec())],
+            ))
+    }
+
+    /// Layer element with values and, if given, indices of them.
+    pub(crate) fn layer_element(
+        name: &'static str,
+        mapping: &str,
+        reference: &str,
+        values: (&'static str, Vec<f64>),
+        indices: Option<(&'static str, Vec<i32>)>,
+    ) -> FbxNode {
+        let string = |s: &str| vec![AttributeValue::String(s.to_owned())];
+        let mut node = FbxNode::new(name, vec![AttributeValue::I32(0)])
+            .with_child(FbxNode::new("MappingInformationType", string(mapping)))
+            .with_child(FbxNode::new("ReferenceInformationType", string(reference)))
+            .with_child(FbxNode::new(
+                values.0,
+                vec![AttributeValue::ArrF64(values.1)],
+            ));
+        if let Some((name, indices)) = indices {
+            node = node.with_child(FbxNode::new(name, vec![AttributeValue::ArrI32(indices)]));
+        }
+        node
+    }
+
+    pub(crate) fn texture(id: i64, file_name: &str, relative_file_name: &str) -> FbxNode {
+        let string = |s: &str| vec![AttributeValue::String(s.to_owned())];
+        object("Texture", "", id, "Texture")
+            .with_child(FbxNode::new("FileName", string(file_name)))
+            .with_child(FbxNode::new("RelativeFilename", string(relative_file_name)))
+    }
+
+    /// Writes `materials.fbx` into a fresh temporary directory `name` next to
+    /// copies of box.png and floor.png, returns path of the file. Models:
+    /// - "Boxed" uses box.png by its relative path;
+    /// - "Floored" stores only absolute Windows paths to floor.png;
+    /// - "Painted" has diffuse color and no texture;
+    /// - "Missing" refers to a texture that does not exist anywhere;
+    /// - "Second" picks its second material with an AllSame layer;
+    /// - "Mixed" has per polygon materials;
+    /// - "Split" is a cube with box.png on three faces and floor.png on the
+    ///   other three.
+    pub(crate) fn material_fixture(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        for file in ["box.png", "floor.png"] {
+            std::fs::copy(
+                Path::new("./src/assets/textures").join(file),
+                directory.join(file),
+            )
+            .unwrap();
+        }
+
+        let triangle = [[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let cube: Vec<[f64; 3]> = (0..8)
+            .map(|i| [1, 2, 4].map(|bit| if i & bit != 0 { 1.0 } else { -1.0 }))
+            .collect();
+        let bytes = write_labeled_document(
+            vec![
+                mesh_geometry(10, &triangle, &[0, 1, !2])
+                    .with_child(material_layer("AllSame", &[0])),
+                mesh_geometry(11, &triangle, &[0, 1, !2])
+                    .with_child(material_layer("AllSame", &[1])),
+                mesh_geometry(12, &triangle, &[0, 1, !2])
+                    .with_child(material_layer("ByPolygon", &[1])),
+                mesh_geometry(
+                    13,
+                    &cube,
+                    &[
+                        0, 1, 3, !2, 4, 6, 7, !5, 0, 4, 5, !1, 2, 3, 7, !6, 0, 2, 6, !4, 1, 5, 7,
+                        !3,
+                    ],
+                )
+                .with_child(material_layer("ByPolygon", &[0, 1, 0, 1, 1, 0])),
+                object("Model", "Mesh", 20, "Boxed"),
+                object("Model", "Mesh", 21, "Floored"),
+                object("Model", "Mesh", 22, "Painted"),
+                object("Model", "Mesh", 23, "Missing"),
+                object("Model", "Mesh", 24, "Second"),
+                object("Model", "Mesh", 25, "Mixed"),
+                object("Model", "Mesh", 26, "Split"),
+                object("Material", "", 50, "Wood"),
+                object("Material", "", 51, "Stone"),
+                object("Material", "", 52, "Red").with_child(properties(vec![
+                    property(
+                        "DiffuseColor",
+                        "Color",
+                        [1.0, 0.0, 0.0].map(AttributeValue::F64).to_vec(),
+                    ),
+                    property("DiffuseFactor", "Number", vec![AttributeValue::F64(0.5)]),
+                ])),
+                object("Material", "", 53, "Lost"),
+                texture(60, "D:\\art\\box.png", "box.png"),
+                texture(
+                    61,
+                    "C:\\Users\\artist\\maps\\floor.png",
+                    "..\\..\\maps\\floor.png",
+                ),
+                texture(63, "C:\\nowhere\\gone.png", ""),
+            ],
+            &[
+                (10, 20),
+                (10, 21),
+                (10, 22),
+                (10, 23),
+                (11, 24),
+                (12, 25),
+                (13, 26),
+                (20, 0),
+                (21, 0),
+                (22, 0),
+                (23, 0),
+                (24, 0),
+                (25, 0),
+                (26, 0),
+                (50, 20),
+                (51, 21),
+                (52, 22),
+                (53, 23),
+                (52, 24),
+                (50, 24),
+                (52, 25),
+                (50, 25),
+                (50, 26),
+                (51, 26),
+            ],
+            &[
+                (60, 50, "DiffuseColor"),
+                (61, 51, "DiffuseColor"),
+                (63, 53, "DiffuseColor"),
+            ],
+        );
+        let path = directory.join("materials.fbx");
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    pub(crate) fn load_document(bytes: Vec<u8>) -> Model {
+        load_from_reader(
+            Cursor::new(bytes),
+            "test",
+            Path::new(""),
+            &ImportOptions::default(),
+        )
+        .unwrap()
+    }
+
+    /// Control point indices of triangle corners.
+    fn triangulate(
+        polygon_vertices: &[i32],
+        points: &[[f32; 2]],
+    ) -> Result<Vec<i32>, ResourceError> {
+        let positions: Vec<_> = points
+            .iter()
+            .map(|&[x, y]| Vector3::new(x, y, 0.0))
+            .collect();
+        let vertices = decode_polygons(polygon_vertices, positions.len())?;
+        Ok(triangulate_polygons(&vertices, &positions)
+            .0
+            .into_iter()
+            .map(|corner| vertices[corner].control_point as i32)
+            .collect())
+    }
+
+    fn triangle_count(model: &Model, node: usize) -> usize {
+        model.get_node(node).unwrap().get_surfaces()[0]
+            .data
+            .borrow()
+            .get_triangle_count()
+    }
+
+    #[test]
+    fn fan_triangulation() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.5, 2.0]];
+        // Quad and triangle, ends are encoded as !index.
+        let indices = triangulate(&[0, 1, 2, !3, 3, 2, !4], &points).unwrap();
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3, 3, 2, 4]);
+
+        assert!(triangulate(&[0, 1, 2], &points[..3]).is_err());
+        assert!(triangulate(&[0, 1, !5], &points[..3]).is_err());
+    }
+
+    #[test]
+    fn concave_triangulation() {
+        // L-shape starting at its inner corner, a fan would cover the notch.
+        let points = [
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+        ];
+        let indices = triangulate(&[0, 1, 2, 3, 4, !5], &points).unwrap();
+        assert_eq!(indices.len(), 12);
+        let area: f32 = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| Vector2::from(points[triangle[i] as usize]));
+                let (ab, ac) = (b - a, c - a);
+                // Winding of the polygon is kept.
+                let area = (ab.x * ac.y - ab.y * ac.x) * 0.5;
+                assert!(area > 0.0);
+                area
+            })
+            .sum();
+        assert!((area - 3.0).abs() < 1e-5);
+
+        let bytes = write_document(
+            vec![
+                mesh_geometry(
+                    10,
+                    &points.map(|[x, y]| [x as f64, y as f64, 0.0]),
+                    // Second polygon has zero area, third repeats a point.
+                    &[0, 1, 2, 3, 4, !5, 0, 1, !1, 3, 3, !3],
+                ),
+                object("Model", "Mesh", 20, "Notched"),
+            ],
+            &[(10, 20), (20, 0)],
+        );
+        let model = load_document(bytes);
+        assert_eq!(model.get_import_report().degenerate_polygons, 2);
+        assert_eq!(triangle_count(&model, 1), 4);
+    }
+
+    #[test]
+    fn cube_geometry() {
+        let model = load(
+            Path::new("./src/assets/models/cube.fbx"),
+            &ImportOptions::default(),
+        )
+        .unwrap();
+        let names: Vec<_> = (0..model.get_node_count())
+            .map(|i| model.get_node(i).unwrap().get_name())
+            .collect();
+        assert_eq!(names, ["cube", "Cube", "Camera", "Light"]);
+        // Camera and light of the file come in as empty nodes.
+        let meshes: Vec<usize> = (0..model.get_node_count())
+            .filter(|i| !model.get_node(*i).unwrap().get_surfaces().is_empty())
+            .collect();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(triangle_count(&model, meshes[0]), 12);
+        let data = model.get_node(meshes[0]).unwrap().get_surfaces()[0]
+            .data
+            .clone();
+        let data = data.borrow();
+        // Flat normals of the file split every corner into three vertices.
+        assert_eq!(data.get_vertex_count(), 24);
+        assert!(data.get_indices().iter().all(|i| i < 24));
+        for (normal, tangent) in data.get_normals().iter().zip(data.get_tangents()) {
+            assert!((normal.abs().max() - 1.0).abs() < 1e-4);
+            assert!((normal.norm() - 1.0).abs() < 1e-4);
+            assert!(normal.dot(&tangent.xyz()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn ngon_geometry() {
+        // Hexagon in XZ plane next to a quad sharing one of its edges, the
+        // mesh is used by two models.
+        let points = [
+            [1.0, 0.0, 0.0],
+            [0.5, 0.0, 0.866],
+            [-0.5, 0.0, 0.866],
+            [-1.0, 0.0, 0.0],
+            [-0.5, 0.0, -0.866],
+            [0.5, 0.0, -0.866],
+            [2.0, 0.0, 0.0],
+            [2.0, 0.0, -1.0],
+        ];
+        let polygons = [0, 1, 2, 3, 4, !5, 0, 5, 7, !6];
+        let bytes = write_document(
+            vec![
+                mesh_geometry(10, &points, &polygons),
+                object("Model", "Mesh", 20, "First"),
+                object("Model", "Mesh", 21, "Second"),
+            ],
+            &[(10, 20), (10, 21), (20, 0), (21, 0)],
+        );
+        let model = load_document(bytes);
+        assert_eq!(model.get_node_count(), 3);
+        let mut names: Vec<_> = (1..3)
+            .map(|i| model.get_node(i).unwrap().get_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["First", "Second"]);
+        // Hexagon gives 4 triangles, quad 2.
+        assert_eq!(triangle_count(&model, 1), 6);
+        let first = &model.get_node(1).unwrap().get_surfaces()[0].data;
+        let second = &model.get_node(2).unwrap().get_surfaces()[0].data;
+        assert!(Rc::ptr_eq(first, second));
+
+        let data = first.borrow();
+        assert_eq!(data.get_vertex_count(), points.len());
+        assert_eq!(&data.get_indices().to_u32()[..6], [0, 1, 2, 0, 2, 3]);
+        assert_eq!(&data.get_indices().to_u32()[12..], [0, 5, 7, 0, 7, 6]);
+        // Polygons wind counterclockwise seen from below.
+        assert!(data.get_normals().iter().all(|n| (n.y + 1.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn culling() {
+        let culling =
+            |mode: &str| FbxNode::new("Culling", vec![AttributeValue::String(mode.to_owned())]);
+        let square = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let bytes = write_document(
+            vec![
+                mesh_geometry(10, &square, &[0, 1, 2, !3]),
+                object("Model", "Mesh", 20, "Leaf").with_child(culling("CullingOff")),
+                object("Model", "Mesh", 21, "Wall").with_child(culling("CullingOnCCW")),
+            ],
+            &[(10, 20), (10, 21), (20, 0), (21, 0)],
+        );
+        let model = load_document(bytes);
+        for i in 1..3 {
+            let node = model.get_node(i).unwrap();
+            let two_sided = node.get_surfaces()[0].is_two_sided();
+            assert_eq!(two_sided, node.get_name() == "Leaf", "{}", node.get_name());
+        }
+    }
+
+    #[test]
+    fn broken_geometry() {
+        let bytes = write_document(
+            vec![
+                mesh_geometry(10, &[[0.0; 3], [1.0, 0.0, 0.0]], &[0, 1, !2]),
+                object("Model", "Mesh", 20, "Broken"),
+            ],
+            &[(10, 20), (20, 0)],
+        );
+        assert!(load_from_reader(
+            Cursor::new(bytes),
+            "test",
+            Path::new(""),
+            &ImportOptions::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn import_errors() {
+        let open = |bytes: Vec<u8>| {
+            load_from_reader(
+                Cursor::new(bytes),
+                "test",
+                Path::new(""),
+                &ImportOptions::default(),
+            )
+        };
+        assert!(matches!(
+            load(
+                Path::new("./src/assets/models/ascii.fbx"),
+                &ImportOptions::default()
+            ),
+            Err(ResourceError::Model(ModelImportError::AsciiFbx))
+        ));
+        assert!(matches!(
+            load(
+                Path::new("./src/assets/models/missing.fbx"),
+                &ImportOptions::default()
+            ),
+            Err(ResourceError::Io(_))
+        ));
+        assert!(matches!(
+            open(Vec::new()),
+            Err(ResourceError::Model(ModelImportError::AsciiFbx))
+        ));
+
+        let bytes = write_document(vec![object("Model", "Null", 20, "Cut")], &[(20, 0)]);
+        for len in [bytes.len() / 2, 25] {
+            let result = open(bytes[..len].to_vec());
+            assert!(
+                matches!(
+                    result,
+                    Err(ResourceError::Model(ModelImportError::Malformed(_)))
+                ),
+                "{} bytes: {:?}",
+                len,
+                result
+            );
+        }
+
+        let mut old = bytes.clone();
+        old[23..27].copy_from_slice(&6100u32.to_le_bytes());
+        assert!(matches!(
+            open(old),
+            Err(ResourceError::Model(ModelImportError::UnsupportedVersion(
+                6100
+            )))
+        ));
+    }
+
+    #[test]
+    fn model_hierarchy() {
+        let bytes = write_document(
+            vec![
+                mesh_geometry(
+                    10,
+                    &[[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+                    &[0, 1, !2],
+                ),
+                object("Model", "Null", 30, "Body").with_child(properties(vec![
+                    vector_property("Lcl Translation", [1.0, 2.0, 3.0]),
+                    vector_property("Lcl Rotation", [0.0, 90.0, 0.0]),
+                ])),
+                object("Model", "Mesh", 31, "Arm").with_child(properties(vec![
+                    vector_property("Lcl Translation", [0.0, 1.0, 0.0]),
+                    vector_property("Lcl Rotation", [30.0, 0.0, 45.0]),
+                    vector_property("Lcl Scaling", [2.0, 2.0, 2.0]),
+                    property("RotationOrder", "enum", vec![AttributeValue::I32(5)]),
+                    property("RotationActive", "bool", vec![AttributeValue::I32(1)]),
+                ])),
+                object("Model", "Null", 32, "Hand").with_child(properties(vec![vector_property(
+                    "Lcl Translation",
+                    [0.0, 0.0, -1.0],
+                )])),
+                object("Model", "Mesh", 33, "Prop"),
+                object("Model", "Null", 40, "Loose"),
+            ],
+            &[
+                (10, 31),
+                (10, 33),
+                (30, 0),
+                (31, 30),
+                (32, 31),
+                (33, 30),
+                (33, 32),
+            ],
+        );
+        let model = load_document(bytes);
+        let name = |index: usize| model.get_node(index).unwrap().get_name();
+        let children = |index: usize| -> Vec<&str> {
+            let node = model.get_node(index).unwrap();
+            node.children.iter().map(|child| name(*child)).collect()
+        };
+        let find = |name: &str| -> Vec<usize> {
+            (0..model.get_node_count())
+                .filter(|i| model.get_node(*i).unwrap().get_name() == name)
+                .collect()
+        };
+
+        assert_eq!(children(0), ["Body", "Loose"]);
+        let body = find("Body")[0];
+        assert_eq!(children(body), ["Arm", "Prop"]);
+        let arm = find("Arm")[0];
+        assert_eq!(children(arm), ["Hand"]);
+        let hand = find("Hand")[0];
+        assert_eq!(children(hand), ["Prop"]);
+
+        let node = |index: usize| model.get_node(index).unwrap();
+        assert_eq!(node(body).local_position, Vector3::new(1.0, 2.0, 3.0));
+        let quarter_turn =
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_2);
+        assert!(node(body).local_rotation.angle_to(&quarter_turn) < 1e-5);
+        assert_eq!(node(body).local_scale, Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(node(arm).local_position, Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(node(arm).local_scale, Vector3::new(2.0, 2.0, 2.0));
+        let zyx = quat_from_euler(Vector3::new(30.0, 0.0, 45.0), RotationOrder::Zyx);
+        assert!(node(arm).local_rotation.angle_to(&zyx) < 1e-5);
+        assert_eq!(node(hand).local_position, Vector3::new(0.0, 0.0, -1.0));
+
+        // Null models are empty, instanced prop shares geometry with the arm.
+        assert!(node(body).get_surfaces().is_empty());
+        let props = find("Prop");
+        assert_eq!(props.len(), 2);
+        let arm_data = &node(arm).get_surfaces()[0].data;
+        for prop in props {
+            assert!(Rc::ptr_eq(&node(prop).get_surfaces()[0].data, arm_data));
+        }
+    }
+
+    #[test]
+    fn model_cycle() {
+        let bytes = write_document(
+            vec![
+                object("Model", "Null", 30, "Root"),
+                object("Model", "Null", 31, "A"),
+                object("Model", "Null", 32, "B"),
+            ],
+            &[(30, 0), (31, 30), (32, 31), (31, 32)],
+        );
+        assert!(load_from_reader(
+            Cursor::new(bytes),
+            "test",
+            Path::new(""),
+            &ImportOptions::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn materials() {
+        let model = load(
+            &material_fixture("balala_fbx_materials"),
+            &ImportOptions::default(),
+        )
+        .unwrap();
+        let surface = |name: &str| -> ModelSurface {
+            (0..model.get_node_count())
+                .map(|i| model.get_node(i).unwrap())
+                .find(|node| node.get_name() == name)
+                .unwrap()
+                .get_surfaces()[0]
+                .clone()
+        };
+
+        let boxed = surface("Boxed");
+        assert_eq!(boxed.get_texture_path(), Some(Path::new("box.png")));
+        assert_eq!(boxed.get_tint(), Color::WHITE);
+        // Baked Windows paths fall back to the file next to the model.
+        let floored = surface("Floored");
+        assert_eq!(floored.get_texture_path(), Some(Path::new("floor.png")));
+
+        let painted = surface("Painted");
+        assert_eq!(painted.get_texture_path(), None);
+        assert_eq!(painted.get_tint(), Color::opaque(0.5, 0.0, 0.0));
+        // Missing texture leaves default FBX diffuse color.
+        let missing = surface("Missing");
+        assert_eq!(missing.get_texture_path(), None);
+        assert_eq!(missing.get_tint(), Color::opaque(0.8, 0.8, 0.8));
+
+        assert_eq!(
+            surface("Second").get_texture_path(),
+            Some(Path::new("box.png"))
+        );
+        // The only polygon uses the second material.
+        assert_eq!(
+            surface("Mixed").get_texture_path(),
+            Some(Path::new("box.png"))
+        );
+        // Geometry stays shared between differently looking models.
+        assert!(Rc::ptr_eq(&boxed.data, &painted.data));
+
+        let split = model
+            .nodes
+            .iter()
+            .find(|node| node.get_name() == "Split")
+            .unwrap();
+        let surfaces = split.get_surfaces();
+        let textures: Vec<_> = surfaces
+            .iter()
+            .map(|surface| surface.get_texture_path().unwrap())
+            .collect();
+        assert_eq!(textures, [Path::new("box.png"), Path::new("floor.png")]);
+        // Both materials draw their halves of one buffer.
+        assert!(Rc::ptr_eq(&surfaces[0].data, &surfaces[1].data));
+        assert_eq!(surfaces[0].data.borrow().get_indices().len(), 36);
+        let ranges: Vec<_> = surfaces.iter().map(|s| s.get_draw_range()).collect();
+        assert_eq!(
+            ranges,
+            [Some(DrawRange::new(0, 18)), Some(DrawRange::new(18, 18))]
+        );
+        assert_eq!(boxed.get_draw_range(), None);
+        let report = model.get_import_report();
+        assert!(report.mesh_surfaces.contains(&("Split".to_owned(), 2)));
+        assert!(report.mesh_surfaces.contains(&("Mixed".to_owned(), 1)));
+
+        let model = ModelHandle::new(Path::new("materials.fbx"), model);
+        let mut scene = Scene::new();
+        scene.instantiate_model(&model);
+        let handle = scene.find_nodes(|node| node.get_name() == "Split")[0].clone();
+        let NodeKind::Mesh(mesh) = scene.borrow_node(&handle).unwrap().borrow_kind() else {
+            panic!("expected mesh");
+        };
+        let instanced: Vec<_> = mesh.surfaces.iter().map(|s| s.get_draw_range()).collect();
+        assert_eq!(instanced, ranges);
+    }
+
+    /// Unit quad in XY plane facing +Z made of two triangles.
+    const QUAD: [[f64; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const QUAD_POLYGONS: [i32; 6] = [0, 1, !2, 0, 2, !3];
+
+    fn load_layers(layers: Vec<FbxNode>) -> Model {
+        let mut geometry = mesh_geometry(10, &QUAD, &QUAD_POLYGONS);
+        geometry.children.extend(layers);
+        load_document(write_document(
+            vec![geometry, object("Model", "Mesh", 20, "Quad")],
+            &[(10, 20), (20, 0)],
+        ))
+    }
+
+    #[test]
+    fn control_point_normals() {
+        let normals = [
+            [0.0, 0.0, 1.0],
+            [0.6, 0.0, 0.8],
+            [0.0, 0.6, 0.8],
+            [-0.6, 0.0, 0.8],
+        ];
+        let model = load_layers(vec![layer_element(
+            "LayerElementNormal",
+            "ByVertice",
+            "Direct",
+            ("Normals", normals.iter().flatten().copied().collect()),
+            None,
+        )]);
+        let data = model.get_node(1).unwrap().get_surfaces()[0].data.clone();
+        let data = data.borrow();
+        // Corners shared by the triangles have equal attributes and merge.
+        assert_eq!(data.get_vertex_count(), 4);
+        assert_eq!(data.get_indices().to_u32(), [0, 1, 2, 0, 2, 3]);
+        for (i, point) in QUAD.iter().enumerate() {
+            let expected = Vector3::from(point.map(|v| v as f32));
+            assert_eq!(data.get_positions()[i], expected);
+            let expected = Vector3::from(normals[i].map(|v| v as f32));
+            assert!((data.get_normals()[i] - expected).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn polygon_vertex_uvs() {
+        let model = load_layers(vec![
+            layer_element(
+                "LayerElementNormal",
+                "ByPolygon",
+                "Direct",
+                ("Normals", vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0]),
+                None,
+            ),
+            layer_element(
+                "LayerElementUV",
+                "ByPolygonVertex",
+                "IndexToDirect",
+                ("UV", vec![0.0, 0.0, 0.5, 0.0, 0.5, 0.25, 0.0, 0.25]),
+                Some(("UVIndex", vec![0, 1, 2, 0, 2, 3])),
+            ),
+        ]);
+        let data = model.get_node(1).unwrap().get_surfaces()[0].data.clone();
+        let data = data.borrow();
+        assert_eq!(data.get_vertex_count(), 4);
+        // V is flipped.
+        assert_eq!(
+            data.get_tex_coords(),
+            [
+                Vector2::new(0.0, 1.0),
+                Vector2::new(0.5, 1.0),
+                Vector2::new(0.5, 0.75),
+                Vector2::new(0.0, 0.75)
+            ]
+        );
+        assert!(data
+            .get_normals()
+            .iter()
+            .all(|normal| *normal == Vector3::z()));
+        // U goes along +X, flipped V along -Y.
+        for tangent in data.get_tangents() {
+            assert!((tangent.xyz() - Vector3::x()).norm() < 1e-6);
+            assert_eq!(tangent.w, -1.0);
+        }
+    }
+
+    #[test]
+    fn unsupported_layers() {
+        let model = load_layers(vec![
+            layer_element(
+                "LayerElementNormal",
+                "ByEdge",
+                "Direct",
+                ("Normals", vec![0.0; 15]),
+                None,
+            ),
+            layer_element(
+                "LayerElementUV",
+                "ByPolygonVertex",
+                "IndexToDirect",
+                ("UV", vec![0.0; 8]),
+                Some(("UVIndex", vec![0, 1, 2, 0, 2, 7])),
+            ),
+        ]);
+        let data = model.get_node(1).unwrap().get_surfaces()[0].data.clone();
+        let data = data.borrow();
+        // Falls back to control points with recalculated normals.
+        assert_eq!(data.get_vertex_count(), 4);
+        assert!(data
+            .get_normals()
+            .iter()
+            .all(|normal| (normal - Vector3::z()).norm() < 1e-6));
+        assert!(data
+            .get_tex_coords()
+            .iter()
+            .all(|uv| *uv == Vector2::zeros()));
+    }
+
+    #[test]
+    fn pivot_transform() {
+        // Cube with corners at +-1.
+        let points: Vec<[f64; 3]> = (0..8)
+            .map(|i| [1, 2, 4].map(|bit| if i & bit != 0 { 1.0 } else { -1.0 }))
+            .collect();
+        let polygons = [
+            0, 1, 3, !2, 4, 6, 7, !5, 0, 4, 5, !1, 2, 3, 7, !6, 0, 2, 6, !4, 1, 5, 7, !3,
+        ];
+        let bytes = write_document(
+            vec![
+                mesh_geometry(10, &points, &polygons),
+                // Turned about its (1, 1, 1) corner.
+                object("Model", "Mesh", 20, "Hinged").with_child(properties(vec![
+                    vector_property("Lcl Rotation", [0.0, 0.0, 90.0]),
+                    vector_property("RotationPivot", [1.0, 1.0, 1.0]),
+                ])),
+                // Grown from the same corner, then pre-rotated about the
+                // origin and moved by offset and translation.
+                object("Model", "Mesh", 21, "Scaled").with_child(properties(vec![
+                    property("RotationActive", "bool", vec![AttributeValue::I32(1)]),
+                    vector_property("PreRotation", [0.0, 0.0, 90.0]),
+                    vector_property("ScalingPivot", [1.0, 1.0, 1.0]),
+                    vector_property("Lcl Scaling", [2.0, 2.0, 2.0]),
+                    vector_property("RotationOffset", [0.0, 5.0, 0.0]),
+                    vector_property("Lcl Translation", [10.0, 0.0, 0.0]),
+                ])),
+                // Scaling offset is a translation applied before rotation.
+                object("Model", "Mesh", 22, "Shifted").with_child(properties(vec![
+                    vector_property("Lcl Rotation", [0.0, 90.0, 0.0]),
+                    vector_property("ScalingOffset", [0.0, 0.0, 3.0]),
+                ])),
+            ],
+            &[(10, 20), (10, 21), (10, 22), (20, 0), (21, 0), (22, 0)],
+        );
+        let model = ModelHandle::new(Path::new("pivots.fbx"), load_document(bytes));
+        let mut scene = Scene::new();
+        scene.instantiate_model(&model);
+        scene.update(1.0, 0.0);
+
+        let corner = |name: &str, index: usize| -> Vector3<f32> {
+            let handle = scene.find_nodes(|node| node.get_name() == name)[0].clone();
+            let node = scene.borrow_node(&handle).unwrap();
+            let NodeKind::Mesh(mesh) = node.borrow_kind() else {
+                panic!("expected mesh");
+            };
+            let position = mesh.surfaces[0].data.borrow().get_positions()[index];
+            node.global_transform
+                .transform_point(&Point3::from(position))
+                .coords
+        };
+        let expectations = [
+            ("Hinged", 7, [1.0, 1.0, 1.0]),
+            ("Hinged", 0, [3.0, -1.0, -1.0]),
+            ("Hinged", 5, [3.0, 1.0, 1.0]),
+            ("Scaled", 7, [9.0, 6.0, 1.0]),
+            ("Scaled", 0, [13.0, 2.0, -3.0]),
+            ("Shifted", 7, [4.0, 1.0, -1.0]),
+            ("Shifted", 0, [2.0, -1.0, 1.0]),
+        ];
+        for (name, index, expected) in expectations {
+            let position = corner(name, index);
+            assert!(
+                (position - Vector3::from(expected)).norm() < 1e-4,
+                "{} corner {}: {:?}",
+                name,
+                index,
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn unit_and_axis_conversion() {
+        // 1x1x2 meter box standing on the ground, modelled in centimeters
+        // with Z up.
+        let points: Vec<[f64; 3]> = (0..8)
+            .map(|i| {
+                let [x, y, z] = [1, 2, 4].map(|bit| i & bit != 0);
+                [
+                    if x { 50.0 } else { -50.0 },
+                    if y { 50.0 } else { -50.0 },
+                    if z { 200.0 } else { 0.0 },
+                ]
+            })
+            .collect();
+        let polygons = [
+            0, 1, 3, !2, 4, 6, 7, !5, 0, 4, 5, !1, 2, 3, 7, !6, 0, 2, 6, !4, 1, 5, 7, !3,
+        ];
+        let settings = properties(vec![
+            property("UpAxis", "int", vec![AttributeValue::I32(2)]),
+            property("UpAxisSign", "int", vec![AttributeValue::I32(1)]),
+            property("FrontAxis", "int", vec![AttributeValue::I32(1)]),
+            property("FrontAxisSign", "int", vec![AttributeValue::I32(-1)]),
+            property("CoordAxis", "int", vec![AttributeValue::I32(0)]),
+            property("CoordAxisSign", "int", vec![AttributeValue::I32(1)]),
+            property("UnitScaleFactor", "double", vec![AttributeValue::F64(1.0)]),
+        ]);
+        let bytes = write_document_with(
+            vec![FbxNode::new("GlobalSettings", vec![]).with_child(settings)],
+            vec![
+                mesh_geometry(10, &points, &polygons),
+                object("Model", "Mesh", 20, "Box"),
+            ],
+            &[(10, 20), (20, 0)],
+            &[],
+        );
+        let bounds = |options: &ImportOptions| {
+            let model =
+                load_from_reader(Cursor::new(bytes.clone()), "test", Path::new(""), options)
+                    .unwrap();
+            let model = ModelHandle::new(Path::new("box.fbx"), model);
+            let mut scene = Scene::new();
+            let root = scene.instantiate_model(&model);
+            scene.update(1.0, 0.0);
+            let root = scene.borrow_node(&root).unwrap();
+            let up = root.global_transform.transform_vector(&Vector3::z());
+            let box_node = scene.borrow_node(&root.children()[0]).unwrap();
+            let NodeKind::Mesh(mesh) = box_node.borrow_kind() else {
+                panic!("expected mesh");
+            };
+            let bounds = mesh.surfaces[0].data.borrow().bounding_box();
+            (bounds.transform(&box_node.global_transform), up)
+        };
+
+        let (meters, up) = bounds(&ImportOptions::default());
+        assert!((up.normalize() - Vector3::y()).norm() < 1e-5);
+        assert!((meters.min - Vector3::new(-0.5, 0.0, -0.5)).norm() < 1e-5);
+        assert!((meters.max - Vector3::new(0.5, 2.0, 0.5)).norm() < 1e-5);
+
+        let (centimeters, _) = bounds(&ImportOptions { unit: 0.01 });
+        assert!((centimeters.max - Vector3::new(50.0, 200.0, 50.0)).norm() < 1e-3);
+    }
+
+    /// Animation curve with keys at times in seconds.
+    fn curve(id: i64, keys: &[(f64, f32)]) -> FbxNode {
+        object("AnimationCurve", "", id, "")
+            .with_child(FbxNode::new(
+                "KeyTime",
+                vec![AttributeValue::ArrI64(
+                    keys.iter()
+                        .map(|(time, _)| (time * 46_186_158_000.0) as i64)
+                        .collect(),
+                )],
+            ))
+            .with_child(FbxNode::new(
+                "KeyValueFloat",
+                vec![AttributeValue::ArrF32(
+                    keys.iter().map(|(_, value)| *value).collect(),
+                )],
+            ))
+    }
+
+    fn curve_node(id: i64, name: &str, value: [f64; 3]) -> FbxNode {
+        let component =
+            |name: &str, value: f64| property(name, "Number", vec![AttributeValue::F64(value)]);
+        object("AnimationCurveNode", "", id, name).with_child(properties(vec![
+            component("d|X", value[0]),
+            component("d|Y", value[1]),
+            component("d|Z", value[2]),
+        ]))
+    }
+
+    #[test]
+    fn animation_curves() {
+        let bytes = write_labeled_document(
+            vec![
+                object("Model", "Null", 20, "Fan"),
+                object("AnimationStack", "", 30, "Spin"),
+                object("AnimationLayer", "", 31, "Base"),
+                curve_node(32, "R", [0.0; 3]),
+                curve(33, &[(0.0, 0.0), (1.0, 90.0)]),
+                curve_node(34, "T", [1.0, 2.0, 3.0]),
+                object("AnimationStack", "", 40, "Pulse"),
+                object("AnimationLayer", "", 41, "Base"),
+                curve_node(42, "S", [1.0; 3]),
+                curve(43, &[(0.0, 2.0)]),
+            ],
+            &[(20, 0), (31, 30), (32, 31), (34, 31), (41, 40), (42, 41)],
+            &[
+                (32, 20, "Lcl Rotation"),
+                (33, 32, "d|Y"),
+                (34, 20, "Lcl Translation"),
+                (42, 20, "Lcl Scaling"),
+                (43, 42, "d|X"),
+            ],
+        );
+        let model = load_document(bytes);
+        let names: Vec<_> = model
+            .get_animations()
+            .iter()
+            .map(|animation| animation.get_animation().get_name())
+            .collect();
+        assert_eq!(names, ["Spin", "Pulse"]);
+
+        let spin = &model.get_animations()[0];
+        assert_eq!(spin.get_track_nodes(), [1]);
+        let track = &spin.get_animation().get_tracks()[0];
+        assert_eq!(spin.get_animation().get_length(), 1.0);
+        for (time, degrees) in [(0.0, 0.0), (0.5, 45.0), (1.0, 90.0)] {
+            let expected = quat_from_euler(Vector3::new(0.0, degrees, 0.0), RotationOrder::Xyz);
+            let rotation = track.sample_rotation(time).unwrap();
+            assert!(rotation.angle_to(&expected) < 1e-5, "{} s", time);
+        }
+        // Channel without curves still gives a key.
+        assert_eq!(
+            track.sample_position(0.5),
+            Some(Vector3::new(1.0, 2.0, 3.0))
+        );
+        let pulse = &model.get_animations()[1].get_animation().get_tracks()[0];
+        assert_eq!(pulse.sample_scale(0.0), Some(Vector3::new(2.0, 1.0, 1.0)));
+
+        // Instances play the first animation.
+        let model = ModelHandle::new(Path::new("fan.fbx"), model);
+        let mut scene = Scene::new();
+        let root = scene.instantiate_model(&model);
+        let fan = scene.borrow_node(&root).unwrap().children()[0].clone();
+        let animations = scene.find_animations(|_| true);
+        assert_eq!(animations.len(), 2);
+        let spin = scene.borrow_animation(&animations[0]).unwrap();
+        assert!(spin.is_enabled());
+        assert_eq!(spin.get_tracks()[0].get_node(), &fan);
+        assert!(!scene.borrow_animation(&animations[1]).unwrap().is_enabled());
+        scene.update(1.0, 0.25);
+        let quarter = quat_from_euler(Vector3::new(0.0, 22.5, 0.0), RotationOrder::Xyz);
+        let rotation = scene.borrow_node(&fan).unwrap().get_local_rotation();
+        assert!(rotation.angle_to(&quarter) < 1e-5);
+    }
+
+    #[test]
+    fn influence_limit() {
+        let (bones, weights) =
+            strongest_influences(&[(0, 0.1), (1, 0.4), (2, 0.2), (3, 0.4), (4, 0.2), (5, 0.1)]);
+        // Equal weights keep the lower bone first, the two weakest are dropped.
+        assert_eq!(bones, [1, 3, 2, 4]);
+        assert!((weights - Vector4::new(0.4, 0.4, 0.2, 0.2) / 1.2).norm() < 1e-6);
+
+        let (bones, weights) = strongest_influences(&[(7, 0.5)]);
+        assert_eq!(bones, [7, 0, 0, 0]);
+        assert_eq!(weights, Vector4::new(1.0, 0.0, 0.0, 0.0));
+    }
+
+    /// Skin cluster of control points with bind transforms of the mesh and
+    /// of the linked bone.
+    fn cluster(
+        id: i64,
+        points: &[(i32, f64)],
+        transform: Matrix4<f64>,
+        link: Matrix4<f64>,
+    ) -> FbxNode {
+        let matrix = |m: Matrix4<f64>| AttributeValue::ArrF64(m.as_slice().to_vec());
+        object("SubDeformer", "Cluster", id, "")
+            .with_child(FbxNode::new(
+                "Indexes",
+                vec![AttributeValue::ArrI32(points.iter().map(|p| p.0).collect())],
+            ))
+            .with_child(FbxNode::new(
+                "Weights",
+                vec![AttributeValue::ArrF64(points.iter().map(|p| p.1).collect())],
+            ))
+            .with_child(FbxNode::new("Transform", vec![matrix(transform)]))
+            .with_child(FbxNode::new("TransformLink", vec![matrix(link)]))
+    }
+
+    #[test]
+    fn skin_clusters() {
+        let points = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 2.0, 0.0],
+            [0.0, 2.0, 0.0],
+        ];
+        let tip = Matrix4::new_translation(&Vector3::new(0.0, 1.0, 0.0));
+        let bytes = write_document(
+            vec![
+                mesh_geometry(10, &points, &[0, 1, 2, !3]),
+                object("Model", "Mesh", 20, "Skinned"),
+                object("Model", "LimbNode", 21, "Root"),
+                object("Model", "LimbNode", 22, "Tip").with_child(properties(vec![
+                    vector_property("Lcl Translation", [0.0, 1.0, 0.0]),
+                ])),
+                object("Model", "Mesh", 23, "Rigid"),
+                mesh_geometry(11, &points, &[0, 1, 2, !3]),
+                object("Deformer", "Skin", 30, "Skin"),
+                cluster(
+                    31,
+                    &[(0, 1.0), (1, 1.0), (2, 0.5), (3, 0.25)],
+                    Matrix4::identity(),
+                    Matrix4::identity(),
+                ),
+                cluster(32, &[(2, 0.5), (3, 0.75)], Matrix4::identity(), tip),
+            ],
+            &[
+                (10, 20),
+                (20, 0),
+                (21, 0),
+                (22, 21),
+                (11, 23),
+                (23, 0),
+                (30, 10),
+                (31, 30),
+                (32, 30),
+                (21, 31),
+                (22, 32),
+            ],
+        );
+        let model = load_document(bytes);
+        let node = |name: &str| {
+            model
+                .nodes
+                .iter()
+                .position(|node| node.get_name() == name)
+                .unwrap()
+        };
+        let skinned = &model.nodes[node("Skinned")];
+        let data = skinned.get_surfaces()[0].data.borrow();
+        assert_eq!(
+            data.get_bone_indices(),
+            [[0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 0, 0], [1, 0, 0, 0]]
+        );
+        assert_eq!(
+            data.get_bone_weights(),
+            [
+                Vector4::new(1.0, 0.0, 0.0, 0.0),
+                Vector4::new(1.0, 0.0, 0.0, 0.0),
+                Vector4::new(0.5, 0.5, 0.0, 0.0),
+                Vector4::new(0.75, 0.25, 0.0, 0.0),
+            ]
+        );
+        let bones: Vec<_> = skinned
+            .get_bones()
+            .iter()
+            .map(|bone| bone.get_node())
+            .collect();
+        assert_eq!(bones, [node("Root"), node("Tip")]);
+        assert_eq!(
+            *skinned.get_bones()[1].get_transform_link(),
+            tip.cast::<f32>()
+        );
+        assert_eq!(
+            *skinned.get_bones()[1].get_inverse_bind_pose(),
+            Matrix4::new_translation(&Vector3::new(0.0, -1.0, 0.0))
+        );
+        // Geometry without skin deformer has no skinning channels.
+        let rigid = &model.nodes[node("Rigid")];
+        assert!(!rigid.get_surfaces()[0].data.borrow().is_skinned());
+        assert!(rigid.get_bones().is_empty());
+        drop(data);
+
+        // Bones at bind pose leave vertices in place, moved bones carry them.
+        let model = ModelHandle::new(Path::new("skin.fbx"), model);
+        let mut scene = Scene::new();
+        scene.instantiate_model(&model);
+        scene.update(1.0, 0.0);
+        let bone_matrices = |scene: &Scene| {
+            let handle = scene.find_nodes(|node| node.get_name() == "Skinned")[0].clone();
+            let NodeKind::Mesh(mesh) = scene.borrow_node(&handle).unwrap().borrow_kind() else {
+                panic!("expected mesh");
+            };
+            mesh.get_bone_matrices().to_vec()
+        };
+        assert_eq!(bone_matrices(&scene), [Matrix4::identity(); 2]);
+        let tip = scene.find_nodes(|node| node.get_name() == "Tip")[0].clone();
+        scene
+            .borrow_node_mut(&tip)
+            .unwrap()
+            .set_local_position(Vector3::new(1.0, 1.0, 0.0));
+        scene.update(1.0, 0.0);
+        let moved = bone_matrices(&scene);
+        assert_eq!(moved[0], Matrix4::identity());
+        assert!((moved[1] - Matrix4::new_translation(&Vector3::x())).norm() < 1e-6);
+    }
+
+    #[test]
+    fn cameras_and_lights() {
+        let number =
+            |name: &str, value: f64| property(name, "Number", vec![AttributeValue::F64(value)]);
+        let enumeration =
+            |name: &str, value: i32| property(name, "enum", vec![AttributeValue::I32(value)]);
+        let bytes =
+            write_document_with(
+                vec![FbxNode::new("GlobalSettings", vec![])
+                    .with_child(properties(vec![number("UnitScaleFactor", 100.0)]))],
+                vec![
+                    object("Model", "Camera", 20, "Eye").with_child(properties(vec![
+                        vector_property("Lcl Translation", [0.0, 1.0, 5.0]),
+                    ])),
+                    object("NodeAttribute", "Camera", 21, "").with_child(properties(vec![
+                        enumeration("ApertureMode", 2),
+                        number("FieldOfView", 60.0),
+                        number("NearPlane", 0.5),
+                        number("FarPlane", 200.0),
+                    ])),
+                    object("Model", "Null", 28, "Strap").with_child(properties(vec![
+                        vector_property("Lcl Translation", [0.0, 0.0, 2.0]),
+                    ])),
+                    object("Model", "Camera", 22, "Lens"),
+                    // 35 mm lens with full frame film height of 24 mm.
+                    object("NodeAttribute", "Camera", 23, "").with_child(properties(vec![
+                        enumeration("ApertureMode", 3),
+                        number("FocalLength", 35.0),
+                        number("FilmHeight", 24.0 / 25.4),
+                    ])),
+                    object("Model", "Light", 24, "Bulb").with_child(properties(vec![
+                        vector_property("Lcl Translation", [2.0, 3.0, 0.0]),
+                    ])),
+                    object("NodeAttribute", "Light", 25, "").with_child(properties(vec![
+                        enumeration("LightType", 0),
+                        property(
+                            "Color",
+                            "Color",
+                            [1.0, 0.5, 0.25].map(AttributeValue::F64).to_vec(),
+                        ),
+                        number("Intensity", 50.0),
+                        property("EnableFarAttenuation", "bool", vec![AttributeValue::I32(1)]),
+                        number("FarAttenuationEnd", 8.0),
+                    ])),
+                    object("Model", "Light", 26, "Spot"),
+                    object("NodeAttribute", "Light", 27, "")
+                        .with_child(properties(vec![enumeration("LightType", 2)])),
+                ],
+                &[
+                    (20, 0),
+                    (21, 20),
+                    (22, 0),
+                    (23, 22),
+                    (24, 0),
+                    (25, 24),
+                    (26, 0),
+                    (27, 26),
+                    (28, 20),
+                ],
+                &[],
+            );
+        let model = ModelHandle::new(Path::new("view.fbx"), load_document(bytes));
+        let mut scene = Scene::new();
+        scene.instantiate_model(&model);
+        scene.update(1.0, 0.0);
+        let node = |name: &str| {
+            let handle = scene.find_nodes(|node| node.get_name() == name)[0].clone();
+            scene.borrow_node(&handle).unwrap()
+        };
+
+        let eye = node("Eye");
+        let NodeKind::Camera(camera) = eye.borrow_kind() else {
+            panic!("expected camera");
+        };
+        assert!((camera.get_fov() - 60.0).abs() < 1e-4);
+        assert_eq!((camera.get_z_near(), camera.get_z_far()), (0.5, 200.0));
+        assert!((eye.get_global_position() - Vector3::new(0.0, 1.0, 5.0)).norm() < 1e-5);
+        // View looks along FBX +X keeping +Y up, the node is not turned.
+        let (look, up) = camera.get_view_axes(&eye.global_transform);
+        assert!((look - Vector3::x()).norm() < 1e-5);
+        assert!((up - Vector3::y()).norm() < 1e-5);
+        assert!((eye.get_look_vector() - Vector3::z()).norm() < 1e-5);
+        let ahead = camera
+            .get_view_projection_matrix()
+            .transform_point(&Point3::new(10.0, 1.0, 5.0));
+        assert!(ahead.x.abs() < 1e-5 && ahead.y.abs() < 1e-5);
+        // Children keep their FBX placement.
+        let strap = node("Strap").get_global_position();
+        assert!(
+            (strap - Vector3::new(0.0, 1.0, 7.0)).norm() < 1e-5,
+            "{:?}",
+            strap
+        );
+
+        let NodeKind::Camera(lens) = node("Lens").borrow_kind() else {
+            panic!("expected camera");
+        };
+        assert!((lens.get_fov() - 37.849).abs() < 1e-2, "{}", lens.get_fov());
+
+        let bulb = node("Bulb");
+        let NodeKind::Light(light) = bulb.borrow_kind() else {
+            panic!("expected light");
+        };
+        let color = light.get_color().rgb() * light.get_intensity();
+        assert!((color - Vector3::new(0.5, 0.25, 0.125)).norm() < 1e-6);
+        assert_eq!(light.get_radius(), 8.0);
+        assert!((bulb.get_global_position() - Vector3::new(2.0, 3.0, 0.0)).norm() < 1e-5);
+
+        // Spot lights degrade to point lights.
+        assert!(matches!(node("Spot").borrow_kind(), NodeKind::Light(_)));
+    }
+}
@@ -1,12 +1,76 @@
+pub mod fbx;
+pub mod model;
 pub mod texture;
-use std::path::{Path, PathBuf};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
 
-use crate::resource::texture::*;
+use crate::resource::{
+    model::{Model, ModelImportError},
+    texture::*,
+};
+
+#[derive(Debug)]
+pub enum ResourceError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    /// File was read but its content is malformed.
+    Parse(String),
+    /// Model file is in a format or version the importer doesn't read.
+    Model(ModelImportError),
+    /// No loader for the file extension.
+    UnsupportedFormat(PathBuf),
+    /// Path is already loaded as a resource of another kind.
+    KindMismatch(PathBuf),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::Io(err) => write!(f, "I/O error: {}", err),
+            ResourceError::Image(err) => write!(f, "image error: {}", err),
+            ResourceError::Parse(msg) => write!(f, "parse error: {}", msg),
+            ResourceError::Model(err) => write!(f, "model import error: {}", err),
+            ResourceError::UnsupportedFormat(path) => {
+                write!(f, "unsupported resource format: {:?}", path)
+            }
+            ResourceError::KindMismatch(path) => {
+                write!(
+                    f,
+                    "{:?} is already loaded as a different resource kind",
+                    path
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {}
+
+impl From<std::io::Error> for ResourceError {
+    fn from(err: std::io::Error) -> Self {
+        ResourceError::Io(err)
+    }
+}
+
+impl From<ModelImportError> for ResourceError {
+    fn from(err: ModelImportError) -> Self {
+        ResourceError::Model(err)
+    }
+}
+
+impl From<image::ImageError> for ResourceError {
+    fn from(err: image::ImageError) -> Self {
+        ResourceError::Image(err)
+    }
+}
 
 #[derive(Debug)]
 pub enum ResourceKind {
     Base,
     Texture(Texture),
+    Model(Model),
 }
 
 #[derive(Debug)]
@@ -0,0 +1,312 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+
+use crate::{
+    math::color::Color,
+    renderer::surface::{DrawRange, SurfaceSharedData, SurfaceSharedDataRef},
+    resource::{fbx, Resource, ResourceError, ResourceKind},
+    scene::{
+        animation::Animation,
+        node::{Camera, Light},
+    },
+};
+
+/// Surface of a model node. Geometry and texture are shared by all instances
+/// of the model.
+#[derive(Debug, Clone)]
+pub struct ModelSurface {
+    pub(crate) data: SurfaceSharedDataRef,
+    pub(crate) texture: Option<Rc<RefCell<Resource>>>,
+    /// Diffuse texture file relative to the model file, requested when the
+    /// model is loaded through the engine.
+    pub(crate) texture_path: Option<PathBuf>,
+    pub(crate) tint: Color,
+    pub(crate) two_sided: bool,
+    /// Indices of the shared buffer used by this surface, None for all.
+    pub(crate) range: Option<DrawRange>,
+}
+
+impl ModelSurface {
+    pub fn new(data: SurfaceSharedData) -> Self {
+        Self {
+            data: Rc::new(RefCell::new(data)),
+            texture: None,
+            texture_path: None,
+            tint: Color::WHITE,
+            two_sided: false,
+            range: None,
+        }
+    }
+
+    pub fn get_texture_path(&self) -> Option<&Path> {
+        self.texture_path.as_deref()
+    }
+
+    pub fn get_tint(&self) -> Color {
+        self.tint
+    }
+
+    pub fn is_two_sided(&self) -> bool {
+        self.two_sided
+    }
+
+    pub fn get_draw_range(&self) -> Option<DrawRange> {
+        self.range
+    }
+}
+
+/// Node deforming a skinned model node, becomes `Bone` of its mesh on
+/// instantiation.
+#[derive(Debug, Clone)]
+pub struct ModelBone {
+    /// Model node index of the bone.
+    pub(crate) node: usize,
+    /// Global transform of the bone at bind time.
+    pub(crate) transform_link: Matrix4<f32>,
+    pub(crate) inverse_bind_pose: Matrix4<f32>,
+}
+
+impl ModelBone {
+    pub fn get_node(&self) -> usize {
+        self.node
+    }
+
+    pub fn get_transform_link(&self) -> &Matrix4<f32> {
+        &self.transform_link
+    }
+
+    pub fn get_inverse_bind_pose(&self) -> &Matrix4<f32> {
+        &self.inverse_bind_pose
+    }
+}
+
+/// Kind of scene node made from a model node. Nodes with surfaces become
+/// meshes regardless.
+#[derive(Debug, Clone)]
+pub enum ModelNodeKind {
+    Base,
+    Camera(Camera),
+    Light(Light),
+}
+
+/// Node of model hierarchy, becomes scene node on instantiation.
+#[derive(Debug)]
+pub struct ModelNode {
+    pub(crate) name: String,
+    pub(crate) kind: ModelNodeKind,
+    pub(crate) local_position: Vector3<f32>,
+    pub(crate) local_rotation: UnitQuaternion<f32>,
+    pub(crate) local_scale: Vector3<f32>,
+    /// Pivot transform, see `Node::calculate_local_transform`.
+    pub(crate) pre_rotation: UnitQuaternion<f32>,
+    pub(crate) post_rotation: UnitQuaternion<f32>,
+    pub(crate) rotation_offset: Vector3<f32>,
+    pub(crate) rotation_pivot: Vector3<f32>,
+    pub(crate) scaling_offset: Vector3<f32>,
+    pub(crate) scaling_pivot: Vector3<f32>,
+    pub(crate) surfaces: Vec<ModelSurface>,
+    /// Bones of skinned surfaces, indexed by their bone indices.
+    pub(crate) bones: Vec<ModelBone>,
+    pub(crate) children: Vec<usize>,
+}
+
+impl ModelNode {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: ModelNodeKind::Base,
+            local_position: Vector3::zeros(),
+            local_rotation: UnitQuaternion::identity(),
+            local_scale: Vector3::new(1.0, 1.0, 1.0),
+            pre_rotation: UnitQuaternion::identity(),
+            post_rotation: UnitQuaternion::identity(),
+            rotation_offset: Vector3::zeros(),
+            rotation_pivot: Vector3::zeros(),
+            scaling_offset: Vector3::zeros(),
+            scaling_pivot: Vector3::zeros(),
+            surfaces: Vec::new(),
+            bones: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_kind(&self) -> &ModelNodeKind {
+        &self.kind
+    }
+
+    pub fn get_surfaces(&self) -> &[ModelSurface] {
+        &self.surfaces
+    }
+
+    pub fn get_bones(&self) -> &[ModelBone] {
+        &self.bones
+    }
+}
+
+/// Model file that can't be imported in its format or version.
+#[derive(Debug)]
+pub enum ModelImportError {
+    /// Text FBX, only binary files are read.
+    AsciiFbx,
+    /// Binary FBX of a version without importer.
+    UnsupportedVersion(u32),
+    /// Binary FBX with broken structure, e.g. cut short.
+    Malformed(String),
+}
+
+impl fmt::Display for ModelImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelImportError::AsciiFbx => {
+                write!(f, "ASCII FBX is not supported, re-export as binary")
+            }
+            ModelImportError::UnsupportedVersion(version) => write!(
+                f,
+                "FBX version {} is not supported, re-export as FBX 7.4 or 7.5 binary",
+                version
+            ),
+            ModelImportError::Malformed(msg) => write!(f, "malformed FBX: {}", msg),
+        }
+    }
+}
+
+/// Settings of model import.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImportOptions {
+    /// Length of the engine unit in meters, models are scaled from their
+    /// file units to it.
+    pub unit: f32,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self { unit: 1.0 }
+    }
+}
+
+/// Problems met while importing a model that still left it usable.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    /// Polygons with zero area, left out of geometry.
+    pub degenerate_polygons: usize,
+    /// Name of every mesh node and number of its surfaces, one per material
+    /// used by its polygons.
+    pub mesh_surfaces: Vec<(String, usize)>,
+}
+
+/// Animation of model nodes, tracks get bound to scene nodes on
+/// instantiation.
+#[derive(Debug, Clone)]
+pub struct ModelAnimation {
+    pub(crate) animation: Animation,
+    /// Model node index of every track.
+    pub(crate) nodes: Vec<usize>,
+}
+
+impl ModelAnimation {
+    pub fn get_animation(&self) -> &Animation {
+        &self.animation
+    }
+
+    pub fn get_track_nodes(&self) -> &[usize] {
+        &self.nodes
+    }
+}
+
+/// Node hierarchy loaded from a model file, instantiated into scenes by
+/// `Scene::instantiate_model`.
+#[derive(Debug)]
+pub struct Model {
+    /// First node is root of the hierarchy.
+    pub(crate) nodes: Vec<ModelNode>,
+    pub(crate) animations: Vec<ModelAnimation>,
+    pub(crate) report: ImportReport,
+}
+
+impl Model {
+    pub fn new(root_name: &str) -> Self {
+        Self {
+            nodes: vec![ModelNode::new(root_name)],
+            animations: Vec::new(),
+            report: ImportReport::default(),
+        }
+    }
+
+    /// Loads model from file, format is chosen by extension. Textures are
+    /// not loaded, see `ModelSurface::get_texture_path`.
+    pub fn load(path: &Path) -> Result<Model, ResourceError> {
+        Self::load_with_options(path, &ImportOptions::default())
+    }
+
+    pub fn load_with_options(path: &Path, options: &ImportOptions) -> Result<Model, ResourceError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("fbx") => fbx::load(path, options),
+            _ => Err(ResourceError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Adds node as child of `parent` and returns its index.
+    pub fn add_node(&mut self, node: ModelNode, parent: usize) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(node);
+        self.nodes[parent].children.push(index);
+        index
+    }
+
+    pub fn root(&self) -> &ModelNode {
+        &self.nodes[0]
+    }
+
+    pub fn get_node(&self, index: usize) -> Option<&ModelNode> {
+        self.nodes.get(index)
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn get_animations(&self) -> &[ModelAnimation] {
+        &self.animations
+    }
+
+    pub fn get_import_report(&self) -> &ImportReport {
+        &self.report
+    }
+
+    pub(crate) fn surfaces_mut(&mut self) -> impl Iterator<Item = &mut ModelSurface> {
+        self.nodes
+            .iter_mut()
+            .flat_map(|node| node.surfaces.iter_mut())
+    }
+}
+
+/// Shared reference to a model resource.
+#[derive(Debug, Clone)]
+pub struct ModelHandle(pub(crate) Rc<RefCell<Resource>>);
+
+impl ModelHandle {
+    pub fn new(path: &Path, model: Model) -> Self {
+        Self(Rc::new(RefCell::new(Resource::new(
+            path,
+            ResourceKind::Model(model),
+        ))))
+    }
+
+    pub fn resource(&self) -> &Rc<RefCell<Resource>> {
+        &self.0
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.0.borrow().path.clone()
+    }
+}
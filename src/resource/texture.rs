@@ -1,7 +1,9 @@
 use std::path::*;
 
-use bytemuck::{Pod, Zeroable};
 use glow::NativeTexture;
+use nalgebra::Vector2;
+
+use crate::renderer::deletion::{self, GpuObject};
 
 #[derive(Debug)]
 pub struct Texture {
@@ -30,4 +32,39 @@ impl Texture {
             gpu_tex: None,
         })
     }
+
+    /// Brightness of a texel from 0 to 1 with Rec. 709 weights, alpha is
+    /// ignored. Coordinates are clamped to the image, empty images are black.
+    pub fn get_luminance(&self, x: u32, y: u32) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+        let x = x.min(self.width - 1) as usize;
+        let y = y.min(self.height - 1) as usize;
+        let i = (y * self.width as usize + x) * 4;
+        let [r, g, b] = [0, 1, 2].map(|c| self.pixels[i + c] as f32 / 255.0);
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    /// Luminance at texture coordinates interpolated between the four nearest
+    /// texels. Corner texels are at 0 and 1, so images of any size map to
+    /// the whole range.
+    pub fn sample_luminance(&self, uv: Vector2<f32>) -> f32 {
+        let x = uv.x.clamp(0.0, 1.0) * self.width.saturating_sub(1) as f32;
+        let y = uv.y.clamp(0.0, 1.0) * self.height.saturating_sub(1) as f32;
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (tx, ty) = (x.fract(), y.fract());
+        let top = self.get_luminance(x0, y0) * (1.0 - tx) + self.get_luminance(x0 + 1, y0) * tx;
+        let bottom =
+            self.get_luminance(x0, y0 + 1) * (1.0 - tx) + self.get_luminance(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        if let Some(texture) = self.gpu_tex.take() {
+            deletion::schedule(GpuObject::Texture(texture));
+        }
+    }
 }
@@ -0,0 +1,270 @@
+use nalgebra::{UnitQuaternion, Vector3};
+
+use crate::{
+    math::Lerp,
+    scene::node::Node,
+    utils::pool::{Handle, Pool},
+};
+
+/// Value of a track channel at given time in seconds.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f32, value: T) -> Self {
+        Self { time, value }
+    }
+}
+
+/// Samples keys sorted by time, values before the first and after the last
+/// key are held.
+fn sample<T: Copy>(
+    keys: &[Keyframe<T>],
+    time: f32,
+    interpolate: impl Fn(&T, &T, f32) -> T,
+) -> Option<T> {
+    let next = keys.iter().position(|key| key.time > time);
+    match next {
+        None => keys.last().map(|key| key.value),
+        Some(0) => Some(keys[0].value),
+        Some(next) => {
+            let (a, b) = (&keys[next - 1], &keys[next]);
+            let t = (time - a.time) / (b.time - a.time);
+            Some(interpolate(&a.value, &b.value, t))
+        }
+    }
+}
+
+/// Keyframes of local transform of one node. Channels without keys leave
+/// the node as it is.
+#[derive(Debug, Clone)]
+pub struct Track {
+    node: Handle<Node>,
+    positions: Vec<Keyframe<Vector3<f32>>>,
+    rotations: Vec<Keyframe<UnitQuaternion<f32>>>,
+    scales: Vec<Keyframe<Vector3<f32>>>,
+}
+
+impl Track {
+    pub fn new(node: Handle<Node>) -> Self {
+        Self {
+            node,
+            positions: Vec::new(),
+            rotations: Vec::new(),
+            scales: Vec::new(),
+        }
+    }
+
+    pub fn get_node(&self) -> &Handle<Node> {
+        &self.node
+    }
+
+    pub fn set_node(&mut self, node: Handle<Node>) {
+        self.node = node;
+    }
+
+    /// Keys are sorted by time.
+    pub fn set_positions(&mut self, mut keys: Vec<Keyframe<Vector3<f32>>>) {
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.positions = keys;
+    }
+
+    pub fn set_rotations(&mut self, mut keys: Vec<Keyframe<UnitQuaternion<f32>>>) {
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.rotations = keys;
+    }
+
+    pub fn set_scales(&mut self, mut keys: Vec<Keyframe<Vector3<f32>>>) {
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.scales = keys;
+    }
+
+    pub fn get_positions(&self) -> &[Keyframe<Vector3<f32>>] {
+        &self.positions
+    }
+
+    pub fn get_rotations(&self) -> &[Keyframe<UnitQuaternion<f32>>] {
+        &self.rotations
+    }
+
+    pub fn get_scales(&self) -> &[Keyframe<Vector3<f32>>] {
+        &self.scales
+    }
+
+    pub fn sample_position(&self, time: f32) -> Option<Vector3<f32>> {
+        sample(&self.positions, time, Lerp::lerp)
+    }
+
+    pub fn sample_rotation(&self, time: f32) -> Option<UnitQuaternion<f32>> {
+        sample(&self.rotations, time, |a, b, t| {
+            a.try_slerp(b, t, f32::EPSILON).unwrap_or(*b)
+        })
+    }
+
+    pub fn sample_scale(&self, time: f32) -> Option<Vector3<f32>> {
+        sample(&self.scales, time, Lerp::lerp)
+    }
+
+    /// Time of the last key.
+    pub fn get_length(&self) -> f32 {
+        [
+            self.positions.last().map(|key| key.time),
+            self.rotations.last().map(|key| key.time),
+            self.scales.last().map(|key| key.time),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(0.0, f32::max)
+    }
+}
+
+/// Tracks played together, updated by the scene it is added to.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    name: String,
+    tracks: Vec<Track>,
+    time: f32,
+    looping: bool,
+    enabled: bool,
+}
+
+impl Animation {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            tracks: Vec::new(),
+            time: 0.0,
+            looping: true,
+            enabled: true,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn add_track(&mut self, track: Track) {
+        self.tracks.push(track);
+    }
+
+    pub fn get_tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn get_tracks_mut(&mut self) -> &mut [Track] {
+        &mut self.tracks
+    }
+
+    /// Length of the longest track.
+    pub fn get_length(&self) -> f32 {
+        self.tracks
+            .iter()
+            .map(Track::get_length)
+            .fold(0.0, f32::max)
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.get_length());
+    }
+
+    /// Looping animation starts over after its end, otherwise it stops at
+    /// the last frame.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Disabled animation neither advances nor touches its nodes.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let length = self.get_length();
+        self.time += dt;
+        if self.looping && length > 0.0 {
+            self.time = self.time.rem_euclid(length);
+        } else {
+            self.time = self.time.clamp(0.0, length);
+        }
+    }
+
+    /// Sets local transforms of animated nodes to the current time.
+    pub fn apply(&self, nodes: &mut Pool<Node>) {
+        for track in self.tracks.iter() {
+            if let Some(node) = nodes.borrow_mut(&track.node) {
+                if let Some(position) = track.sample_position(self.time) {
+                    node.set_local_position(position);
+                }
+                if let Some(rotation) = track.sample_rotation(self.time) {
+                    node.set_local_rotation(rotation);
+                }
+                if let Some(scale) = track.sample_scale(self.time) {
+                    node.set_local_scale(scale);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use super::{Animation, Keyframe, Track};
+    use crate::utils::pool::Handle;
+
+    #[test]
+    fn track_sampling() {
+        let mut track = Track::new(Handle::none());
+        track.set_positions(vec![
+            Keyframe::new(1.0, Vector3::new(2.0, 0.0, 0.0)),
+            Keyframe::new(0.0, Vector3::zeros()),
+        ]);
+        track.set_rotations(vec![Keyframe::new(0.0, UnitQuaternion::identity())]);
+        assert_eq!(track.get_length(), 1.0);
+        assert_eq!(track.sample_position(-1.0), Some(Vector3::zeros()));
+        assert_eq!(
+            track.sample_position(0.25),
+            Some(Vector3::new(0.5, 0.0, 0.0))
+        );
+        assert_eq!(
+            track.sample_position(2.0),
+            Some(Vector3::new(2.0, 0.0, 0.0))
+        );
+        // Single key holds its value.
+        assert_eq!(track.sample_rotation(0.5), Some(UnitQuaternion::identity()));
+        assert_eq!(track.sample_scale(0.5), None);
+    }
+
+    #[test]
+    fn animation_time() {
+        let mut track = Track::new(Handle::none());
+        track.set_scales(vec![
+            Keyframe::new(0.0, Vector3::zeros()),
+            Keyframe::new(2.0, Vector3::zeros()),
+        ]);
+        let mut animation = Animation::new("grow");
+        animation.add_track(track);
+        animation.update(2.5);
+        assert_eq!(animation.get_time(), 0.5);
+
+        animation.set_looping(false);
+        animation.update(3.0);
+        assert_eq!(animation.get_time(), 2.0);
+    }
+}
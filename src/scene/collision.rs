@@ -0,0 +1,274 @@
+use nalgebra::Vector3;
+
+use crate::math::aabb::AxisAlignedBoundingBox;
+
+/// Overlap smaller than this is treated as touching, so a box resting on a
+/// surface does not collide with it because of rounding errors.
+const PENETRATION_EPSILON: f32 = 1.0e-4;
+
+/// Axes in order of resolution: horizontal movement first, so a box slides
+/// along walls, then vertical.
+const AXES: [usize; 3] = [0, 2, 1];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MoveResult {
+    /// Box after movement.
+    pub body: AxisAlignedBoundingBox,
+    /// Applied displacement, includes ejection and stepping up.
+    pub offset: Vector3<f32>,
+    /// Movement was blocked on corresponding axis.
+    pub blocked: Vector3<bool>,
+    /// Box ended up resting on top of a collider.
+    pub grounded: bool,
+}
+
+fn penetrates(a: &AxisAlignedBoundingBox, b: &AxisAlignedBoundingBox) -> bool {
+    (0..3).all(|i| {
+        a.min[i] < b.max[i] - PENETRATION_EPSILON && a.max[i] > b.min[i] + PENETRATION_EPSILON
+    })
+}
+
+/// Returns true if box overlaps any collider by more than touching.
+pub fn overlaps_any(body: &AxisAlignedBoundingBox, colliders: &[AxisAlignedBoundingBox]) -> bool {
+    colliders.iter().any(|collider| penetrates(body, collider))
+}
+
+/// Distance to the nearest collider along a ray, `direction` must be
+/// normalized. Hits further than `max_distance` are ignored.
+pub fn cast_ray(
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+    colliders: &[AxisAlignedBoundingBox],
+) -> Option<f32> {
+    colliders
+        .iter()
+        .filter_map(|collider| collider.ray_intersection(origin, direction))
+        .filter(|distance| *distance <= max_distance)
+        .reduce(f32::min)
+}
+
+/// Pushes box out of colliders it already overlaps, each time along the axis
+/// of smallest penetration.
+pub fn depenetrate(
+    mut body: AxisAlignedBoundingBox,
+    colliders: &[AxisAlignedBoundingBox],
+) -> AxisAlignedBoundingBox {
+    for collider in colliders.iter() {
+        if !penetrates(&body, collider) {
+            continue;
+        }
+        let mut push = Vector3::zeros();
+        let mut smallest = f32::MAX;
+        for i in 0..3 {
+            let up = collider.max[i] - body.min[i];
+            let down = collider.min[i] - body.max[i];
+            let amount = if up < -down { up } else { down };
+            if amount.abs() < smallest {
+                smallest = amount.abs();
+                push = Vector3::zeros();
+                push[i] = amount;
+            }
+        }
+        body = body.translate(push);
+    }
+    body
+}
+
+/// Moves box by `motion` one axis at a time (X, Z, then Y) and clamps it
+/// against colliders. Horizontal movement into a collider whose top is at
+/// most `step_height` above the bottom of the box steps up onto it instead.
+pub fn move_and_collide(
+    body: AxisAlignedBoundingBox,
+    motion: Vector3<f32>,
+    colliders: &[AxisAlignedBoundingBox],
+    step_height: f32,
+) -> MoveResult {
+    let start = body.min;
+    let mut body = depenetrate(body, colliders);
+    let mut blocked = Vector3::repeat(false);
+    let mut grounded = false;
+
+    for &axis in AXES.iter() {
+        let amount = motion[axis];
+        if amount == 0.0 {
+            continue;
+        }
+        let mut offset = Vector3::zeros();
+        offset[axis] = amount;
+        body = body.translate(offset);
+
+        for collider in colliders.iter() {
+            if !penetrates(&body, collider) {
+                continue;
+            }
+
+            if axis != 1 {
+                let rise = collider.max.y - body.min.y;
+                if rise > 0.0 && rise <= step_height {
+                    let lifted = body.translate(Vector3::new(0.0, rise, 0.0));
+                    if !overlaps_any(&lifted, colliders) {
+                        body = lifted;
+                        continue;
+                    }
+                }
+            }
+
+            let mut push = Vector3::zeros();
+            push[axis] = if amount > 0.0 {
+                collider.min[axis] - body.max[axis]
+            } else {
+                collider.max[axis] - body.min[axis]
+            };
+            body = body.translate(push);
+            blocked[axis] = true;
+            if axis == 1 && amount < 0.0 {
+                grounded = true;
+            }
+        }
+    }
+
+    MoveResult {
+        body,
+        offset: body.min - start,
+        blocked,
+        grounded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::{cast_ray, depenetrate, move_and_collide};
+    use crate::math::aabb::AxisAlignedBoundingBox;
+
+    fn aabb(min: [f32; 3], max: [f32; 3]) -> AxisAlignedBoundingBox {
+        AxisAlignedBoundingBox::new(Vector3::from(min), Vector3::from(max))
+    }
+
+    fn unit_at(x: f32, y: f32, z: f32) -> AxisAlignedBoundingBox {
+        aabb([x - 0.5, y - 0.5, z - 0.5], [x + 0.5, y + 0.5, z + 0.5])
+    }
+
+    #[test]
+    fn slides_along_wall() {
+        // Wall along Z at x >= 1.
+        let wall = aabb([1.0, -10.0, -10.0], [2.0, 10.0, 10.0]);
+        let body = unit_at(0.0, 0.0, 0.0);
+        let result = move_and_collide(body, Vector3::new(1.0, 0.0, 1.0), &[wall], 0.0);
+        assert!(result.blocked.x && !result.blocked.z);
+        assert!((result.body.max.x - 1.0).abs() < 1e-6);
+        assert!((result.offset.z - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lands_on_floor() {
+        let floor = aabb([-10.0, -1.0, -10.0], [10.0, 0.0, 10.0]);
+        let body = unit_at(0.0, 0.6, 0.0);
+        let result = move_and_collide(body, Vector3::new(0.0, -0.5, 0.0), &[floor], 0.0);
+        assert!(result.grounded && result.blocked.y);
+        assert_eq!(result.body.min.y, 0.0);
+
+        // Resting box keeps being grounded without drifting.
+        let again = move_and_collide(result.body, Vector3::new(0.0, -0.01, 0.0), &[floor], 0.0);
+        assert!(again.grounded);
+        assert_eq!(again.body.min.y, 0.0);
+    }
+
+    #[test]
+    fn walking_across_adjacent_floor_tiles() {
+        // Tiles share an edge, box resting on them must not catch on the seam.
+        let tiles = [
+            aabb([-1.0, -1.0, -1.0], [0.0, 0.0, 1.0]),
+            aabb([0.0, -1.0, -1.0], [1.0, 0.0, 1.0]),
+        ];
+        let mut body = aabb([-0.9, 0.0, -0.2], [-0.5, 1.0, 0.2]);
+        for _ in 0..10 {
+            let result = move_and_collide(body, Vector3::new(0.1, -0.05, 0.0), &tiles, 0.0);
+            assert!(!result.blocked.x);
+            assert!(result.grounded);
+            assert_eq!(result.body.min.y, 0.0);
+            body = result.body;
+        }
+    }
+
+    #[test]
+    fn steps_onto_low_edge() {
+        let ground = aabb([-10.0, -1.0, -10.0], [10.0, 0.0, 10.0]);
+        let ledge = aabb([1.0, 0.0, -10.0], [10.0, 0.05, 10.0]);
+        let colliders = [ground, ledge];
+        let mut body = aabb([0.0, 0.0, -0.4], [0.8, 1.8, 0.4]);
+        let mut heights = Vec::new();
+        for _ in 0..10 {
+            let result = move_and_collide(body, Vector3::new(0.1, -0.01, 0.0), &colliders, 0.1);
+            assert!(!result.blocked.x);
+            assert!(result.grounded);
+            body = result.body;
+            heights.push(body.min.y);
+        }
+        assert_eq!(*heights.last().unwrap(), 0.05);
+        // Once on the ledge the box stays there, no bouncing.
+        let first_on_ledge = heights.iter().position(|h| *h == 0.05).unwrap();
+        assert!(heights[first_on_ledge..].iter().all(|h| *h == 0.05));
+
+        // Too high to step on.
+        let wall = aabb([1.0, 0.0, -10.0], [10.0, 0.5, 10.0]);
+        let body = aabb([0.0, 0.0, -0.4], [0.8, 1.8, 0.4]);
+        let result = move_and_collide(body, Vector3::new(0.5, 0.0, 0.0), &[ground, wall], 0.1);
+        assert!(result.blocked.x);
+        assert_eq!(result.body.max.x, 1.0);
+    }
+
+    #[test]
+    fn corner() {
+        // Two walls meeting in a corner at x = 1, z = 1.
+        let walls = [
+            aabb([1.0, -10.0, -10.0], [2.0, 10.0, 2.0]),
+            aabb([-10.0, -10.0, 1.0], [2.0, 10.0, 2.0]),
+        ];
+        let body = unit_at(0.0, 0.0, 0.0);
+        let result = move_and_collide(body, Vector3::new(2.0, 0.0, 2.0), &walls, 0.0);
+        assert!(result.blocked.x && result.blocked.z);
+        assert_eq!(result.body.max.x, 1.0);
+        assert_eq!(result.body.max.z, 1.0);
+
+        // Moving away from the corner is not blocked.
+        let away = move_and_collide(result.body, Vector3::new(-1.0, 0.0, -1.0), &walls, 0.0);
+        assert!(!away.blocked.x && !away.blocked.z);
+    }
+
+    #[test]
+    fn enclosed_start_ejects_along_smallest_axis() {
+        // Body sunk 0.2 into the top of a wide box, smallest exit is up.
+        let block = aabb([-5.0, -5.0, -5.0], [5.0, 0.0, 5.0]);
+        let body = unit_at(0.0, 0.3, 0.0);
+        let ejected = depenetrate(body, &[block]);
+        assert!((ejected.min.y - 0.0).abs() < 1e-6);
+        assert_eq!(ejected.min.x, body.min.x);
+        assert_eq!(ejected.min.z, body.min.z);
+
+        // Fully inside a tall thin box, smallest exit is along +X.
+        let pillar = aabb([-0.8, -10.0, -5.0], [0.7, 10.0, 5.0]);
+        let ejected = depenetrate(unit_at(0.0, 0.0, 0.0), &[pillar]);
+        assert!((ejected.min.x - 0.7).abs() < 1e-6);
+
+        let result = move_and_collide(unit_at(0.0, 0.0, 0.0), Vector3::zeros(), &[pillar], 0.0);
+        assert!((result.offset.x - 1.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ray_hits_nearest_collider() {
+        let colliders = [unit_at(5.0, 0.0, 0.0), unit_at(3.0, 0.0, 0.0)];
+        let direction = Vector3::new(1.0, 0.0, 0.0);
+        assert_eq!(
+            cast_ray(Vector3::zeros(), direction, 10.0, &colliders),
+            Some(2.5)
+        );
+        assert_eq!(cast_ray(Vector3::zeros(), direction, 2.0, &colliders), None);
+        assert_eq!(
+            cast_ray(Vector3::zeros(), -direction, 10.0, &colliders),
+            None
+        );
+    }
+}
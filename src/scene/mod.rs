@@ -1,19 +1,76 @@
-use nalgebra::{Matrix4, Vector3};
+use nalgebra::Matrix4;
 
-use crate::utils::pool::{Handle, Pool};
+use crate::{
+    math::{aabb::AxisAlignedBoundingBox, color::Color, ray::Ray},
+    renderer::surface::Surface,
+    resource::{
+        model::{ModelHandle, ModelNodeKind},
+        ResourceKind,
+    },
+    utils::pool::{Handle, Pool},
+};
 
-use self::node::{Node, NodeKind};
+use self::{
+    animation::Animation,
+    node::{Bone, Mesh, Node, NodeKind},
+};
 
+pub mod animation;
+pub mod collision;
 pub mod node;
 
+/// Ambient color of new scenes.
+pub const DEFAULT_AMBIENT_COLOR: Color = Color::opaque(0.2, 0.2, 0.2);
+
 pub struct Scene {
     pub(crate) nodes: Pool<Node>,
 
+    animations: Pool<Animation>,
+
     pub(crate) root: Handle<Node>,
 
+    stack: Vec<(Handle<Node>, bool)>,
+    /// Script nodes reached by the last transform pass.
+    scripts: Vec<Handle<Node>>,
+    local_transform_updates: usize,
+
+    /// Camera set by `set_active_camera`, may be stale.
+    active_camera: Handle<Node>,
+    single_camera: bool,
+    ambient_color: Color,
+
+    update_enabled: bool,
+    render_enabled: bool,
+    time_scale: f32,
+    elapsed_time: f32,
+}
+
+/// Depth-first iterator over a subtree, see `Scene::iter`.
+pub struct SceneIter<'a> {
+    nodes: &'a Pool<Node>,
     stack: Vec<Handle<Node>>,
 }
 
+impl<'a> Iterator for SceneIter<'a> {
+    type Item = (Handle<Node>, &'a Node);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(handle) = self.stack.pop() {
+            if let Some(node) = self.nodes.borrow(&handle) {
+                self.stack.extend(node.children.iter().rev().cloned());
+                return Some((handle, node));
+            }
+        }
+        None
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Scene {
     pub fn new() -> Scene {
         let mut nodes: Pool<Node> = Pool::new();
@@ -21,22 +78,374 @@ impl Scene {
 
         Scene {
             nodes,
+            animations: Pool::new(),
             stack: Vec::new(),
+            scripts: Vec::new(),
+            local_transform_updates: 0,
             root,
+            active_camera: Handle::none(),
+            single_camera: false,
+            ambient_color: DEFAULT_AMBIENT_COLOR,
+            update_enabled: true,
+            render_enabled: true,
+            time_scale: 1.0,
+            elapsed_time: 0.0,
         }
     }
 
+    /// Disabled scene is skipped by engine update, its state stays intact.
+    pub fn set_update_enabled(&mut self, enabled: bool) {
+        self.update_enabled = enabled;
+    }
+
+    pub fn is_update_enabled(&self) -> bool {
+        self.update_enabled
+    }
+
+    /// Disabled scene is skipped by engine render.
+    pub fn set_render_enabled(&mut self, enabled: bool) {
+        self.render_enabled = enabled;
+    }
+
+    pub fn is_render_enabled(&self) -> bool {
+        self.render_enabled
+    }
+
+    /// Marks camera to use for picking and for rendering in single camera
+    /// mode. Handles of other nodes are accepted but ignored until they are
+    /// replaced.
+    pub fn set_active_camera(&mut self, handle: &Handle<Node>) {
+        self.active_camera = handle.clone();
+    }
+
+    /// Camera set by `set_active_camera` if it is still an enabled camera of
+    /// this scene, otherwise enabled camera of the lowest render order.
+    pub fn active_camera(&self) -> Option<Handle<Node>> {
+        let enabled_camera = |node: &Node| match node.borrow_kind() {
+            NodeKind::Camera(camera) if camera.is_enabled() => Some(camera.get_render_order()),
+            _ => None,
+        };
+        if self
+            .nodes
+            .borrow(&self.active_camera)
+            .and_then(enabled_camera)
+            .is_some()
+        {
+            return Some(self.active_camera.clone());
+        }
+        self.iter()
+            .filter_map(|(handle, node)| enabled_camera(node).map(|order| (order, handle)))
+            .min_by_key(|(order, _)| *order)
+            .map(|(_, handle)| handle)
+    }
+
+    /// Single camera scene is rendered only from its active camera.
+    pub fn set_single_camera(&mut self, single: bool) {
+        self.single_camera = single;
+    }
+
+    pub fn is_single_camera(&self) -> bool {
+        self.single_camera
+    }
+
+    /// Light reaching every surface regardless of lights of the scene, dim
+    /// grey by default. Alpha is ignored.
+    pub fn set_ambient_color(&mut self, color: Color) {
+        self.ambient_color = color;
+    }
+
+    pub fn get_ambient_color(&self) -> Color {
+        self.ambient_color
+    }
+
+    /// Scene's own speed multiplier, applied on top of engine time scale.
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = if scale.is_finite() {
+            scale.max(0.0)
+        } else {
+            1.0
+        };
+    }
+
+    pub fn get_time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Scaled simulation time this scene has been updated for.
+    pub fn get_elapsed_time(&self) -> f32 {
+        self.elapsed_time
+    }
+
     /// Transfers ownership of node into scene.
     /// Returns handle to node.
     pub fn add_node(&mut self, node: Node) -> Handle<Node> {
-        let handle = self.nodes.spawn(node);
+        let handle = self.add_node_unlinked(node);
         self.link_nodes(&handle, &self.root.clone());
         handle
     }
 
-    /// Destroys node
+    /// Same as `add_node` but leaves the node without parent. Unlinked nodes
+    /// are neither updated nor rendered until linked under the root, see
+    /// `link_nodes`.
+    pub fn add_node_unlinked(&mut self, mut node: Node) -> Handle<Node> {
+        if let NodeKind::Script(script) = &mut node.kind {
+            script.on_added();
+        }
+        self.nodes.spawn(node)
+    }
+
+    /// Hidden node all other nodes descend from, updates and rendering
+    /// start from it.
+    pub fn root(&self) -> Handle<Node> {
+        self.root.clone()
+    }
+
+    /// Copies subtree of `root` into `dest`, the copy is linked to root of
+    /// `dest`. Meshes share geometry and textures with the originals, bones
+    /// inside the subtree are bound to their copies. Returns handle of the
+    /// copied root, none if `root` doesn't resolve.
+    pub fn copy_node(&self, root: &Handle<Node>, dest: &mut Scene) -> Handle<Node> {
+        let copies = self.copy_subtree(root);
+        dest.spawn_copies(copies)
+    }
+
+    /// Copies subtree of `root` within the scene, see `copy_node`.
+    pub fn instantiate(&mut self, root: &Handle<Node>) -> Handle<Node> {
+        let copies = self.copy_subtree(root);
+        self.spawn_copies(copies)
+    }
+
+    /// Unlinked copies of subtree nodes with their original handles and
+    /// index of the parent copy, parents come before children.
+    fn copy_subtree(&self, root: &Handle<Node>) -> Vec<(Handle<Node>, Node, Option<usize>)> {
+        let mut copies = Vec::new();
+        let mut stack = vec![(root.clone(), None)];
+        while let Some((handle, parent)) = stack.pop() {
+            if let Some(node) = self.nodes.borrow(&handle) {
+                let index = copies.len();
+                for child in node.children.iter().rev() {
+                    stack.push((child.clone(), Some(index)));
+                }
+                copies.push((handle, node.copy_unlinked(), parent));
+            }
+        }
+        copies
+    }
+
+    fn spawn_copies(&mut self, copies: Vec<(Handle<Node>, Node, Option<usize>)>) -> Handle<Node> {
+        let mut originals = Vec::with_capacity(copies.len());
+        let mut handles: Vec<Handle<Node>> = Vec::with_capacity(copies.len());
+        for (original, node, parent) in copies {
+            let handle = self.nodes.spawn(node);
+            let parent = parent.map_or(self.root.clone(), |parent| handles[parent].clone());
+            self.link_nodes(&handle, &parent);
+            originals.push(original);
+            handles.push(handle);
+        }
+        for handle in handles.iter() {
+            if let Some(NodeKind::Mesh(mesh)) =
+                self.nodes.borrow_mut(handle).map(Node::borrow_kind_mut)
+            {
+                if mesh.get_bones().is_empty() {
+                    continue;
+                }
+                let bones = mesh
+                    .get_bones()
+                    .iter()
+                    .map(|bone| {
+                        let mut bone = bone.clone();
+                        if let Some(i) = originals.iter().position(|h| *h == bone.node) {
+                            bone.node = handles[i].clone();
+                        }
+                        bone
+                    })
+                    .collect();
+                mesh.set_bones(bones);
+            }
+        }
+        handles.first().cloned().unwrap_or_else(Handle::none)
+    }
+
+    /// Animation is played by scene updates while enabled.
+    pub fn add_animation(&mut self, animation: Animation) -> Handle<Animation> {
+        self.animations.spawn(animation)
+    }
+
+    pub fn remove_animation(&mut self, handle: Handle<Animation>) {
+        self.animations.free(handle);
+    }
+
+    pub fn borrow_animation(&self, handle: &Handle<Animation>) -> Option<&Animation> {
+        self.animations.borrow(handle)
+    }
+
+    pub fn borrow_animation_mut(&mut self, handle: &Handle<Animation>) -> Option<&mut Animation> {
+        self.animations.borrow_mut(handle)
+    }
+
+    pub fn find_animations(
+        &self,
+        mut predicate: impl FnMut(&Animation) -> bool,
+    ) -> Vec<Handle<Animation>> {
+        self.animations
+            .pair_iter()
+            .filter(|(_, animation)| predicate(animation))
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Creates nodes described by the model, links them under scene root and
+    /// returns handle of the new subtree root. Geometry and textures are
+    /// shared with the model and every other instance of it. Animations of
+    /// the model are added bound to the new nodes, only the first one is
+    /// enabled.
+    pub fn instantiate_model(&mut self, model: &ModelHandle) -> Handle<Node> {
+        let resource = model.0.borrow();
+        let ResourceKind::Model(model) = resource.borrow_kind() else {
+            return Handle::none();
+        };
+
+        let mut root = Handle::none();
+        let mut handles = vec![Handle::none(); model.nodes.len()];
+        let mut stack = vec![(0, self.root.clone())];
+        while let Some((index, parent)) = stack.pop() {
+            let model_node = &model.nodes[index];
+            let kind = if model_node.surfaces.is_empty() {
+                match &model_node.kind {
+                    ModelNodeKind::Base => NodeKind::Base,
+                    ModelNodeKind::Camera(camera) => NodeKind::Camera(camera.clone()),
+                    ModelNodeKind::Light(light) => NodeKind::Light(light.clone()),
+                }
+            } else {
+                let mut mesh = Mesh::default();
+                for model_surface in model_node.surfaces.iter() {
+                    let mut surface = Surface::new(&model_surface.data);
+                    let material = surface.material_mut();
+                    material.set_diffuse(model_surface.tint);
+                    material.set_two_sided(model_surface.two_sided);
+                    if let Some(texture) = &model_surface.texture {
+                        surface.set_texture(texture.clone());
+                    }
+                    surface.set_draw_range(model_surface.range);
+                    mesh.surfaces.push(surface);
+                }
+                NodeKind::Mesh(mesh)
+            };
+            let mut node = Node::new(kind);
+            node.set_name(&model_node.name);
+            node.set_local_position(model_node.local_position);
+            node.set_local_rotation(model_node.local_rotation);
+            node.set_local_scale(model_node.local_scale);
+            node.set_pre_rotation(model_node.pre_rotation);
+            node.set_post_rotation(model_node.post_rotation);
+            node.set_rotation_offset(model_node.rotation_offset);
+            node.set_rotation_pivot(model_node.rotation_pivot);
+            node.set_scaling_offset(model_node.scaling_offset);
+            node.set_scaling_pivot(model_node.scaling_pivot);
+
+            let handle = self.nodes.spawn(node);
+            self.link_nodes(&handle, &parent);
+            if index == 0 {
+                root = handle.clone();
+            }
+            for &child in model_node.children.iter().rev() {
+                stack.push((child, handle.clone()));
+            }
+            handles[index] = handle;
+        }
+
+        for (model_node, handle) in model.nodes.iter().zip(handles.iter()) {
+            if model_node.bones.is_empty() {
+                continue;
+            }
+            let bones = model_node
+                .bones
+                .iter()
+                .map(|bone| Bone {
+                    node: handles[bone.node].clone(),
+                    inverse_bind_pose: bone.inverse_bind_pose,
+                })
+                .collect();
+            if let Some(NodeKind::Mesh(mesh)) =
+                self.nodes.borrow_mut(handle).map(Node::borrow_kind_mut)
+            {
+                mesh.set_bones(bones);
+            }
+        }
+
+        for (i, model_animation) in model.animations.iter().enumerate() {
+            let mut animation = model_animation.animation.clone();
+            for (track, &node) in animation
+                .get_tracks_mut()
+                .iter_mut()
+                .zip(model_animation.nodes.iter())
+            {
+                track.set_node(handles[node].clone());
+            }
+            animation.set_enabled(i == 0);
+            self.animations.spawn(animation);
+        }
+        root
+    }
+
+    /// Destroys node together with all its descendants, handles that don't
+    /// resolve are ignored.
     pub fn remove_node(&mut self, handle: Handle<Node>) {
-        self.nodes.free(handle);
+        if self.nodes.borrow(&handle).is_none() {
+            return;
+        }
+        self.unlink_node(&handle);
+        let mut stack = vec![handle];
+        while let Some(handle) = stack.pop() {
+            if let Some(node) = self.nodes.borrow_mut(&handle) {
+                stack.extend(node.children.iter().cloned());
+                if let NodeKind::Script(script) = &mut node.kind {
+                    script.on_removed();
+                }
+            }
+            self.nodes.free(handle);
+        }
+    }
+
+    /// Number of local transforms recalculated by the last `update`, only
+    /// nodes changed since the previous one are recalculated.
+    pub fn get_local_transform_updates(&self) -> usize {
+        self.local_transform_updates
+    }
+
+    /// Removes all nodes and animations, keeping allocated storage. The scene
+    /// gets a fresh root, handles from before stop resolving.
+    pub fn clear(&mut self) {
+        for node in self.nodes.iter_mut() {
+            if let NodeKind::Script(script) = &mut node.kind {
+                script.on_removed();
+            }
+        }
+        self.nodes.clear();
+        self.animations.clear();
+        self.active_camera = Handle::none();
+        self.root = self.nodes.spawn(Node::new(NodeKind::Base));
+    }
+
+    /// Whether the handle points to a node of this scene that wasn't removed.
+    pub fn is_valid_handle(&self, handle: &Handle<Node>) -> bool {
+        self.nodes.is_valid(handle)
+    }
+
+    /// Number of alive nodes in the scene. The root is counted, so a new
+    /// scene has one node.
+    pub fn node_count(&self) -> usize {
+        self.nodes.alive_count()
+    }
+
+    /// Borrows two distinct nodes at once, None if the handles are equal or
+    /// either doesn't resolve.
+    pub fn borrow_two_nodes_mut(
+        &mut self,
+        a: &Handle<Node>,
+        b: &Handle<Node>,
+    ) -> Option<(&mut Node, &mut Node)> {
+        self.nodes.borrow_two_mut(a, b)
     }
 
     pub fn borrow_node(&self, handle: &Handle<Node>) -> Option<&Node> {
@@ -52,18 +461,63 @@ impl Scene {
         self.unlink_node(child_handle);
         if let Some(child) = self.nodes.borrow_mut(child_handle) {
             child.parent = parent_handle.clone();
+            // Global transform has to follow the new parent.
+            child.transform_dirty = true;
             if let Some(parent) = self.nodes.borrow_mut(parent_handle) {
                 parent.children.push(child_handle.clone());
             }
         }
     }
 
+    /// Links child to parent keeping its world transform, as of the last
+    /// `update`, instead of the local one. Nodes with singular transforms
+    /// keep their local transform.
+    pub fn link_nodes_keep_global(
+        &mut self,
+        child_handle: &Handle<Node>,
+        parent_handle: &Handle<Node>,
+    ) {
+        let parent_global = self
+            .nodes
+            .borrow(parent_handle)
+            .map_or_else(Matrix4::identity, |parent| parent.global_transform);
+        if let Some(child) = self.nodes.borrow_mut(child_handle) {
+            let local = parent_global
+                .try_inverse()
+                .map(|inverse| inverse * child.global_transform);
+            if !local.is_some_and(|local| child.set_local_transform_from(&local)) {
+                println!(
+                    "{:?} can't keep its world transform when relinked",
+                    child.get_name()
+                );
+            }
+        }
+        self.link_nodes(child_handle, parent_handle);
+    }
+
+    /// Whether `maybe_ancestor` is found walking up the parents of `node`,
+    /// a node is not its own ancestor.
+    pub fn is_ancestor_of(&self, maybe_ancestor: &Handle<Node>, node: &Handle<Node>) -> bool {
+        let mut handle = match self.nodes.borrow(node) {
+            Some(node) => &node.parent,
+            None => return false,
+        };
+        while let Some(parent) = self.nodes.borrow(handle) {
+            if handle == maybe_ancestor {
+                return true;
+            }
+            handle = &parent.parent;
+        }
+        false
+    }
+
     pub fn unlink_node(&mut self, node_handle: &Handle<Node>) {
         let mut parent_handle: Handle<Node> = Handle::none();
         // Replace parent handle of child
         if let Some(node) = self.nodes.borrow_mut(node_handle) {
             parent_handle = node.parent.clone();
             node.parent = Handle::none();
+            node.transform_dirty = true;
         }
         // Remove child from parent's children list
         if let Some(parent) = self.nodes.borrow_mut(&parent_handle) {
@@ -73,45 +527,1182 @@ impl Scene {
         }
     }
 
-    pub fn update(&mut self, aspect_ratio: f32) {
-        // Calculate transforms on nodes
-        self.stack.clear();
-        self.stack.push(self.root.clone());
-        loop {
-            match self.stack.pop() {
-                Some(handle) => {
-                    // Calculate local transform and get parent handle
-                    let mut parent_handle: Handle<Node> = Handle::none();
-                    if let Some(node) = self.nodes.borrow_mut(&handle) {
-                        node.calculate_local_transform();
-                        parent_handle = node.parent.clone();
+    /// Collects world bounds of collider meshes that intersect `region`. Scene
+    /// has no spatial index yet, so this is a linear scan over all nodes; world
+    /// bounds use transforms of the last `update`.
+    pub fn query_colliders(
+        &self,
+        region: &AxisAlignedBoundingBox,
+        colliders: &mut Vec<AxisAlignedBoundingBox>,
+    ) {
+        for node in self.nodes.iter().filter(|node| node.is_collider()) {
+            if let NodeKind::Mesh(mesh) = node.borrow_kind() {
+                if let Some(local) = mesh.bounding_box() {
+                    let world = local.transform(&node.global_transform);
+                    if world.intersects(region) {
+                        colliders.push(world);
                     }
+                }
+            }
+        }
+    }
 
-                    // Extract parent's local transform
-                    let mut parent_global_transform = Matrix4::identity();
-                    if let Some(parent) = self.nodes.borrow_mut(&parent_handle) {
-                        parent_global_transform = parent.global_transform;
-                    }
+    /// Iterates nodes reachable from root in depth-first order, root first.
+    /// Parents come before their children and siblings keep their order.
+    pub fn iter(&self) -> SceneIter<'_> {
+        self.iter_from(&self.root)
+    }
 
-                    if let Some(node) = self.nodes.borrow_mut(&handle) {
-                        node.global_transform = parent_global_transform * node.local_transform;
+    /// Same as `iter` for subtree of `handle`, empty if it doesn't resolve.
+    pub fn iter_from(&self, handle: &Handle<Node>) -> SceneIter<'_> {
+        SceneIter {
+            nodes: &self.nodes,
+            stack: vec![handle.clone()],
+        }
+    }
 
-                        let eye = node.get_global_position();
-                        let look = node.get_look_vector();
+    /// Calls `f` for every node reachable from root, in the order of `iter`.
+    /// Children are read after `f` returns.
+    pub fn visit_mut(&mut self, mut f: impl FnMut(Handle<Node>, &mut Node)) {
+        let mut stack = vec![self.root.clone()];
+        while let Some(handle) = stack.pop() {
+            if let Some(node) = self.nodes.borrow_mut(&handle) {
+                f(handle, node);
+                stack.extend(node.children.iter().rev().cloned());
+            }
+        }
+    }
 
-                        let up = node.get_up_vector();
+    /// Collects nodes reachable from root that match the predicate, in
+    /// depth-first order. Root itself is not tested.
+    pub fn find_nodes(&self, mut predicate: impl FnMut(&Node) -> bool) -> Vec<Handle<Node>> {
+        let mut result = Vec::new();
+        let mut stack = Vec::new();
+        if let Some(root) = self.nodes.borrow(&self.root) {
+            stack.extend(root.children.iter().rev().cloned());
+        }
+        while let Some(handle) = stack.pop() {
+            if let Some(node) = self.nodes.borrow(&handle) {
+                if predicate(node) {
+                    result.push(handle.clone());
+                }
+                stack.extend(node.children.iter().rev().cloned());
+            }
+        }
+        result
+    }
 
-                        if let NodeKind::Camera(camera) = node.borrow_kind_mut() {
-                            camera.calculate_matrices(eye.into(), look.into(), up, aspect_ratio);
-                        }
+    /// Nodes reachable from root with the tag, in depth-first order. Nodes of
+    /// unlinked subtrees are not found.
+    pub fn find_nodes_by_tag(&self, tag: u64) -> Vec<Handle<Node>> {
+        self.find_nodes(|node| node.tag() == tag)
+    }
 
-                        for child_handle in node.children.iter() {
-                            self.stack.push(child_handle.clone());
-                        }
+    /// First node of `find_nodes_by_tag`.
+    pub fn find_first_by_tag(&self, tag: u64) -> Option<Handle<Node>> {
+        self.iter()
+            .skip(1)
+            .find(|(_, node)| node.tag() == tag)
+            .map(|(handle, _)| handle)
+    }
+
+    /// Finds the nearest mesh node reachable from root hit by the ray, tested
+    /// against world bounds of meshes. Returns node and distance along the ray.
+    /// Hidden nodes are skipped along with their subtree, like in rendering.
+    pub fn pick(&self, ray: &Ray) -> Option<(Handle<Node>, f32)> {
+        let mut nearest: Option<(Handle<Node>, f32)> = None;
+        let mut stack = vec![self.root.clone()];
+        while let Some(handle) = stack.pop() {
+            let node = match self.nodes.borrow(&handle) {
+                Some(node) if node.is_globally_visible() => node,
+                _ => continue,
+            };
+            if let NodeKind::Mesh(mesh) = node.borrow_kind() {
+                let hit = mesh.bounding_box().and_then(|local| {
+                    ray.aabb_intersection(&local.transform(&node.global_transform))
+                });
+                if let Some(distance) = hit {
+                    if nearest.as_ref().is_none_or(|(_, best)| distance < *best) {
+                        nearest = Some((handle.clone(), distance));
                     }
                 }
-                None => break,
+            }
+            stack.extend(node.children.iter().cloned());
+        }
+        nearest
+    }
+
+    /// Updates transforms of all nodes reachable from root, returns number of
+    /// updated nodes. `dt` is scaled by scene time scale.
+    pub fn update(&mut self, aspect_ratio: f32, dt: f32) -> usize {
+        let dt = dt * self.time_scale;
+        self.elapsed_time += dt;
+
+        let expired: Vec<_> = self
+            .nodes
+            .pair_iter_mut()
+            .filter_map(|(handle, node)| node.update_lifetime(dt).then_some(handle))
+            .collect();
+        for handle in expired {
+            // Root stays, its lifetime just ends.
+            if handle != self.root {
+                self.remove_node(handle);
             }
         }
+
+        for animation in self.animations.iter_mut() {
+            if animation.is_enabled() {
+                animation.update(dt);
+                animation.apply(&mut self.nodes);
+            }
+        }
+
+        let mut updated = 0;
+        self.local_transform_updates = 0;
+        // Global transforms are recalculated for changed nodes and their
+        // whole subtrees, the flag tells that an ancestor has changed.
+        self.stack.clear();
+        self.scripts.clear();
+        self.stack.push((self.root.clone(), false));
+        while let Some((handle, parent_changed)) = self.stack.pop() {
+            let parent_handle = match self.nodes.borrow(&handle) {
+                Some(node) => node.parent.clone(),
+                None => continue,
+            };
+            // Parents are visited first, so their global transform is final
+            let (node, parent_global_transform, parent_visibility) =
+                if self.nodes.is_valid(&parent_handle) {
+                    let Some((node, parent)) = self.nodes.borrow_two_mut(&handle, &parent_handle)
+                    else {
+                        continue;
+                    };
+                    (node, parent.global_transform, parent.global_visibility)
+                } else {
+                    let Some(node) = self.nodes.borrow_mut(&handle) else {
+                        continue;
+                    };
+                    (node, Matrix4::identity(), true)
+                };
+
+            let mut changed = parent_changed;
+            if node.transform_dirty {
+                node.calculate_local_transform();
+                self.local_transform_updates += 1;
+                changed = true;
+            }
+
+            node.global_visibility = parent_visibility && node.is_visible();
+            let global_transform = if changed {
+                parent_global_transform * node.local_transform
+            } else {
+                node.global_transform
+            };
+            node.prev_global_transform = if node.reset_interpolation {
+                node.reset_interpolation = false;
+                global_transform
+            } else {
+                node.global_transform
+            };
+            node.global_transform = global_transform;
+
+            let eye = node.get_global_position();
+
+            // Aspect ratio may change with no node moving.
+            if let NodeKind::Camera(camera) = node.borrow_kind_mut() {
+                if camera.is_enabled() {
+                    let (look, up) = camera.get_view_axes(&global_transform);
+                    camera.calculate_matrices(eye.into(), look.into(), up, aspect_ratio);
+                }
+            }
+
+            if let NodeKind::Script(_) = node.kind {
+                self.scripts.push(handle.clone());
+            }
+
+            for child_handle in node.children.iter() {
+                self.stack.push((child_handle.clone(), changed));
+            }
+            updated += 1;
+        }
+
+        for handle in self.scripts.iter() {
+            if let Some(node) = self.nodes.borrow_mut(handle) {
+                // Script is taken out so it can borrow its own node.
+                if let NodeKind::Script(mut script) =
+                    std::mem::replace(&mut node.kind, NodeKind::Base)
+                {
+                    script.update(node, dt);
+                    node.kind = NodeKind::Script(script);
+                }
+            }
+        }
+
+        // Skinning needs final transforms of both the mesh and its bones.
+        let skinned: Vec<_> = self
+            .nodes
+            .pair_iter()
+            .filter_map(|(handle, node)| {
+                let NodeKind::Mesh(mesh) = node.borrow_kind() else {
+                    return None;
+                };
+                if mesh.get_bones().is_empty() {
+                    return None;
+                }
+                let mesh_inverse = node
+                    .global_transform
+                    .try_inverse()
+                    .unwrap_or_else(Matrix4::identity);
+                let matrices = mesh
+                    .get_bones()
+                    .iter()
+                    .map(|bone| match self.nodes.borrow(&bone.node) {
+                        Some(bone_node) => {
+                            mesh_inverse * bone_node.global_transform * bone.inverse_bind_pose
+                        }
+                        // Removed bones leave their vertices in bind pose.
+                        None => Matrix4::identity(),
+                    })
+                    .collect();
+                Some((handle, matrices))
+            })
+            .collect();
+        for (handle, matrices) in skinned {
+            if let Some(NodeKind::Mesh(mesh)) =
+                self.nodes.borrow_mut(&handle).map(Node::borrow_kind_mut)
+            {
+                mesh.set_bone_matrices(matrices);
+            }
+        }
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        f32::consts::{FRAC_PI_2, PI},
+        rc::Rc,
+    };
+
+    use nalgebra::{Point3, UnitQuaternion, Vector2, Vector3, Vector4};
+
+    use super::{
+        animation::Animation,
+        node::{Camera, Light, LightKind, Mesh, Node, NodeBehavior, NodeKind},
+        Scene, SceneIter,
+    };
+    use crate::{
+        math::{color::Color, ray::Ray, rect::Rect},
+        utils::pool::Handle,
+    };
+
+    #[test]
+    fn find_nodes_depth_first() {
+        let mut scene = Scene::new();
+        let named = |scene: &mut Scene, name: &str| {
+            let mut node = Node::new(NodeKind::Base);
+            node.set_name(name);
+            scene.add_node(node)
+        };
+        let a = named(&mut scene, "a");
+        named(&mut scene, "b");
+        let child = named(&mut scene, "a_child");
+        let detached = named(&mut scene, "a_detached");
+        scene.link_nodes(&child, &a);
+        scene.unlink_node(&detached);
+
+        let names = |handles: Vec<_>| -> Vec<String> {
+            handles
+                .iter()
+                .map(|h| scene.borrow_node(h).unwrap().get_name().to_string())
+                .collect()
+        };
+        assert_eq!(names(scene.find_nodes(|_| true)), ["a", "a_child", "b"]);
+        assert_eq!(
+            names(scene.find_nodes(|node| node.get_name().starts_with('a'))),
+            ["a", "a_child"]
+        );
+        assert!(scene.find_nodes(|_| true).iter().all(|h| *h != detached));
+    }
+
+    #[test]
+    fn copy_subtree() {
+        let mut scene = Scene::new();
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        let data = mesh.get_surfaces()[0].data.clone();
+        let mut crate_node = Node::new(NodeKind::Mesh(mesh));
+        crate_node.set_name("crate");
+        crate_node.set_local_position(Vector3::new(1.0, 0.0, 0.0));
+        let prefab = scene.add_node(crate_node);
+        let light = scene.add_node(Node::new(NodeKind::Light(Light::default())));
+        scene.link_nodes(&light, &prefab);
+        let strong_count = Rc::strong_count(&data);
+
+        let copy = scene.instantiate(&prefab);
+        assert!(copy != prefab);
+        assert_eq!(Rc::strong_count(&data), strong_count + 1);
+        let root = scene.root.clone();
+        assert!(scene.borrow_node(&copy).unwrap().parent == root);
+        let copied_light = scene.borrow_node(&copy).unwrap().children[0].clone();
+        assert!(copied_light != light);
+        assert!(matches!(
+            scene.borrow_node(&copied_light).unwrap().borrow_kind(),
+            NodeKind::Light(_)
+        ));
+
+        scene
+            .borrow_node_mut(&copy)
+            .unwrap()
+            .set_local_position(Vector3::new(5.0, 0.0, 0.0));
+        scene.update(1.0, 0.0);
+        let position = |handle| scene.borrow_node(handle).unwrap().get_global_position();
+        assert_eq!(position(&prefab), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(position(&copied_light), Vector3::new(5.0, 0.0, 0.0));
+
+        let mut other = Scene::new();
+        let foreign = scene.copy_node(&prefab, &mut other);
+        assert_eq!(other.borrow_node(&foreign).unwrap().get_name(), "crate");
+        assert_eq!(other.node_count(), 3);
+        assert_eq!(Rc::strong_count(&data), strong_count + 2);
+        assert!(scene.copy_node(&Handle::none(), &mut other) == Handle::none());
+    }
+
+    #[test]
+    fn clean_nodes_skipped() {
+        let mut scene = Scene::new();
+        let mut parent = Handle::none();
+        for i in 0..10_000 {
+            let mut node = Node::new(NodeKind::Base);
+            node.set_local_position(Vector3::new(1.0, 0.0, 0.0));
+            let handle = scene.add_node(node);
+            // Every tenth node starts a new chain.
+            if i % 10 != 0 {
+                scene.link_nodes(&handle, &parent);
+            }
+            parent = handle;
+        }
+        scene.update(1.0, 0.0);
+        assert_eq!(scene.get_local_transform_updates(), 10_001);
+        scene.update(1.0, 0.0);
+        assert_eq!(scene.get_local_transform_updates(), 0);
+        let leaf = parent.clone();
+        assert_eq!(
+            scene.borrow_node(&leaf).unwrap().get_global_position(),
+            Vector3::new(10.0, 0.0, 0.0)
+        );
+
+        // Only the moved node is recalculated, its subtree still follows.
+        let leaf_parent = scene.borrow_node(&leaf).unwrap().parent.clone();
+        scene
+            .borrow_node_mut(&leaf_parent)
+            .unwrap()
+            .offset(Vector3::new(0.0, 2.0, 0.0));
+        scene.update(1.0, 0.0);
+        assert_eq!(scene.get_local_transform_updates(), 1);
+        assert_eq!(
+            scene.borrow_node(&leaf).unwrap().get_global_position(),
+            Vector3::new(10.0, 2.0, 0.0)
+        );
+
+        // Relinked nodes follow their new parent.
+        let root = scene.root.clone();
+        scene.link_nodes(&leaf, &root);
+        scene.update(1.0, 0.0);
+        assert_eq!(scene.get_local_transform_updates(), 1);
+        assert_eq!(
+            scene.borrow_node(&leaf).unwrap().get_global_position(),
+            Vector3::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn iteration_order() {
+        let mut scene = Scene::new();
+        let named = |scene: &mut Scene, name: &str| {
+            let mut node = Node::new(NodeKind::Base);
+            node.set_name(name);
+            scene.add_node(node)
+        };
+        let a = named(&mut scene, "a");
+        let b = named(&mut scene, "b");
+        let a1 = named(&mut scene, "a1");
+        let a2 = named(&mut scene, "a2");
+        let a11 = named(&mut scene, "a11");
+        scene.link_nodes(&a1, &a);
+        scene.link_nodes(&a2, &a);
+        scene.link_nodes(&a11, &a1);
+        let names = |iter: SceneIter| {
+            iter.map(|(_, node)| node.get_name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(scene.iter()), ["Node", "a", "a1", "a11", "a2", "b"]);
+        assert_eq!(names(scene.iter_from(&a1)), ["a1", "a11"]);
+        assert!(scene.iter().next().unwrap().0 == scene.root);
+
+        // Freed nodes are never yielded, nor through handles of reused slots.
+        scene.remove_node(a1);
+        scene.remove_node(b);
+        assert_eq!(names(scene.iter()), ["Node", "a", "a2"]);
+        named(&mut scene, "c");
+        assert_eq!(names(scene.iter()), ["Node", "a", "a2", "c"]);
+        assert_eq!(scene.iter_from(&a11).count(), 0);
+
+        let mut visited = Vec::new();
+        scene.visit_mut(|handle, node| {
+            node.offset(Vector3::new(1.0, 0.0, 0.0));
+            visited.push(handle);
+        });
+        let handles: Vec<_> = scene.iter().map(|(handle, _)| handle).collect();
+        assert!(visited == handles);
+        scene.update(1.0, 0.0);
+        assert_eq!(
+            scene.borrow_node(&a2).unwrap().get_global_position(),
+            Vector3::new(3.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn relink_keeping_global() {
+        let mut scene = Scene::new();
+        let mut parent = Node::new(NodeKind::Base);
+        parent.set_local_position(Vector3::new(3.0, -1.0, 2.0));
+        parent.set_local_rotation(UnitQuaternion::from_euler_angles(0.4, 1.1, -0.3));
+        parent.set_local_scale(Vector3::new(2.0, 0.5, 1.5));
+        let parent = scene.add_node(parent);
+        let mut child = Node::new(NodeKind::Base);
+        child.set_local_position(Vector3::new(-4.0, 5.0, 1.0));
+        child.set_local_rotation(UnitQuaternion::from_euler_angles(0.0, 0.0, 0.7));
+        child.set_pre_rotation(UnitQuaternion::from_euler_angles(0.2, 0.0, 0.0));
+        child.set_rotation_pivot(Vector3::new(0.5, 0.0, 0.0));
+        let child = scene.add_node(child);
+        scene.update(1.0, 0.0);
+        let position = |scene: &Scene| scene.borrow_node(&child).unwrap().get_global_position();
+        let look = |scene: &Scene| scene.borrow_node(&child).unwrap().get_look_vector();
+        let before = position(&scene);
+
+        // Non-uniform parent scale shears rotated children, so only the
+        // position is exact there.
+        scene.link_nodes_keep_global(&child, &parent);
+        scene.update(1.0, 0.0);
+        assert!((position(&scene) - before).norm() < 1e-4);
+
+        // Back to root, nothing is lost on the way.
+        let root = scene.root.clone();
+        scene.link_nodes_keep_global(&child, &root);
+        scene.update(1.0, 0.0);
+        assert!((position(&scene) - before).norm() < 1e-4);
+        assert!(scene.borrow_node(&child).unwrap().parent == root);
+
+        // Uniform scale keeps orientation too.
+        scene
+            .borrow_node_mut(&parent)
+            .unwrap()
+            .set_local_scale(Vector3::new(2.0, 2.0, 2.0));
+        scene.update(1.0, 0.0);
+        let look_before = look(&scene);
+        scene.link_nodes_keep_global(&child, &parent);
+        scene.update(1.0, 0.0);
+        assert!((position(&scene) - before).norm() < 1e-4);
+        assert!((look(&scene) - look_before).norm() < 1e-4);
+    }
+
+    #[test]
+    fn ancestors() {
+        let mut scene = Scene::new();
+        let top = scene.add_node(Node::new(NodeKind::Base));
+        let middle = scene.add_node(Node::new(NodeKind::Base));
+        let bottom = scene.add_node(Node::new(NodeKind::Base));
+        scene.link_nodes(&middle, &top);
+        scene.link_nodes(&bottom, &middle);
+        let root = scene.root.clone();
+
+        let node = |handle| scene.borrow_node(handle).unwrap();
+        assert!(node(&bottom).parent() == middle);
+        assert!(node(&top).children() == [middle.clone()]);
+        assert!(node(&root).parent() == Handle::none());
+
+        assert!(scene.is_ancestor_of(&top, &bottom));
+        assert!(scene.is_ancestor_of(&middle, &bottom));
+        assert!(scene.is_ancestor_of(&root, &bottom));
+        assert!(!scene.is_ancestor_of(&bottom, &top));
+        assert!(!scene.is_ancestor_of(&bottom, &bottom));
+        assert!(!scene.is_ancestor_of(&top, &root));
+
+        scene.unlink_node(&middle);
+        assert!(!scene.is_ancestor_of(&root, &middle));
+        assert!(!scene.is_ancestor_of(&top, &bottom));
+        assert!(scene.is_ancestor_of(&middle, &bottom));
+        assert!(!scene.is_ancestor_of(&Handle::none(), &middle));
+    }
+
+    #[test]
+    fn node_lifetime() {
+        let mut scene = Scene::new();
+        let mut flash = Node::new(NodeKind::Base);
+        flash.set_lifetime(0.5);
+        let flash = scene.add_node(flash);
+        let spark = scene.add_node(Node::new(NodeKind::Base));
+        scene.link_nodes(&spark, &flash);
+        let sibling = scene.add_node(Node::new(NodeKind::Base));
+
+        scene.update(1.0, 0.25);
+        assert_eq!(
+            scene.borrow_node(&flash).unwrap().get_lifetime(),
+            Some(0.25)
+        );
+        assert!(scene.borrow_node(&spark).unwrap().get_lifetime().is_none());
+
+        scene.update(1.0, 0.25);
+        assert!(scene.borrow_node(&flash).is_none());
+        assert!(scene.borrow_node(&spark).is_none());
+        assert!(scene.borrow_node(&sibling).is_some());
+        assert!(scene.borrow_node(&scene.root).unwrap().children() == [sibling.clone()]);
+
+        // Time scale stretches lifetime.
+        let mut scene = Scene::new();
+        let mut debris = Node::new(NodeKind::Base);
+        debris.set_lifetime(1.0);
+        let debris = scene.add_node(debris);
+        scene.set_time_scale(0.5);
+        scene.update(1.0, 1.0);
+        assert!(scene.borrow_node(&debris).is_some());
+        scene.update(1.0, 1.0);
+        assert!(scene.borrow_node(&debris).is_none());
+    }
+
+    #[test]
+    fn tags() {
+        const SPAWN: u64 = 7;
+        let mut scene = Scene::new();
+        let tagged = |scene: &mut Scene, tag| {
+            let mut node = Node::new(NodeKind::Base);
+            node.set_tag(tag);
+            scene.add_node(node)
+        };
+        let first = tagged(&mut scene, 1);
+        let nested = tagged(&mut scene, SPAWN);
+        let second = tagged(&mut scene, SPAWN);
+        let lost = tagged(&mut scene, SPAWN);
+        scene.link_nodes(&nested, &first);
+        scene.unlink_node(&lost);
+
+        assert!(scene.find_nodes_by_tag(SPAWN) == [nested.clone(), second]);
+        assert!(scene.find_first_by_tag(SPAWN) == Some(nested));
+        assert!(scene.find_first_by_tag(2).is_none());
+        // Untagged nodes default to zero, root is never found.
+        assert!(scene.find_nodes_by_tag(0).is_empty());
+        assert_eq!(scene.borrow_node(&lost).unwrap().tag(), SPAWN);
+    }
+
+    #[test]
+    fn global_rotation_and_scale() {
+        let mut scene = Scene::new();
+        let turn = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2);
+        let mut parent = Node::new(NodeKind::Base);
+        parent.set_local_rotation(turn);
+        parent.set_local_scale(Vector3::new(2.0, 2.0, 2.0));
+        let parent = scene.add_node(parent);
+        let tilt = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.5);
+        let mut child = Node::new(NodeKind::Base);
+        child.set_local_rotation(tilt);
+        child.set_local_scale(Vector3::new(1.0, 3.0, 0.5));
+        let child = scene.add_node(child);
+        scene.link_nodes(&child, &parent);
+        let mut flat = Node::new(NodeKind::Base);
+        flat.set_local_scale(Vector3::new(1.0, 0.0, 1.0));
+        let flat = scene.add_node(flat);
+        scene.link_nodes(&flat, &child);
+        scene.update(1.0, 0.0);
+
+        let child = scene.borrow_node(&child).unwrap();
+        assert!(child.get_global_rotation().angle_to(&(turn * tilt)) < 1e-5);
+        assert!((child.get_global_scale() - Vector3::new(2.0, 6.0, 1.0)).norm() < 1e-5);
+
+        // Squashed axis can't give a rotation, scale is still measured.
+        let flat = scene.borrow_node(&flat).unwrap();
+        assert_eq!(flat.get_global_rotation(), UnitQuaternion::identity());
+        assert!((flat.get_global_scale() - Vector3::new(2.0, 0.0, 1.0)).norm() < 1e-5);
+
+        // Mirroring goes to X scale.
+        let mut mirror = Node::new(NodeKind::Base);
+        mirror.set_local_scale(Vector3::new(1.0, 1.0, -1.0));
+        let mirror = scene.add_node(mirror);
+        scene.update(1.0, 0.0);
+        let mirror = scene.borrow_node(&mirror).unwrap();
+        assert!((mirror.get_global_scale() - Vector3::new(-1.0, 1.0, 1.0)).norm() < 1e-5);
+        let flip = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI);
+        assert!(mirror.get_global_rotation().angle_to(&flip) < 1e-5);
+    }
+
+    #[test]
+    fn stale_handles() {
+        let mut scene = Scene::new();
+        // Root alone.
+        assert_eq!(scene.node_count(), 1);
+        let removed = scene.add_node(Node::new(NodeKind::Base));
+        assert!(scene.is_valid_handle(&removed));
+        assert_eq!(scene.node_count(), 2);
+        scene.remove_node(removed.clone());
+        assert!(!scene.is_valid_handle(&removed));
+        assert_eq!(scene.node_count(), 1);
+
+        // The new node takes the freed slot, old handle still misses it.
+        let mut node = Node::new(NodeKind::Base);
+        node.set_name("new");
+        let reused = scene.add_node(node);
+        assert_eq!(reused.index, removed.index);
+        assert!(scene.is_valid_handle(&reused));
+        assert!(!scene.is_valid_handle(&removed));
+        assert!(scene.borrow_node(&removed).is_none());
+        assert_eq!(scene.borrow_node(&reused).unwrap().get_name(), "new");
+        assert!(!scene.is_valid_handle(&Handle::none()));
+        assert_eq!(scene.node_count(), 2);
+    }
+
+    #[test]
+    fn local_transform_getters() {
+        let position = Vector3::new(1.0, 2.0, 3.0);
+        let rotation = UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3);
+        let scale = Vector3::new(2.0, 1.0, 0.5);
+        let pre_rotation = UnitQuaternion::from_euler_angles(0.5, 0.0, 0.0);
+        let post_rotation = UnitQuaternion::from_euler_angles(0.0, 0.0, -0.4);
+        let mut node = Node::new(NodeKind::Base);
+        node.set_local_position(position);
+        node.set_local_rotation(rotation);
+        node.set_local_scale(scale);
+        node.set_pre_rotation(pre_rotation);
+        node.set_post_rotation(post_rotation);
+        node.set_rotation_pivot(Vector3::x());
+        node.set_rotation_offset(Vector3::y());
+        node.set_scaling_pivot(Vector3::z());
+        node.set_scaling_offset(-Vector3::x());
+        node.calculate_local_transform();
+
+        assert_eq!(node.get_local_position(), position);
+        assert_eq!(node.get_local_rotation(), rotation);
+        assert_eq!(node.get_local_scale(), scale);
+        assert_eq!(node.get_pre_rotation(), pre_rotation);
+        assert_eq!(node.get_post_rotation(), post_rotation);
+        assert_eq!(node.get_rotation_pivot(), Vector3::x());
+        assert_eq!(node.get_rotation_offset(), Vector3::y());
+        assert_eq!(node.get_scaling_pivot(), Vector3::z());
+        assert_eq!(node.get_scaling_offset(), -Vector3::x());
+
+        node.offset(Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(node.get_local_position(), Vector3::new(1.0, 2.0, 4.0));
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        ticks: usize,
+        added: bool,
+        removed: Rc<Cell<bool>>,
+    }
+
+    impl NodeBehavior for Counter {
+        fn update(&mut self, node: &mut Node, _dt: f32) {
+            self.ticks += 1;
+            node.offset(Vector3::new(1.0, 0.0, 0.0));
+        }
+
+        fn on_added(&mut self) {
+            self.added = true;
+        }
+
+        fn on_removed(&mut self) {
+            self.removed.set(true);
+        }
+    }
+
+    #[test]
+    fn script_nodes() {
+        let mut scene = Scene::new();
+        let removed = Rc::new(Cell::new(false));
+        let counter = Counter {
+            removed: removed.clone(),
+            ..Default::default()
+        };
+        let script = scene.add_node(Node::new(NodeKind::Script(Box::new(counter))));
+        let ticks = |scene: &Scene| {
+            let node = scene.borrow_node(&script).unwrap();
+            assert!(node.script_as::<Counter>().unwrap().added);
+            node.script_as::<Counter>().unwrap().ticks
+        };
+        assert_eq!(ticks(&scene), 0);
+        for _ in 0..3 {
+            scene.update(1.0, 0.1);
+        }
+        assert_eq!(ticks(&scene), 3);
+        // Moves made by the script show up with the next update.
+        assert_eq!(
+            scene.borrow_node(&script).unwrap().get_global_position(),
+            Vector3::new(2.0, 0.0, 0.0)
+        );
+        assert!(scene
+            .borrow_node(&scene.root)
+            .unwrap()
+            .script_as::<Counter>()
+            .is_none());
+
+        // Unlinked scripts are not updated.
+        scene.unlink_node(&script);
+        scene.update(1.0, 0.1);
+        assert_eq!(ticks(&scene), 3);
+
+        scene.remove_node(script);
+        assert!(removed.get());
+    }
+
+    #[test]
+    fn corner_pivot() {
+        let mut scene = Scene::new();
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        let mut cube = Node::new(NodeKind::Mesh(mesh));
+        cube.set_local_position(Vector3::new(10.0, 0.0, 0.0));
+        cube.set_local_scale(Vector3::new(2.0, 2.0, 2.0));
+        cube.set_rotation_pivot_point(Vector3::new(0.5, -0.5, 0.5));
+        let cube = scene.add_node(cube);
+        let corner = |scene: &mut Scene, point: [f32; 3]| {
+            scene.update(1.0, 0.0);
+            let transform = scene.borrow_node(&cube).unwrap().global_transform;
+            transform.transform_point(&Point3::from(point)).coords
+        };
+        // Pivot alone doesn't move the cube.
+        assert_eq!(
+            corner(&mut scene, [-0.5, 0.5, -0.5]),
+            Vector3::new(9.0, 1.0, -1.0)
+        );
+
+        scene
+            .borrow_node_mut(&cube)
+            .unwrap()
+            .set_local_rotation(UnitQuaternion::from_axis_angle(
+                &Vector3::y_axis(),
+                FRAC_PI_2,
+            ));
+        let pivot = corner(&mut scene, [0.5, -0.5, 0.5]);
+        assert!((pivot - Vector3::new(11.0, -1.0, 1.0)).norm() < 1e-5);
+        let opposite = corner(&mut scene, [-0.5, 0.5, -0.5]);
+        assert!((opposite - Vector3::new(9.0, 1.0, 3.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn clear_scene() {
+        let mut scene = Scene::new();
+        let old_root = scene.root();
+        let parent = scene.add_node(Node::new(NodeKind::Base));
+        let child = scene.add_node(Node::new(NodeKind::Base));
+        scene.link_nodes(&child, &parent);
+        scene.add_animation(Animation::new("idle"));
+
+        scene.clear();
+        assert_eq!(scene.node_count(), 1);
+        assert!(scene.find_animations(|_| true).is_empty());
+        for handle in [&old_root, &parent, &child] {
+            assert!(!scene.is_valid_handle(handle));
+        }
+
+        let node = scene.add_node(Node::new(NodeKind::Base));
+        assert!(scene.borrow_node(&node).unwrap().parent() == scene.root());
+        assert!(scene.root() != old_root);
+        scene.update(1.0, 0.0);
+        assert_eq!(scene.iter().count(), 2);
+    }
+
+    #[test]
+    fn camera_zoom() {
+        let mut scene = Scene::new();
+        let camera = scene.add_node(Node::new(NodeKind::Camera(Camera::default())));
+        let projected_x = |scene: &mut Scene| {
+            scene.update(1.0, 0.0);
+            let clip = camera_mut(scene, &camera).get_view_projection_matrix()
+                * Vector4::new(1.0, 0.0, 10.0, 1.0);
+            clip.x / clip.w
+        };
+        let wide = projected_x(&mut scene);
+        camera_mut(&mut scene, &camera).set_fov(22.5);
+        let zoomed = projected_x(&mut scene);
+        let ratio = (22.5f32.to_radians()).tan() / (11.25f32.to_radians()).tan();
+        assert!((zoomed / wide - ratio).abs() < 1e-4, "{} {}", wide, zoomed);
+
+        let camera = camera_mut(&mut scene, &camera);
+        camera.set_fov(0.0);
+        assert_eq!(camera.get_fov(), 1.0);
+        camera.set_z_near(-1.0);
+        camera.set_z_far(0.5);
+        assert_eq!((camera.get_z_near(), camera.get_z_far()), (1.0, 1000.0));
+        camera.set_z_near(2000.0);
+        assert_eq!(camera.get_z_near(), 1.0);
+        camera.set_clip_planes(2000.0, 5000.0);
+        assert_eq!((camera.get_z_near(), camera.get_z_far()), (2000.0, 5000.0));
+    }
+
+    #[test]
+    fn spot_light_validation() {
+        let mut light = Light::spot(Color::WHITE, 20.0, 30.0, 15.0);
+        let spot = LightKind::Spot {
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+            distance: 15.0,
+        };
+        assert_eq!(light.get_kind(), spot);
+        light.set_spot_angles(40.0, 30.0);
+        light.set_spot_angles(10.0, 90.0);
+        light.set_spot_angles(-5.0, 30.0);
+        light.set_spot_distance(0.0);
+        light.set_spot_distance(f32::INFINITY);
+        assert_eq!(light.get_kind(), spot);
+        light.set_spot_angles(30.0, 30.0);
+        light.set_spot_distance(5.0);
+        assert_eq!(
+            light.get_kind(),
+            LightKind::Spot {
+                inner_angle: 30.0,
+                outer_angle: 30.0,
+                distance: 5.0,
+            }
+        );
+
+        // Cone setters don't turn other kinds into spots.
+        let mut point = Light::default();
+        point.set_spot_angles(10.0, 20.0);
+        assert_eq!(point.get_kind(), LightKind::Point);
+        point.set_kind(LightKind::Spot {
+            inner_angle: 10.0,
+            outer_angle: 5.0,
+            distance: 1.0,
+        });
+        assert_eq!(point.get_kind(), LightKind::Point);
+    }
+
+    #[test]
+    fn split_screen() {
+        let mut scene = Scene::new();
+        let mut split = |x: f32, rotation| {
+            let mut camera = Camera::default();
+            camera.set_viewport(Rect::new(x, 0.0, 0.5, 1.0));
+            let mut node = Node::new(NodeKind::Camera(camera));
+            node.set_local_rotation(rotation);
+            scene.add_node(node)
+        };
+        let player = split(0.0, UnitQuaternion::identity());
+        let top_down = split(
+            0.5,
+            UnitQuaternion::from_axis_angle(&Vector3::x_axis(), FRAC_PI_2),
+        );
+        // Each half of a 2:1 window is square, so offsets along camera side
+        // and up axes project to the same size.
+        scene.update(2.0, 0.0);
+        for handle in [&player, &top_down] {
+            let node = scene.borrow_node(handle).unwrap();
+            let NodeKind::Camera(camera) = node.borrow_kind() else {
+                unreachable!();
+            };
+            let ahead = node.get_global_position() + node.get_look_vector() * 10.0;
+            let ndc = |point: Vector3<f32>| {
+                let clip = camera.get_view_projection_matrix() * point.push(1.0);
+                Vector2::new(clip.x, clip.y) / clip.w
+            };
+            let side = ndc(ahead + node.get_side_vector());
+            let up = ndc(ahead + node.get_up_vector());
+            assert!(side.x.abs() > 0.1);
+            assert!(
+                (side.x.abs() - up.y.abs()).abs() < 1e-5,
+                "{:?} {:?}",
+                side,
+                up
+            );
+        }
+
+        // Halves of odd window width share the middle edge.
+        let viewports: Vec<_> = [&player, &top_down]
+            .iter()
+            .map(|handle| {
+                let node = scene.borrow_node(handle).unwrap();
+                let NodeKind::Camera(camera) = node.borrow_kind() else {
+                    unreachable!();
+                };
+                camera.get_viewport_pixels(Vector2::new(1601.0, 800.0))
+            })
+            .collect();
+        assert_eq!(viewports[0].x + viewports[0].width, viewports[1].x);
+        assert_eq!(viewports[1].x + viewports[1].width, 1601);
+
+        let mut camera = Camera::default();
+        camera.set_viewport(Rect::new(0.75, -1.0, 0.5, 3.0));
+        assert_eq!(camera.get_viewport(), Rect::new(0.75, 0.0, 0.25, 1.0));
+    }
+
+    #[test]
+    fn camera_rays() {
+        let mut scene = Scene::new();
+        let mut camera = Camera::default();
+        camera.set_viewport(Rect::new(0.5, 0.0, 0.5, 1.0));
+        let mut node = Node::new(NodeKind::Camera(camera));
+        node.set_local_position(Vector3::new(1.0, 2.0, 3.0));
+        node.set_local_rotation(UnitQuaternion::from_euler_angles(0.3, -0.8, 0.0));
+        let handle = scene.add_node(node);
+        scene.update(2.0, 0.0);
+
+        let node = scene.borrow_node(&handle).unwrap();
+        let NodeKind::Camera(camera) = node.borrow_kind() else {
+            unreachable!();
+        };
+        let viewport = camera.get_viewport_pixels(Vector2::new(800.0, 400.0));
+        assert_eq!(viewport, Rect::new(400, 0, 400, 400));
+        let eye = node.get_global_position();
+        let look = node.get_look_vector();
+
+        let center = camera.make_ray(Vector2::new(600.0, 200.0), viewport);
+        assert!((center.dir - look).norm() < 1e-4);
+        assert!((center.origin - (eye + look * camera.get_z_near())).norm() < 1e-4);
+
+        // Bottom left corner of the viewport, not of the window.
+        let corner = camera.make_ray(Vector2::new(400.0, 0.0), viewport);
+        for t in [0.0, 10.0, 100.0] {
+            let clip = camera.get_view_projection_matrix() * corner.point_at(t).push(1.0);
+            let ndc = Vector2::new(clip.x, clip.y) / clip.w;
+            assert!((ndc - Vector2::new(-1.0, -1.0)).norm() < 1e-3, "{:?}", ndc);
+        }
+        // Empty viewport falls back to the look ray from the eye.
+        let fallback = camera.make_ray(Vector2::zeros(), Rect::new(0, 0, 0, 10));
+        assert!((fallback.dir - look).norm() < 1e-4);
+        assert!((fallback.origin - eye).norm() < 1e-4);
+    }
+
+    #[test]
+    fn camera_projection_round_trip() {
+        let mut scene = Scene::new();
+        let mut camera = Camera::default();
+        camera.set_viewport(Rect::new(0.0, 0.25, 0.5, 0.75));
+        let mut node = Node::new(NodeKind::Camera(camera));
+        node.set_local_position(Vector3::new(-2.0, 1.0, 5.0));
+        node.set_local_rotation(UnitQuaternion::from_euler_angles(-0.2, 0.6, 0.1));
+        let handle = scene.add_node(node);
+        scene.update(2.0, 0.0);
+
+        let node = scene.borrow_node(&handle).unwrap();
+        let NodeKind::Camera(camera) = node.borrow_kind() else {
+            unreachable!();
+        };
+        let viewport = camera.get_viewport_pixels(Vector2::new(800.0, 400.0));
+        assert_eq!(viewport, Rect::new(0, 100, 400, 300));
+        let screen_points = [
+            Vector2::new(200.0, 250.0),
+            Vector2::new(0.0, 100.0),
+            Vector2::new(399.5, 399.5),
+            Vector2::new(0.5, 399.0),
+        ];
+        for screen in screen_points {
+            for depth in [-1.0, -0.5, 0.0, 0.9] {
+                let world = camera.screen_to_world(screen, depth, viewport);
+                let projected = camera.world_to_screen(world, viewport).unwrap();
+                assert!((projected - screen).norm() < 1e-2, "{:?}", projected);
+            }
+        }
+
+        // Point in the middle of the view projects to the viewport center.
+        let ahead = node.get_global_position() + node.get_look_vector() * 10.0;
+        let center = camera.world_to_screen(ahead, viewport).unwrap();
+        assert!((center - Vector2::new(200.0, 250.0)).norm() < 1e-2);
+        // Behind the camera and between the eye and the near plane.
+        let behind = node.get_global_position() - node.get_look_vector();
+        assert_eq!(camera.world_to_screen(behind, viewport), None);
+        let close = node.get_global_position() + node.get_look_vector() * 0.5;
+        assert_eq!(camera.world_to_screen(close, viewport), None);
+    }
+
+    #[test]
+    fn active_camera() {
+        let mut scene = Scene::new();
+        assert_eq!(scene.active_camera(), None);
+        let main = scene.add_node(Node::new(NodeKind::Camera(Camera::default())));
+        let mut minimap = Camera::default();
+        minimap.set_render_order(-1);
+        let minimap = scene.add_node(Node::new(NodeKind::Camera(minimap)));
+        let mesh = scene.add_node(Node::new(NodeKind::Base));
+        // Falls back to the lowest render order.
+        assert_eq!(scene.active_camera(), Some(minimap.clone()));
+
+        scene.set_active_camera(&main);
+        assert_eq!(scene.active_camera(), Some(main.clone()));
+        // Non-camera, empty and removed handles fall back as well.
+        scene.set_active_camera(&mesh);
+        assert_eq!(scene.active_camera(), Some(minimap.clone()));
+        scene.set_active_camera(&Handle::none());
+        assert_eq!(scene.active_camera(), Some(minimap.clone()));
+        scene.set_active_camera(&main);
+        camera_mut(&mut scene, &main).set_enabled(false);
+        assert_eq!(scene.active_camera(), Some(minimap.clone()));
+        scene.remove_node(minimap);
+        assert_eq!(scene.active_camera(), None);
+    }
+
+    fn camera_mut<'a>(scene: &'a mut Scene, handle: &Handle<Node>) -> &'a mut Camera {
+        match scene.borrow_node_mut(handle).unwrap().borrow_kind_mut() {
+            NodeKind::Camera(camera) => camera,
+            _ => panic!("expected camera"),
+        }
+    }
+
+    #[test]
+    fn remove_subtree() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(Node::new(NodeKind::Base));
+        let middle = scene.add_node(Node::new(NodeKind::Base));
+        let sibling = scene.add_node(Node::new(NodeKind::Base));
+        let leaves = [
+            scene.add_node(Node::new(NodeKind::Base)),
+            scene.add_node(Node::new(NodeKind::Base)),
+        ];
+        scene.link_nodes(&middle, &parent);
+        scene.link_nodes(&sibling, &parent);
+        for leaf in leaves.iter() {
+            scene.link_nodes(leaf, &middle);
+        }
+        assert_eq!(scene.node_count(), 6);
+
+        scene.remove_node(middle.clone());
+        assert!(scene.borrow_node(&parent).unwrap().children == [sibling]);
+        assert!(scene.borrow_node(&middle).is_none());
+        assert!(leaves.iter().all(|leaf| scene.borrow_node(leaf).is_none()));
+        assert_eq!(scene.node_count(), 3);
+
+        // Stale and empty handles are ignored.
+        scene.remove_node(middle);
+        scene.remove_node(Handle::none());
+        assert_eq!(scene.node_count(), 3);
+    }
+
+    #[test]
+    fn deep_hierarchy_transform() {
+        let mut scene = Scene::new();
+        let mut node = |position: [f32; 3], rotation: UnitQuaternion<f32>| {
+            let mut node = Node::new(NodeKind::Base);
+            node.set_local_position(Vector3::from(position));
+            node.set_local_rotation(rotation);
+            scene.add_node(node)
+        };
+        let grandparent = node(
+            [1.0, 0.0, 0.0],
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2),
+        );
+        let parent = node(
+            [0.0, 0.0, 2.0],
+            UnitQuaternion::from_axis_angle(&Vector3::x_axis(), FRAC_PI_2),
+        );
+        let leaf = node([0.0, 1.0, 0.0], UnitQuaternion::identity());
+        scene.link_nodes(&parent, &grandparent);
+        scene.link_nodes(&leaf, &parent);
+        scene.update(1.0, 0.0);
+
+        // Leaf offset turns to +Z in the parent, then to +X in the
+        // grandparent: (1, 0, 0) + (3, 0, 0).
+        let position = |scene: &Scene| scene.borrow_node(&leaf).unwrap().get_global_position();
+        assert!((position(&scene) - Vector3::new(4.0, 0.0, 0.0)).norm() < 1e-5);
+
+        scene
+            .borrow_node_mut(&grandparent)
+            .unwrap()
+            .set_local_position(Vector3::new(0.0, 5.0, 0.0));
+        scene.update(1.0, 0.0);
+        assert!((position(&scene) - Vector3::new(3.0, 5.0, 0.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn pick_nearest_mesh() {
+        let mut scene = Scene::new();
+        let mut handles = Vec::new();
+        for z in [-5.0, -10.0] {
+            let mut mesh = Mesh::default();
+            mesh.make_cube();
+            let mut node = Node::new(NodeKind::Mesh(mesh));
+            node.set_local_position(Vector3::new(0.0, 0.0, z));
+            handles.push(scene.add_node(node));
+        }
+        scene.add_node(Node::new(NodeKind::Base));
+        scene.update(1.0, 0.0);
+
+        let ray = Ray::new(Vector3::zeros(), Vector3::new(0.0, 0.0, -1.0)).unwrap();
+        let (handle, distance) = scene.pick(&ray).unwrap();
+        assert!(handle == handles[0]);
+        assert_eq!(distance, 4.5);
+
+        let sky = Ray::new(Vector3::zeros(), Vector3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!(scene.pick(&sky).is_none());
+
+        // Hidden meshes and meshes under hidden parents can't be hit.
+        let group = scene.add_node(Node::new(NodeKind::Base));
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        let mut node = Node::new(NodeKind::Mesh(mesh));
+        node.set_local_position(Vector3::new(0.0, 0.0, -2.0));
+        let child = scene.add_node(node);
+        scene.link_nodes(&child, &group);
+        scene.update(1.0, 0.0);
+        assert!(scene.pick(&ray).unwrap().0 == child);
+
+        scene.borrow_node_mut(&group).unwrap().set_visibility(false);
+        scene
+            .borrow_node_mut(&handles[0])
+            .unwrap()
+            .set_visibility(false);
+        scene.update(1.0, 0.0);
+        let (handle, distance) = scene.pick(&ray).unwrap();
+        assert!(handle == handles[1]);
+        assert_eq!(distance, 9.5);
+    }
+
+    #[test]
+    fn interpolated_transform() {
+        let mut scene = Scene::new();
+        let handle = scene.add_node(Node::new(NodeKind::Base));
+        scene.update(1.0, 0.1);
+
+        // First update must not blend from identity.
+        let node = scene.borrow_node(&handle).unwrap();
+        assert_eq!(
+            node.get_interpolated_global_transform(0.5),
+            node.global_transform
+        );
+
+        scene
+            .borrow_node_mut(&handle)
+            .unwrap()
+            .set_local_position(Vector3::new(10.0, 0.0, 0.0));
+        scene.update(1.0, 0.1);
+        let node = scene.borrow_node(&handle).unwrap();
+        let blended = node.get_interpolated_global_transform(0.25);
+        assert!((blended[12] - 2.5).abs() < 1e-5);
+        assert_eq!(node.get_interpolated_global_transform(1.0)[12], 10.0);
+    }
+
+    #[test]
+    fn world_bounding_box() {
+        let mut scene = Scene::new();
+        let mut mesh = Mesh::default();
+        mesh.make_cube();
+        let mut node = Node::new(NodeKind::Mesh(mesh));
+        node.set_local_scale(Vector3::new(100.0, 0.1, 100.0));
+        let floor = scene.add_node(node);
+        let empty = scene.add_node(Node::new(NodeKind::Mesh(Mesh::default())));
+        scene.update(1.0, 0.1);
+
+        let bounds = scene
+            .borrow_node(&floor)
+            .unwrap()
+            .world_bounding_box()
+            .unwrap();
+        let half = Vector3::new(50.0, 0.05, 50.0);
+        assert!((bounds.max - half).norm() < 1e-5);
+        assert!((bounds.min + half).norm() < 1e-5);
+        assert!(scene
+            .borrow_node(&empty)
+            .unwrap()
+            .world_bounding_box()
+            .is_none());
     }
 }
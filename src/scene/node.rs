@@ -1,39 +1,220 @@
-use std::{any::Any, cell::RefCell, ops::Mul, rc::Rc};
+use std::{any::Any, cell::RefCell, rc::Rc};
 
-use bytemuck::Zeroable;
-use nalgebra::{Matrix4, Point3, Quaternion, Rotation3, UnitQuaternion, Vector2, Vector3};
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector2, Vector3};
 
 use crate::{
-    math::rect::Rect,
-    renderer::surface::{Surface, SurfaceSharedData},
-    utils::pool::Handle, resource::Resource,
+    math::{
+        aabb::AxisAlignedBoundingBox, color::Color, compose, decompose, ray::Ray, rect::Rect,
+        sphere::BoundingSphere,
+    },
+    renderer::surface::{Surface, SurfaceSharedData, SurfaceSharedDataRef},
+    resource::{texture::Texture, Resource},
+    utils::pool::Handle,
 };
-#[derive(Debug)]
+/// How light spreads from its node.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum LightKind {
+    /// Shines from the node position in all directions, fading out at the
+    /// radius.
+    #[default]
+    Point,
+    /// Shines along the look vector of the node everywhere with the same
+    /// strength, like the sun.
+    Directional,
+    /// Cone along the look vector of the node, like a flashlight. Angles are
+    /// half-angles in degrees, light fades out between the inner and the
+    /// outer one and towards the distance.
+    Spot {
+        inner_angle: f32,
+        outer_angle: f32,
+        distance: f32,
+    },
+}
+
+impl LightKind {
+    /// Spots need `0 <= inner <= outer < 90` and positive finite distance.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            LightKind::Spot {
+                inner_angle,
+                outer_angle,
+                distance,
+            } => {
+                inner_angle >= 0.0
+                    && inner_angle <= outer_angle
+                    && outer_angle < 90.0
+                    && distance > 0.0
+                    && distance.is_finite()
+            }
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Light {
+    kind: LightKind,
     radius: f32,
-    color: Vector3<f32>,
+    color: Color,
+    /// Multiplies color, may go above one.
+    intensity: f32,
 }
 
-impl Light {
-    pub fn default() -> Light {
+impl Default for Light {
+    fn default() -> Light {
         Light {
+            kind: LightKind::Point,
             radius: 10.0,
-            color: Vector3::new(1., 1., 1.),
+            color: Color::WHITE,
+            intensity: 1.0,
         }
     }
 }
-#[derive(Debug)]
+
+impl Light {
+    /// Point light.
+    pub fn new(radius: f32, color: Color) -> Light {
+        let mut light = Light {
+            color,
+            ..Default::default()
+        };
+        light.set_radius(radius);
+        light
+    }
+
+    pub fn directional(color: Color) -> Light {
+        Light {
+            kind: LightKind::Directional,
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn spot(color: Color, inner_angle: f32, outer_angle: f32, distance: f32) -> Light {
+        let mut light = Light {
+            color,
+            ..Default::default()
+        };
+        light.set_kind(LightKind::Spot {
+            inner_angle,
+            outer_angle,
+            distance,
+        });
+        light
+    }
+
+    /// Invalid spots are rejected, see `LightKind::is_valid`.
+    pub fn set_kind(&mut self, kind: LightKind) {
+        if kind.is_valid() {
+            self.kind = kind;
+        } else {
+            println!("invalid light kind {:?}", kind);
+        }
+    }
+
+    /// Sets cone half-angles in degrees of a spot, rejected unless
+    /// `0 <= inner <= outer < 90`. Other kinds are left as they are.
+    pub fn set_spot_angles(&mut self, inner_angle: f32, outer_angle: f32) {
+        if let LightKind::Spot { distance, .. } = self.kind {
+            self.set_kind(LightKind::Spot {
+                inner_angle,
+                outer_angle,
+                distance,
+            });
+        }
+    }
+
+    /// Sets range of a spot, rejected unless positive. Other kinds are left
+    /// as they are.
+    pub fn set_spot_distance(&mut self, distance: f32) {
+        if let LightKind::Spot {
+            inner_angle,
+            outer_angle,
+            ..
+        } = self.kind
+        {
+            self.set_kind(LightKind::Spot {
+                inner_angle,
+                outer_angle,
+                distance,
+            });
+        }
+    }
+
+    pub fn get_kind(&self) -> LightKind {
+        self.kind
+    }
+
+    /// Only point lights are limited by radius, spots have their distance.
+    /// Non-positive values are rejected.
+    pub fn set_radius(&mut self, radius: f32) {
+        if radius > 0.0 && radius.is_finite() {
+            self.radius = radius;
+        } else {
+            println!("invalid light radius {}", radius);
+        }
+    }
+
+    pub fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Alpha is ignored.
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    pub fn get_color(&self) -> Color {
+        self.color
+    }
+
+    /// Multiplies color, negative values are rejected.
+    pub fn set_intensity(&mut self, intensity: f32) {
+        if intensity >= 0.0 && intensity.is_finite() {
+            self.intensity = intensity;
+        } else {
+            println!("invalid light intensity {}", intensity);
+        }
+    }
+
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    /// Returns sphere of influence of the light located at given world position.
+    /// Spot lights reach their distance, directional ones are unbounded.
+    pub fn bounding_sphere(&self, position: Vector3<f32>) -> BoundingSphere {
+        let radius = match self.kind {
+            LightKind::Point => self.radius,
+            LightKind::Directional => f32::INFINITY,
+            LightKind::Spot { distance, .. } => distance,
+        };
+        BoundingSphere::new(position, radius)
+    }
+}
+#[derive(Debug, Clone)]
 pub struct Camera {
     fov: f32,
     z_near: f32,
     z_far: f32,
     viewport: Rect<f32>,
+    /// Background of the viewport, None keeps what is already drawn there.
+    clear_color: Option<Color>,
+    /// Disabled cameras are neither updated nor rendered from.
+    enabled: bool,
+    /// Cameras of a scene render from the lowest order up.
+    render_order: i32,
+    /// Layers of nodes the camera draws.
+    cull_mask: u32,
+    /// Turns the view relative to its node, children of the node are not
+    /// affected.
+    view_rotation: UnitQuaternion<f32>,
     view_matrix: Matrix4<f32>,
     projection_matrix: Matrix4<f32>,
 }
 
-impl Camera {
-    pub fn default() -> Camera {
+impl Default for Camera {
+    fn default() -> Camera {
         let fov: f32 = 45.0;
         let z_near: f32 = 1.;
         let z_far: f32 = 1000.;
@@ -50,9 +231,16 @@ impl Camera {
                 width: 1.0,
                 height: 1.0,
             },
+            clear_color: None,
+            enabled: true,
+            render_order: 0,
+            cull_mask: u32::MAX,
+            view_rotation: UnitQuaternion::identity(),
         }
     }
+}
 
+impl Camera {
     pub fn calculate_matrices(
         &mut self,
         pos: Point3<f32>,
@@ -64,49 +252,370 @@ impl Camera {
 
         self.view_matrix = Matrix4::look_at_rh(&pos, &point, &up);
 
+        // Viewport is relative to the window, aspect is of the window.
+        let aspect = if self.viewport.height > 0.0 {
+            aspect * self.viewport.width / self.viewport.height
+        } else {
+            aspect
+        };
         self.projection_matrix =
             Matrix4::new_perspective(aspect, self.fov.to_radians(), self.z_near, self.z_far);
+        debug_assert!(
+            self.projection_matrix.iter().all(|v| v.is_finite()),
+            "camera projection is not finite, aspect ratio {}",
+            aspect
+        );
+    }
+
+    /// Sets vertical field of view in degrees, takes effect on next scene update.
+    /// Clamped to 1..179 degrees.
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(1.0, 179.0);
+    }
+
+    pub fn get_fov(&self) -> f32 {
+        self.fov
     }
 
+    /// Sets distance to near clipping plane, takes effect on next scene update.
+    /// Values not between zero and far plane are rejected, use
+    /// `set_clip_planes` to move both planes past each other.
+    pub fn set_z_near(&mut self, z_near: f32) {
+        self.set_clip_planes(z_near, self.z_far);
+    }
+
+    pub fn get_z_near(&self) -> f32 {
+        self.z_near
+    }
+
+    /// Sets distance to far clipping plane, takes effect on next scene update.
+    /// Values not beyond near plane are rejected.
+    pub fn set_z_far(&mut self, z_far: f32) {
+        self.set_clip_planes(self.z_near, z_far);
+    }
+
+    /// Sets both clipping planes, rejected unless `0 < z_near < z_far`.
+    pub fn set_clip_planes(&mut self, z_near: f32, z_far: f32) {
+        if z_near > 0.0 && z_near < z_far && z_far.is_finite() {
+            self.z_near = z_near;
+            self.z_far = z_far;
+        } else {
+            println!("invalid camera clip planes {} and {}", z_near, z_far);
+        }
+    }
+
+    pub fn get_z_far(&self) -> f32 {
+        self.z_far
+    }
+
+    /// Sets part of the window the camera renders to, in 0..1 window units
+    /// from the bottom left corner. The rect is clamped to the window.
+    pub fn set_viewport(&mut self, viewport: Rect<f32>) {
+        let x = viewport.x.clamp(0.0, 1.0);
+        let y = viewport.y.clamp(0.0, 1.0);
+        self.viewport = Rect {
+            x,
+            y,
+            width: viewport.width.clamp(0.0, 1.0 - x),
+            height: viewport.height.clamp(0.0, 1.0 - y),
+        };
+    }
+
+    pub fn get_viewport(&self) -> Rect<f32> {
+        self.viewport
+    }
+
+    /// Viewport background, None by default so the viewport only gets its
+    /// depth cleared.
+    pub fn set_clear_color(&mut self, color: Option<Color>) {
+        self.clear_color = color;
+    }
+
+    pub fn get_clear_color(&self) -> Option<Color> {
+        self.clear_color
+    }
+
+    /// Switching cameras is disabling one and enabling another. Matrices of
+    /// a disabled camera are updated once it is enabled again.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Cameras of a scene are rendered from the lowest order up, cameras of
+    /// the same order keep scene order. Overlays such as a minimap need a
+    /// higher order than the view they are drawn over.
+    pub fn set_render_order(&mut self, order: i32) {
+        self.render_order = order;
+    }
+
+    pub fn get_render_order(&self) -> i32 {
+        self.render_order
+    }
+
+    /// Bitmask of node layers drawn by this camera, all by default.
+    pub fn set_cull_mask(&mut self, mask: u32) {
+        self.cull_mask = mask;
+    }
+
+    pub fn get_cull_mask(&self) -> u32 {
+        self.cull_mask
+    }
+
+    /// Identity looks along +Z of the node with +Y up, same as other nodes.
+    pub fn set_view_rotation(&mut self, rotation: UnitQuaternion<f32>) {
+        self.view_rotation = rotation;
+    }
+
+    pub fn get_view_rotation(&self) -> UnitQuaternion<f32> {
+        self.view_rotation
+    }
+
+    /// Returns look and up vectors of the camera placed at given world
+    /// transform, turned by the view rotation.
+    pub fn get_view_axes(&self, transform: &Matrix4<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        let basis = transform.fixed_view::<3, 3>(0, 0) * self.view_rotation.to_rotation_matrix();
+        (basis.column(2).into_owned(), basis.column(1).into_owned())
+    }
+
+    /// Viewport in pixels. Edges are rounded, so viewports sharing an edge
+    /// neither overlap nor leave a gap.
     pub fn get_viewport_pixels(&self, client_size: Vector2<f32>) -> Rect<i32> {
+        let left = (self.viewport.x * client_size.x).round() as i32;
+        let bottom = (self.viewport.y * client_size.y).round() as i32;
+        let right = ((self.viewport.x + self.viewport.width) * client_size.x).round() as i32;
+        let top = ((self.viewport.y + self.viewport.height) * client_size.y).round() as i32;
         Rect {
-            x: (self.viewport.x * client_size.x) as i32,
-            y: (self.viewport.y * client_size.y) as i32,
-            width: (self.viewport.width * client_size.x) as i32,
-            height: (self.viewport.height * client_size.y) as i32,
+            x: left,
+            y: bottom,
+            width: right - left,
+            height: top - bottom,
         }
     }
 
     pub fn get_view_projection_matrix(&self) -> Matrix4<f32> {
         self.projection_matrix * self.view_matrix
     }
+
+    /// Ray from the near plane through `screen_point` toward the far plane,
+    /// using matrices of the last scene update. The point is in window pixels
+    /// from the bottom left corner like `viewport_pixels`, which maps to the
+    /// whole frustum; points outside of it give rays outside the frustum.
+    /// Empty viewports and degenerate matrices give the ray from the camera
+    /// position along its look vector.
+    pub fn make_ray(&self, screen_point: Vector2<f32>, viewport_pixels: Rect<i32>) -> Ray {
+        if viewport_pixels.width > 0 && viewport_pixels.height > 0 {
+            let ndc = screen_to_ndc(screen_point, viewport_pixels);
+            if let Some(inverse) = self.get_view_projection_matrix().try_inverse() {
+                let near = inverse.transform_point(&Point3::new(ndc.x, ndc.y, -1.0));
+                let far = inverse.transform_point(&Point3::new(ndc.x, ndc.y, 1.0));
+                if let Some(ray) = Ray::new(near.coords, far - near) {
+                    return ray;
+                }
+            }
+        }
+        self.look_ray()
+    }
+
+    fn look_ray(&self) -> Ray {
+        let camera_to_world = self
+            .view_matrix
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let origin = camera_to_world.transform_point(&Point3::origin()).coords;
+        Ray::new(origin, camera_to_world.transform_vector(&-Vector3::z())).unwrap_or(Ray {
+            origin,
+            dir: -Vector3::z(),
+        })
+    }
+
+    /// Projects world point to window pixels from the bottom left corner, same
+    /// as `make_ray` takes. Points outside the viewport still project, beyond
+    /// its edges. Returns None for points behind the near plane and for empty
+    /// viewports.
+    pub fn world_to_screen(
+        &self,
+        world: Vector3<f32>,
+        viewport_pixels: Rect<i32>,
+    ) -> Option<Vector2<f32>> {
+        if viewport_pixels.width <= 0 || viewport_pixels.height <= 0 {
+            return None;
+        }
+        let clip = self.get_view_projection_matrix() * world.push(1.0);
+        if clip.w <= 0.0 || clip.z < -clip.w {
+            return None;
+        }
+        let ndc = Vector2::new(clip.x, clip.y) / clip.w;
+        Some(Vector2::new(
+            viewport_pixels.x as f32 + (ndc.x + 1.0) * 0.5 * viewport_pixels.width as f32,
+            viewport_pixels.y as f32 + (ndc.y + 1.0) * 0.5 * viewport_pixels.height as f32,
+        ))
+    }
+
+    /// Inverse of `world_to_screen`, `depth_ndc` is -1 at the near plane and
+    /// 1 at the far one. Empty viewports and degenerate matrices give NaN.
+    pub fn screen_to_world(
+        &self,
+        screen: Vector2<f32>,
+        depth_ndc: f32,
+        viewport_pixels: Rect<i32>,
+    ) -> Vector3<f32> {
+        let ndc = screen_to_ndc(screen, viewport_pixels);
+        let inverse = self
+            .get_view_projection_matrix()
+            .try_inverse()
+            .unwrap_or_else(Matrix4::zeros);
+        inverse
+            .transform_point(&Point3::new(ndc.x, ndc.y, depth_ndc))
+            .coords
+    }
+
+    /// Returns view-projection matrix of camera placed at given world transform,
+    /// projection is taken from last `calculate_matrices` call.
+    pub fn get_view_projection_matrix_at(&self, transform: &Matrix4<f32>) -> Matrix4<f32> {
+        let eye = Point3::new(transform[12], transform[13], transform[14]);
+        let (look, up) = self.get_view_axes(transform);
+        self.projection_matrix * Matrix4::look_at_rh(&eye, &(eye + look), &up)
+    }
 }
 
-#[derive(Debug)]
+/// Window pixels to normalized device coordinates of the viewport.
+fn screen_to_ndc(screen: Vector2<f32>, viewport_pixels: Rect<i32>) -> Vector2<f32> {
+    let x = (screen.x - viewport_pixels.x as f32) / viewport_pixels.width as f32;
+    let y = (screen.y - viewport_pixels.y as f32) / viewport_pixels.height as f32;
+    Vector2::new(x * 2.0 - 1.0, y * 2.0 - 1.0)
+}
+
+/// Node deforming vertices of a skinned mesh.
+#[derive(Debug, Clone)]
+pub struct Bone {
+    pub node: Handle<Node>,
+    /// Transforms mesh space to bone space at bind time.
+    pub inverse_bind_pose: Matrix4<f32>,
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct Mesh {
     pub(crate) surfaces: Vec<Surface>,
+    /// Multiplies texture color of every surface.
+    tint: Color,
+    bones: Vec<Bone>,
+    bone_matrices: Vec<Matrix4<f32>>,
 }
 
 impl Mesh {
-    pub fn default() -> Mesh {
-        Mesh {
-            surfaces: Vec::new(),
-        }
+    /// Procedural shapes share their data with other meshes of the same
+    /// shape, see `SurfaceSharedData::cached_cube`.
+    pub fn make_cube(&mut self) {
+        self.set_shape(SurfaceSharedData::cached_cube());
     }
 
-    pub fn make_cube(&mut self) {
+    /// See `SurfaceSharedData::make_cylinder`.
+    pub fn make_cylinder(&mut self, sides: usize, height: f32, radius: f32, caps: bool) {
+        self.set_shape(SurfaceSharedData::cached_cylinder(
+            sides, height, radius, caps,
+        ));
+    }
+
+    /// See `SurfaceSharedData::make_cone`.
+    pub fn make_cone(&mut self, sides: usize, height: f32, radius: f32) {
+        self.set_shape(SurfaceSharedData::cached_cone(sides, height, radius));
+    }
+
+    /// See `SurfaceSharedData::make_terrain`. Terrains are not shared,
+    /// every call builds new data.
+    pub fn make_terrain(
+        &mut self,
+        heightmap: &Texture,
+        size: Vector2<f32>,
+        height_scale: f32,
+        resolution: usize,
+    ) {
+        let data = SurfaceSharedData::make_terrain(heightmap, size, height_scale, resolution);
+        self.set_shape(Rc::new(RefCell::new(data)));
+    }
+
+    /// Replaces surfaces with a single one of given data.
+    fn set_shape(&mut self, data: SurfaceSharedDataRef) {
         self.surfaces.clear();
-        let data = Rc::new(RefCell::new(SurfaceSharedData::make_cube()));
         self.surfaces.push(Surface::new(&data));
     }
 
+    pub fn set_tint(&mut self, tint: Color) {
+        self.tint = tint;
+    }
+
+    pub fn get_tint(&self) -> Color {
+        self.tint
+    }
+
+    pub fn get_surfaces(&self) -> &[Surface] {
+        &self.surfaces
+    }
+
+    pub fn add_surface(&mut self, surface: Surface) {
+        self.surfaces.push(surface);
+    }
+
     pub fn apply_texture(&mut self, tex: Rc<RefCell<Resource>>) {
         for surface in self.surfaces.iter_mut() {
             surface.set_texture(tex.clone());
         }
     }
 
-    
+    /// Bones are indexed by bone indices of skinned surfaces.
+    pub fn set_bones(&mut self, bones: Vec<Bone>) {
+        self.bone_matrices = vec![Matrix4::identity(); bones.len()];
+        self.bones = bones;
+    }
+
+    pub fn get_bones(&self) -> &[Bone] {
+        &self.bones
+    }
+
+    /// Skinning matrices moving bind pose vertices to the current pose in
+    /// mesh space, one per bone. Scene sets them on every update from the
+    /// bone nodes.
+    pub fn set_bone_matrices(&mut self, matrices: Vec<Matrix4<f32>>) {
+        self.bone_matrices = matrices;
+    }
+
+    pub fn get_bone_matrices(&self) -> &[Matrix4<f32>] {
+        &self.bone_matrices
+    }
+
+    /// Local space bounds of all surfaces, None if mesh has no vertices.
+    pub fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        self.surfaces
+            .iter()
+            .filter(|surface| surface.data.borrow().get_vertex_count() > 0)
+            .map(|surface| surface.data.borrow().bounding_box())
+            .reduce(|a, b| a.merge(&b))
+    }
+}
+
+/// Per-frame logic of a script node, see `NodeKind::Script`.
+pub trait NodeBehavior: Any {
+    /// Called by every scene update after transforms are calculated, for
+    /// script nodes reachable from root. `node` is the script node itself
+    /// with `Base` kind in place of the script while it runs. Transform
+    /// changes are picked up by the next update.
+    fn update(&mut self, node: &mut Node, dt: f32);
+
+    /// Called once the node is added to a scene.
+    fn on_added(&mut self) {}
+
+    /// Called before the node is destroyed by the scene.
+    fn on_removed(&mut self) {}
+}
+
+impl std::fmt::Debug for dyn NodeBehavior {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NodeBehavior")
+    }
 }
 
 #[derive(Debug)]
@@ -118,6 +627,9 @@ pub enum NodeKind {
 
     /// User-defined node kind
     Custom(Box<dyn Any>),
+
+    /// User-defined node updated by the scene every frame.
+    Script(Box<dyn NodeBehavior>),
 }
 
 #[derive(Debug)]
@@ -134,9 +646,26 @@ pub struct Node {
     scaling_offset: Vector3<f32>,
     scaling_pivot: Vector3<f32>,
     pub(super) parent: Handle<Node>,
-    pub(crate) children: Vec<Handle<Node>>,
+    pub(super) children: Vec<Handle<Node>>,
     pub local_transform: Matrix4<f32>,
     pub(crate) global_transform: Matrix4<f32>,
+    /// Global transform of previous scene update, used for render interpolation.
+    pub(crate) prev_global_transform: Matrix4<f32>,
+    pub(crate) reset_interpolation: bool,
+    /// Local transform is outdated, set by transform setters and linking.
+    pub(crate) transform_dirty: bool,
+    /// Mesh bounds of this node block movement, see `Scene::query_colliders`.
+    collider: bool,
+    /// Hidden nodes are skipped by the renderer together with their subtree.
+    visibility: bool,
+    /// False if the node or any of its ancestors is hidden, updated by scene.
+    pub(crate) global_visibility: bool,
+    /// Seconds left before the scene removes the node with its subtree.
+    lifetime: Option<f32>,
+    /// Game defined category, see `Scene::find_nodes_by_tag`.
+    tag: u64,
+    /// Render layers, drawn by cameras whose cull mask shares a bit with it.
+    layers: u32,
 }
 
 impl Node {
@@ -157,23 +686,76 @@ impl Node {
             scaling_pivot: Vector3::zeros(),
             local_transform: Matrix4::identity(),
             global_transform: Matrix4::identity(),
+            prev_global_transform: Matrix4::identity(),
+            reset_interpolation: true,
+            transform_dirty: true,
+            collider: false,
+            visibility: true,
+            global_visibility: true,
+            lifetime: None,
+            tag: 0,
+            layers: u32::MAX,
+        }
+    }
+
+    /// Copy of the node without parent and children. Custom and script kinds
+    /// can't be cloned, their copies become base nodes.
+    pub(crate) fn copy_unlinked(&self) -> Node {
+        let kind = match &self.kind {
+            NodeKind::Base => NodeKind::Base,
+            NodeKind::Light(light) => NodeKind::Light(light.clone()),
+            NodeKind::Camera(camera) => NodeKind::Camera(camera.clone()),
+            NodeKind::Mesh(mesh) => NodeKind::Mesh(mesh.clone()),
+            NodeKind::Custom(_) | NodeKind::Script(_) => {
+                println!("{:?}: custom node kind is copied as base node", self.name);
+                NodeKind::Base
+            }
+        };
+        Node {
+            name: self.name.clone(),
+            kind,
+            local_scale: self.local_scale,
+            local_position: self.local_position,
+            local_rotation: self.local_rotation,
+            pre_rotation: self.pre_rotation,
+            post_rotation: self.post_rotation,
+            rotation_offset: self.rotation_offset,
+            rotation_pivot: self.rotation_pivot,
+            scaling_offset: self.scaling_offset,
+            scaling_pivot: self.scaling_pivot,
+            parent: Handle::none(),
+            children: Vec::new(),
+            local_transform: self.local_transform,
+            global_transform: self.global_transform,
+            prev_global_transform: self.prev_global_transform,
+            reset_interpolation: true,
+            transform_dirty: true,
+            collider: self.collider,
+            visibility: self.visibility,
+            global_visibility: self.global_visibility,
+            lifetime: self.lifetime,
+            tag: self.tag,
+            layers: self.layers,
         }
     }
 
+    /// Composes local transform the way FBX does:
+    /// `T * Roff * Rp * Rpre * R * Rpost^-1 * Rp^-1 * Soff * Sp * S * Sp^-1`,
+    /// offsets and pivots are translations.
     pub fn calculate_local_transform(&mut self) {
         let pre_rotation = self.pre_rotation.to_homogeneous();
-        let post_rotation = self.post_rotation.to_homogeneous().try_inverse().unwrap();
+        let post_rotation = self.post_rotation.inverse().to_homogeneous();
         let rotation = self.local_rotation.to_homogeneous();
         let scale = Matrix4::new_nonuniform_scaling(&self.local_scale);
 
         let translation = Matrix4::new_translation(&self.local_position);
 
-        let rotation_offset = Matrix4::new_rotation(self.rotation_offset);
-        let rotation_pivot = Matrix4::new_rotation(self.rotation_pivot);
-        let rotation_pivot_inv = rotation_pivot.try_inverse().unwrap();
-        let scale_offset = Matrix4::from_scaled_axis(self.scaling_offset);
-        let scale_pivot = Matrix4::from_scaled_axis(self.scaling_pivot);
-        let scale_pivot_inv = scale_pivot.try_inverse().unwrap();
+        let rotation_offset = Matrix4::new_translation(&self.rotation_offset);
+        let rotation_pivot = Matrix4::new_translation(&self.rotation_pivot);
+        let rotation_pivot_inv = Matrix4::new_translation(&-self.rotation_pivot);
+        let scale_offset = Matrix4::new_translation(&self.scaling_offset);
+        let scale_pivot = Matrix4::new_translation(&self.scaling_pivot);
+        let scale_pivot_inv = Matrix4::new_translation(&-self.scaling_pivot);
 
         self.local_transform = translation
             * rotation_offset
@@ -186,6 +768,26 @@ impl Node {
             * scale_pivot
             * scale
             * scale_pivot_inv;
+        self.transform_dirty = false;
+    }
+
+    /// Sets local position, rotation and scale so that the local transform
+    /// becomes `transform`, pre/post rotations and pivots are kept. Shear
+    /// can't be represented and is dropped, translation is always exact.
+    /// Returns false and keeps the node as is if the matrix is singular.
+    pub fn set_local_transform_from(&mut self, transform: &Matrix4<f32>) -> bool {
+        let Some((translation, rotation, scale)) = decompose(transform) else {
+            return false;
+        };
+        self.local_rotation = self.pre_rotation.inverse() * rotation * self.post_rotation;
+        self.local_scale = scale;
+        // Pivots and offsets move the node too, position makes up the rest.
+        self.local_position = Vector3::zeros();
+        self.calculate_local_transform();
+        self.local_position = translation - self.local_transform.fixed_view::<3, 1>(0, 3);
+        self.calculate_local_transform();
+        self.transform_dirty = true;
+        true
     }
 
     pub fn borrow_kind(&self) -> &NodeKind {
@@ -196,26 +798,243 @@ impl Node {
         &mut self.kind
     }
 
+    /// Behavior of a script node if it is a `T`.
+    pub fn script_as<T: NodeBehavior>(&self) -> Option<&T> {
+        match &self.kind {
+            NodeKind::Script(script) => (script.as_ref() as &dyn Any).downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn script_as_mut<T: NodeBehavior>(&mut self) -> Option<&mut T> {
+        match &mut self.kind {
+            NodeKind::Script(script) => (script.as_mut() as &mut dyn Any).downcast_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn get_local_position(&self) -> Vector3<f32> {
+        self.local_position
+    }
+
     pub fn set_local_position(&mut self, pos: Vector3<f32>) {
         self.local_position = pos;
+        self.transform_dirty = true;
     }
 
     pub fn set_local_rotation(&mut self, rot: UnitQuaternion<f32>) {
         self.local_rotation = rot;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_local_rotation(&self) -> UnitQuaternion<f32> {
+        self.local_rotation
     }
 
     pub fn set_local_scale(&mut self, scl: Vector3<f32>) {
         self.local_scale = scl;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_local_scale(&self) -> Vector3<f32> {
+        self.local_scale
+    }
+
+    /// Rotation applied before the local one, see `calculate_local_transform`.
+    pub fn set_pre_rotation(&mut self, rotation: UnitQuaternion<f32>) {
+        self.pre_rotation = rotation;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_pre_rotation(&self) -> UnitQuaternion<f32> {
+        self.pre_rotation
+    }
+
+    /// Rotation whose inverse is applied after the local one.
+    pub fn set_post_rotation(&mut self, rotation: UnitQuaternion<f32>) {
+        self.post_rotation = rotation;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_post_rotation(&self) -> UnitQuaternion<f32> {
+        self.post_rotation
+    }
+
+    /// Point in local space the node rotates about.
+    pub fn set_rotation_pivot(&mut self, pivot: Vector3<f32>) {
+        self.rotation_pivot = pivot;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_rotation_pivot(&self) -> Vector3<f32> {
+        self.rotation_pivot
+    }
+
+    /// Makes the node rotate about `point` given in mesh space, before
+    /// scaling. Placement of the unrotated node doesn't change.
+    pub fn set_rotation_pivot_point(&mut self, point: Vector3<f32>) {
+        let scaled = self.scaling_offset
+            + self.scaling_pivot
+            + self
+                .local_scale
+                .component_mul(&(point - self.scaling_pivot));
+        self.set_rotation_pivot(scaled);
+    }
+
+    pub fn set_rotation_offset(&mut self, offset: Vector3<f32>) {
+        self.rotation_offset = offset;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_rotation_offset(&self) -> Vector3<f32> {
+        self.rotation_offset
+    }
+
+    /// Point in local space the node scales about.
+    pub fn set_scaling_pivot(&mut self, pivot: Vector3<f32>) {
+        self.scaling_pivot = pivot;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_scaling_pivot(&self) -> Vector3<f32> {
+        self.scaling_pivot
+    }
+
+    pub fn set_scaling_offset(&mut self, offset: Vector3<f32>) {
+        self.scaling_offset = offset;
+        self.transform_dirty = true;
+    }
+
+    pub fn get_scaling_offset(&self) -> Vector3<f32> {
+        self.scaling_offset
     }
 
     pub fn offset(&mut self, vec: Vector3<f32>) {
         self.local_position += &vec;
+        self.transform_dirty = true;
+    }
+
+    pub fn set_collider(&mut self, collider: bool) {
+        self.collider = collider;
+    }
+
+    pub fn is_collider(&self) -> bool {
+        self.collider
+    }
+
+    pub fn set_visibility(&mut self, visibility: bool) {
+        self.visibility = visibility;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visibility
+    }
+
+    /// Visibility including ancestors, as of the last scene update.
+    pub fn is_globally_visible(&self) -> bool {
+        self.global_visibility
+    }
+
+    /// Bitmask of render layers, all by default. Mesh is drawn only by
+    /// cameras with a cull mask sharing a bit with it, independent of
+    /// visibility. Layers are not inherited by children.
+    pub fn set_layers(&mut self, layers: u32) {
+        self.layers = layers;
+    }
+
+    pub fn get_layers(&self) -> u32 {
+        self.layers
+    }
+
+    /// Parent node, none for scene root and unlinked nodes. Links are
+    /// changed through the scene, see `Scene::link_nodes`.
+    pub fn parent(&self) -> Handle<Node> {
+        self.parent.clone()
+    }
+
+    pub fn children(&self) -> &[Handle<Node>] {
+        &self.children
+    }
+
+    /// Node is removed together with its subtree by the scene update that
+    /// runs its lifetime out, time is scaled by scene time scale.
+    pub fn set_lifetime(&mut self, seconds: f32) {
+        self.lifetime = Some(seconds);
+    }
+
+    /// Makes the node live until removed explicitly.
+    pub fn clear_lifetime(&mut self) {
+        self.lifetime = None;
+    }
+
+    /// Seconds left to live, None for nodes without lifetime.
+    pub fn get_lifetime(&self) -> Option<f32> {
+        self.lifetime
+    }
+
+    pub fn set_tag(&mut self, tag: u64) {
+        self.tag = tag;
+    }
+
+    pub fn tag(&self) -> u64 {
+        self.tag
     }
 
     pub fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
     }
 
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Counts lifetime down, returns true once it has run out.
+    pub(super) fn update_lifetime(&mut self, dt: f32) -> bool {
+        match self.lifetime.as_mut() {
+            Some(lifetime) => {
+                *lifetime -= dt;
+                *lifetime <= 0.0
+            }
+            None => false,
+        }
+    }
+
+    /// Returns global transform blended between two last scene updates, `alpha`
+    /// of 1.0 gives current transform.
+    pub fn get_interpolated_global_transform(&self, alpha: f32) -> Matrix4<f32> {
+        if alpha >= 1.0 || self.prev_global_transform == self.global_transform {
+            return self.global_transform;
+        }
+        match (
+            decompose(&self.prev_global_transform),
+            decompose(&self.global_transform),
+        ) {
+            (Some((t0, r0, s0)), Some((t1, r1, s1))) => compose(
+                t0.lerp(&t1, alpha),
+                r0.try_slerp(&r1, alpha, f32::EPSILON).unwrap_or(r1),
+                s0.lerp(&s1, alpha),
+            ),
+            _ => self.global_transform,
+        }
+    }
+
+    /// Makes next scene update skip blending from the old transform, use it
+    /// after teleporting node so it won't be smeared across the screen.
+    pub fn reset_interpolation(&mut self) {
+        self.reset_interpolation = true;
+    }
+
+    /// Bounds of the mesh in world space as of the last scene update, None
+    /// for other kinds and meshes without vertices.
+    pub fn world_bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        match &self.kind {
+            NodeKind::Mesh(mesh) => mesh
+                .bounding_box()
+                .map(|local| local.transform(&self.global_transform)),
+            _ => None,
+        }
+    }
+
     pub fn get_global_position(&self) -> Vector3<f32> {
         Vector3::new(
             self.global_transform[12],
@@ -247,4 +1066,24 @@ impl Node {
             self.global_transform[6],
         )
     }
+
+    /// World rotation as of the last scene update. Mirrored transforms are
+    /// represented by negative X scale, so the rotation stays proper.
+    /// Identity if any axis is scaled to zero.
+    pub fn get_global_rotation(&self) -> UnitQuaternion<f32> {
+        decompose(&self.global_transform).map_or_else(UnitQuaternion::identity, |(_, r, _)| r)
+    }
+
+    /// World scale as of the last scene update, negative on X for mirrored
+    /// transforms, see `get_global_rotation`.
+    pub fn get_global_scale(&self) -> Vector3<f32> {
+        match decompose(&self.global_transform) {
+            Some((_, _, scale)) => scale,
+            None => Vector3::new(
+                self.get_side_vector().norm(),
+                self.get_up_vector().norm(),
+                self.get_look_vector().norm(),
+            ),
+        }
+    }
 }
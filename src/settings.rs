@@ -0,0 +1,404 @@
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    game_input_map,
+    player::{default_input_map, PlayerSettings},
+};
+use balala::{
+    engine::input_map::{InputBinding, InputMap},
+    renderer::renderer::WindowSettings,
+};
+use serde::{Deserialize, Serialize};
+
+/// Version written to new settings files, bump it when the meaning of an
+/// existing field changes.
+pub const SETTINGS_VERSION: u32 = 1;
+
+const SETTINGS_FIELDS: [&str; 4] = ["version", "video", "player", "bindings"];
+const VIDEO_FIELDS: [&str; 5] = ["width", "height", "fullscreen", "vsync", "msaa"];
+const PLAYER_FIELDS: [&str; 15] = [
+    "mouse_sensitivity",
+    "controller_sensitivity",
+    "invert_y",
+    "min_pitch",
+    "max_pitch",
+    "sprint_multiplier",
+    "crouch_multiplier",
+    "third_person_distance",
+    "third_person_height",
+    "min_fov",
+    "max_fov",
+    "fov_step",
+    "min_third_person_distance",
+    "max_third_person_distance",
+    "distance_step",
+];
+
+/// Video and input options kept between runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub version: u32,
+    pub video: WindowSettings,
+    pub player: PlayerSettings,
+    /// Bindings of player and game actions by action name.
+    pub bindings: BTreeMap<String, Vec<InputBinding>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let mut bindings = BTreeMap::new();
+        for map in [default_input_map(), game_input_map()] {
+            for (action, keys) in map.actions() {
+                bindings.insert(action.to_string(), keys.to_vec());
+            }
+        }
+        Self {
+            version: SETTINGS_VERSION,
+            video: WindowSettings::default(),
+            player: PlayerSettings::default(),
+            bindings,
+        }
+    }
+}
+
+/// Overwrites fields of `value` with the ones in `table` one by one, so a
+/// field of wrong type only loses itself.
+fn merge_fields<T>(
+    value: &mut T,
+    table: &toml::Table,
+    section: &str,
+    fields: &[&str],
+    warnings: &mut Vec<String>,
+) where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let Ok(mut merged) = toml::Table::try_from(&*value) else {
+        return;
+    };
+    for (key, field) in table.iter() {
+        if !fields.contains(&key.as_str()) {
+            warnings.push(format!("unknown field {}.{}", section, key));
+            continue;
+        }
+        let mut candidate = merged.clone();
+        candidate.insert(key.clone(), field.clone());
+        match candidate.clone().try_into::<T>() {
+            Ok(result) => {
+                *value = result;
+                merged = candidate;
+            }
+            Err(err) => warnings.push(format!("invalid {}.{}: {}", section, key, err.message())),
+        }
+    }
+}
+
+impl Settings {
+    /// Platform config location, `None` if the home directory is unknown.
+    pub fn default_path() -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+        let dir = if cfg!(windows) {
+            PathBuf::from(var("APPDATA")?)
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from(var("HOME")?).join("Library/Application Support")
+        } else {
+            var("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| Some(PathBuf::from(var("HOME")?).join(".config")))?
+        };
+        Some(dir.join("balala").join("settings.toml"))
+    }
+
+    /// Parses settings, anything missing or invalid keeps its default and
+    /// is described in the returned warnings. Never fails.
+    pub fn from_toml(source: &str) -> (Settings, Vec<String>) {
+        let mut settings = Settings::default();
+        let mut warnings = Vec::new();
+        let table = match source.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                warnings.push(format!("not a valid TOML file: {}", err.message()));
+                return (settings, warnings);
+            }
+        };
+        for key in table.keys() {
+            if !SETTINGS_FIELDS.contains(&key.as_str()) {
+                warnings.push(format!("unknown field {}", key));
+            }
+        }
+        match table.get("version").map(|version| version.as_integer()) {
+            Some(Some(version)) if version > SETTINGS_VERSION as i64 => warnings.push(format!(
+                "written by newer version {}, fields it added are ignored",
+                version
+            )),
+            Some(None) => warnings.push("invalid version".to_string()),
+            _ => (),
+        }
+
+        let section = |name: &str, warnings: &mut Vec<String>| match table.get(name) {
+            Some(toml::Value::Table(section)) => Some(section),
+            Some(_) => {
+                warnings.push(format!("{} must be a table", name));
+                None
+            }
+            None => None,
+        };
+        if let Some(video) = section("video", &mut warnings) {
+            let mut window = settings.video;
+            merge_fields(&mut window, video, "video", &VIDEO_FIELDS, &mut warnings);
+            let defaults = WindowSettings::default();
+            if window.width == 0 || window.height == 0 {
+                warnings.push("invalid video size, must be positive".to_string());
+                (window.width, window.height) = (defaults.width, defaults.height);
+            }
+            if let Some(samples) = window.msaa.filter(|s| !s.is_power_of_two() || *s > 16) {
+                warnings.push(format!("invalid video.msaa {}, using default", samples));
+                window.msaa = defaults.msaa;
+            }
+            settings.video = window;
+        }
+        if let Some(player) = section("player", &mut warnings) {
+            merge_fields(
+                &mut settings.player,
+                player,
+                "player",
+                &PLAYER_FIELDS,
+                &mut warnings,
+            );
+        }
+        if let Some(bindings) = section("bindings", &mut warnings) {
+            for (action, value) in bindings.iter() {
+                let Some(current) = settings.bindings.get_mut(action) else {
+                    warnings.push(format!("unknown action {}", action));
+                    continue;
+                };
+                let Some(values) = value.as_array() else {
+                    warnings.push(format!("bindings.{} must be an array", action));
+                    continue;
+                };
+                let mut keys = Vec::new();
+                for value in values {
+                    match value.clone().try_into::<InputBinding>() {
+                        Ok(binding) => keys.push(binding),
+                        Err(_) => warnings.push(format!("invalid binding {} of {}", value, action)),
+                    }
+                }
+                // An entry of only broken bindings would leave the action
+                // unusable, explicit empty array still unbinds it.
+                if !keys.is_empty() || values.is_empty() {
+                    *current = keys;
+                }
+            }
+        }
+        (settings, warnings)
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(&Settings {
+            version: SETTINGS_VERSION,
+            ..self.clone()
+        })
+    }
+
+    /// Reads settings file, problems are reported and replaced by defaults.
+    pub fn load(path: &Path) -> Settings {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                let (settings, warnings) = Settings::from_toml(&source);
+                for warning in warnings {
+                    println!("{:?}: {}", path, warning);
+                }
+                settings
+            }
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    println!("failed to read {:?}: {}, using defaults", path, err);
+                }
+                Settings::default()
+            }
+        }
+    }
+
+    /// Writes settings file, creating its directory.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let source = self
+            .to_toml()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, source)
+    }
+
+    /// Replaces bindings of actions that exist in `map`, so the same settings
+    /// feed both player controls and game input map.
+    pub fn apply_bindings(&self, map: &mut InputMap) {
+        let known: Vec<String> = map
+            .actions()
+            .map(|(action, _)| action.to_string())
+            .collect();
+        for action in known {
+            if let Some(bindings) = self.bindings.get(&action) {
+                map.set_bindings(&action, bindings.clone());
+            }
+        }
+    }
+}
+
+/// Settings shared by the game and its level. Every `edit` bumps the
+/// revision, holders compare it to pick up changes.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsHandle {
+    settings: Rc<RefCell<Settings>>,
+    revision: Rc<Cell<u32>>,
+}
+
+impl SettingsHandle {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings: Rc::new(RefCell::new(settings)),
+            revision: Rc::default(),
+        }
+    }
+
+    pub fn borrow(&self) -> Ref<'_, Settings> {
+        self.settings.borrow()
+    }
+
+    pub fn edit(&self, f: impl FnOnce(&mut Settings)) {
+        f(&mut self.settings.borrow_mut());
+        self.revision.set(self.revision.get().wrapping_add(1));
+    }
+
+    pub fn get_revision(&self) -> u32 {
+        self.revision.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::{ACTION_CROUCH, ACTION_JUMP, ACTION_MOVE_FORWARD, ACTION_SPRINT};
+    use winit::event::{MouseButton, VirtualKeyCode};
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = Settings {
+            video: WindowSettings {
+                width: 1280,
+                height: 720,
+                fullscreen: true,
+                vsync: false,
+                msaa: Some(4),
+            },
+            ..Default::default()
+        };
+        settings.player.mouse_sensitivity = 0.5;
+        settings.player.invert_y = true;
+        settings.bindings.insert(
+            ACTION_JUMP.to_string(),
+            vec![
+                InputBinding::Key(VirtualKeyCode::J),
+                InputBinding::Mouse(MouseButton::Middle),
+            ],
+        );
+        settings
+            .bindings
+            .insert(ACTION_CROUCH.to_string(), Vec::new());
+
+        let source = settings.to_toml().unwrap();
+        assert!(source.starts_with(&format!("version = {}", SETTINGS_VERSION)));
+        let (restored, warnings) = Settings::from_toml(&source);
+        assert_eq!(warnings, Vec::<String>::new());
+        assert_eq!(restored, settings);
+
+        let path = std::env::temp_dir()
+            .join(format!("balala_settings_{}", std::process::id()))
+            .join("settings.toml");
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path), settings);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        // Missing file is not an error.
+        assert_eq!(Settings::load(&path), Settings::default());
+    }
+
+    #[test]
+    fn settings_recover_broken_fields() {
+        let source = r#"
+version = 1
+volume = 3
+
+[video]
+width = "wide"
+height = 720
+msaa = 3
+vsync = false
+
+[player]
+mouse_sensitivity = 0.5
+invert_y = "yes"
+gamma = 2.2
+
+[bindings]
+jump = [{ Key = "J" }]
+move_forward = [{ Key = "NotAKey" }, { Key = "Up" }]
+sprint = [{ Key = "Nope" }]
+crouch = []
+dance = [{ Key = "K" }]
+"#;
+        let (settings, warnings) = Settings::from_toml(source);
+        let defaults = Settings::default();
+        assert_eq!(settings.video.width, defaults.video.width);
+        assert_eq!(settings.video.height, 720);
+        assert_eq!(settings.video.msaa, defaults.video.msaa);
+        assert!(!settings.video.vsync);
+        assert_eq!(settings.player.mouse_sensitivity, 0.5);
+        assert_eq!(settings.player.invert_y, defaults.player.invert_y);
+        assert_eq!(
+            settings.bindings[ACTION_JUMP],
+            [InputBinding::Key(VirtualKeyCode::J)]
+        );
+        assert_eq!(
+            settings.bindings[ACTION_MOVE_FORWARD],
+            [InputBinding::Key(VirtualKeyCode::Up)]
+        );
+        assert_eq!(
+            settings.bindings[ACTION_SPRINT],
+            defaults.bindings[ACTION_SPRINT]
+        );
+        assert!(settings.bindings[ACTION_CROUCH].is_empty());
+        assert!(!settings.bindings.contains_key("dance"));
+        assert_eq!(warnings.len(), 8, "{:#?}", warnings);
+        for field in [
+            "unknown field volume",
+            "invalid video.width",
+            "invalid video.msaa 3",
+            "invalid player.invert_y",
+            "unknown field player.gamma",
+            "of move_forward",
+            "of sprint",
+            "unknown action dance",
+        ] {
+            assert!(
+                warnings.iter().any(|warning| warning.contains(field)),
+                "{} not in {:#?}",
+                field,
+                warnings
+            );
+        }
+
+        let (settings, warnings) = Settings::from_toml("video = 5\nversion = 7");
+        assert_eq!(settings, defaults);
+        assert_eq!(warnings.len(), 2);
+        let (settings, warnings) = Settings::from_toml("[video");
+        assert_eq!(settings, defaults);
+        assert_eq!(warnings.len(), 1);
+        let (settings, warnings) = Settings::from_toml("[video]\nwidth = 0");
+        assert_eq!(settings.video.width, defaults.video.width);
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -1 +1 @@
-pub mod pool;
\ No newline at end of file
+pub mod pool;
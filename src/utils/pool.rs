@@ -1,8 +1,9 @@
 use std::marker::PhantomData;
 
 struct PoolRecord<T: Sized> {
+    /// Bumped every time the record is reused, handles to earlier payloads
+    /// carry older stamps and don't resolve. Never 0, see `Handle::none`.
     stamp: u32,
-    generation: u32,
     payload: Option<T>,
 }
 
@@ -35,6 +36,14 @@ impl<T> PartialEq for Handle<T> {
 }
 
 impl<T> Handle<T> {
+    fn new(index: usize, stamp: u32) -> Self {
+        Handle {
+            index: index as u32,
+            stamp,
+            type_marker: PhantomData,
+        }
+    }
+
     pub fn none() -> Self {
         Handle {
             index: 0,
@@ -44,6 +53,12 @@ impl<T> Handle<T> {
     }
 }
 
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Pool<T> {
     pub fn new() -> Self {
         Pool {
@@ -55,11 +70,11 @@ impl<T> Pool<T> {
     pub fn spawn(&mut self, payload: T) -> Handle<T> {
         if let Some(free_index) = self.free_stack.pop() {
             let record = &mut self.records[free_index as usize];
-            record.generation += 1;
+            record.stamp = record.stamp.checked_add(1).unwrap_or(1);
             record.payload.replace(payload);
             return Handle {
                 index: free_index,
-                stamp: record.generation,
+                stamp: record.stamp,
                 type_marker: PhantomData,
             };
         }
@@ -67,13 +82,12 @@ impl<T> Pool<T> {
         // No free records, create new one
         let record: PoolRecord<T> = PoolRecord {
             stamp: 1,
-            generation: 1,
             payload: Some(payload),
         };
 
         let handle = Handle {
             index: self.records.len() as u32,
-            stamp: record.generation,
+            stamp: record.stamp,
             type_marker: PhantomData,
         };
 
@@ -82,6 +96,11 @@ impl<T> Pool<T> {
         handle
     }
 
+    /// Whether the handle points to a live payload it was spawned for.
+    pub fn is_valid(&self, handle: &Handle<T>) -> bool {
+        self.borrow(handle).is_some()
+    }
+
     pub fn borrow(&self, handle: &Handle<T>) -> Option<&T> {
         let index = handle.index as usize;
         if index < self.records.len() {
@@ -108,18 +127,96 @@ impl<T> Pool<T> {
         None
     }
 
+    /// Borrows two distinct payloads at once, None if the handles are equal
+    /// or either doesn't resolve.
+    pub fn borrow_two_mut(&mut self, a: &Handle<T>, b: &Handle<T>) -> Option<(&mut T, &mut T)> {
+        let (ia, ib) = (a.index as usize, b.index as usize);
+        if ia == ib || !self.is_valid(a) || !self.is_valid(b) {
+            return None;
+        }
+        let (low, high) = self.records.split_at_mut(ia.max(ib));
+        let (first, second) = (&mut low[ia.min(ib)], &mut high[0]);
+        let (first, second) = (first.payload.as_mut()?, second.payload.as_mut()?);
+        Some(if ia < ib {
+            (first, second)
+        } else {
+            (second, first)
+        })
+    }
+
+    /// Destroys payload of the handle, stale handles are ignored.
     pub fn free(&mut self, handle: Handle<T>) {
-        let index = handle.index as usize;
-        if index < self.records.len() {
-            self.free_stack.push(handle.index);
-            self.records[index].payload.take();
+        self.take(handle);
+    }
+
+    /// Moves payload out of the pool and frees its record, None for stale
+    /// handles. The handle stays dead even after the record is reused.
+    pub fn take(&mut self, handle: Handle<T>) -> Option<T> {
+        let record = self.records.get_mut(handle.index as usize)?;
+        if record.stamp != handle.stamp {
+            return None;
+        }
+        let payload = record.payload.take()?;
+        self.free_stack.push(handle.index);
+        Some(payload)
+    }
+
+    /// Frees every record the predicate returns false for.
+    pub fn retain(&mut self, predicate: impl FnMut(Handle<T>, &mut T) -> bool) {
+        self.retain_with(predicate, |_, _| {});
+    }
+
+    /// Same as `retain`, payloads of freed records are passed to `on_free`
+    /// for cleanup instead of being dropped.
+    pub fn retain_with(
+        &mut self,
+        mut predicate: impl FnMut(Handle<T>, &mut T) -> bool,
+        mut on_free: impl FnMut(Handle<T>, T),
+    ) {
+        for (i, record) in self.records.iter_mut().enumerate() {
+            let Some(payload) = record.payload.as_mut() else {
+                continue;
+            };
+            let handle = Handle::new(i, record.stamp);
+            if !predicate(handle.clone(), payload) {
+                if let Some(payload) = record.payload.take() {
+                    on_free(handle, payload);
+                }
+                self.free_stack.push(i as u32);
+            }
         }
     }
 
+    /// Number of records, free ones included. See `alive_count` for occupied
+    /// ones.
     pub fn capacity(&self) -> usize {
         self.records.len()
     }
 
+    /// Makes room for `additional` more records without reallocation.
+    pub fn reserve(&mut self, additional: usize) {
+        let free = self.free_stack.len();
+        self.records.reserve(additional.saturating_sub(free));
+    }
+
+    /// Frees every record, all handles stop resolving. Records are kept and
+    /// reused by the next spawns, starting from the first one.
+    pub fn clear(&mut self) {
+        self.free_stack.clear();
+        for (i, record) in self.records.iter_mut().enumerate().rev() {
+            record.payload = None;
+            self.free_stack.push(i as u32);
+        }
+    }
+
+    /// Number of occupied records.
+    pub fn alive_count(&self) -> usize {
+        self.records
+            .iter()
+            .filter(|record| record.payload.is_some())
+            .count()
+    }
+
     pub fn at_mut(&mut self, n: usize) -> Option<&mut T> {
         if n < self.records.len() {
             if let Some(payload) = &mut self.records[n].payload {
@@ -129,6 +226,47 @@ impl<T> Pool<T> {
         None
     }
 
+    /// Handle of occupied record `n`.
+    pub fn handle_at(&self, n: usize) -> Option<Handle<T>> {
+        let record = self.records.get(n)?;
+        record
+            .payload
+            .as_ref()
+            .map(|_| Handle::new(n, record.stamp))
+    }
+
+    /// Iterates occupied records in slot order. The pool stays borrowed, so
+    /// nothing can be spawned or freed until iteration ends.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.records
+            .iter()
+            .filter_map(|record| record.payload.as_ref())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.records
+            .iter_mut()
+            .filter_map(|record| record.payload.as_mut())
+    }
+
+    /// Same as `iter` along with handles of the records.
+    pub fn pair_iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
+        self.records.iter().enumerate().filter_map(|(i, record)| {
+            let payload = record.payload.as_ref()?;
+            Some((Handle::new(i, record.stamp), payload))
+        })
+    }
+
+    pub fn pair_iter_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        self.records
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, record)| {
+                let payload = record.payload.as_mut()?;
+                Some((Handle::new(i, record.stamp), payload))
+            })
+    }
+
     pub fn at(&self, n: usize) -> Option<&T> {
         if n < self.records.len() {
             if let Some(payload) = &self.records[n].payload {
@@ -138,3 +276,155 @@ impl<T> Pool<T> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Handle, Pool};
+
+    #[test]
+    fn stale_handles() {
+        let mut pool = Pool::new();
+        let first = pool.spawn("first");
+        pool.free(first.clone());
+        assert!(pool.borrow(&first).is_none());
+
+        let second = pool.spawn("second");
+        assert_eq!(second.index, first.index);
+        assert!(second != first);
+        assert!(pool.borrow(&first).is_none());
+        assert!(pool.borrow_mut(&first).is_none());
+        assert_eq!(pool.borrow(&second), Some(&"second"));
+
+        // Freeing through the stale handle leaves the new payload alone and
+        // doesn't put the slot on the free list twice.
+        pool.free(first.clone());
+        pool.free(first);
+        assert_eq!(pool.borrow(&second), Some(&"second"));
+        let third = pool.spawn("third");
+        assert!(third.index != second.index);
+        assert_eq!(pool.alive_count(), 2);
+
+        assert!(pool.borrow(&Handle::none()).is_none());
+        pool.free(Handle::none());
+        assert_eq!(pool.alive_count(), 2);
+    }
+
+    #[test]
+    fn borrow_two() {
+        let mut pool = Pool::new();
+        let a = pool.spawn(1);
+        let b = pool.spawn(2);
+        let freed = pool.spawn(3);
+        pool.free(freed.clone());
+
+        let (first, second) = pool.borrow_two_mut(&b, &a).unwrap();
+        std::mem::swap(first, second);
+        assert_eq!(pool.borrow(&a), Some(&2));
+        assert_eq!(pool.borrow(&b), Some(&1));
+
+        assert!(pool.borrow_two_mut(&a, &a.clone()).is_none());
+        assert!(pool.borrow_two_mut(&a, &freed).is_none());
+        assert!(pool.borrow_two_mut(&Handle::none(), &b).is_none());
+    }
+
+    #[test]
+    fn take() {
+        let mut pool = Pool::new();
+        let handle = pool.spawn(String::from("scene"));
+        assert_eq!(pool.take(handle.clone()).as_deref(), Some("scene"));
+        assert!(!pool.is_valid(&handle));
+        assert!(pool.borrow(&handle).is_none());
+        assert!(pool.take(handle.clone()).is_none());
+
+        let reused = pool.spawn(String::from("next"));
+        assert_eq!(reused.index, handle.index);
+        assert!(!pool.is_valid(&handle));
+        assert!(pool.take(handle).is_none());
+        assert_eq!(pool.borrow(&reused).map(String::as_str), Some("next"));
+    }
+
+    #[test]
+    fn clear() {
+        let mut pool = Pool::new();
+        pool.reserve(4);
+        let handles: Vec<_> = (0..4).map(|i| pool.spawn(i)).collect();
+        pool.free(handles[1].clone());
+        assert_eq!(pool.capacity(), 4);
+        assert_eq!(pool.alive_count(), 3);
+
+        pool.clear();
+        assert_eq!(pool.capacity(), 4);
+        assert_eq!(pool.alive_count(), 0);
+        assert!(handles.iter().all(|handle| !pool.is_valid(handle)));
+
+        // Records are reused in order, old handles stay dead.
+        let first = pool.spawn(10);
+        assert_eq!(first.index, 0);
+        assert!(!pool.is_valid(&handles[0]));
+        for i in 0..4 {
+            pool.spawn(i);
+        }
+        assert_eq!(pool.capacity(), 5);
+        assert_eq!(pool.alive_count(), 5);
+    }
+
+    #[test]
+    fn retain() {
+        let mut pool = Pool::new();
+        let handles: Vec<_> = (0..6).map(|i| pool.spawn(i)).collect();
+        pool.free(handles[5].clone());
+        pool.retain(|_, value| *value % 2 == 0);
+        let alive: Vec<_> = handles.iter().map(|handle| pool.is_valid(handle)).collect();
+        assert_eq!(alive, [true, false, true, false, true, false]);
+
+        let mut freed = Vec::new();
+        pool.retain_with(
+            |handle, _| handle != handles[2],
+            |handle, value| freed.push((handle, value)),
+        );
+        assert!(freed == [(handles[2].clone(), 2)]);
+        assert!(pool.is_valid(&handles[0]) && pool.is_valid(&handles[4]));
+        assert!(!pool.is_valid(&handles[2]));
+        assert_eq!(pool.alive_count(), 2);
+
+        // Freed records are reused.
+        for i in 0..4 {
+            pool.spawn(i);
+        }
+        assert_eq!(pool.capacity(), 6);
+    }
+
+    #[test]
+    fn iteration() {
+        let mut pool = Pool::new();
+        let handles: Vec<_> = (0..6).map(|i| pool.spawn(i)).collect();
+        pool.free(handles[0].clone());
+        pool.free(handles[2].clone());
+        pool.free(handles[3].clone());
+        assert_eq!(pool.iter().copied().collect::<Vec<_>>(), [1, 4, 5]);
+
+        for value in pool.iter_mut() {
+            *value *= 10;
+        }
+        let pairs: Vec<_> = pool.pair_iter().map(|(h, v)| (h, *v)).collect();
+        assert!(pairs[0].0 == handles[1] && pairs[0].1 == 10);
+        assert!(pairs[1].0 == handles[4] && pairs[1].1 == 40);
+        assert!(pairs[2].0 == handles[5] && pairs[2].1 == 50);
+
+        // Reused slot yields its new handle.
+        let reused = pool.spawn(7);
+        let mut visited = Vec::new();
+        for (handle, value) in pool.pair_iter_mut() {
+            visited.push(handle);
+            *value += 1;
+        }
+        let expected = [
+            handles[1].clone(),
+            reused,
+            handles[4].clone(),
+            handles[5].clone(),
+        ];
+        assert!(visited == expected);
+        assert_eq!(pool.iter().copied().collect::<Vec<_>>(), [11, 8, 41, 51]);
+    }
+}